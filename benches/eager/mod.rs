@@ -0,0 +1,374 @@
+//! The tokenizer as it was before it became zero-copy and lazy, kept as a baseline for the benchmark.
+//!
+//! It reads the whole source into a `Vec<char>`, copies the text of every token
+//! into its own `String`, and scans every token before the first is returned.
+//! Only the tokens that the synthetic benchmark source uses are recognized.
+
+
+use unicode_ident::{
+    is_xid_start,
+    is_xid_continue,
+};
+
+use boron_lang::{
+    error::{throw, Error},
+    tokenizer::TokenType,
+};
+
+
+/// Creates a character stream.
+pub struct CharStream {
+    source: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+/// Provides functions for the `CharStream` struct.
+impl CharStream {
+    /// Constructs a new character stream from a given string.
+    pub fn new(src: String) -> Self {
+        Self {
+            source: src.chars().collect::<Vec<char>>(),
+            index: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Gets the next character in the stream without advancing the stream.
+    pub fn peek(&self) -> Option<char> {
+        self.look_ahead(0)
+    }
+
+    /// Gets the nth character ahead without advancing the stream.
+    pub fn look_ahead(&self, n: usize) -> Option<char> {
+        self.source.get(self.index + n).copied()
+    }
+
+    /// Gets the next character in the stream and advances the stream.
+    pub fn next_char(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.index += 1;
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(character)
+    }
+
+    /// Gets the line and column of the next character in the stream.
+    pub fn location(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+}
+
+
+/// Defines an abstraction over tokens, each owning a copy of its text.
+#[derive(Clone, Debug)]
+pub struct Token {
+    val: String,
+    t: TokenType,
+    doc: Option<String>,
+}
+
+/// Provides functions for the `Token` struct.
+impl Token {
+    /// Constructs a new token from a string and a token type.
+    pub fn new(token_value: String, token_type: TokenType) -> Self {
+        Self {
+            val: token_value,
+            t: token_type,
+            doc: None,
+        }
+    }
+
+    /// Gets the string associated with this token.
+    pub fn get_value(&self) -> String {
+        self.val.to_owned()
+    }
+
+    /// Gets the type associated with this token.
+    pub fn get_type(&self) -> TokenType {
+        self.t
+    }
+
+    /// Gets the doc comment attached to this token, if any.
+    pub fn get_doc(&self) -> Option<String> {
+        self.doc.to_owned()
+    }
+}
+
+
+/// Provides an abstraction over tokenization behavior.
+pub struct Tokenizer {
+    tokenstream: Vec<Token>,
+    index: usize,
+}
+
+const WHITESPACE: &str = "\r\n\t ,";
+
+/// Checks if a character may begin an identifier.
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || is_xid_start(c)
+}
+
+/// Checks if a character may continue an identifier.
+fn is_identifier_continue(c: char) -> bool {
+    c == '_' || is_xid_continue(c)
+}
+
+/// Provides functions for the `Tokenizer` struct.
+impl Tokenizer {
+    /// Constructs a new token stream from a string, scanning every token up front.
+    ///
+    /// Whitespace and comments are discarded, except for doc comments,
+    /// which are attached to the token that follows them.
+    pub fn new(string: String) -> Self {
+        let mut charstream = CharStream::new(string);
+
+        let mut tokenstream = Vec::new();
+        let mut docs: Vec<String> = Vec::new();
+
+        while let Some(t) = Self::next_token(&mut charstream) {
+            match t.get_type() {
+                TokenType::DocComment => docs.push(t.val.trim_start_matches('#').trim().to_string()),
+                TokenType::Whitespace
+                | TokenType::Comment => (),
+                _ => {
+                    if docs.is_empty() {
+                        tokenstream.push(t);
+                    } else {
+                        let mut t = t;
+                        t.doc = Some(docs.join("\n"));
+                        tokenstream.push(t);
+                        docs.clear();
+                    }
+                },
+            }
+        }
+
+        Self {
+            tokenstream,
+            index: 0,
+        }
+    }
+
+    /// Constructs a lossless token stream from a string.
+    pub fn lossless(string: String) -> Vec<Token> {
+        let mut charstream = CharStream::new(string);
+
+        let mut tokenstream = Vec::new();
+
+        while let Some(t) = Self::next_token(&mut charstream) {
+            tokenstream.push(t);
+        }
+
+        tokenstream
+    }
+
+    /// Scans a run of whitespace (newlines, spaces, tabs, and commas).
+    fn scan_whitespace(charstream: &mut CharStream, first: char) -> Token {
+        let mut sofar = String::from(first);
+        while let Some(c) = charstream.peek() {
+            if !WHITESPACE.contains(c) {
+                break;
+            }
+            sofar.push(c);
+            charstream.next_char();
+        }
+        Token::new(sofar, TokenType::Whitespace)
+    }
+
+    /// Scans a line comment or a doc comment.
+    fn scan_comment(charstream: &mut CharStream) -> Token {
+        let mut sofar = String::from("#");
+        let token_type = match charstream.peek() {
+            Some('#') => TokenType::DocComment,
+            _ => TokenType::Comment,
+        };
+        while let Some(c) = charstream.peek() {
+            if c == '\n' {
+                break;
+            }
+            sofar.push(c);
+            charstream.next_char();
+        }
+        Token::new(sofar, token_type)
+    }
+
+    /// Yields the next token (including whitespace and comments) from the character stream.
+    fn next_token(charstream: &mut CharStream) -> Option<Token> {
+        let (line, column) = charstream.location();
+
+        let character = charstream.next_char()?;
+
+        let token = match character {
+            // Whitespace
+            c if WHITESPACE.contains(c) => Self::scan_whitespace(charstream, c),
+            // Comment
+            '#' => Self::scan_comment(charstream),
+            // Ternary if
+            '?' => Token::new(character.to_string(), TokenType::TernaryIf),
+            // Ternary else
+            '|' => Token::new(character.to_string(), TokenType::TernaryElse),
+            // Open parenthesis
+            '(' => Token::new(character.to_string(), TokenType::OpenParen),
+            // Closing parenthesis
+            ')' => Token::new(character.to_string(), TokenType::CloseParen),
+            // Open curly brace
+            '{' => Token::new(character.to_string(), TokenType::OpenBrace),
+            // Closing curly brace
+            '}' => Token::new(character.to_string(), TokenType::CloseBrace),
+            // Assignment or function declaration
+            ':' => {
+                match charstream.peek() {
+                    Some(':') => {
+                        charstream.next_char();
+                        Token::new("::".to_string(), TokenType::FnDeclaration)
+                    },
+                    _ => Token::new(character.to_string(), TokenType::Assignment)
+                }
+            },
+            // Plus
+            '+' => Token::new(character.to_string(), TokenType::Plus),
+            // Minus or function return type
+            '-' => {
+                match charstream.peek() {
+                    Some('>') => {
+                        charstream.next_char();
+                        Token::new("->".to_string(), TokenType::FnReturnType)
+                    },
+                    _ => Token::new(character.to_string(), TokenType::Minus)
+                }
+            },
+            // Multiply
+            '*' => Token::new(character.to_string(), TokenType::Multiply),
+            // Divide
+            '/' => Token::new(character.to_string(), TokenType::Divide),
+            // Not
+            '!' => Token::new(character.to_string(), TokenType::Not),
+            // Greater
+            '>' => {
+                match charstream.peek() {
+                    Some('=') => {
+                        charstream.next_char();
+                        Token::new(">=".to_string(), TokenType::GreaterEqual)
+                    },
+                    _ => Token::new(character.to_string(), TokenType::Greater)
+                }
+            },
+            // Less
+            '<' => {
+                match charstream.peek() {
+                    Some('=') => {
+                        charstream.next_char();
+                        Token::new("<=".to_string(), TokenType::LessEqual)
+                    },
+                    _ => Token::new(character.to_string(), TokenType::Less)
+                }
+            }
+            // Equal
+            '=' => Token::new(character.to_string(), TokenType::Equal),
+            // Integer or floating-point
+            '0'..='9' => Self::scan_number(charstream, character, line, column),
+            // Identifier or keyword
+            c if is_identifier_start(c) => Self::scan_identifier(charstream, character),
+            // Unrecognized character
+            _ => throw(Error::UnexpectedCharacter (character, line, column)),
+        };
+
+        Some(token)
+    }
+
+    /// Scans the remainder of an integer or floating-point literal.
+    fn scan_number(charstream: &mut CharStream, first: char, line: usize, column: usize) -> Token {
+        let mut sofar = String::from(first);
+        let mut float = false;
+
+        while let Some(chr) = charstream.peek() {
+            if chr.is_ascii_digit() {
+                sofar.push(chr);
+                charstream.next_char();
+            } else if chr == '.' && !float {
+                float = true;
+                sofar.push(chr);
+                charstream.next_char();
+            } else {
+                break;
+            }
+        }
+
+        if float {
+            match str::parse::<f32>(&sofar) {
+                Ok(_) => Token::new(sofar, TokenType::Float),
+                Err(_) => throw(Error::InvalidNumber (sofar, line, column)),
+            }
+        } else {
+            match str::parse::<i32>(&sofar) {
+                Ok(_) => Token::new(sofar, TokenType::Int),
+                Err(_) => throw(Error::InvalidNumber (sofar, line, column)),
+            }
+        }
+    }
+
+    /// Scans the remainder of an identifier or keyword.
+    fn scan_identifier(charstream: &mut CharStream, first: char) -> Token {
+        let mut sofar = String::from(first);
+
+        while let Some(chr) = charstream.peek() {
+            if is_identifier_continue(chr)
+            || (chr == '.' && charstream.look_ahead(1).is_some_and(is_identifier_start))
+            {
+                sofar.push(chr);
+                charstream.next_char();
+            } else {
+                break;
+            }
+        }
+
+        let token_type = match sofar.as_str() {
+            "int" => TokenType::Type,
+            "flt" => TokenType::Type,
+            "bln" => TokenType::Type,
+            "chr" => TokenType::Type,
+            "let" => TokenType::Let,
+            "use" => TokenType::Use,
+            "struct" => TokenType::Struct,
+            "true" => TokenType::Bool,
+            "false" => TokenType::Bool,
+            "while" => TokenType::While,
+            "if" => TokenType::If,
+            "else" => TokenType::Else,
+            "return" => TokenType::Return,
+            _ => TokenType::Identifier,
+        };
+
+        Token::new(sofar, token_type)
+    }
+
+    /// Gets the next token without advancing the stream.
+    pub fn peek(&self) -> Option<Token> {
+        self.look_ahead(0)
+    }
+
+    /// Gets the nth token ahead without advancing the stream.
+    pub fn look_ahead(&self, n: usize) -> Option<Token> {
+        self.tokenstream.get(self.index + n).cloned()
+    }
+}
+
+
+/// Allows the `Tokenizer` to be consumed one token at a time.
+impl Iterator for Tokenizer {
+    type Item = Token;
+
+    /// Gets the next token and advances the stream.
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.index += 1;
+        token
+    }
+}
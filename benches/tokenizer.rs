@@ -0,0 +1,91 @@
+//! Benchmarks the tokenizer and parser on a large synthetic Boron source file.
+//!
+//! The `eager` benchmarks run the tokenizer as it was before it became zero-copy
+//! and lazy, so that the two can be compared on the same machine.
+
+
+mod eager;
+
+
+use criterion::{
+    black_box,
+    criterion_group,
+    criterion_main,
+    Criterion,
+    Throughput,
+};
+
+use boron_lang::{
+    tokenizer::Tokenizer,
+    parser::Parser,
+};
+
+
+/// Number of functions in the synthetic source (each function spans ten lines).
+const FUNCTIONS: usize = 5_000;
+
+
+/// Generates a synthetic Boron program of roughly `10 * FUNCTIONS` lines.
+fn synthetic_source() -> String {
+    let mut source = String::new();
+    source.push_str("## A point in the plane.\nstruct Point {\n    int x\n    int y\n}\n\n");
+    for i in 0..FUNCTIONS {
+        source.push_str(&format!("## Generated function number {}.\n", i));
+        source.push_str(&format!("function_{} :: int a, int b -> int {{\n", i));
+        source.push_str("    let int total: a * 2 + b - 7\n");
+        source.push_str("    let flt ratio: 3.25 / 1.5\n");
+        source.push_str("    # Loop until the total is large enough\n");
+        source.push_str("    while total < 1000 {\n");
+        source.push_str("        total: total + (a >= b ? a | b)\n");
+        source.push_str("    }\n");
+        source.push_str("    return total\n");
+        source.push_str("}\n");
+    }
+    source
+}
+
+
+fn bench_tokenizer(c: &mut Criterion) {
+    let source = synthetic_source();
+
+    // Both tokenizers must see the same tokens for the comparison to be fair
+    let old = eager::Tokenizer::new(source.clone()).collect::<Vec<_>>();
+    let new = Tokenizer::new(&source).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(old.len(), new.len());
+    for (o, n) in old.iter().zip(&new) {
+        assert_eq!((o.get_type(), o.get_value(), o.get_doc().is_some()), (n.get_type(), n.get_value(), n.get_doc().is_some()));
+    }
+
+    let mut group = c.benchmark_group("tokenizer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+
+    group.bench_function("tokenize", |b| {
+        b.iter(|| Tokenizer::new(black_box(&source)).count())
+    });
+
+    group.bench_function("tokenize_lossless", |b| {
+        b.iter(|| Tokenizer::lossless(black_box(&source)).count())
+    });
+
+    group.bench_function("tokenize_eager", |b| {
+        b.iter(|| eager::Tokenizer::new(black_box(source.clone())).count())
+    });
+
+    group.bench_function("tokenize_lossless_eager", |b| {
+        b.iter(|| eager::Tokenizer::lossless(black_box(source.clone())).len())
+    });
+
+    group.bench_function("parse", |b| {
+        let parser = Parser::new();
+        b.iter(|| {
+            let mut tokenizer = Tokenizer::new(black_box(&source));
+            parser.parse_all(&mut tokenizer).unwrap().len()
+        })
+    });
+
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_tokenizer);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! Parses arbitrary input, which must report errors rather than panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use boron_lang::pipeline;
+
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let _ = pipeline::parse(source);
+    }
+});
@@ -0,0 +1,14 @@
+//! Tokenizes arbitrary input, which must report errors rather than panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use boron_lang::tokenizer::Tokenizer;
+
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let _ = Tokenizer::new(source).count();
+    }
+});
@@ -0,0 +1,162 @@
+//! Defines the interface shared by the Boron compiler's code generators.
+//!
+//! Each backend turns a parsed program into the contents of an output file.
+//! The driver selects a backend by name with `--target`, so a new code
+//! generator or analysis only needs to implement `Backend` and be listed in
+//! `BACKENDS`.
+
+
+use crate::parser::Expression;
+
+use crate::{
+    bytecode,
+    emitter,
+    llvm,
+    wasm,
+    x86_64,
+};
+
+use crate::error::Error;
+
+
+/// Provides an interface for turning a program into an output file.
+pub trait Backend {
+    /// Gets the name that selects this backend with `--target`.
+    fn name(&self) -> &'static str;
+
+    /// Gets the extension of the file an executable is written to.
+    fn extension(&self) -> &'static str;
+
+    /// Compiles a program into the contents of its output file.
+    fn compile_exe(&self, expressions: Vec<Expression>) -> Result<Vec<u8>, Error>;
+
+    /// Gets the extension of the file a library is written to, or `None` if this backend cannot compile libraries.
+    fn lib_extension(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Compiles a library with the given name into the contents of its output file.
+    fn compile_lib(&self, _name: String, _expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Err(Error::LibraryNotSupported (self.name().to_string()))
+    }
+}
+
+
+/// Constructs a backend.
+pub type Constructor = fn() -> Box<dyn Backend>;
+
+
+/// Lists every backend by the name that selects it.  The first is the default.
+pub const BACKENDS: &[(&str, Constructor)] = &[
+    ("c", || Box::new(C)),
+    ("bytecode", || Box::new(Bytecode)),
+    ("llvm", || Box::new(Llvm)),
+    ("wasm", || Box::new(Wasm)),
+    ("x86_64", || Box::new(X86_64)),
+];
+
+
+/// Finds a backend by name.
+pub fn find(name: &str) -> Option<Box<dyn Backend>> {
+    BACKENDS.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, constructor)| constructor())
+}
+
+
+/// Emits standard C, which is compiled by a C compiler of your choice.
+pub struct C;
+
+impl Backend for C {
+    fn name(&self) -> &'static str {
+        "c"
+    }
+
+    fn extension(&self) -> &'static str {
+        "c"
+    }
+
+    fn compile_exe(&self, expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Ok(emitter::Emitter::new().compile_exe(expressions)?.into_bytes())
+    }
+
+    fn lib_extension(&self) -> Option<&'static str> {
+        Some("h")
+    }
+
+    fn compile_lib(&self, name: String, expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Ok(emitter::Emitter::new().compile_lib(name, expressions)?.into_bytes())
+    }
+}
+
+
+/// Compiles to bytecode, which is run by the Boron virtual machine.
+pub struct Bytecode;
+
+impl Backend for Bytecode {
+    fn name(&self) -> &'static str {
+        "bytecode"
+    }
+
+    fn extension(&self) -> &'static str {
+        "brnc"
+    }
+
+    fn compile_exe(&self, expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Ok(bytecode::compile(expressions)?.to_bytes())
+    }
+}
+
+
+/// Emits textual LLVM IR.
+pub struct Llvm;
+
+impl Backend for Llvm {
+    fn name(&self) -> &'static str {
+        "llvm"
+    }
+
+    fn extension(&self) -> &'static str {
+        "ll"
+    }
+
+    fn compile_exe(&self, expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Ok(llvm::Emitter::new().compile_exe(expressions)?.into_bytes())
+    }
+}
+
+
+/// Emits the WebAssembly text format.
+pub struct Wasm;
+
+impl Backend for Wasm {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn extension(&self) -> &'static str {
+        "wat"
+    }
+
+    fn compile_exe(&self, expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Ok(wasm::Emitter::new().compile_exe(expressions)?.into_bytes())
+    }
+}
+
+
+/// Emits x86-64 assembly for the GNU assembler.
+pub struct X86_64;
+
+impl Backend for X86_64 {
+    fn name(&self) -> &'static str {
+        "x86_64"
+    }
+
+    fn extension(&self) -> &'static str {
+        "s"
+    }
+
+    fn compile_exe(&self, expressions: Vec<Expression>) -> Result<Vec<u8>, Error> {
+        Ok(x86_64::Emitter::new().compile_exe(expressions)?.into_bytes())
+    }
+}
@@ -0,0 +1,627 @@
+//! Compiles parsed Boron programs into bytecode.
+
+
+use std::collections::HashMap;
+
+use crate::parser::{
+    Expression,
+    Passing,
+};
+
+use crate::emitter::include_modules;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::error::Error;
+
+use super::{
+    Constant,
+    Function,
+    Instruction,
+    Program,
+    StructDef,
+};
+
+
+/// The name of the function that initializes global variables.
+const INIT: &str = "<init>";
+
+
+/// Compiles a program's top-level expressions, including any modules it uses, into bytecode.
+pub fn compile(expressions: Vec<Expression>) -> Result<Program, Error> {
+    Compiler::default().compile(desugar(include_modules(expressions)?)?)
+}
+
+
+/// Describes where a variable is stored.
+#[derive(Clone, Copy)]
+enum Place {
+    Local (u16),
+    Global (u16),
+}
+
+
+/// Holds the variables of a block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, Place>,
+}
+
+
+/// Holds the state of a compilation.
+#[derive(Default)]
+struct Compiler {
+    program: Program,
+    // Indices of names in the program's name table
+    names: HashMap<String, u32>,
+    // Fields of each struct
+    structs: HashMap<String, HashMap<String, String>>,
+    // Index of each function, and how each of its parameters is passed
+    functions: HashMap<String, (u32, Vec<Passing>)>,
+    globals: HashMap<String, u16>,
+    // Static variables and their initializers, which are compiled into the initialization function
+    statics: Vec<(u16, Expression)>,
+    // State of the function being compiled
+    function: String,
+    code: Vec<Instruction>,
+    scopes: Vec<Scope>,
+    locals: u16,
+}
+
+/// Provides the internals of the bytecode compiler.
+impl Compiler {
+    /// Compiles a program's top-level expressions.
+    fn compile(mut self, expressions: Vec<Expression>) -> Result<Program, Error> {
+        // Register every struct and function first, so they may be used before they are declared
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct {
+                    identifier: i,
+                    variables: v,
+                    ..
+                } => self.declare_struct(i, v),
+                Expression::FnDeclaration {
+                    identifier: i,
+                    arguments: a,
+                    return_type: r,
+                    ..
+                } => self.declare_function(i, a, r),
+                _ => (),
+            }
+        }
+
+        // Declare globals, whose initializers are compiled into the initialization function
+        let mut initializers = Vec::new();
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. } => (),
+                Expression::Assignment {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } | Expression::Const {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } | Expression::Static {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let slot = self.declare_global(i, d);
+                    self.globals.insert(i.to_owned(), slot);
+                    initializers.push((slot, Expression::Assignment {
+                        datatype: d.to_owned(),
+                        identifier: i.to_owned(),
+                        value: v.to_owned(),
+                    }));
+                },
+                _ => return Err(Error::StatementOutsideFunction),
+            }
+        }
+
+        for expression in expressions.iter() {
+            if let Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                body: b,
+                ..
+            } = expression {
+                self.compile_function(i, a, b)?;
+            }
+        }
+
+        // Initialize globals in the order they were declared, followed by statics
+        initializers.append(&mut self.statics);
+        let init = self.program.functions.len() as u32;
+        let name = self.name(INIT);
+        let void = self.name("nul");
+        self.program.functions.push(Function {
+            name,
+            params: Vec::new(),
+            return_type: void,
+            locals: 0,
+            code: Vec::new(),
+        });
+        self.begin(INIT, &[]);
+        for (slot, initializer) in initializers {
+            if let Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } = initializer {
+                self.initialize(Place::Global (slot), &d, &i, &v)?;
+            }
+        }
+        self.end(init);
+        self.program.init = init;
+
+        Ok(self.program)
+    }
+
+    /// Gets the index of a name, adding it to the name table if needed.
+    fn name(&mut self, name: &str) -> u32 {
+        match self.names.get(name) {
+            Some(n) => *n,
+            None => {
+                let index = self.program.names.len() as u32;
+                self.program.names.push(name.to_string());
+                self.names.insert(name.to_string(), index);
+                index
+            },
+        }
+    }
+
+    /// Gets the index of a constant, adding it to the constant pool if needed.
+    fn constant(&mut self, constant: Constant) -> u32 {
+        match self.program.constants.iter().position(|c| *c == constant) {
+            Some(c) => c as u32,
+            None => {
+                self.program.constants.push(constant);
+                (self.program.constants.len() - 1) as u32
+            },
+        }
+    }
+
+    /// Registers a struct declaration.
+    fn declare_struct(&mut self, identifier: &str, variables: &HashMap<String, String>) {
+        // Sort the fields so that the same program always compiles to the same bytes
+        let mut fields = variables.iter().collect::<Vec<(&String, &String)>>();
+        fields.sort();
+        let name = self.name(identifier);
+        let fields = fields.into_iter()
+            .map(|(f, t)| (self.name(f), self.name(t)))
+            .collect();
+        self.program.structs.push(StructDef {
+            name,
+            fields,
+        });
+        self.structs.insert(identifier.to_string(), variables.to_owned());
+    }
+
+    /// Registers a function declaration, reserving its index.
+    fn declare_function(&mut self, identifier: &str, arguments: &[(String, String, Passing)], return_type: &str) {
+        let index = self.program.functions.len() as u32;
+        let name = self.name(identifier);
+        let params = arguments.iter()
+            .map(|(_, t, p)| (self.name(t), *p))
+            .collect();
+        let return_type = self.name(return_type);
+        self.program.functions.push(Function {
+            name,
+            params,
+            return_type,
+            locals: 0,
+            code: Vec::new(),
+        });
+        self.functions.insert(identifier.to_string(), (index, arguments.iter().map(|(_, _, p)| *p).collect()));
+    }
+
+    /// Reserves a slot for a global variable.
+    fn declare_global(&mut self, identifier: &str, datatype: &str) -> u16 {
+        let slot = self.program.globals.len() as u16;
+        let name = self.name(identifier);
+        let datatype = self.name(datatype);
+        self.program.globals.push((name, datatype));
+        slot
+    }
+
+    /// Starts compiling a function, binding its parameters to the first local slots.
+    fn begin(&mut self, name: &str, arguments: &[(String, String, Passing)]) {
+        self.function = name.to_string();
+        self.code = Vec::new();
+        let mut parameters = Scope::default();
+        for (i, (param, _, _)) in arguments.iter().enumerate() {
+            parameters.variables.insert(param.to_owned(), Place::Local (i as u16));
+        }
+        self.scopes = vec![parameters];
+        self.locals = arguments.len() as u16;
+    }
+
+    /// Finishes compiling a function, returning `nul` if control reaches its end.
+    fn end(&mut self, index: u32) {
+        let void = self.constant(Constant::Void);
+        self.emit(Instruction::Push (void));
+        self.emit(Instruction::Return);
+        self.scopes.clear();
+
+        let function = &mut self.program.functions[index as usize];
+        function.locals = self.locals;
+        function.code = std::mem::take(&mut self.code);
+    }
+
+    /// Compiles the body of a function.
+    fn compile_function(&mut self, name: &str, arguments: &[(String, String, Passing)], body: &[Expression]) -> Result<(), Error> {
+        // It's ok to use `unwrap` here because every function was declared before any was compiled
+        let index = self.functions.get(name).unwrap().0;
+        self.begin(name, arguments);
+        self.compile_block(body)?;
+        self.end(index);
+        Ok(())
+    }
+
+    /// Appends an instruction to the function being compiled, returning its position.
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.code.push(instruction);
+        self.code.len() - 1
+    }
+
+    /// Points a previously emitted jump at the next instruction.
+    fn patch(&mut self, jump: usize) {
+        let target = self.code.len() as u32;
+        self.code[jump] = match self.code[jump] {
+            Instruction::Jump (_) => Instruction::Jump (target),
+            Instruction::JumpIfFalse (_) => Instruction::JumpIfFalse (target),
+            i => i,
+        };
+    }
+
+    /// Reserves a new local variable slot.
+    fn local(&mut self) -> Result<u16, Error> {
+        if self.locals == u16::MAX {
+            return Err(Error::CouldNotCompile (format!("too many variables in {}", self.function)));
+        }
+        self.locals += 1;
+        Ok(self.locals - 1)
+    }
+
+    /// Binds a variable in the innermost scope.
+    fn bind(&mut self, name: &str, place: Place) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.variables.insert(name.to_string(), place);
+        }
+    }
+
+    /// Looks up a variable, searching the scopes of the current function and then the globals.
+    fn lookup(&self, name: &str) -> Option<Place> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(p) = scope.variables.get(name) {
+                return Some(*p);
+            }
+        }
+        self.globals.get(name).map(|g| Place::Global (*g))
+    }
+
+    /// Compiles a block in a new scope.
+    fn compile_block(&mut self, body: &[Expression]) -> Result<(), Error> {
+        self.scopes.push(Scope::default());
+        for statement in body {
+            self.compile_statement(statement)?;
+        }
+
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Pushes a reference to a variable or struct field (`point.x`).
+    fn compile_lvalue(&mut self, path: &str) -> Result<(), Error> {
+        let (base, fields) = split_path(path);
+        match self.lookup(base) {
+            Some(Place::Local (l)) => self.emit(Instruction::Local (l)),
+            Some(Place::Global (g)) => self.emit(Instruction::Global (g)),
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        for field in fields {
+            let field = self.name(field);
+            self.emit(Instruction::Field (field));
+        }
+        Ok(())
+    }
+
+    /// Pushes a reference to a variable in a known place.
+    fn compile_place(&mut self, place: Place) {
+        match place {
+            Place::Local (l) => self.emit(Instruction::Local (l)),
+            Place::Global (g) => self.emit(Instruction::Global (g)),
+        };
+    }
+
+    /// Declares a variable in the given place and assigns its initial value.
+    fn initialize(&mut self, place: Place, datatype: &str, identifier: &str, value: &Expression) -> Result<(), Error> {
+        let pointer = datatype.starts_with("own ") || datatype.starts_with("opt ");
+        match value {
+            Expression::StructInit {
+                variables: v,
+            } => {
+                if pointer {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                self.zero(place, datatype);
+                self.compile_fields(place, datatype, v)?;
+            },
+            _ => {
+                if matches!(value, Expression::Null) && !datatype.starts_with("opt ") {
+                    return Err(Error::ExpectedNullable (identifier.to_string()));
+                }
+                // Evaluate the value before declaring the variable, which it may not refer to
+                self.compile_expr(value)?;
+                self.zero(place, datatype);
+                self.compile_place(place);
+                self.emit(Instruction::Assign);
+            },
+        }
+        Ok(())
+    }
+
+    /// Gives a local variable the zero value of its type.  Globals are zeroed when a program is loaded.
+    fn zero(&mut self, place: Place, datatype: &str) {
+        if let Place::Local (l) = place {
+            let datatype = self.name(datatype);
+            self.emit(Instruction::Zero (l, datatype));
+        }
+    }
+
+    /// Assigns the fields of a struct initialization (`{ x 1 y 2 }`).
+    fn compile_fields(&mut self, place: Place, datatype: &str, variables: &HashMap<String, Expression>) -> Result<(), Error> {
+        let structure = match self.structs.get(datatype) {
+            Some(s) => s.to_owned(),
+            None => return Err(Error::UndeclaredVariable (datatype.to_string())),
+        };
+        // Sort the fields so that they are always initialized in the same order
+        let mut fields = variables.iter().collect::<Vec<(&String, &Expression)>>();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (varname, variable) in fields {
+            if !structure.contains_key(varname) {
+                return Err(Error::UndeclaredVariable (format!("{}.{}", datatype, varname)));
+            }
+            self.compile_expr(variable)?;
+            self.compile_place(place);
+            let field = self.name(varname);
+            self.emit(Instruction::Field (field));
+            self.emit(Instruction::Assign);
+        }
+        Ok(())
+    }
+
+    /// Compiles a single statement.
+    fn compile_statement(&mut self, statement: &Expression) -> Result<(), Error> {
+        match statement {
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } | Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                let slot = self.local()?;
+                self.initialize(Place::Local (slot), d, i, v)?;
+                self.bind(i, Place::Local (slot));
+            },
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                // A static variable is a global initialized once, when the program is loaded
+                let slot = self.declare_global(&format!("{}.{}", self.function, i), d);
+                self.statics.push((slot, Expression::Assignment {
+                    datatype: d.to_owned(),
+                    identifier: i.to_owned(),
+                    value: v.to_owned(),
+                }));
+                self.bind(i, Place::Global (slot));
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                let slot = self.local()?;
+                self.zero(Place::Local (slot), d);
+                self.bind(i, Place::Local (slot));
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => match &**v {
+                Expression::StructInit {
+                    variables: fields,
+                } => {
+                    let mut fields = fields.iter().collect::<Vec<(&String, &Expression)>>();
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                    for (varname, variable) in fields {
+                        self.compile_expr(variable)?;
+                        self.compile_lvalue(&format!("{}.{}", i, varname))?;
+                        self.emit(Instruction::Assign);
+                    }
+                },
+                _ => {
+                    self.compile_expr(v)?;
+                    self.compile_lvalue(i)?;
+                    self.emit(Instruction::Assign);
+                },
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                let start = self.code.len() as u32;
+                self.compile_expr(c)?;
+                let exit = self.emit(Instruction::JumpIfFalse (0));
+                self.compile_block(b)?;
+                self.emit(Instruction::Jump (start));
+                self.patch(exit);
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                self.compile_expr(c)?;
+                let exit = self.emit(Instruction::JumpIfFalse (0));
+                self.compile_block(b)?;
+                self.patch(exit);
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                self.compile_expr(c)?;
+                let otherwise = self.emit(Instruction::JumpIfFalse (0));
+                self.compile_block(t)?;
+                let exit = self.emit(Instruction::Jump (0));
+                self.patch(otherwise);
+                self.compile_block(f)?;
+                self.patch(exit);
+            },
+            Expression::Return (v) => {
+                self.compile_expr(v)?;
+                self.emit(Instruction::Return);
+            },
+            Expression::Struct { .. }
+            | Expression::FnDeclaration { .. }
+            | Expression::Use (_) => return Err(Error::CouldNotCompile (format!("declaration inside {}", self.function))),
+            _ => {
+                self.compile_expr(statement)?;
+                self.emit(Instruction::Pop);
+            },
+        }
+        Ok(())
+    }
+
+    /// Compiles an expression, which pushes exactly one value.
+    fn compile_expr(&mut self, expr: &Expression) -> Result<(), Error> {
+        match expr {
+            Expression::Int (i) => self.push(Constant::Int (*i)),
+            Expression::Float (f) => self.push(Constant::Float (*f)),
+            Expression::Bool (b) => self.push(Constant::Bool (*b)),
+            Expression::Char (c) => self.push(Constant::Char (*c)),
+            Expression::Null => self.push(Constant::Null),
+            // Reading a struct copies it, just as assigning a struct does in C
+            Expression::Identifier (i) => {
+                self.compile_lvalue(i)?;
+                self.emit(Instruction::Load);
+            },
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                self.compile_expr(e)?;
+                self.emit(Instruction::Unary ((*o).try_into()?));
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                span: s,
+            } => {
+                self.compile_expr(l)?;
+                self.compile_expr(r)?;
+                self.emit(Instruction::Binary ((*o).try_into()?, s.line as u32, s.column as u32));
+            },
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                self.compile_expr(c)?;
+                let otherwise = self.emit(Instruction::JumpIfFalse (0));
+                self.compile_expr(t)?;
+                let exit = self.emit(Instruction::Jump (0));
+                self.patch(otherwise);
+                self.compile_expr(f)?;
+                self.patch(exit);
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                // Build the struct in a temporary, then move it to the heap
+                let slot = self.local()?;
+                self.zero(Place::Local (slot), d);
+                self.compile_fields(Place::Local (slot), d, v)?;
+                self.emit(Instruction::Local (slot));
+                self.emit(Instruction::Load);
+                self.emit(Instruction::Box);
+            },
+            Expression::StructInit { .. } => return Err(Error::FoundBareStruct),
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => match n.as_str() {
+                "print" => {
+                    for arg in a {
+                        self.compile_expr(arg)?;
+                        self.emit(Instruction::Print);
+                    }
+                    self.push(Constant::Void);
+                },
+                "drop" => {
+                    for arg in a {
+                        match arg {
+                            Expression::Identifier (id) => self.compile_lvalue(id)?,
+                            _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+                        }
+                        self.emit(Instruction::Drop);
+                    }
+                    self.push(Constant::Void);
+                },
+                _ => self.compile_call(n, a)?,
+            },
+            _ => return Err(Error::CouldNotCompile (format!("{:?}", expr))),
+        }
+        Ok(())
+    }
+
+    /// Pushes a constant.
+    fn push(&mut self, constant: Constant) {
+        let index = self.constant(constant);
+        self.emit(Instruction::Push (index));
+    }
+
+    /// Compiles a function call, passing each argument according to the function's signature.
+    ///
+    /// Functions not declared in the program are called as native functions, with every argument passed by value.
+    fn compile_call(&mut self, name: &str, args: &[Expression]) -> Result<(), Error> {
+        if args.len() > u8::MAX as usize {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+        match self.functions.get(name).cloned() {
+            Some((index, passing)) => {
+                if passing.len() != args.len() {
+                    return Err(Error::WrongArgumentCount (name.to_string()));
+                }
+                for (passing, arg) in passing.iter().zip(args.iter()) {
+                    match (passing, arg) {
+                        // A reference shares the caller's variable
+                        (Passing::Reference, Expression::Identifier (id)) => self.compile_lvalue(id)?,
+                        (Passing::Reference, _) => return Err(Error::ExpectedReferenceArgument (name.to_string())),
+                        _ => self.compile_expr(arg)?,
+                    }
+                }
+                self.emit(Instruction::Call (index, args.len() as u8));
+            },
+            None => {
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                let name = self.name(name);
+                self.emit(Instruction::CallNative (name, args.len() as u8));
+            },
+        }
+        Ok(())
+    }
+}
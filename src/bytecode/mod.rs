@@ -0,0 +1,565 @@
+//! A compact bytecode format and stack-based virtual machine for Boron.
+//!
+//! Programs are compiled from parsed expressions by `compiler`, can be
+//! saved to and loaded from bytes, and are run by the `Vm` in `vm`.  Host
+//! Rust functions can be registered with the VM and called from Boron
+//! like any other function.
+
+
+pub mod compiler;
+pub mod vm;
+
+
+pub use compiler::compile;
+pub use vm::Vm;
+
+use crate::parser::{
+    Passing,
+    TokenType,
+};
+
+use crate::interpreter::Value;
+
+use crate::error::Error;
+
+
+/// Identifies a serialized Boron program.
+const MAGIC: &[u8; 4] = b"BRNB";
+
+/// The version of the serialized format, incremented whenever it changes.
+const FORMAT_VERSION: u16 = 1;
+
+
+/// Enumerates the operators that instructions can apply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Greater,
+    Less,
+    Equal,
+    GreaterEqual,
+    LessEqual,
+    Not,
+}
+
+/// Converts an operator token into an operator.
+impl TryFrom<TokenType> for Operator {
+    type Error = Error;
+
+    fn try_from(t: TokenType) -> Result<Operator, Error> {
+        match t {
+            TokenType::Plus => Ok(Operator::Plus),
+            TokenType::Minus => Ok(Operator::Minus),
+            TokenType::Multiply => Ok(Operator::Multiply),
+            TokenType::Divide => Ok(Operator::Divide),
+            TokenType::Greater => Ok(Operator::Greater),
+            TokenType::Less => Ok(Operator::Less),
+            TokenType::Equal => Ok(Operator::Equal),
+            TokenType::GreaterEqual => Ok(Operator::GreaterEqual),
+            TokenType::LessEqual => Ok(Operator::LessEqual),
+            TokenType::Not => Ok(Operator::Not),
+            _ => Err(Error::InvalidOperator (format!("{:?}", t))),
+        }
+    }
+}
+
+/// Converts an operator back into its token type.
+impl From<Operator> for TokenType {
+    fn from(o: Operator) -> TokenType {
+        match o {
+            Operator::Plus => TokenType::Plus,
+            Operator::Minus => TokenType::Minus,
+            Operator::Multiply => TokenType::Multiply,
+            Operator::Divide => TokenType::Divide,
+            Operator::Greater => TokenType::Greater,
+            Operator::Less => TokenType::Less,
+            Operator::Equal => TokenType::Equal,
+            Operator::GreaterEqual => TokenType::GreaterEqual,
+            Operator::LessEqual => TokenType::LessEqual,
+            Operator::Not => TokenType::Not,
+        }
+    }
+}
+
+
+/// Enumerates the instructions of the virtual machine.
+///
+/// Variables are accessed through references: `Local` and `Global` push a
+/// reference to a variable, `Field` follows it to a field, and `Load` and
+/// `Assign` read and write the variable it refers to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    // Push a constant, by index into the constant pool
+    Push (u32),
+    // Push a reference to a local variable, by slot
+    Local (u16),
+    // Push a reference to a global variable, by slot
+    Global (u16),
+    // Replace a reference to a struct with a reference to one of its fields, by name
+    Field (u32),
+    // Replace a reference with a copy of the value it refers to
+    Load,
+    // Pop a reference and then a value, and store the value in the variable referred to
+    Assign,
+    // Declare a local variable of the given type (by name), holding its zero value
+    Zero (u16, u32),
+    // Discard the value on top of the stack
+    Pop,
+    // Apply a unary operator
+    Unary (Operator),
+    // Apply a binary operator, reporting errors at the given line and column
+    Binary (Operator, u32, u32),
+    // Jump to the given instruction
+    Jump (u32),
+    // Pop a condition and jump to the given instruction if it is false
+    JumpIfFalse (u32),
+    // Call a function, by index, with the given number of arguments
+    Call (u32, u8),
+    // Call a native function, by name, with the given number of arguments
+    CallNative (u32, u8),
+    // Return the value on top of the stack
+    Return,
+    // Pop a value and print it
+    Print,
+    // Pop a reference to a heap pointer, and free it
+    Drop,
+    // Move the struct on top of the stack to the heap, replacing it with a pointer
+    Box,
+}
+
+
+/// Enumerates the constants that instructions can push.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constant {
+    Int (i32),
+    Float (f32),
+    Bool (bool),
+    Char (char),
+    Null,
+    Void,
+}
+
+/// Provides functions for the `Constant` enum.
+impl Constant {
+    /// Converts this constant into a runtime value.
+    pub fn to_value(self) -> Value {
+        match self {
+            Constant::Int (i) => Value::Int (i),
+            Constant::Float (f) => Value::Float (f),
+            Constant::Bool (b) => Value::Bool (b),
+            Constant::Char (c) => Value::Char (c),
+            Constant::Null => Value::Pointer (None),
+            Constant::Void => Value::Void,
+        }
+    }
+}
+
+
+/// Holds the declaration of a struct.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructDef {
+    // Names of the struct and of its fields' names and types
+    pub name: u32,
+    pub fields: Vec<(u32, u32)>,
+}
+
+
+/// Holds a compiled function.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    // Names of the function and of its parameters' and return types
+    pub name: u32,
+    pub params: Vec<(u32, Passing)>,
+    pub return_type: u32,
+    // Number of local variable slots, including parameters
+    pub locals: u16,
+    pub code: Vec<Instruction>,
+}
+
+
+/// Holds a compiled Boron program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    // Every name used by the program, referred to by index
+    pub names: Vec<String>,
+    pub constants: Vec<Constant>,
+    pub structs: Vec<StructDef>,
+    // Names and types of global variables, by slot
+    pub globals: Vec<(u32, u32)>,
+    pub functions: Vec<Function>,
+    // Index of the function that initializes global variables
+    pub init: u32,
+}
+
+/// Provides an interface for programs.
+impl Program {
+    /// Gets a name by index.
+    pub fn name(&self, index: u32) -> Result<&str, Error> {
+        match self.names.get(index as usize) {
+            Some(n) => Ok(n),
+            None => Err(Error::InvalidBytecode (format!("name {} out of range", index))),
+        }
+    }
+
+    /// Gets a function by index.
+    pub fn function(&self, index: usize) -> Result<&Function, Error> {
+        match self.functions.get(index) {
+            Some(f) => Ok(f),
+            None => Err(Error::InvalidBytecode (format!("function {} out of range", index))),
+        }
+    }
+
+    /// Finds a function by name.
+    pub fn find_function(&self, name: &str) -> Option<usize> {
+        self.functions.iter().position(|f| self.name(f.name) == Ok(name))
+    }
+
+    /// Serializes this program.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes.extend_from_slice(MAGIC);
+        w.u16(FORMAT_VERSION);
+
+        w.u32(self.names.len() as u32);
+        for name in self.names.iter() {
+            w.u32(name.len() as u32);
+            w.bytes.extend_from_slice(name.as_bytes());
+        }
+
+        w.u32(self.constants.len() as u32);
+        for constant in self.constants.iter() {
+            match constant {
+                Constant::Int (i) => {
+                    w.u8(0);
+                    w.u32(*i as u32);
+                },
+                Constant::Float (f) => {
+                    w.u8(1);
+                    w.u32(f.to_bits());
+                },
+                Constant::Bool (b) => {
+                    w.u8(2);
+                    w.u8(*b as u8);
+                },
+                Constant::Char (c) => {
+                    w.u8(3);
+                    w.u32(*c as u32);
+                },
+                Constant::Null => w.u8(4),
+                Constant::Void => w.u8(5),
+            }
+        }
+
+        w.u32(self.structs.len() as u32);
+        for s in self.structs.iter() {
+            w.u32(s.name);
+            w.u32(s.fields.len() as u32);
+            for (name, datatype) in s.fields.iter() {
+                w.u32(*name);
+                w.u32(*datatype);
+            }
+        }
+
+        w.u32(self.globals.len() as u32);
+        for (name, datatype) in self.globals.iter() {
+            w.u32(*name);
+            w.u32(*datatype);
+        }
+
+        w.u32(self.functions.len() as u32);
+        for f in self.functions.iter() {
+            w.u32(f.name);
+            w.u32(f.params.len() as u32);
+            for (datatype, passing) in f.params.iter() {
+                w.u32(*datatype);
+                w.u8(match passing {
+                    Passing::Value => 0,
+                    Passing::ConstReference => 1,
+                    Passing::Reference => 2,
+                });
+            }
+            w.u32(f.return_type);
+            w.u16(f.locals);
+            w.u32(f.code.len() as u32);
+            for instruction in f.code.iter() {
+                w.instruction(*instruction);
+            }
+        }
+
+        w.u32(self.init);
+        w.bytes
+    }
+
+    /// Deserializes a program.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, Error> {
+        let mut r = Reader {
+            bytes,
+            index: 0,
+        };
+        if r.take(4)? != MAGIC {
+            return Err(Error::InvalidBytecode ("not a Boron program".to_string()));
+        }
+        let version = r.u16()?;
+        if version != FORMAT_VERSION {
+            return Err(Error::InvalidBytecode (format!("unsupported format version {}", version)));
+        }
+
+        let mut program = Program::default();
+
+        for _ in 0..r.u32()? {
+            let length = r.u32()? as usize;
+            match String::from_utf8(r.take(length)?.to_vec()) {
+                Ok(s) => program.names.push(s),
+                Err(_) => return Err(Error::InvalidBytecode ("invalid name".to_string())),
+            }
+        }
+
+        for _ in 0..r.u32()? {
+            let constant = match r.u8()? {
+                0 => Constant::Int (r.u32()? as i32),
+                1 => Constant::Float (f32::from_bits(r.u32()?)),
+                2 => Constant::Bool (r.u8()? != 0),
+                3 => match char::from_u32(r.u32()?) {
+                    Some(c) => Constant::Char (c),
+                    None => return Err(Error::InvalidBytecode ("invalid character".to_string())),
+                },
+                4 => Constant::Null,
+                5 => Constant::Void,
+                t => return Err(Error::InvalidBytecode (format!("unknown constant tag {}", t))),
+            };
+            program.constants.push(constant);
+        }
+
+        for _ in 0..r.u32()? {
+            let name = r.u32()?;
+            let fields = (0..r.u32()?).map(|_| Ok((r.u32()?, r.u32()?))).collect::<Result<_, Error>>()?;
+            program.structs.push(StructDef {
+                name,
+                fields,
+            });
+        }
+
+        for _ in 0..r.u32()? {
+            let global = (r.u32()?, r.u32()?);
+            program.globals.push(global);
+        }
+
+        for _ in 0..r.u32()? {
+            let name = r.u32()?;
+            let mut params = Vec::new();
+            for _ in 0..r.u32()? {
+                let datatype = r.u32()?;
+                let passing = match r.u8()? {
+                    0 => Passing::Value,
+                    1 => Passing::ConstReference,
+                    2 => Passing::Reference,
+                    p => return Err(Error::InvalidBytecode (format!("unknown passing mode {}", p))),
+                };
+                params.push((datatype, passing));
+            }
+            let return_type = r.u32()?;
+            let locals = r.u16()?;
+            let code = (0..r.u32()?).map(|_| r.instruction()).collect::<Result<_, Error>>()?;
+            program.functions.push(Function {
+                name,
+                params,
+                return_type,
+                locals,
+                code,
+            });
+        }
+
+        program.init = r.u32()?;
+        if r.index != bytes.len() {
+            return Err(Error::InvalidBytecode ("trailing bytes".to_string()));
+        }
+
+        // Check every function index, so that a corrupted program is rejected before it runs
+        let count = program.functions.len();
+        if program.init as usize >= count {
+            return Err(Error::InvalidBytecode (format!("function {} out of range", program.init)));
+        }
+        for function in program.functions.iter() {
+            for instruction in function.code.iter() {
+                if let Instruction::Call (f, _) = instruction {
+                    if *f as usize >= count {
+                        return Err(Error::InvalidBytecode (format!("function {} out of range", f)));
+                    }
+                }
+            }
+        }
+        Ok(program)
+    }
+}
+
+
+/// Enumerates the opcodes of serialized instructions.
+const OPERATORS: [Operator; 10] = [
+    Operator::Plus,
+    Operator::Minus,
+    Operator::Multiply,
+    Operator::Divide,
+    Operator::Greater,
+    Operator::Less,
+    Operator::Equal,
+    Operator::GreaterEqual,
+    Operator::LessEqual,
+    Operator::Not,
+];
+
+
+/// Writes little-endian values into a byte buffer.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.bytes.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn operator(&mut self, o: Operator) {
+        // It's ok to use `unwrap` here because `OPERATORS` lists every operator
+        self.u8(OPERATORS.iter().position(|p| *p == o).unwrap() as u8);
+    }
+
+    /// Writes an opcode followed by its operands.
+    fn instruction(&mut self, instruction: Instruction) {
+        match instruction {
+            Instruction::Push (c) => {
+                self.u8(0);
+                self.u32(c);
+            },
+            Instruction::Local (s) => {
+                self.u8(1);
+                self.u16(s);
+            },
+            Instruction::Global (s) => {
+                self.u8(2);
+                self.u16(s);
+            },
+            Instruction::Field (n) => {
+                self.u8(3);
+                self.u32(n);
+            },
+            Instruction::Load => self.u8(4),
+            Instruction::Assign => self.u8(5),
+            Instruction::Zero (s, t) => {
+                self.u8(6);
+                self.u16(s);
+                self.u32(t);
+            },
+            Instruction::Pop => self.u8(7),
+            Instruction::Unary (o) => {
+                self.u8(8);
+                self.operator(o);
+            },
+            Instruction::Binary (o, line, column) => {
+                self.u8(9);
+                self.operator(o);
+                self.u32(line);
+                self.u32(column);
+            },
+            Instruction::Jump (t) => {
+                self.u8(10);
+                self.u32(t);
+            },
+            Instruction::JumpIfFalse (t) => {
+                self.u8(11);
+                self.u32(t);
+            },
+            Instruction::Call (f, n) => {
+                self.u8(12);
+                self.u32(f);
+                self.u8(n);
+            },
+            Instruction::CallNative (f, n) => {
+                self.u8(13);
+                self.u32(f);
+                self.u8(n);
+            },
+            Instruction::Return => self.u8(14),
+            Instruction::Print => self.u8(15),
+            Instruction::Drop => self.u8(16),
+            Instruction::Box => self.u8(17),
+        }
+    }
+}
+
+
+/// Reads little-endian values from a byte buffer.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        match self.bytes.get(self.index..self.index + n) {
+            Some(b) => {
+                self.index += n;
+                Ok(b)
+            },
+            None => Err(Error::InvalidBytecode ("unexpected end of program".to_string())),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn operator(&mut self) -> Result<Operator, Error> {
+        let index = self.u8()?;
+        match OPERATORS.get(index as usize) {
+            Some(o) => Ok(*o),
+            None => Err(Error::InvalidBytecode (format!("unknown operator {}", index))),
+        }
+    }
+
+    /// Reads an opcode followed by its operands.
+    fn instruction(&mut self) -> Result<Instruction, Error> {
+        match self.u8()? {
+            0 => Ok(Instruction::Push (self.u32()?)),
+            1 => Ok(Instruction::Local (self.u16()?)),
+            2 => Ok(Instruction::Global (self.u16()?)),
+            3 => Ok(Instruction::Field (self.u32()?)),
+            4 => Ok(Instruction::Load),
+            5 => Ok(Instruction::Assign),
+            6 => Ok(Instruction::Zero (self.u16()?, self.u32()?)),
+            7 => Ok(Instruction::Pop),
+            8 => Ok(Instruction::Unary (self.operator()?)),
+            9 => Ok(Instruction::Binary (self.operator()?, self.u32()?, self.u32()?)),
+            10 => Ok(Instruction::Jump (self.u32()?)),
+            11 => Ok(Instruction::JumpIfFalse (self.u32()?)),
+            12 => Ok(Instruction::Call (self.u32()?, self.u8()?)),
+            13 => Ok(Instruction::CallNative (self.u32()?, self.u8()?)),
+            14 => Ok(Instruction::Return),
+            15 => Ok(Instruction::Print),
+            16 => Ok(Instruction::Drop),
+            17 => Ok(Instruction::Box),
+            o => Err(Error::InvalidBytecode (format!("unknown opcode {}", o))),
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! A stack-based virtual machine that runs Boron bytecode.
+
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+};
+
+use crate::parser::{
+    Passing,
+    Span,
+};
+
+use crate::interpreter::{
+    binop,
+    unop,
+    Cell,
+    Value,
+};
+
+use crate::error::Error;
+
+use super::{
+    Instruction,
+    Program,
+};
+
+
+/// The deepest chain of function calls that will be run.
+const MAX_DEPTH: usize = 100_000;
+
+
+/// A host function that can be called from Boron, given its arguments by value.
+pub type Native = Box<dyn Fn(&[Value]) -> Value>;
+
+
+/// Enumerates the items on the operand stack.
+enum Operand {
+    Value (Value),
+    // Reference to a variable or struct field
+    Ref (Cell),
+}
+
+
+/// Holds the state of a function call.
+struct Frame {
+    function: usize,
+    // Index of the next instruction to run
+    pc: usize,
+    locals: Vec<Cell>,
+}
+
+
+/// Runs compiled Boron programs.
+pub struct Vm {
+    program: Program,
+    // Fields of each struct, by name
+    structs: HashMap<String, Vec<(String, String)>>,
+    globals: Vec<Cell>,
+    natives: HashMap<String, Native>,
+}
+
+/// Provides an interface for the virtual machine.
+impl Vm {
+    /// Loads a program, initializing its global variables.
+    pub fn new(program: Program) -> Result<Self, Error> {
+        let mut structs = HashMap::new();
+        for s in program.structs.iter() {
+            let mut fields = Vec::new();
+            for (f, t) in s.fields.iter() {
+                fields.push((program.name(*f)?.to_string(), program.name(*t)?.to_string()));
+            }
+            structs.insert(program.name(s.name)?.to_string(), fields);
+        }
+
+        let mut vm = Self {
+            program,
+            structs,
+            globals: Vec::new(),
+            natives: HashMap::new(),
+        };
+        vm.globals = vm.program.globals.iter()
+            .map(|(_, t)| Ok(new_cell(vm.zero(vm.program.name(*t)?)?)))
+            .collect::<Result<_, Error>>()?;
+        vm.execute(vm.program.init as usize, Vec::new())?;
+        Ok(vm)
+    }
+
+    /// Registers a host function, which Boron code can call by name like any other function.
+    ///
+    /// Functions declared in the program take precedence over natives of the same name.
+    pub fn register_native<F: Fn(&[Value]) -> Value + 'static>(&mut self, name: &str, function: F) {
+        self.natives.insert(name.to_string(), Box::new(function));
+    }
+
+    /// Runs the `main` function of the program and returns its exit code.
+    pub fn run(&self) -> Result<i32, Error> {
+        match self.call("main", Vec::new())? {
+            Value::Int (i) => Ok(i),
+            _ => Ok(0),
+        }
+    }
+
+    /// Calls a function of the program by name, passing each argument by value.
+    pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let function = match self.program.find_function(name) {
+            Some(f) => f,
+            None => return Err(Error::UndeclaredFunction (name.to_string())),
+        };
+        let args = args.into_iter().map(Operand::Value).collect();
+        let locals = self.bind(function, args)?;
+        self.execute(function, locals)
+    }
+
+    /// Constructs the zero value of a datatype, as used for uninitialized variables and fields.
+    fn zero(&self, datatype: &str) -> Result<Value, Error> {
+        match datatype {
+            "int" => Ok(Value::Int (0)),
+            "flt" => Ok(Value::Float (0.0)),
+            "bln" => Ok(Value::Bool (false)),
+            "chr" => Ok(Value::Char ('\0')),
+            "nul" => Ok(Value::Void),
+            _ if datatype.starts_with("own ") || datatype.starts_with("opt ") => Ok(Value::Pointer (None)),
+            _ => {
+                let structure = match self.structs.get(datatype) {
+                    Some(s) => s,
+                    None => return Err(Error::UndeclaredVariable (datatype.to_string())),
+                };
+                let fields = structure.iter()
+                    .map(|(f, t)| Ok((f.to_owned(), new_cell(self.zero(t)?))))
+                    .collect::<Result<_, Error>>()?;
+                Ok(Value::Struct (datatype.to_string(), fields))
+            },
+        }
+    }
+
+    /// Binds the arguments of a call to the local variables of the function being called.
+    fn bind(&self, function: usize, args: Vec<Operand>) -> Result<Vec<Cell>, Error> {
+        let f = self.program.function(function)?;
+        let name = self.program.name(f.name)?;
+        if f.params.len() != args.len() {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+
+        let mut locals = Vec::with_capacity(f.locals as usize);
+        for ((datatype, passing), arg) in f.params.iter().zip(args) {
+            let cell = match (passing, arg) {
+                // A reference shares the caller's variable
+                (Passing::Reference, Operand::Ref (r)) => r,
+                // A struct passed by constant reference may be given as a heap pointer
+                (Passing::ConstReference, arg) => match value(arg) {
+                    Value::Pointer (Some(p)) => new_cell(p.borrow().deep_copy()),
+                    Value::Pointer (None) => return Err(Error::NullDereference (name.to_string())),
+                    v => new_cell(v),
+                },
+                (_, arg) => new_cell(value(arg).coerce(self.program.name(*datatype)?)),
+            };
+            locals.push(cell);
+        }
+        while locals.len() < f.locals as usize {
+            locals.push(new_cell(Value::Void));
+        }
+        Ok(locals)
+    }
+
+    /// Runs a function until it returns.
+    fn execute(&self, function: usize, locals: Vec<Cell>) -> Result<Value, Error> {
+        let mut frames = vec![Frame {
+            function,
+            pc: 0,
+            locals,
+        }];
+        let mut stack: Vec<Operand> = Vec::new();
+
+        loop {
+            // It's ok to use `unwrap` here because returning from the last frame leaves the loop
+            let frame = frames.last_mut().unwrap();
+            let code = &self.program.function(frame.function)?.code;
+            let instruction = match code.get(frame.pc) {
+                Some(i) => *i,
+                None => return Err(Error::InvalidBytecode ("missing return".to_string())),
+            };
+            frame.pc += 1;
+
+            match instruction {
+                Instruction::Push (c) => match self.program.constants.get(c as usize) {
+                    Some(c) => stack.push(Operand::Value (c.to_value())),
+                    None => return Err(Error::InvalidBytecode (format!("constant {} out of range", c))),
+                },
+                Instruction::Local (l) => match frame.locals.get(l as usize) {
+                    Some(c) => stack.push(Operand::Ref (c.to_owned())),
+                    None => return Err(Error::InvalidBytecode (format!("local {} out of range", l))),
+                },
+                Instruction::Global (g) => match self.globals.get(g as usize) {
+                    Some(c) => stack.push(Operand::Ref (c.to_owned())),
+                    None => return Err(Error::InvalidBytecode (format!("global {} out of range", g))),
+                },
+                Instruction::Field (f) => {
+                    let field = self.program.name(f)?;
+                    let cell = reference(pop(&mut stack)?)?;
+                    let next = match &*cell.borrow() {
+                        Value::Struct (_, fields) => fields.get(field).cloned(),
+                        Value::Pointer (Some(p)) => match &*p.borrow() {
+                            Value::Struct (_, fields) => fields.get(field).cloned(),
+                            _ => None,
+                        },
+                        Value::Pointer (None) => return Err(Error::NullDereference (field.to_string())),
+                        _ => None,
+                    };
+                    match next {
+                        Some(c) => stack.push(Operand::Ref (c)),
+                        None => return Err(Error::UndeclaredVariable (field.to_string())),
+                    }
+                },
+                Instruction::Load => {
+                    let cell = reference(pop(&mut stack)?)?;
+                    let value = cell.borrow().deep_copy();
+                    stack.push(Operand::Value (value));
+                },
+                Instruction::Assign => {
+                    let cell = reference(pop(&mut stack)?)?;
+                    let new = value(pop(&mut stack)?);
+                    let new = cell.borrow().coerce_like(new);
+                    *cell.borrow_mut() = new;
+                },
+                Instruction::Zero (l, t) => {
+                    let zero = self.zero(self.program.name(t)?)?;
+                    match frame.locals.get_mut(l as usize) {
+                        Some(c) => *c = new_cell(zero),
+                        None => return Err(Error::InvalidBytecode (format!("local {} out of range", l))),
+                    }
+                },
+                Instruction::Pop => {
+                    pop(&mut stack)?;
+                },
+                Instruction::Unary (o) => {
+                    let v = value(pop(&mut stack)?);
+                    stack.push(Operand::Value (unop(o.into(), v)?));
+                },
+                Instruction::Binary (o, line, column) => {
+                    let right = value(pop(&mut stack)?);
+                    let left = value(pop(&mut stack)?);
+                    let span = Span {
+                        start: 0,
+                        end: 0,
+                        line: line as usize,
+                        column: column as usize,
+                    };
+                    stack.push(Operand::Value (binop(o.into(), left, right, &span)?));
+                },
+                Instruction::Jump (t) => frame.pc = t as usize,
+                Instruction::JumpIfFalse (t) => {
+                    if !value(pop(&mut stack)?).is_truthy()? {
+                        frame.pc = t as usize;
+                    }
+                },
+                Instruction::Call (f, n) => {
+                    let name = self.program.function(f as usize)?.name;
+                    if frames.len() >= MAX_DEPTH {
+                        return Err(Error::StackOverflow (self.program.name(name)?.to_string()));
+                    }
+                    let args = split(&mut stack, n)?;
+                    let locals = self.bind(f as usize, args)?;
+                    frames.push(Frame {
+                        function: f as usize,
+                        pc: 0,
+                        locals,
+                    });
+                },
+                Instruction::CallNative (f, n) => {
+                    let name = self.program.name(f)?;
+                    let native = match self.natives.get(name) {
+                        Some(n) => n,
+                        None => return Err(Error::UndeclaredFunction (name.to_string())),
+                    };
+                    let args = split(&mut stack, n)?.into_iter()
+                        .map(value)
+                        .collect::<Vec<Value>>();
+                    stack.push(Operand::Value (native(&args)));
+                },
+                Instruction::Return => {
+                    let return_type = self.program.function(frame.function)?.return_type;
+                    let result = value(pop(&mut stack)?).coerce(self.program.name(return_type)?);
+                    frames.pop();
+                    if frames.is_empty() {
+                        return Ok(result);
+                    }
+                    stack.push(Operand::Value (result));
+                },
+                Instruction::Print => value(pop(&mut stack)?).print()?,
+                Instruction::Drop => {
+                    let cell = reference(pop(&mut stack)?)?;
+                    if !matches!(*cell.borrow(), Value::Pointer (_)) {
+                        return Err(Error::ExpectedPointer ("drop".to_string()));
+                    }
+                    *cell.borrow_mut() = Value::Pointer (None);
+                },
+                Instruction::Box => {
+                    let v = value(pop(&mut stack)?);
+                    stack.push(Operand::Value (Value::Pointer (Some(new_cell(v)))));
+                },
+            }
+        }
+    }
+}
+
+
+/// Creates a new cell holding a value.
+fn new_cell(value: Value) -> Cell {
+    Rc::new(RefCell::new(value))
+}
+
+
+/// Pops an operand off the stack.
+fn pop(stack: &mut Vec<Operand>) -> Result<Operand, Error> {
+    match stack.pop() {
+        Some(o) => Ok(o),
+        None => Err(Error::InvalidBytecode ("stack underflow".to_string())),
+    }
+}
+
+
+/// Pops the given number of operands off the stack, in the order they were pushed.
+fn split(stack: &mut Vec<Operand>, n: u8) -> Result<Vec<Operand>, Error> {
+    if stack.len() < n as usize {
+        return Err(Error::InvalidBytecode ("stack underflow".to_string()));
+    }
+    Ok(stack.split_off(stack.len() - n as usize))
+}
+
+
+/// Gets the value of an operand, reading the variable a reference refers to.
+fn value(operand: Operand) -> Value {
+    match operand {
+        Operand::Value (v) => v,
+        Operand::Ref (r) => r.borrow().deep_copy(),
+    }
+}
+
+
+/// Gets the variable an operand refers to.
+fn reference(operand: Operand) -> Result<Cell, Error> {
+    match operand {
+        Operand::Ref (r) => Ok(r),
+        Operand::Value (_) => Err(Error::InvalidBytecode ("expected a reference".to_string())),
+    }
+}
@@ -0,0 +1,183 @@
+//! Records what a directory build compiled, so that later builds can skip files that have not changed.
+//!
+//! The cache lives in a `.boron-cache` directory inside the directory being
+//! built.  It holds a hash of each compiled file, along with the hash of each
+//! module that file `use`s.  A file is fresh if neither it nor any module it
+//! uses, directly or through other modules, has changed since it was compiled.
+//! A cache written by another version of the compiler, or for another target,
+//! is ignored.
+
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::error::Error;
+
+use crate::modules::{locate, normalize};
+
+use crate::parser::Expression;
+
+use crate::version::VERSION;
+
+
+/// The directory, inside the directory being built, that holds the cache.
+pub const CACHE_DIRECTORY: &str = ".boron-cache";
+
+/// The file, inside the cache directory, that describes the last build.
+const MANIFEST: &str = "manifest.json";
+
+
+/// Describes a compiled file as it was when it was compiled.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    // Hash of the source of the file
+    hash: String,
+    // Hash of the source of each module the file uses, by filename
+    uses: BTreeMap<String, String>,
+}
+
+
+/// Describes the last build of a directory.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    // Version of the compiler and target that produced the build
+    version: String,
+    target: String,
+    // Each compiled file, by filename
+    files: BTreeMap<String, Entry>,
+}
+
+
+/// Provides an abstraction over the build cache of a directory.
+pub struct Cache {
+    directory: PathBuf,
+    manifest: Manifest,
+}
+
+impl Cache {
+    /// Opens the cache of a directory, treating every file as stale if there is no usable cache.
+    pub fn open(directory: &str, target: &str) -> Self {
+        let mut cache = Self::empty(directory, target);
+        let manifest = read_to_string(cache.directory.join(MANIFEST))
+            .ok()
+            .and_then(|m| serde_json::from_str::<Manifest>(&m).ok());
+        if let Some(m) = manifest {
+            if m.version == VERSION && m.target == target {
+                cache.manifest = m;
+            }
+        }
+        cache
+    }
+
+    /// Constructs an empty cache for a directory, in which every file is stale.
+    pub fn empty(directory: &str, target: &str) -> Self {
+        Self {
+            directory: Path::new(directory).join(CACHE_DIRECTORY),
+            manifest: Manifest {
+                version: VERSION.to_string(),
+                target: target.to_string(),
+                files: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Checks if a file was compiled and has not changed since, along with every module it uses.
+    pub fn is_fresh(&self, filename: &str) -> bool {
+        self.fresh(&normalize(filename), &mut HashMap::new())
+    }
+
+    /// Checks if a file is fresh, remembering the answer for each file already checked.
+    fn fresh(&self, filename: &str, checked: &mut HashMap<String, bool>) -> bool {
+        if let Some(f) = checked.get(filename) {
+            return *f;
+        }
+        // A module that uses itself, through other modules, does not make itself stale
+        checked.insert(filename.to_string(), true);
+
+        let fresh = match self.manifest.files.get(filename) {
+            Some(entry) => hash_file(filename).as_ref() == Some(&entry.hash)
+                && entry.uses.iter().all(|(module, hash)| {
+                    hash_file(module).as_ref() == Some(hash)
+                        && (!self.manifest.files.contains_key(module) || self.fresh(module, checked))
+                }),
+            None => false,
+        };
+        checked.insert(filename.to_string(), fresh);
+        fresh
+    }
+
+    /// Records that a file was compiled from the given source.
+    pub fn record(&mut self, filename: &str, source: &str, expressions: &[Expression]) -> Result<(), Error> {
+        let uses = uses(filename, expressions)?.into_iter()
+            .map(|module| {
+                let hash = hash_file(&module).unwrap_or_default();
+                (module, hash)
+            })
+            .collect();
+        let entry = Entry {
+            hash: hash(source),
+            uses,
+        };
+        self.manifest.files.insert(normalize(filename), entry);
+        Ok(())
+    }
+
+    /// Forgets every file except those given, such as files that have been deleted.
+    pub fn retain(&mut self, filenames: &[String]) {
+        let filenames = filenames.iter().map(|f| normalize(f)).collect::<Vec<String>>();
+        self.manifest.files.retain(|f, _| filenames.contains(f));
+    }
+
+    /// Writes the cache to disk.
+    pub fn save(&self) -> Result<(), Error> {
+        let display = self.directory.display().to_string();
+        if create_dir_all(&self.directory).is_err() {
+            return Err(Error::CouldNotCreate (display));
+        }
+        let manifest = match serde_json::to_string_pretty(&self.manifest) {
+            Ok(m) => m,
+            Err(_) => return Err(Error::CouldNotWriteFile (display)),
+        };
+        if write(self.directory.join(MANIFEST), manifest).is_err() {
+            return Err(Error::CouldNotWriteFile (display));
+        }
+        Ok(())
+    }
+}
+
+
+/// Lists the source files of the modules used by the program in a file.
+pub fn uses(filename: &str, expressions: &[Expression]) -> Result<Vec<String>, Error> {
+    expressions.iter()
+        .filter_map(|e| match e {
+            Expression::Use (m) => Some(locate(m, filename)),
+            _ => None,
+        })
+        .collect()
+}
+
+
+/// Hashes source code with 64-bit FNV-1a, which gives the same hash on every machine and in every run.
+pub fn hash(source: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in source.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+
+/// Hashes the source of a file, if it can be read.
+fn hash_file(filename: &str) -> Option<String> {
+    read_to_string(filename).ok().map(|s| hash(&s))
+}
+
@@ -0,0 +1,296 @@
+//! Compile-time evaluation of constant expressions for the Boron compiler.
+
+
+use std::collections::HashMap;
+
+use crate::parser::{
+    Expression,
+    Span,
+    TokenType,
+};
+
+use crate::error::Error;
+
+
+/// The deepest chain of `const` function calls that will be evaluated.
+const MAX_DEPTH: usize = 256;
+
+/// The most loop iterations that will be evaluated in a single `const` function call.
+const MAX_ITERATIONS: usize = 1_000_000;
+
+
+/// Describes how control leaves a statement.
+enum Flow {
+    Next,
+    Return (Expression),
+}
+
+
+/// Describes why an expression could not be evaluated.
+enum Failure {
+    // The expression cannot be evaluated at compile time
+    NotConstant,
+    // Evaluating the expression failed, such as by overflowing
+    Error (Error),
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        Failure::Error (e)
+    }
+}
+
+
+/// Evaluates constant expressions and calls to `const` functions.
+pub struct Evaluator<'a> {
+    // Resolves the values of constants
+    constants: &'a dyn Fn(&String) -> Result<Option<Expression>, Error>,
+    // Resolves `const` function declarations
+    functions: &'a dyn Fn(&String) -> Result<Option<Expression>, Error>,
+}
+
+/// Provides an interface for evaluating expressions at compile time.
+impl<'a> Evaluator<'a> {
+    /// Constructs a new evaluator from lookups for constants and `const` functions.
+    pub fn new(
+        constants: &'a dyn Fn(&String) -> Result<Option<Expression>, Error>,
+        functions: &'a dyn Fn(&String) -> Result<Option<Expression>, Error>,
+    ) -> Self {
+        Self {
+            constants,
+            functions,
+        }
+    }
+
+    /// Evaluates a constant expression into a literal.
+    ///
+    /// Returns `None` if the expression cannot be evaluated at compile time.
+    /// Integer overflow and division by zero are reported as errors.
+    pub fn evaluate(&self, expr: &Expression) -> Result<Option<Expression>, Error> {
+        match self.eval(expr, &mut Vec::new(), 0) {
+            Ok(value) => Ok(Some(value)),
+            Err(Failure::NotConstant) => Ok(None),
+            Err(Failure::Error (e)) => Err(e),
+        }
+    }
+
+    /// Evaluates an expression given the local variables of the `const` functions being called.
+    fn eval(&self, expr: &Expression, locals: &mut Vec<HashMap<String, Expression>>, depth: usize) -> Result<Expression, Failure> {
+        match expr {
+            Expression::Int (_)
+            | Expression::Float (_)
+            | Expression::Bool (_)
+            | Expression::Char (_)
+            | Expression::Null => Ok(expr.to_owned()),
+            Expression::Identifier (i) => match locals.iter().rev().find_map(|l| l.get(i)) {
+                Some(v) => Ok(v.to_owned()),
+                // Function bodies cannot see anything but constants and their own locals
+                None => (self.constants)(i)?.ok_or(Failure::NotConstant),
+            },
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                span: s,
+            } => {
+                let value = self.eval(e, locals, depth)?;
+                match (o, value) {
+                    (TokenType::Minus, Expression::Int (i)) => match i.checked_neg() {
+                        Some(n) => Ok(Expression::Int (n)),
+                        None => Err(Error::IntegerOverflow (s.line, s.column).into()),
+                    },
+                    (TokenType::Minus, Expression::Float (f)) => Ok(Expression::Float (-f)),
+                    (TokenType::Not, Expression::Bool (b)) => Ok(Expression::Bool (!b)),
+                    _ => Err(Failure::NotConstant),
+                }
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                span: s,
+            } => {
+                let left = self.eval(l, locals, depth)?;
+                let right = self.eval(r, locals, depth)?;
+                evaluate_binop(*o, left, right, s)
+            },
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => match self.eval(c, locals, depth)? {
+                Expression::Bool (true) => self.eval(t, locals, depth),
+                Expression::Bool (false) => self.eval(f, locals, depth),
+                _ => Err(Failure::NotConstant),
+            },
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => {
+                let (arguments, body) = match (self.functions)(n)? {
+                    Some(Expression::FnDeclaration { arguments, body, .. }) => (arguments, body),
+                    _ => return Err(Failure::NotConstant),
+                };
+                if arguments.len() != a.len() || depth >= MAX_DEPTH {
+                    return Err(Failure::NotConstant);
+                }
+
+                // Bind each argument to its parameter in a fresh set of locals
+                let mut frame = HashMap::new();
+                for ((param, _, _), arg) in arguments.iter().zip(a.iter()) {
+                    frame.insert(param.to_owned(), self.eval(arg, locals, depth)?);
+                }
+                let mut callee = vec![frame];
+                match self.exec_block(&body, &mut callee, depth + 1)? {
+                    Flow::Return (v) => Ok(v),
+                    Flow::Next => Err(Failure::NotConstant),
+                }
+            },
+            _ => Err(Failure::NotConstant),
+        }
+    }
+
+    /// Executes the body of a block in a new scope.
+    fn exec_block(&self, body: &[Expression], locals: &mut Vec<HashMap<String, Expression>>, depth: usize) -> Result<Flow, Failure> {
+        locals.push(HashMap::new());
+        let mut flow = Ok(Flow::Next);
+        for statement in body {
+            flow = self.exec(statement, locals, depth);
+            if !matches!(flow, Ok(Flow::Next)) {
+                break;
+            }
+        }
+        locals.pop();
+        flow
+    }
+
+    /// Executes a single statement of a `const` function.
+    fn exec(&self, statement: &Expression, locals: &mut Vec<HashMap<String, Expression>>, depth: usize) -> Result<Flow, Failure> {
+        match statement {
+            Expression::Assignment {
+                identifier: i,
+                value: v,
+                ..
+            } | Expression::Const {
+                identifier: i,
+                value: v,
+                ..
+            } => {
+                let value = self.eval(v, locals, depth)?;
+                // It's ok to use `unwrap` here because every block pushes a set of locals
+                locals.last_mut().unwrap().insert(i.to_owned(), value);
+                Ok(Flow::Next)
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => {
+                let value = self.eval(v, locals, depth)?;
+                let local = match locals.iter_mut().rev().find_map(|l| l.get_mut(i)) {
+                    Some(l) => l,
+                    None => return Err(Failure::NotConstant),
+                };
+                *local = value;
+                Ok(Flow::Next)
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => match self.eval(c, locals, depth)? {
+                Expression::Bool (true) => self.exec_block(b, locals, depth),
+                Expression::Bool (false) => Ok(Flow::Next),
+                _ => Err(Failure::NotConstant),
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => match self.eval(c, locals, depth)? {
+                Expression::Bool (true) => self.exec_block(t, locals, depth),
+                Expression::Bool (false) => self.exec_block(f, locals, depth),
+                _ => Err(Failure::NotConstant),
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                for _ in 0..MAX_ITERATIONS {
+                    match self.eval(c, locals, depth)? {
+                        Expression::Bool (true) => (),
+                        Expression::Bool (false) => return Ok(Flow::Next),
+                        _ => return Err(Failure::NotConstant),
+                    }
+                    if let Flow::Return (v) = self.exec_block(b, locals, depth)? {
+                        return Ok(Flow::Return (v));
+                    }
+                }
+                Err(Failure::NotConstant)
+            },
+            Expression::Return (v) => Ok(Flow::Return (self.eval(v, locals, depth)?)),
+            Expression::FnCall { .. } => {
+                self.eval(statement, locals, depth)?;
+                Ok(Flow::Next)
+            },
+            _ => Err(Failure::NotConstant),
+        }
+    }
+}
+
+
+/// Evaluates a binary operation on two literals.
+fn evaluate_binop(op: TokenType, left: Expression, right: Expression, span: &Span) -> Result<Expression, Failure> {
+    match (left, right) {
+        (Expression::Int (l), Expression::Int (r)) => {
+            let result = match op {
+                TokenType::Plus => l.checked_add(r),
+                TokenType::Minus => l.checked_sub(r),
+                TokenType::Multiply => l.checked_mul(r),
+                TokenType::Divide => {
+                    if r == 0 {
+                        return Err(Error::DivisionByZero (span.line, span.column).into());
+                    }
+                    l.checked_div(r)
+                },
+                _ => return compare(op, l, r),
+            };
+            match result {
+                Some(i) => Ok(Expression::Int (i)),
+                None => Err(Error::IntegerOverflow (span.line, span.column).into()),
+            }
+        },
+        // Mixed arithmetic is promoted to floating-point, as in C
+        (Expression::Int (l), Expression::Float (r)) => evaluate_binop(op, Expression::Float (l as f32), Expression::Float (r), span),
+        (Expression::Float (l), Expression::Int (r)) => evaluate_binop(op, Expression::Float (l), Expression::Float (r as f32), span),
+        (Expression::Float (l), Expression::Float (r)) => match op {
+            TokenType::Plus => Ok(Expression::Float (l + r)),
+            TokenType::Minus => Ok(Expression::Float (l - r)),
+            TokenType::Multiply => Ok(Expression::Float (l * r)),
+            TokenType::Divide => {
+                if r == 0.0 {
+                    return Err(Error::DivisionByZero (span.line, span.column).into());
+                }
+                Ok(Expression::Float (l / r))
+            },
+            _ => compare(op, l, r),
+        },
+        (Expression::Char (l), Expression::Char (r)) => compare(op, l, r),
+        (Expression::Bool (l), Expression::Bool (r)) => match op {
+            TokenType::Equal => Ok(Expression::Bool (l == r)),
+            _ => Err(Failure::NotConstant),
+        },
+        _ => Err(Failure::NotConstant),
+    }
+}
+
+
+/// Evaluates a comparison between two values.
+fn compare<T: PartialOrd>(op: TokenType, left: T, right: T) -> Result<Expression, Failure> {
+    let result = match op {
+        TokenType::Greater => left > right,
+        TokenType::Less => left < right,
+        TokenType::Equal => left == right,
+        TokenType::GreaterEqual => left >= right,
+        TokenType::LessEqual => left <= right,
+        _ => return Err(Failure::NotConstant),
+    };
+    Ok(Expression::Bool (result))
+}
@@ -0,0 +1,288 @@
+//! Rewrites the parsed program into the smaller language that the backends compile.
+//!
+//! Method calls (`point.area(2)`) become calls with the receiver as their first
+//! argument (`area(point 2)`).  Deferred statements are copied to every place
+//! where their block is left: the end of the block, unless it ends in a return,
+//! and before each return inside it, whose value is computed first.  Every
+//! backend compiles the result, so none of them see a method call or a `defer`.
+
+
+use std::str::Split;
+
+use crate::parser::Expression;
+
+use crate::error::Error;
+
+
+/// Name of the variable that holds a return value while deferred statements run.
+const RETURN: &str = "boron_return";
+
+
+/// Rewrites the top-level expressions of a program.
+pub fn desugar(expressions: Vec<Expression>) -> Result<Vec<Expression>, Error> {
+    expressions.into_iter().map(top_level).collect()
+}
+
+
+/// Splits a variable or struct field path (`point.x`) into its variable and the fields that follow it.
+pub fn split_path(path: &str) -> (&str, Split<'_, char>) {
+    let mut fields = path.split('.');
+    // It's ok to use `unwrap` here because `split` always yields at least one item
+    let base = fields.next().unwrap();
+    (base, fields)
+}
+
+
+/// Rewrites a top-level expression, including the bodies of functions and tests.
+fn top_level(expression: Expression) -> Result<Expression, Error> {
+    match expression {
+        Expression::FnDeclaration {
+            identifier,
+            arguments,
+            return_type,
+            body,
+            doc,
+            constant,
+        } => {
+            let body = Function::new(&return_type).block(body)?;
+            Ok(Expression::FnDeclaration {
+                identifier,
+                arguments,
+                return_type,
+                body,
+                doc,
+                constant,
+            })
+        },
+        Expression::Test {
+            name,
+            body,
+        } => Ok(Expression::Test {
+            name,
+            body: Function::new("nul").block(body)?,
+        }),
+        Expression::Defer (_) => Err(Error::DeferOutsideFunction),
+        _ => expression_of(expression),
+    }
+}
+
+
+/// Rewrites the method calls within an expression.
+fn expression_of(expression: Expression) -> Result<Expression, Error> {
+    let boxed = |e: Box<Expression>| expression_of(*e).map(Box::new);
+    Ok(match expression {
+        Expression::FnCall {
+            name,
+            args,
+        } => {
+            let mut args = args.into_iter().map(expression_of).collect::<Result<Vec<Expression>, Error>>()?;
+            // `struct.method(args)` is a call to `method(struct, args)`
+            let names = name.split('.').collect::<Vec<&str>>();
+            match names.len() {
+                1 => Expression::FnCall {
+                    name,
+                    args,
+                },
+                2 => {
+                    args.insert(0, Expression::Identifier (names[0].to_string()));
+                    Expression::FnCall {
+                        name: names[1].to_string(),
+                        args,
+                    }
+                },
+                _ => return Err(Error::TooManyLeadingKeywords (name)),
+            }
+        },
+        Expression::UnaryOp {
+            op,
+            expr,
+            span,
+        } => Expression::UnaryOp {
+            op,
+            expr: boxed(expr)?,
+            span,
+        },
+        Expression::BinOp {
+            left,
+            op,
+            right,
+            span,
+        } => Expression::BinOp {
+            left: boxed(left)?,
+            op,
+            right: boxed(right)?,
+            span,
+        },
+        Expression::StructInit {
+            variables,
+        } => Expression::StructInit {
+            variables: variables.into_iter()
+                .map(|(f, e)| Ok((f, expression_of(e)?)))
+                .collect::<Result<_, Error>>()?,
+        },
+        Expression::New {
+            datatype,
+            variables,
+        } => Expression::New {
+            datatype,
+            variables: variables.into_iter()
+                .map(|(f, e)| Ok((f, expression_of(e)?)))
+                .collect::<Result<_, Error>>()?,
+        },
+        Expression::Assignment {
+            datatype,
+            identifier,
+            value,
+        } => Expression::Assignment {
+            datatype,
+            identifier,
+            value: boxed(value)?,
+        },
+        Expression::Const {
+            datatype,
+            identifier,
+            value,
+        } => Expression::Const {
+            datatype,
+            identifier,
+            value: boxed(value)?,
+        },
+        Expression::Static {
+            datatype,
+            identifier,
+            value,
+        } => Expression::Static {
+            datatype,
+            identifier,
+            value: boxed(value)?,
+        },
+        Expression::Reassignment {
+            identifier,
+            value,
+        } => Expression::Reassignment {
+            identifier,
+            value: boxed(value)?,
+        },
+        Expression::TernaryIfElse {
+            condition,
+            body_true,
+            body_false,
+        } => Expression::TernaryIfElse {
+            condition: boxed(condition)?,
+            body_true: boxed(body_true)?,
+            body_false: boxed(body_false)?,
+        },
+        Expression::Assert {
+            condition,
+            span,
+        } => Expression::Assert {
+            condition: boxed(condition)?,
+            span,
+        },
+        Expression::AssertEq {
+            left,
+            right,
+            span,
+        } => Expression::AssertEq {
+            left: boxed(left)?,
+            right: boxed(right)?,
+            span,
+        },
+        _ => expression,
+    })
+}
+
+
+/// Holds the statements deferred in each open block of a function body.
+struct Function<'a> {
+    return_type: &'a str,
+    deferred: Vec<Vec<Expression>>,
+}
+
+impl<'a> Function<'a> {
+    /// Starts rewriting the body of a function with the given return type.
+    fn new(return_type: &'a str) -> Self {
+        Self {
+            return_type,
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Rewrites a block, then appends its deferred statements in reverse order.
+    fn block(&mut self, body: Vec<Expression>) -> Result<Vec<Expression>, Error> {
+        self.deferred.push(Vec::new());
+        let mut statements = Vec::new();
+        for statement in body {
+            self.statement(statement, &mut statements)?;
+        }
+
+        // It's ok to use `unwrap` here because a block was pushed above
+        let deferred = self.deferred.pop().unwrap();
+        // A block ending in a return has already run its deferred statements
+        if !matches!(statements.last(), Some(Expression::Return (_))) {
+            statements.extend(deferred.into_iter().rev());
+        }
+        Ok(statements)
+    }
+
+    /// Rewrites a statement into the statements that replace it.
+    fn statement(&mut self, statement: Expression, statements: &mut Vec<Expression>) -> Result<(), Error> {
+        match statement {
+            Expression::Defer (d) => {
+                let mut deferred = Vec::new();
+                self.statement(*d, &mut deferred)?;
+                // It's ok to use `unwrap` here because statements are only rewritten inside a block
+                self.deferred.last_mut().unwrap().extend(deferred);
+            },
+            Expression::Return (v) => {
+                let value = expression_of(*v)?;
+                // Deferred statements run innermost first, up to the function boundary
+                let unwind = self.deferred.iter()
+                    .rev()
+                    .flat_map(|d| d.iter().rev().cloned())
+                    .collect::<Vec<Expression>>();
+                if unwind.is_empty() {
+                    statements.push(Expression::Return (Box::new(value)));
+                } else if self.return_type == "nul" {
+                    // Nothing is returned, so the value is only computed for its effects
+                    statements.push(value);
+                    statements.extend(unwind);
+                    statements.push(Expression::Return (Box::new(Expression::Int (0))));
+                } else {
+                    statements.push(Expression::Assignment {
+                        datatype: self.return_type.to_string(),
+                        identifier: RETURN.to_string(),
+                        value: Box::new(value),
+                    });
+                    statements.extend(unwind);
+                    statements.push(Expression::Return (Box::new(Expression::Identifier (RETURN.to_string()))));
+                }
+            },
+            Expression::While {
+                condition,
+                body,
+            } => statements.push(Expression::While {
+                condition: Box::new(expression_of(*condition)?),
+                body: self.block(body)?,
+            }),
+            Expression::If {
+                condition,
+                body,
+            } => statements.push(Expression::If {
+                condition: Box::new(expression_of(*condition)?),
+                body: self.block(body)?,
+            }),
+            Expression::IfElse {
+                condition,
+                body_true,
+                body_false,
+            } => statements.push(Expression::IfElse {
+                condition: Box::new(expression_of(*condition)?),
+                body_true: self.block(body_true)?,
+                body_false: self.block(body_false)?,
+            }),
+            _ => statements.push(expression_of(statement)?),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Dumps the tokens and syntax tree of a program for debugging and for external tools.
+//!
+//! `tokens` and `ast` produce JSON in a stable format.  Every span is an object with
+//! the byte offsets `start` and `end` and the `line` and `column` (both starting at 1)
+//! of its first character.
+//!
+//! - `tokens` is an array of objects with the token's `type` (the name of its
+//!   `TokenType`), its `value` as written in the source, its `span`, and its `doc`
+//!   comment if one precedes it.  Whitespace and comments are not included.
+//! - `ast` is an array of objects, one per top-level declaration, with the `span` of
+//!   its source text and its `expression`.  Expressions are written in serde's
+//!   externally tagged form: `"Null"`, `{"Int": 1}`, or `{"BinOp": {...}}`.  Unary
+//!   and binary operations hold the `span` of their operator.
+//!
+//! `tree` prints the syntax tree in a readable, indented form.
+
+
+use std::slice;
+
+use serde::Serialize;
+
+use crate::error::Error;
+
+use crate::parser::{
+    Expression,
+    Parser,
+    Passing,
+    Span,
+    Tokenizer,
+};
+
+
+/// Indents each level of the tree view.
+const INDENT: &str = "  ";
+
+
+/// Describes a top-level expression and where it was found, for serialization.
+#[derive(Serialize)]
+struct Item<'a> {
+    span: Span,
+    expression: &'a Expression,
+}
+
+
+/// Writes a value as indented JSON.
+fn to_json(value: &impl Serialize, kind: &str) -> Result<String, Error> {
+    match serde_json::to_string_pretty(value) {
+        Ok(mut json) => {
+            json.push('\n');
+            Ok(json)
+        },
+        Err(_) => Err(Error::CouldNotEmit (kind.to_string())),
+    }
+}
+
+
+/// Dumps the tokens of a program as JSON.
+pub fn tokens(source: &str) -> Result<String, Error> {
+    let tokens = Tokenizer::new(source).collect::<Result<Vec<_>, Error>>()?;
+    to_json(&tokens, "tokens")
+}
+
+
+/// Dumps the syntax tree of a program as JSON.
+pub fn ast(source: &str) -> Result<String, Error> {
+    let mut tokenizer = Tokenizer::new(source);
+    let expressions = Parser::new().parse_spanned(&mut tokenizer)?;
+
+    let items = expressions.iter()
+        .map(|(span, expression)| Item {
+            span: *span,
+            expression,
+        })
+        .collect::<Vec<Item>>();
+    to_json(&items, "syntax tree")
+}
+
+
+/// Dumps the syntax tree of a program in a readable, indented form.
+///
+/// Each top-level declaration and operator is followed by its line and column.
+pub fn tree(source: &str) -> Result<String, Error> {
+    let mut tokenizer = Tokenizer::new(source);
+    let expressions = Parser::new().parse_spanned(&mut tokenizer)?;
+
+    let mut output = String::new();
+    for (span, expression) in &expressions {
+        let mut lines = String::new();
+        node(expression, 0, &mut lines);
+        // The location goes on the first line of the declaration
+        match lines.split_once('\n') {
+            Some((first, rest)) => {
+                output.push_str(&format!("{} @ {}:{}\n{}", first, span.line, span.column, rest));
+            },
+            None => output.push_str(&lines),
+        }
+    }
+    Ok(output)
+}
+
+
+/// Writes one line of the tree view.
+fn line(text: &str, depth: usize, output: &mut String) {
+    output.push_str(&INDENT.repeat(depth));
+    output.push_str(text);
+    output.push('\n');
+}
+
+
+/// Writes a labelled group of expressions in the tree view.
+fn group(label: &str, expressions: &[Expression], depth: usize, output: &mut String) {
+    line(label, depth, output);
+    for expression in expressions {
+        node(expression, depth + 1, output);
+    }
+}
+
+
+/// Writes an expression and the expressions within it in the tree view.
+fn node(expression: &Expression, depth: usize, output: &mut String) {
+    match expression {
+        Expression::Int (i) => line(&format!("Int {}", i), depth, output),
+        Expression::Float (f) => line(&format!("Float {:?}", f), depth, output),
+        Expression::Bool (b) => line(&format!("Bool {}", b), depth, output),
+        Expression::Char (c) => line(&format!("Char {:?}", c), depth, output),
+        Expression::Null => line("Null", depth, output),
+        Expression::Identifier (s) => line(&format!("Identifier {}", s), depth, output),
+        Expression::Type (s) => line(&format!("Type {}", s), depth, output),
+        Expression::UnaryOp { op, expr, span } => {
+            line(&format!("UnaryOp {:?} @ {}:{}", op, span.line, span.column), depth, output);
+            node(expr, depth + 1, output);
+        },
+        Expression::BinOp { left, op, right, span } => {
+            line(&format!("BinOp {:?} @ {}:{}", op, span.line, span.column), depth, output);
+            node(left, depth + 1, output);
+            node(right, depth + 1, output);
+        },
+        Expression::Declaration { datatype, identifier } => {
+            line(&format!("Declaration {} {}", datatype, identifier), depth, output);
+        },
+        Expression::Struct { identifier, variables, .. } => {
+            line(&format!("Struct {}", identifier), depth, output);
+            let mut fields = variables.iter().collect::<Vec<_>>();
+            fields.sort();
+            for (field, datatype) in fields {
+                line(&format!("{} {}", datatype, field), depth + 1, output);
+            }
+        },
+        Expression::StructInit { variables } => {
+            line("StructInit", depth, output);
+            fields(variables.iter().collect(), depth + 1, output);
+        },
+        Expression::New { datatype, variables } => {
+            line(&format!("New {}", datatype), depth, output);
+            fields(variables.iter().collect(), depth + 1, output);
+        },
+        Expression::Assignment { datatype, identifier, value } => {
+            line(&format!("Assignment {} {}", datatype, identifier), depth, output);
+            node(value, depth + 1, output);
+        },
+        Expression::Const { datatype, identifier, value } => {
+            line(&format!("Const {} {}", datatype, identifier), depth, output);
+            node(value, depth + 1, output);
+        },
+        Expression::Static { datatype, identifier, value } => {
+            line(&format!("Static {} {}", datatype, identifier), depth, output);
+            node(value, depth + 1, output);
+        },
+        Expression::Reassignment { identifier, value } => {
+            line(&format!("Reassignment {}", identifier), depth, output);
+            node(value, depth + 1, output);
+        },
+        Expression::FnCall { name, args } => group(&format!("FnCall {}", name), args, depth, output),
+        Expression::While { condition, body } => {
+            line("While", depth, output);
+            group("condition", slice::from_ref(condition), depth + 1, output);
+            group("body", body, depth + 1, output);
+        },
+        Expression::If { condition, body } => {
+            line("If", depth, output);
+            group("condition", slice::from_ref(condition), depth + 1, output);
+            group("body", body, depth + 1, output);
+        },
+        Expression::IfElse { condition, body_true, body_false } => {
+            line("IfElse", depth, output);
+            group("condition", slice::from_ref(condition), depth + 1, output);
+            group("then", body_true, depth + 1, output);
+            group("else", body_false, depth + 1, output);
+        },
+        Expression::TernaryIfElse { condition, body_true, body_false } => {
+            line("TernaryIfElse", depth, output);
+            group("condition", slice::from_ref(condition), depth + 1, output);
+            group("then", slice::from_ref(body_true), depth + 1, output);
+            group("else", slice::from_ref(body_false), depth + 1, output);
+        },
+        Expression::FnDeclaration { identifier, arguments, return_type, body, constant, .. } => {
+            let mut signature = match constant {
+                true => format!("FnDeclaration const {}", identifier),
+                false => format!("FnDeclaration {}", identifier),
+            };
+            if !arguments.is_empty() {
+                signature.push_str(" ::");
+            }
+            for (name, datatype, passing) in arguments {
+                match passing {
+                    Passing::Reference => signature.push_str(&format!(" ref {} {}", datatype, name)),
+                    _ => signature.push_str(&format!(" {} {}", datatype, name)),
+                }
+            }
+            signature.push_str(&format!(" -> {}", return_type));
+            group(&signature, body, depth, output);
+        },
+        Expression::Return (value) => group("Return", slice::from_ref(value), depth, output),
+        Expression::Defer (value) => group("Defer", slice::from_ref(value), depth, output),
+        Expression::Use (module) => line(&format!("Use {}", module), depth, output),
+        Expression::Test { name, body } => group(&format!("Test {:?}", name), body, depth, output),
+        Expression::Assert { condition, span } => {
+            line(&format!("Assert @ {}:{}", span.line, span.column), depth, output);
+            node(condition, depth + 1, output);
+        },
+        Expression::AssertEq { left, right, span } => {
+            line(&format!("AssertEq @ {}:{}", span.line, span.column), depth, output);
+            node(left, depth + 1, output);
+            node(right, depth + 1, output);
+        },
+    }
+}
+
+
+/// Writes the fields of a struct initialization in order in the tree view.
+fn fields(mut variables: Vec<(&String, &Expression)>, depth: usize, output: &mut String) {
+    variables.sort_by(|a, b| a.0.cmp(b.0));
+    for (field, value) in variables {
+        group(field, slice::from_ref(value), depth, output);
+    }
+}
@@ -2,9 +2,11 @@
 
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     env,
     ffi::OsStr,
+    fmt,
+    fs::read_to_string,
 };
 
 use chrono::{
@@ -17,13 +19,76 @@ use crate::version::VERSION;
 
 use crate::parser::{
     Expression,
+    Parser,
+    Passing,
     TokenType,
+    Tokenizer,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
+
+use crate::consteval::Evaluator;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::modules::locate;
+
+
+/// Runtime support emitted into every executable and library.
+///
+/// The guard allows several Boron libraries to be included in the same program.
+const RUNTIME: &str = "// Runtime begins here
+#ifndef BORON_RUNTIME
+#define BORON_RUNTIME
+static inline void *boron_new(size_t size, const void *value) {
+void *pointer = malloc(size);
+if (pointer == NULL) {
+fprintf(stderr, \"Out of memory\\n\");
+exit(1);
+}
+memcpy(pointer, value, size);
+return pointer;
+}
+static inline void boron_assert(bool holds, const char *file, int line, const char *message) {
+if (!holds) {
+fflush(stdout);
+fprintf(stderr, \"%s:%d: %s\\n\", file, line, message);
+exit(1);
+}
+}
+#endif
+";
+
+
+/// Runs each test in its own process, so that a failed assertion only ends that test.
+///
+/// The tests are listed in `boron_tests` and their names in `boron_test_names`.  The
+/// exit code is the number of tests that failed.
+const HARNESS: &str = "// Test harness begins here
+#include <unistd.h>
+#include <sys/wait.h>
+int main(void) {
+int failed = 0;
+for (int i = 0; boron_tests[i] != NULL; i++) {
+fflush(stdout);
+fflush(stderr);
+pid_t pid = fork();
+if (pid == 0) {
+boron_tests[i]();
+fflush(stdout);
+exit(0);
+}
+int status = 0;
+if (pid < 0 || waitpid(pid, &status, 0) < 0 || !WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+printf(\"test %s ... FAILED\\n\", boron_test_names[i]);
+failed++;
+} else {
+printf(\"test %s ... ok\\n\", boron_test_names[i]);
+}
+}
+return failed > 255 ? 255 : failed;
+}
+";
 
 
 /// Holds a list of variable scopes.
@@ -32,6 +97,12 @@ pub struct Environment {
     scopes: Vec<Scope>,
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Provides an interface for the environment.
 impl Environment {
     /// Constructs a new environment.
@@ -45,7 +116,13 @@ impl Environment {
     pub fn add(&mut self, parent: Option<usize>) -> usize {
         let new = Scope {
             variables: HashMap::new(),
+            references: HashSet::new(),
+            moved: HashSet::new(),
             structs: HashMap::new(),
+            functions: HashMap::new(),
+            constants: HashMap::new(),
+            const_fns: HashMap::new(),
+            return_type: None,
             id: self.scopes.len(),
             parent,
         };
@@ -54,56 +131,207 @@ impl Environment {
         id
     }
 
+    /// Gets the scope with the given ID.
+    fn scope(&self, id: usize) -> Result<&Scope, Error> {
+        match self.scopes.get(id) {
+            Some(s) => Ok(s),
+            None => Err(Error::InvalidScope (id)),
+        }
+    }
+
+    /// Gets the scope with the given ID for modification.
+    fn scope_mut(&mut self, id: usize) -> Result<&mut Scope, Error> {
+        match self.scopes.get_mut(id) {
+            Some(s) => Ok(s),
+            None => Err(Error::InvalidScope (id)),
+        }
+    }
+
     /// Registers a variable within the given scope.
-    pub fn register(&mut self, id: usize, varname: String, variable: Variable) {
-        self.scopes[id].register(varname, variable);
+    pub fn register(&mut self, id: usize, varname: String, variable: Variable) -> Result<(), Error> {
+        self.scope_mut(id)?.register(varname, variable);
+        Ok(())
     }
 
     /// Registers a structure within the given scope.
-    pub fn register_struct(&mut self, id: usize, varname: String, structure: HashMap<String, Variable>) {
-        self.scopes[id].register_struct(varname, structure);
+    pub fn register_struct(&mut self, id: usize, varname: String, structure: HashMap<String, Variable>) -> Result<(), Error> {
+        self.scope_mut(id)?.register_struct(varname, structure);
+        Ok(())
+    }
+
+    /// Marks a variable within the given scope as a pointer to its value.
+    pub fn register_reference(&mut self, id: usize, varname: String) -> Result<(), Error> {
+        self.scope_mut(id)?.register_reference(varname);
+        Ok(())
+    }
+
+    /// Marks a heap pointer as moved, or as holding a value again, in the scope that declares it.
+    pub fn register_move(&mut self, id: usize, varname: String, moved: bool) -> Result<(), Error> {
+        let scope = self.scope(id)?;
+        match (scope.get(&varname), scope.get_parent()) {
+            (Some(_), _) => {
+                self.scope_mut(id)?.register_move(varname, moved);
+                Ok(())
+            },
+            (None, Some(p)) => self.register_move(p, varname, moved),
+            (None, None) => Err(Error::UndeclaredVariable (varname)),
+        }
+    }
+
+    /// Registers a function signature within the given scope.
+    pub fn register_fn(&mut self, id: usize, name: String, signature: Signature) -> Result<(), Error> {
+        self.scope_mut(id)?.register_fn(name, signature);
+        Ok(())
+    }
+
+    /// Registers the value of a compile-time constant within the given scope.
+    pub fn register_const(&mut self, id: usize, name: String, value: Expression) -> Result<(), Error> {
+        self.scope_mut(id)?.register_const(name, value);
+        Ok(())
+    }
+
+    /// Registers the declaration of a `const` function within the given scope.
+    pub fn register_const_fn(&mut self, id: usize, name: String, declaration: Expression) -> Result<(), Error> {
+        self.scope_mut(id)?.register_const_fn(name, declaration);
+        Ok(())
+    }
+
+    /// Marks the given scope as the outermost scope of a function body.
+    pub fn register_return_type(&mut self, id: usize, return_type: String) -> Result<(), Error> {
+        self.scope_mut(id)?.register_return_type(return_type);
+        Ok(())
+    }
+
+    /// Gets the return type of the function enclosing the given scope, if any.
+    pub fn lookup_return_type(&self, id: usize) -> Result<Option<String>, Error> {
+        let scope = self.scope(id)?;
+        if let Some(r) = scope.get_return_type() {
+            return Ok(Some(r.to_owned()));
+        }
+        match scope.get_parent() {
+            Some(p) => self.lookup_return_type(p),
+            None => Ok(None),
+        }
     }
 
     /// Looks up a variable in the given scope.
-    pub fn lookup(&self, id: usize, varname: &String) -> Variable {
-        match self.scopes[id].get(varname) {
-            Some(s) => s.to_owned(),
-            None => match self.scopes[id].get_parent() {
+    pub fn lookup(&self, id: usize, varname: &String) -> Result<Variable, Error> {
+        let scope = self.scope(id)?;
+        match scope.get(varname) {
+            Some(s) => Ok(s.to_owned()),
+            None => match scope.get_parent() {
                 Some(p) => self.lookup(p, varname),
-                None => throw(Error::UndeclaredVariable (varname.to_string())),
+                None => Err(Error::UndeclaredVariable (varname.to_string())),
             }
         }
     }
 
     /// Checks if a variable exists in the given scope.
-    pub fn check(&self, id: usize, varname: &String) -> bool {
-        match self.scopes[id].get(varname) {
-            Some(_) => true,
-            None => match self.scopes[id].get_parent() {
+    pub fn check(&self, id: usize, varname: &String) -> Result<bool, Error> {
+        let scope = self.scope(id)?;
+        match scope.get(varname) {
+            Some(_) => Ok(true),
+            None => match scope.get_parent() {
                 Some(p) => self.check(p, varname),
-                None => false,
+                None => Ok(false),
+            }
+        }
+    }
+
+    /// Checks if a variable is held by reference (as a pointer) in the given scope.
+    ///
+    /// Only the innermost declaration of the variable is considered, so a local
+    /// variable shadowing a reference parameter is not a reference.
+    pub fn is_reference(&self, id: usize, varname: &String) -> Result<bool, Error> {
+        let scope = self.scope(id)?;
+        match scope.get(varname) {
+            Some(_) => Ok(scope.is_reference(varname)),
+            None => match scope.get_parent() {
+                Some(p) => self.is_reference(p, varname),
+                None => Ok(false),
+            }
+        }
+    }
+
+    /// Checks if a heap pointer has been moved out of in the given scope.
+    pub fn is_moved(&self, id: usize, varname: &String) -> Result<bool, Error> {
+        let scope = self.scope(id)?;
+        match scope.get(varname) {
+            Some(_) => Ok(scope.is_moved(varname)),
+            None => match scope.get_parent() {
+                Some(p) => self.is_moved(p, varname),
+                None => Ok(false),
+            }
+        }
+    }
+
+    /// Looks up a function signature in the given scope.
+    /// Functions declared outside of Boron (such as those from C headers) have no signature.
+    pub fn lookup_fn(&self, id: usize, name: &String) -> Result<Option<Signature>, Error> {
+        let scope = self.scope(id)?;
+        match scope.get_fn(name) {
+            Some(s) => Ok(Some(s.to_owned())),
+            None => match scope.get_parent() {
+                Some(p) => self.lookup_fn(p, name),
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// Looks up the value of a compile-time constant in the given scope.
+    ///
+    /// Returns `None` if the innermost declaration of the name is not a constant.
+    pub fn lookup_const(&self, id: usize, name: &String) -> Result<Option<Expression>, Error> {
+        let scope = self.scope(id)?;
+        if let Some(c) = scope.get_const(name) {
+            return Ok(Some(c.to_owned()));
+        }
+        if scope.get(name).is_some() {
+            return Ok(None);
+        }
+        match scope.get_parent() {
+            Some(p) => self.lookup_const(p, name),
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up the declaration of a `const` function in the given scope.
+    pub fn lookup_const_fn(&self, id: usize, name: &String) -> Result<Option<Expression>, Error> {
+        let scope = self.scope(id)?;
+        match scope.get_const_fn(name) {
+            Some(f) => Ok(Some(f.to_owned())),
+            None => match scope.get_parent() {
+                Some(p) => self.lookup_const_fn(p, name),
+                None => Ok(None),
             }
         }
     }
 
+    /// Checks if the given scope is the top level of a file.
+    pub fn is_global(&self, id: usize) -> Result<bool, Error> {
+        Ok(self.scope(id)?.get_parent().is_none())
+    }
+
     /// Looks up a structure in the given scope.
-    pub fn lookup_struct(&self, id: usize, varname: &String) -> HashMap<String, Variable> {
-        match self.scopes[id].get_struct(varname) {
-            Some(s) => s.to_owned(),
-            None => match self.scopes[id].get_parent() {
+    pub fn lookup_struct(&self, id: usize, varname: &String) -> Result<HashMap<String, Variable>, Error> {
+        let scope = self.scope(id)?;
+        match scope.get_struct(varname) {
+            Some(s) => Ok(s.to_owned()),
+            None => match scope.get_parent() {
                 Some(p) => self.lookup_struct(p, varname),
-                None => throw(Error::UndeclaredVariable (varname.to_string())),
+                None => Err(Error::UndeclaredVariable (varname.to_string())),
             }
         }
     }
 
     /// Checks if a structure exists in the given scope.
-    pub fn check_struct(&self, id: usize, varname: &String) -> bool {
-        match self.scopes[id].get_struct(varname) {
-            Some(_) => true,
-            None => match self.scopes[id].get_parent() {
+    pub fn check_struct(&self, id: usize, varname: &String) -> Result<bool, Error> {
+        let scope = self.scope(id)?;
+        match scope.get_struct(varname) {
+            Some(_) => Ok(true),
+            None => match scope.get_parent() {
                 Some(p) => self.check_struct(p, varname),
-                None => false,
+                None => Ok(false),
             }
         }
     }
@@ -119,6 +347,10 @@ pub enum Variable {
     Char,
     Void,
     Struct (String),
+    // Owning pointer to a heap-allocated struct
+    Owned (String),
+    // Nullable owning pointer to a heap-allocated struct
+    Nullable (String),
 }
 
 /// Provides an interface for functions on variable types.
@@ -131,12 +363,31 @@ impl Variable {
             "bln" => Variable::Bool,
             "chr" => Variable::Char,
             "nul" => Variable::Void,
-            _ => Variable::Struct (string.to_string()),
+            _ => match string.split_once(' ') {
+                Some(("own", s)) => Variable::Owned (s.to_string()),
+                Some(("opt", s)) => Variable::Nullable (s.to_string()),
+                _ => Variable::Struct (string.to_string()),
+            },
         }
     }
+
+    /// Gets the name of the struct this variable holds or points to, if any.
+    pub fn get_struct(&self) -> Option<&String> {
+        match self {
+            Variable::Struct (s)
+            | Variable::Owned (s)
+            | Variable::Nullable (s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Checks if this variable is a pointer to a heap-allocated struct.
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Variable::Owned (_) | Variable::Nullable (_))
+    }
     
     /// Generates C code to print a variable.
-    pub fn print(var: Self, id: &String) -> String {
+    pub fn print(var: Self, id: &str) -> Result<String, Error> {
         let mut emitted = String::new();
         match var {
             Variable::Int => {
@@ -160,22 +411,55 @@ impl Variable {
                 emitted.push_str(");\n");
             },
             Variable::Void => {
-                emitted.push_str("printf(\"\\n\")");
-            },
-            Variable::Struct (_) => {
-                unreachable!()
+                // Nothing is printed but a newline, though the expression still runs for its effects
+                emitted.push_str(id);
+                emitted.push_str(";\nprintf(\"\\n\");\n");
             },
+            Variable::Struct (_)
+            | Variable::Owned (_)
+            | Variable::Nullable (_) => return Err(Error::CouldNotEmit (id.to_string())),
         }
-        emitted.to_owned()
+        Ok(emitted.to_owned())
     }
 }
 
 
+/// Writes a variable type as it is spelled in Boron.
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Variable::Int => write!(f, "int"),
+            Variable::Float => write!(f, "flt"),
+            Variable::Bool => write!(f, "bln"),
+            Variable::Char => write!(f, "chr"),
+            Variable::Void => write!(f, "nul"),
+            Variable::Struct (s) => write!(f, "{}", s),
+            Variable::Owned (s) => write!(f, "own {}", s),
+            Variable::Nullable (s) => write!(f, "opt {}", s),
+        }
+    }
+}
+
+
+/// Represents the signature of a function declared in Boron.
+#[derive(Clone, Debug)]
+pub struct Signature {
+    pub arguments: Vec<(Variable, Passing)>,
+    pub return_type: Variable,
+}
+
+
 /// Abstracts over variable scopes.
 #[derive(Clone, Debug)]
 pub struct Scope {
     variables: HashMap<String, Variable>,
+    references: HashSet<String>,
+    moved: HashSet<String>,
     structs: HashMap<String, HashMap<String, Variable>>,
+    functions: HashMap<String, Signature>,
+    constants: HashMap<String, Expression>,
+    const_fns: HashMap<String, Expression>,
+    return_type: Option<String>,
     id: usize,
     parent: Option<usize>,
 }
@@ -194,23 +478,89 @@ impl Scope {
 
     /// Registers a variable within the scope.
     pub fn register(&mut self, varname: String, variable: Variable) {
+        // A redeclared variable holds a new value
+        self.moved.remove(&varname);
         self.variables.insert(varname, variable);
     }
 
+    /// Marks a heap pointer within the scope as moved, or as holding a value again.
+    pub fn register_move(&mut self, varname: String, moved: bool) {
+        if moved {
+            self.moved.insert(varname);
+        } else {
+            self.moved.remove(&varname);
+        }
+    }
+
+    /// Marks a variable within the scope as a pointer to its value.
+    pub fn register_reference(&mut self, varname: String) {
+        self.references.insert(varname);
+    }
+
     /// Registers a structure within the scope.
     pub fn register_struct(&mut self, varname: String, structure: HashMap<String, Variable>) {
         self.structs.insert(varname, structure);
     }
 
+    /// Registers a function signature within the scope.
+    pub fn register_fn(&mut self, name: String, signature: Signature) {
+        self.functions.insert(name, signature);
+    }
+
+    /// Registers the value of a compile-time constant within the scope.
+    pub fn register_const(&mut self, name: String, value: Expression) {
+        self.constants.insert(name, value);
+    }
+
+    /// Registers the declaration of a `const` function within the scope.
+    pub fn register_const_fn(&mut self, name: String, declaration: Expression) {
+        self.const_fns.insert(name, declaration);
+    }
+
+    /// Marks the scope as the outermost scope of a function body.
+    pub fn register_return_type(&mut self, return_type: String) {
+        self.return_type = Some(return_type);
+    }
+
+    /// Gets the return type of the function whose body this scope is, if any.
+    pub fn get_return_type(&self) -> Option<&String> {
+        self.return_type.as_ref()
+    }
+
     /// Looks up a variable in the given scope.
     pub fn get(&self, varname: &String) -> Option<&Variable> {
         self.variables.get(varname)
     }
 
+    /// Checks if a variable in the given scope is held by reference.
+    pub fn is_reference(&self, varname: &String) -> bool {
+        self.references.contains(varname)
+    }
+
+    /// Checks if a heap pointer in the given scope has been moved.
+    pub fn is_moved(&self, varname: &String) -> bool {
+        self.moved.contains(varname)
+    }
+
+    /// Looks up the value of a compile-time constant in the given scope.
+    pub fn get_const(&self, name: &String) -> Option<&Expression> {
+        self.constants.get(name)
+    }
+
+    /// Looks up the declaration of a `const` function in the given scope.
+    pub fn get_const_fn(&self, name: &String) -> Option<&Expression> {
+        self.const_fns.get(name)
+    }
+
     /// Looks up a structure in the given scope.
     pub fn get_struct(&self, varname: &String) -> Option<&HashMap<String, Variable>> {
         self.structs.get(varname)
     }
+
+    /// Looks up a function signature in the given scope.
+    pub fn get_fn(&self, name: &String) -> Option<&Signature> {
+        self.functions.get(name)
+    }
 }
 
 
@@ -218,6 +568,14 @@ impl Scope {
 pub struct Emitter {
     code: String,
     environment: Environment,
+    // Name of the source file, reported when an assertion fails
+    filename: String,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Provides an interface for the Boron-to-C emitter.
@@ -227,11 +585,12 @@ impl Emitter {
         Self {
             code: String::new(),
             environment: Environment::new(),
+            filename: "<input>".to_string(),
         }
     }
 
     /// Emits an operation symbol based on a token type.
-    fn match_op(&self, op: TokenType) -> String {
+    fn match_op(&self, op: TokenType) -> Result<String, Error> {
         let op_str = match op {
             TokenType::Plus => "+",
             TokenType::Minus => "-",
@@ -243,10 +602,10 @@ impl Emitter {
             TokenType::Not => "!",
             TokenType::GreaterEqual => ">=",
             TokenType::LessEqual => "<=",
-            _ => throw(Error::CouldNotEmit ("operation".to_string())),
+            _ => return Err(Error::CouldNotEmit ("operation".to_string())),
         };
 
-        op_str.to_owned()
+        Ok(op_str.to_owned())
     }
 
     /// Emits a datatype name based on the C name.
@@ -257,6 +616,10 @@ impl Emitter {
             "bln" => "bool".to_string(),
             "chr" => "char".to_string(),
             "nul" => "void".to_string(),
+            _ if Variable::from(&datatype).is_pointer() => {
+                // It's ok to use `unwrap` here because every pointer type names a struct
+                format!("struct {} *", Variable::from(&datatype).get_struct().unwrap())
+            },
             _ => if lhs {
                 format!("struct {}", datatype.as_str())
             } else {
@@ -267,93 +630,381 @@ impl Emitter {
         type_str
     }
 
-    /// Emits a variable name, dereferencing a struct field if necessary.
-    fn match_var(&self, var: String, in_fn: bool) -> String {
-        if in_fn {
-            var.replace(".", "->")
+    /// Emits a character, escaping it if necessary.
+    fn match_char(&self, c: char) -> String {
+        match c {
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            '\r' => "\\r".to_string(),
+            '\0' => "\\0".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\'' => "\\'".to_string(),
+            _ => c.to_string(),
+        }
+    }
+
+    /// Emits a string literal, escaping its characters if necessary.
+    fn match_str(&self, s: &str) -> String {
+        let escaped = s.chars()
+            .map(|c| match c {
+                '"' => "\\\"".to_string(),
+                '\'' => "'".to_string(),
+                _ => self.match_char(c),
+            })
+            .collect::<String>();
+        format!("\"{}\"", escaped)
+    }
+
+    /// Emits a variable name, dereferencing it if it is held by reference.
+    ///
+    /// Fields are accessed with `->` through references and heap pointers, and with `.` otherwise.
+    fn match_var(&self, var: String, scope: usize) -> Result<String, Error> {
+        let (base, fields) = split_path(&var);
+        let base = base.to_string();
+        let fields = fields.collect::<Vec<&str>>();
+        if self.environment.is_moved(scope, &base)? {
+            return Err(Error::UseAfterMove (base));
+        }
+
+        let reference = self.environment.is_reference(scope, &base)?;
+        let mut vartype = if self.environment.check(scope, &base)? {
+            Some(self.environment.lookup(scope, &base)?)
+        } else {
+            None
+        };
+
+        let mut emitted = base.to_owned();
+        for (index, field) in fields.iter().enumerate() {
+            let pointer = vartype.as_ref().is_some_and(|v| v.is_pointer());
+            if index == 0 && reference {
+                // A reference to a heap pointer must be dereferenced before following the pointer
+                if pointer {
+                    emitted = format!("(*{})->", emitted);
+                } else {
+                    emitted.push_str("->");
+                }
+            } else if pointer {
+                emitted.push_str("->");
+            } else {
+                emitted.push('.');
+            }
+            emitted.push_str(field);
+
+            // Find the type of this field so that the next access can be emitted correctly
+            vartype = match vartype.as_ref().and_then(|v| v.get_struct()) {
+                Some(s) if self.environment.check_struct(scope, s)? => {
+                    self.environment.lookup_struct(scope, s)?.get(*field).cloned()
+                },
+                _ => None,
+            };
+        }
+
+        if reference && fields.is_empty() {
+            Ok(format!("(*{})", base))
+        } else {
+            Ok(emitted)
+        }
+    }
+
+    /// Emits a pointer to a variable, without taking the address of a reference.
+    fn match_address(&self, var: String, scope: usize) -> Result<String, Error> {
+        if !var.contains('.') && self.environment.is_reference(scope, &var)? {
+            Ok(var)
         } else {
-            var
+            Ok(format!("&{}", self.match_var(var, scope)?))
         }
     }
 
     /// Aliases imports from the standard library if necessary.
-    fn match_module(&self, module: String) -> String {
-        let top = module.split("/").collect::<Vec<&str>>();
-        match top[0] {
-            "std" => {
-                // Get the current working directory
-                let current_path = match env::current_dir() {
-                    Ok(p) => p.as_path().to_owned(),
-                    Err(_) => throw(Error::CouldNotGetCurrentDir),
-                };
-                for path in current_path.ancestors() {
-                    if path.to_path_buf().join(".boron-std").is_dir() {
-                        // Get the rest of the path (everything after `.boron-std`)
-                        let mut rest_of_path = top[1..].iter().map(|x| OsStr::new(x)).collect::<Vec<&OsStr>>();
-                        rest_of_path.insert(0, &OsStr::new(".boron-std"));
-                        
-                        let output =  path
-                            .iter()
-                            .chain(rest_of_path)
-                            .map(|x| match x.to_str() {
-                                Some(s) => s.to_string(),
-                                None => throw(Error::CouldNotEmit ("use".to_string())),
-                            })
-                            .collect::<Vec<String>>();
-                        if output[0] == "/" {
-                            return format!("/{}", output[1..].join("/"));
-                        } else {
-                            return output.join("/");
-                        }
+    fn match_module(&self, module: String) -> Result<String, Error> {
+        resolve_module(module)
+    }
+
+    /// Emits a function parameter according to how it is passed.
+    fn match_param(&self, name: &str, datatype: &str, passing: Passing) -> String {
+        let datatype = self.match_type(datatype.to_string(), true);
+        match passing {
+            Passing::Value => format!("{} {}", datatype, name),
+            Passing::ConstReference => format!("const {} *{}", datatype, name),
+            Passing::Reference => format!("{} *{}", datatype, name),
+        }
+    }
+
+    /// Emits a function signature (without a body or semicolon).
+    fn emit_signature(&self, identifier: &str, arguments: &[(String, String, Passing)], return_type: &str) -> String {
+        let params = arguments.iter()
+            .map(|(arg, argtype, passing)| self.match_param(arg, argtype, *passing))
+            .collect::<Vec<String>>();
+        format!("{} {}({})", self.match_type(return_type.to_string(), true), identifier, params.join(", "))
+    }
+
+    /// Emits a `free` expression for each heap pointer passed to `drop`.
+    fn emit_free(&self, args: Vec<Expression>, scope: usize) -> Result<String, Error> {
+        let mut emitted = Vec::new();
+        for arg in args {
+            match arg {
+                Expression::Identifier (id) => {
+                    if !self.match_var_type(&id, scope)?.is_some_and(|v| v.is_pointer()) {
+                        return Err(Error::ExpectedPointer (id));
                     }
+                    // Clear the pointer so that it cannot be used after being freed
+                    let var = self.match_var(id, scope)?;
+                    emitted.push(format!("free({});\n{} = NULL", var, var));
+                },
+                _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+            }
+        }
+        Ok(emitted.join(";\n"))
+    }
+
+    /// Looks up the type of a variable or struct field, following heap pointers.
+    fn match_var_type(&self, var: &String, scope: usize) -> Result<Option<Variable>, Error> {
+        if self.environment.check(scope, var)? {
+            return Ok(Some(self.environment.lookup(scope, var)?));
+        }
+        let (base, path) = split_path(var);
+        let base = base.to_string();
+        if !self.environment.check(scope, &base)? {
+            return Ok(None);
+        }
+        let mut vartype = self.environment.lookup(scope, &base)?;
+        for field in path {
+            let structure = match vartype.get_struct() {
+                Some(s) if self.environment.check_struct(scope, s)? => self.environment.lookup_struct(scope, s)?,
+                _ => return Ok(None),
+            };
+            vartype = match structure.get(field) {
+                Some(v) => v.to_owned(),
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(vartype))
+    }
+
+    /// Checks that `null` is only ever assigned to a nullable pointer.
+    fn check_null(&self, var: &String, value: &Expression, scope: usize) -> Result<(), Error> {
+        if let Expression::Null = value {
+            if !matches!(self.match_var_type(var, scope)?, Some(Variable::Nullable (_))) {
+                return Err(Error::ExpectedNullable (var.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves a heap pointer out of the variable it is read from when it is stored in an `own` variable,
+    /// so that the variable cannot be used or dropped afterwards.
+    fn move_into(&mut self, target: Option<Variable>, value: &Expression, scope: usize) -> Result<(), Error> {
+        if let (Some(Variable::Owned (_)), Expression::Identifier (id)) = (target, value) {
+            if !id.contains('.') && self.environment.check(scope, id)? && self.environment.lookup(scope, id)?.is_pointer() {
+                self.environment.register_move(scope, id.to_string(), true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates an expression at compile time, returning an error naming `identifier` if it is not constant.
+    fn eval_const(&self, identifier: &str, value: &Expression, scope: usize) -> Result<Expression, Error> {
+        match self.evaluate(value, scope)? {
+            Some(v) => Ok(v),
+            None => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Evaluates an expression at compile time, if possible.
+    fn evaluate(&self, value: &Expression, scope: usize) -> Result<Option<Expression>, Error> {
+        let constants = |name: &String| self.environment.lookup_const(scope, name);
+        let functions = |name: &String| self.environment.lookup_const_fn(scope, name);
+        Evaluator::new(&constants, &functions).evaluate(value)
+    }
+
+    /// Folds an operation or `const` function call whose operands are all known at compile time.
+    fn fold(&self, expr: &Expression, scope: usize) -> Result<Option<Expression>, Error> {
+        match expr {
+            Expression::UnaryOp { .. }
+            | Expression::BinOp { .. }
+            | Expression::TernaryIfElse { .. } => self.evaluate(expr, scope),
+            Expression::FnCall {
+                name: n,
+                ..
+            } if self.environment.lookup_const_fn(scope, n)?.is_some() => self.evaluate(expr, scope),
+            _ => Ok(None),
+        }
+    }
+
+    /// Emits and registers a variable with static storage, whose initializer must be constant.
+    fn emit_static(&mut self, datatype: &String, identifier: &String, value: &Expression, scope: usize) -> Result<String, Error> {
+        // Register the variable, and each of its fields if it is a struct
+        let vartype = Variable::from(datatype);
+        self.environment.register(scope, identifier.clone(), vartype.to_owned())?;
+        if let Variable::Struct (s) = &vartype {
+            let structure: HashMap<String, Variable> = self.environment.lookup_struct(scope, s)?;
+            for (varname, variable) in structure.iter() {
+                // `scoped_name` takes the form `struct.field`
+                let scoped_name = format!("{}.{}", &identifier, &varname);
+                self.environment.register(scope, scoped_name.to_owned(), variable.to_owned())?;
+            }
+        }
+
+        let initializer = match value {
+            Expression::StructInit {
+                variables: v,
+            } => {
+                if vartype.is_pointer() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
                 }
-                throw(Error::CouldNotReadFile (module));          
+                let structure = self.environment.lookup_struct(scope, datatype)?;
+                let mut fields = Vec::new();
+                // Emit the fields by name, so the same program always compiles to the same C
+                for (varname, variable) in v.iter().collect::<BTreeMap<_, _>>() {
+                    let scoped_name = format!("{}.{}", &identifier, &varname);
+                    if !structure.contains_key(varname) {
+                        return Err(Error::UndeclaredVariable (scoped_name));
+                    }
+                    let field = self.eval_const(&scoped_name, variable, scope)?;
+                    fields.push(format!(".{} = {}", varname, self.emit(&field, scope)?));
+                }
+                format!("{{ {} }}", fields.join(", "))
             },
-            _ => module.to_owned()
-        }
+            _ => {
+                let constant = self.eval_const(identifier, value, scope)?;
+                self.check_null(identifier, &constant, scope)?;
+                self.emit(&constant, scope)?
+            },
+        };
+
+        Ok(format!("{} {} = {}", self.match_type(datatype.to_string(), true), identifier, initializer))
     }
 
     /// Emits a `printf` expression.
-    fn emit_printf(&self, args: Vec<Expression>, scope: usize, in_fn: bool) -> String {
+    fn emit_printf(&mut self, args: Vec<Expression>, scope: usize) -> Result<String, Error> {
         // "print" is a special case due to idiosyncracies of C & Boron
         let mut emitted = String::new();
         for arg in args {
-            if let Expression::Identifier (id) = arg {
-                let var: Variable = self.environment.lookup(scope, &id);
-                emitted.push_str(&Variable::print(var, &self.match_var(id.to_string(), in_fn)));
-            }
+            // Each argument is printed according to its type
+            let var = self.infer(&arg, scope)?;
+            emitted.push_str(&Variable::print(var, &self.emit(&arg, scope)?)?);
         }
         emitted.pop();
         emitted.pop();
-        emitted
+        Ok(emitted)
+    }
+
+    /// Infers the type of an expression without emitting it.
+    fn infer(&self, expr: &Expression, scope: usize) -> Result<Variable, Error> {
+        match expr {
+            Expression::Int (_) => Ok(Variable::Int),
+            Expression::Float (_) => Ok(Variable::Float),
+            Expression::Bool (_) => Ok(Variable::Bool),
+            Expression::Char (_) => Ok(Variable::Char),
+            Expression::Identifier (id) => match self.match_var_type(id, scope)? {
+                Some(v) => Ok(v),
+                None => Err(Error::UndeclaredVariable (id.to_string())),
+            },
+            Expression::UnaryOp {
+                op: TokenType::Not,
+                ..
+            } => Ok(Variable::Bool),
+            Expression::UnaryOp {
+                expr: e,
+                ..
+            } => self.infer(e, scope),
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => match o {
+                TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Multiply
+                | TokenType::Divide => match (self.infer(l, scope)?, self.infer(r, scope)?) {
+                    (Variable::Float, _) | (_, Variable::Float) => Ok(Variable::Float),
+                    _ => Ok(Variable::Int),
+                },
+                _ => Ok(Variable::Bool),
+            },
+            Expression::TernaryIfElse {
+                body_true: t,
+                ..
+            } => self.infer(t, scope),
+            Expression::FnCall {
+                name: n,
+                ..
+            } => match self.environment.lookup_fn(scope, n)? {
+                Some(signature) => Ok(signature.return_type),
+                None => Err(Error::UndeclaredFunction (n.to_string())),
+            },
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
     }
 
     /// Emits a block of code.
-    fn emit_block(&mut self, block: Vec<Expression>, parent: Option<usize>, in_fn: bool) -> (String, String, String, String) {
+    fn emit_block(&mut self, block: Vec<Expression>, parent: Option<usize>) -> Result<(String, String, String, String), Error> {
         let scope = self.environment.add(parent);
+        self.emit_scope(block, scope)
+    }
+
+    /// Emits a block of code into an existing scope.
+    fn emit_scope(&mut self, block: Vec<Expression>, scope: usize) -> Result<(String, String, String, String), Error> {
         let mut code = String::new();
         let mut functions = String::new();
         let mut structs = String::new();
         let mut includes = String::new();
 
+        // Register every function in this block up front, so that calls can be emitted
+        // according to the callee's signature regardless of declaration order
+        for expression in block.iter() {
+            if let Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                constant: c,
+                ..
+            } = expression {
+                if *c {
+                    self.environment.register_const_fn(scope, i.to_string(), expression.to_owned())?;
+                }
+                let signature = Signature {
+                    arguments: a.iter().map(|(_, t, p)| (Variable::from(t), *p)).collect(),
+                    return_type: Variable::from(r),
+                };
+                self.environment.register_fn(scope, i.to_string(), signature)?;
+                functions.push_str(&self.emit_signature(i, a, r));
+                functions.push_str(";\n");
+            }
+        }
+
         for expression in block {
-            let line = &self.emit(&expression, scope, in_fn);
+            // Tests are only compiled by `compile_tests`, which turns them into functions
+            if let Expression::Test { .. } = expression {
+                if !self.environment.is_global(scope)? {
+                    return Err(Error::TestInsideFunction);
+                }
+                continue;
+            }
+
+            // Top-level code is emitted at file scope, where C only allows declarations
+            if self.environment.is_global(scope)? && !matches!(
+                expression,
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. }
+                | Expression::Use (_)
+                | Expression::Assignment { .. }
+                | Expression::Const { .. }
+                | Expression::Static { .. }
+            ) {
+                return Err(Error::StatementOutsideFunction);
+            }
+
+            let line = &self.emit(&expression, scope)?;
             match expression {
-                Expression::Struct {
-                    identifier: _,
-                    variables: _,
-                } => {
+                Expression::Struct { .. } => {
                     structs.push_str(line);
                     structs.push_str(";\n");
                 },
-                Expression::FnDeclaration {
-                    identifier: _,
-                    arguments: _,
-                    return_type: _,
-                    body: _,
-                } => {
+                Expression::FnDeclaration { .. } => {
                     functions.push_str(line);
-                    functions.push_str("\n");
+                    functions.push('\n');
                 },
                 Expression::While {
                     condition: _,
@@ -367,12 +1018,12 @@ impl Emitter {
                     body_false: _,
                 } => {
                     code.push_str(line);
-                    code.push_str("\n");
+                    code.push('\n');
                 },
                 Expression::Use (_) => {
                     includes.push_str(line);
-                    includes.push_str("\n");
-                }
+                    includes.push('\n');
+                },
                 _ => {
                     code.push_str(line);
                     code.push_str(";\n");
@@ -380,28 +1031,36 @@ impl Emitter {
             };
         }
 
-        (includes.to_owned(), structs.to_owned(), functions.to_owned(), code.to_owned())
+        Ok((includes.to_owned(), structs.to_owned(), functions.to_owned(), code.to_owned()))
     }
 
     /// Emits an expression.
-    fn emit(&mut self, expr: &Expression, scope: usize, in_fn: bool) -> String {
+    fn emit(&mut self, expr: &Expression, scope: usize) -> Result<String, Error> {
+        if let Some(folded) = self.fold(expr, scope)? {
+            return self.emit(&folded, scope);
+        }
+
         // Emit the given expression as a string
         let value: String = match expr {
             Expression::Int (i) => format!("{}", i),
-            Expression::Float (f) => format!("{}", f),
+            // Floats always keep a decimal point, so that folded constants stay floating-point
+            Expression::Float (f) => format!("{:?}", f),
             Expression::Bool (b) => format!("{}", b),
-            Expression::Char (c) => format!("'{}'", c),
-            Expression::Identifier (s) => format!("{}", self.match_var(s.to_string(), in_fn)),
-            Expression::Type (t) => throw(Error::CouldNotEmit (t.to_string())),
+            Expression::Char (c) => format!("'{}'", self.match_char(*c)),
+            Expression::Null => "NULL".to_string(),
+            Expression::Identifier (s) => self.match_var(s.to_string(), scope)?,
+            Expression::Type (t) => return Err(Error::CouldNotEmit (t.to_string())),
             Expression::UnaryOp {
                 op: o,
                 expr: e,
-            } => format!("{}{}", self.match_op(*o), self.emit(&*e, scope, in_fn)),
+                ..
+            } => format!("{}{}", self.match_op(*o)?, self.emit(e, scope)?),
             Expression::BinOp {
                 left: l,
                 op: o,
                 right: r,
-            } => format!("({} {} {})", self.emit(&*l, scope, in_fn), self.match_op(*o), self.emit(&*r, scope, in_fn)),
+                ..
+            } => format!("({} {} {})", self.emit(l, scope)?, self.match_op(*o)?, self.emit(r, scope)?),
             Expression::Declaration {
                 datatype: d,
                 identifier: i,
@@ -409,13 +1068,14 @@ impl Emitter {
             Expression::Struct {
                 identifier: i,
                 variables: v,
+                ..
             } => {
                 let mut emitted = "struct ".to_string();
-                emitted.push_str(&i);
+                emitted.push_str(i);
                 emitted.push_str(" {\n");
                 let mut variables = HashMap::new();
-                // Push each variable in the structure
-                for (varname, vartype) in v.iter() {
+                // Push each variable in the structure by name, so the same program always compiles to the same C
+                for (varname, vartype) in v.iter().collect::<BTreeMap<_, _>>() {
                     emitted.push_str(&self.match_type(vartype.to_string(), false));
                     emitted.push(' ');
                     emitted.push_str(varname);
@@ -423,15 +1083,42 @@ impl Emitter {
                     variables.insert(varname.to_owned(), Variable::from(vartype));
                 }
                 // Register this structure in the scope
-                self.environment.register_struct(scope, i.to_string(), variables);
-                emitted.push_str("}");
+                self.environment.register_struct(scope, i.to_string(), variables)?;
+                emitted.push('}');
                 emitted.to_owned()
             },
             Expression::StructInit {
                 variables: _,
             } => {
-                throw(Error::FoundBareStruct);
+                return Err(Error::FoundBareStruct);
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                // Allocate the struct on the heap and copy a compound literal into it
+                let structure = self.environment.lookup_struct(scope, d)?;
+                let mut fields = Vec::new();
+                // Emit the fields by name, so the same program always compiles to the same C
+                for (varname, variable) in v.iter().collect::<BTreeMap<_, _>>() {
+                    if !structure.contains_key(varname) {
+                        return Err(Error::UndeclaredVariable (format!("{}.{}", d, varname)));
+                    }
+                    fields.push(format!(".{} = {}", varname, self.emit(variable, scope)?));
+                }
+                format!(
+                    "boron_new(sizeof(struct {}), &(struct {}) {{ {} }})",
+                    d,
+                    d,
+                    fields.join(", "),
+                )
             },
+            // Top-level variables are globals, so they must be initialized statically
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: e,
+            } if self.environment.is_global(scope)? => self.emit_static(d, i, e, scope)?,
             Expression::Assignment {
                 datatype: d,
                 identifier: i,
@@ -441,20 +1128,24 @@ impl Emitter {
                     Expression::StructInit {
                         variables: v,
                     } => {
+                        // Heap pointers must be allocated with `new`
+                        if Variable::from(d).is_pointer() {
+                            return Err(Error::ExpectedNew (i.to_string()));
+                        }
                         // First, declare the struct
                         let mut emitted = format!("struct {} {};\n", &d, &i);
-                        self.environment.register(scope, i.clone(), Variable::from(&d));
-                        let structure = self.environment.lookup_struct(scope, &d);
+                        self.environment.register(scope, i.clone(), Variable::from(d))?;
+                        let structure = self.environment.lookup_struct(scope, d)?;
                         for (index, (varname, variable)) in v.iter().enumerate() {
                             // `scoped_name` takes the form `struct.field`
                             let scoped_name = format!("{}.{}", &i, &varname);
-                            let field = format!("{} = {}", &scoped_name, self.emit(variable, scope, in_fn));
+                            let field = format!("{} = {}", &scoped_name, self.emit(variable, scope)?);
                             let vartype = match structure.get(varname) {
                                 Some(v) => v,
-                                None => throw(Error::UndeclaredVariable (scoped_name.to_owned())),
+                                None => return Err(Error::UndeclaredVariable (scoped_name.to_owned())),
                             };
                             // Register this field as a variable in the current environment
-                            self.environment.register(scope, scoped_name.to_owned(), vartype.to_owned());
+                            self.environment.register(scope, scoped_name.to_owned(), vartype.to_owned())?;
                             emitted.push_str(&field);
                             if index < v.len() - 1 {
                                 emitted.push_str(";\n");
@@ -463,24 +1154,43 @@ impl Emitter {
                         emitted.to_owned()
                     },
                     _ => {
-                        let vartype = Variable::from(&d);
-                        self.environment.register(scope, i.clone(), vartype.to_owned());
+                        let vartype = Variable::from(d);
+                        self.environment.register(scope, i.clone(), vartype.to_owned())?;
+                        self.check_null(i, e, scope)?;
 
                         // If this is a struct, register each field in the given scope
                         if let Variable::Struct (s) = vartype {
-                            let structure: HashMap<String, Variable> = self.environment.lookup_struct(scope, &s);
+                            let structure: HashMap<String, Variable> = self.environment.lookup_struct(scope, &s)?;
                             for (varname, variable) in structure.iter() {
                                 // `scoped_name` takes the form `struct.field`
                                 let scoped_name = format!("{}.{}", &i, &varname);
                                 // Register this field as a variable in the current environment
-                                self.environment.register(scope, scoped_name.to_owned(), variable.to_owned());
+                                self.environment.register(scope, scoped_name.to_owned(), variable.to_owned())?;
                             }
                         }
 
-                        format!("{} {} = {}", self.match_type(d.to_string(), true), self.match_var(i.to_string(), in_fn), self.emit(&*e, scope, in_fn))
+                        let emitted = format!("{} {} = {}", self.match_type(d.to_string(), true), self.match_var(i.to_string(), scope)?, self.emit(e, scope)?);
+                        self.move_into(Some(Variable::from(d)), e, scope)?;
+                        emitted
                     }
                 }
             },
+            Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: e,
+            } => {
+                let value = self.eval_const(i, e, scope)?;
+                self.environment.register(scope, i.clone(), Variable::from(d))?;
+                self.environment.register_const(scope, i.clone(), value.to_owned())?;
+                self.check_null(i, &value, scope)?;
+                format!("static const {} {} = {}", self.match_type(d.to_string(), true), i, self.emit(&value, scope)?)
+            },
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: e,
+            } => format!("static {}", self.emit_static(d, i, e, scope)?),
             Expression::Reassignment {
                 identifier: i,
                 value: e,
@@ -493,7 +1203,7 @@ impl Emitter {
                     for (index, (varname, variable)) in v.iter().enumerate() {
                         // `scoped_name` takes the form `struct.field`
                         let scoped_name = format!("{}.{}", &i, &varname);
-                        let field = format!("{} = {}", &scoped_name, self.emit(variable, scope, in_fn));
+                        let field = format!("{} = {}", self.match_var(scoped_name, scope)?, self.emit(variable, scope)?);
                         emitted.push_str(&field);
                         if index < v.len() - 1 {
                             emitted.push_str(";\n");
@@ -501,40 +1211,59 @@ impl Emitter {
                     }
                     emitted.to_owned()
                 },
-                _ => format!("{} = {}", self.match_var(i.to_string(), in_fn), self.emit(&*e, scope, in_fn))
+                _ => {
+                    self.check_null(i, e, scope)?;
+                    let value = self.emit(e, scope)?;
+                    // Assigning to a moved pointer gives it a value again
+                    if !i.contains('.') && self.environment.check(scope, i)? {
+                        self.environment.register_move(scope, i.to_string(), false)?;
+                    }
+                    let emitted = format!("{} = {}", self.match_var(i.to_string(), scope)?, value);
+                    self.move_into(self.match_var_type(i, scope)?, e, scope)?;
+                    emitted
+                },
             },
             Expression::FnCall {
                 name: n,
                 args: a,
             } => {
                 match n.as_str() {
-                    "print" => self.emit_printf(a.clone(), scope, in_fn),
+                    "print" => self.emit_printf(a.clone(), scope)?,
+                    "drop" => self.emit_free(a.clone(), scope)?,
                     _ => {
-                        let names = n.split(".").collect::<Vec<&str>>();
-                        let mut emitted = match names.len() {
-                            1 => format!("{}(", names[0]),
-                            2 => if a.len() != 0 {
-                                format!("{}(&{}, ", names[1], names[0])
-                            } else {
-                                format!("{}(&{}", names[1], names[0])
+                        // Functions without a Boron signature take every argument by value
+                        let passing: Vec<(Variable, Passing)> = match self.environment.lookup_fn(scope, n)? {
+                            Some(signature) => {
+                                if signature.arguments.len() != a.len() {
+                                    return Err(Error::WrongArgumentCount (n.to_string()));
+                                }
+                                signature.arguments
                             },
-                            _ => throw(Error::TooManyLeadingKeywords (n.to_string())),
+                            None => vec![(Variable::Void, Passing::Value); a.len()],
                         };
 
-                        // Emit each argument recursively
-                        for (idx, arg) in a.iter().enumerate() {
-                            let argument = if let Expression::Identifier (s) = arg {
-                                format!("&{}", s)
-                            } else {
-                                format!("{}", self.emit(arg, scope, in_fn))
+                        // Emit each argument recursively, according to how the callee expects it
+                        let mut arguments = Vec::new();
+                        for (arg, (paramtype, passing)) in a.iter().zip(passing) {
+                            let argument = match passing {
+                                Passing::Value => self.emit(arg, scope)?,
+                                // A heap pointer already refers to its struct, so it is passed as-is
+                                Passing::ConstReference
+                                | Passing::Reference if matches!(
+                                    (arg, &paramtype),
+                                    (Expression::Identifier (s), Variable::Struct (_))
+                                        if self.match_var_type(s, scope)?.is_some_and(|v| v.is_pointer())
+                                ) => self.emit(arg, scope)?,
+                                Passing::ConstReference
+                                | Passing::Reference => match arg {
+                                    Expression::Identifier (s) => self.match_address(s.to_string(), scope)?,
+                                    _ => return Err(Error::ExpectedReferenceArgument (n.to_string())),
+                                },
                             };
-                            emitted.push_str(&argument);
-                            if idx < a.len() - 1 {
-                                emitted.push_str(", ");
-                            }
+                            arguments.push(argument);
                         }
-                        emitted.push(')');
-                        emitted.to_owned()
+
+                        format!("{}({})", n, arguments.join(", "))
                     }
                 }
             },
@@ -544,12 +1273,12 @@ impl Emitter {
             } => {
                 let mut emitted = "while (".to_string();
                 // Emit the condition
-                emitted.push_str(&self.emit(&*c, scope, in_fn));
+                emitted.push_str(&self.emit(c, scope)?);
                 emitted.push_str(") {\n");
                 // Emit each expression in the while loop
-                let block = self.emit_block(b.to_vec(), Some(scope), in_fn).3;
+                let block = self.emit_block(b.to_vec(), Some(scope))?.3;
                 emitted.push_str(&block);
-                emitted.push_str("}");
+                emitted.push('}');
                 emitted.to_owned()
             },
             Expression::If {
@@ -558,12 +1287,12 @@ impl Emitter {
             } => {
                 let mut emitted = "if (".to_string();
                 // Emit the condition
-                emitted.push_str(&self.emit(&*c, scope, in_fn));
+                emitted.push_str(&self.emit(c, scope)?);
                 emitted.push_str(") {\n");
                 // Emit each expression in the if statement
-                let block = self.emit_block(b.to_vec(), Some(scope), in_fn).3;
+                let block = self.emit_block(b.to_vec(), Some(scope))?.3;
                 emitted.push_str(&block);
-                emitted.push_str("}");
+                emitted.push('}');
                 emitted.to_owned()
             },
             Expression::IfElse {
@@ -573,16 +1302,16 @@ impl Emitter {
             } => {
                 let mut emitted = "if (".to_string();
                 // Emit the condition
-                emitted.push_str(&self.emit(&*c, scope, in_fn));
+                emitted.push_str(&self.emit(c, scope)?);
                 emitted.push_str(") {\n");
                 // Emit each expression in the if statement
-                let block_true = self.emit_block(t.to_vec(), Some(scope), in_fn).3;
+                let block_true = self.emit_block(t.to_vec(), Some(scope))?.3;
                 emitted.push_str(&block_true);
                 emitted.push_str("} else {\n");
                 // Emit each expression in the else statement
-                let block_false = self.emit_block(f.to_vec(), Some(scope), in_fn).3;
+                let block_false = self.emit_block(f.to_vec(), Some(scope))?.3;
                 emitted.push_str(&block_false);
-                emitted.push_str("}");
+                emitted.push('}');
                 emitted.to_owned()
             },
             Expression::TernaryIfElse {
@@ -592,15 +1321,15 @@ impl Emitter {
             } => {
                 let mut emitted = "(".to_string();
                 // Emit the condition
-                emitted.push_str(&self.emit(&*c, scope, in_fn));
+                emitted.push_str(&self.emit(c, scope)?);
                 // Emit the ternary if
                 emitted.push_str(" ? ");
                 // Emit the first expression
-                emitted.push_str(&self.emit(&*t, scope, in_fn));
+                emitted.push_str(&self.emit(t, scope)?);
                 // Emit the ternary else
                 emitted.push_str(" : ");
                 // Emit the second expression
-                emitted.push_str(&self.emit(&*f, scope, in_fn));
+                emitted.push_str(&self.emit(f, scope)?);
                 // Emit a closing parenthesis
                 emitted.push(')');
                 
@@ -611,57 +1340,78 @@ impl Emitter {
                 arguments: a,
                 return_type: r,
                 body: b,
+                ..
             } => {
-                // Add the return type to the emitted code
-                let mut emitted = self.match_type(r.to_string(), true);
-                // Add the function name and an opening parenthesis
-                emitted.push(' ');
-                emitted.push_str(&i);
-                emitted.push('(');
-                // Add each argument's type and name
-                // Also register each argument as a variable in the current scope
-                for (index, (arg, argtype)) in a.iter().enumerate() {
-                    emitted.push_str(&self.match_type(argtype.to_string(), false));
-                    emitted.push(' ');
-                    emitted.push_str(arg);
-                    if index < a.len() - 1 {
-                        emitted.push_str(", ");
-                    }
+                let mut emitted = self.emit_signature(i, a, r);
+                emitted.push_str(" {\n");
 
-                    let var = Variable::from(&argtype);
-                    self.environment.register(scope, arg.clone(), Variable::from(&argtype));
+                // Register each argument as a variable in the function's scope
+                let fn_scope = self.environment.add(Some(scope));
+                self.environment.register_return_type(fn_scope, r.to_string())?;
+                for (arg, argtype, passing) in a.iter() {
+                    let var = Variable::from(argtype);
+                    self.environment.register(fn_scope, arg.clone(), var.to_owned())?;
+                    if *passing != Passing::Value {
+                        self.environment.register_reference(fn_scope, arg.clone())?;
+                    }
 
                     // If this is a struct, we need to register each field as well
                     if let Variable::Struct (s) = var {
-                        let fields: HashMap<String, Variable> = self.environment.lookup_struct(scope, &s);
+                        let fields: HashMap<String, Variable> = self.environment.lookup_struct(scope, &s)?;
                         for (varname, variable) in fields.iter() {
                             let scoped_varname = format!("{}.{}", &arg, &varname);
-                            self.environment.register(scope, scoped_varname.to_owned(), variable.to_owned());
+                            self.environment.register(fn_scope, scoped_varname.to_owned(), variable.to_owned())?;
                         }
                     }
                 }
-                emitted.push_str(") {\n");
+
                 // Emit the body
-                let block = self.emit_block(b.to_vec(), Some(scope), true).3;
+                let block = self.emit_block(b.to_vec(), Some(fn_scope))?.3;
                 emitted.push_str(&block);
-                emitted.push_str("}");
+                emitted.push('}');
                 emitted.to_owned()
             },
             Expression::Return (v) => {
-                let mut emitted = "return ".to_string();
-                let expr = self.emit(&*v, scope, true);
-                emitted.push_str(&expr);
-                emitted.to_owned()
+                let expr = self.emit(v, scope)?;
+                // C functions returning `void` cannot return a value, so it is only computed for its effects
+                match (self.environment.lookup_return_type(scope)?.as_deref(), &**v) {
+                    (Some("nul"), Expression::Int (_)) => "return".to_string(),
+                    (Some("nul"), _) => format!("{};\nreturn", expr),
+                    _ => format!("return {}", expr),
+                }
             },
             Expression::Use (m) => {
                 let mut emitted = "#include \"".to_string();
-                emitted.push_str(&self.match_module(m.to_string()));
+                emitted.push_str(&self.match_module(m.to_string())?);
                 emitted.push_str(".h\"");
                 emitted.to_owned()
             },
+            Expression::Test { .. } => return Err(Error::TestInsideFunction),
+            // `desugar` has already copied each deferred statement to where it runs
+            Expression::Defer (_) => return Err(Error::DeferOutsideFunction),
+            Expression::Assert {
+                condition: c,
+                span: s,
+            } => format!(
+                "boron_assert({}, {}, {}, \"assertion failed\")",
+                self.emit(c, scope)?,
+                self.match_str(&self.filename),
+                s.line,
+            ),
+            Expression::AssertEq {
+                left: l,
+                right: r,
+                span: s,
+            } => format!(
+                "boron_assert(({}) == ({}), {}, {}, \"assertion failed: values are not equal\")",
+                self.emit(l, scope)?,
+                self.emit(r, scope)?,
+                self.match_str(&self.filename),
+                s.line,
+            ),
         };
 
-        value.to_owned()
+        Ok(value.to_owned())
     }
 
     /// Emits a section of code and inserts a new line by concatenating to the C program.
@@ -670,8 +1420,24 @@ impl Emitter {
         self.code.push('\n');
     }
 
+    /// Compiles a single expression into C, as if it were written in the body of a function.
+    ///
+    /// `declarations` are emitted at the top level and `statements` in the function body
+    /// beforehand, so that the expression can refer to their structs, functions, and variables.
+    pub fn compile_expression(&mut self, declarations: Vec<Expression>, statements: Vec<Expression>, expression: &Expression) -> Result<String, Error> {
+        let global = self.environment.add(None);
+        self.emit_scope(desugar(declarations)?, global)?;
+
+        let local = self.environment.add(Some(global));
+        self.environment.register_return_type(local, "int".to_string())?;
+        self.emit_scope(desugar(statements)?, local)?;
+
+        let (_, _, _, code) = self.emit_scope(desugar(vec![expression.to_owned()])?, local)?;
+        Ok(code.trim_end().to_string())
+    }
+
     /// Compiles a list of expressions into a string of C code.
-    pub fn compile_exe(&mut self, expressions: Vec<Expression>) -> String {
+    pub fn compile_exe(&mut self, expressions: Vec<Expression>) -> Result<String, Error> {
         // Get current time
         let now = Local::now();
         let datetime: String = format!(
@@ -693,28 +1459,83 @@ impl Emitter {
         self.writeln(&datetime);
         self.writeln("");
 
-        let (includes, structs, functions, code) = self.emit_block(expressions, None, false);
+        let (includes, structs, functions, code) = self.emit_block(desugar(expressions)?, None)?;
 
         // Emit #include statements
         self.writeln("#include <stdio.h>");
         self.writeln("#include <stdbool.h>");
+        self.writeln("#include <stdlib.h>");
+        self.writeln("#include <string.h>");
         self.writeln(&includes);
         self.writeln("");
+        self.writeln(RUNTIME);
         
         // Emit header (functions + structs)
         self.writeln("// Structs begin here");
         self.writeln(&structs);
+        self.writeln("// Globals begin here");
+        self.writeln(&code);
         self.writeln("// Functions begin here");
         self.writeln(&functions);
 
-        // Emit main function
-        self.writeln(&code);
+        Ok(self.code.to_owned())
+    }
 
-        self.code.to_owned()
+    /// Compiles the tests in a list of expressions into a C program that runs them.
+    ///
+    /// Each test becomes a function, and `main` is replaced by a harness that runs
+    /// every test in a child process and reports which passed.  `filename` is
+    /// reported when an assertion fails.
+    pub fn compile_tests(&mut self, filename: &str, expressions: Vec<Expression>) -> Result<String, Error> {
+        self.filename = filename.to_string();
+
+        let mut names = Vec::new();
+        let mut program = Vec::new();
+        for expression in expressions {
+            match expression {
+                Expression::Test {
+                    name: n,
+                    body: b,
+                } => {
+                    program.push(Expression::FnDeclaration {
+                        identifier: format!("boron_test_{}", names.len()),
+                        arguments: Vec::new(),
+                        return_type: "nul".to_string(),
+                        body: b,
+                        doc: None,
+                        constant: false,
+                    });
+                    names.push(n);
+                },
+                // The harness takes the place of the program's own `main`
+                Expression::FnDeclaration {
+                    identifier: ref i,
+                    ..
+                } if i == "main" => (),
+                _ => program.push(expression),
+            }
+        }
+
+        self.compile_exe(program)?;
+
+        let mut tests = (0..names.len())
+            .map(|i| format!("boron_test_{}", i))
+            .collect::<Vec<String>>();
+        tests.push("NULL".to_string());
+        let mut names = names.iter()
+            .map(|n| self.match_str(n))
+            .collect::<Vec<String>>();
+        names.push("NULL".to_string());
+
+        self.writeln(&format!("static void (*const boron_tests[])(void) = {{ {} }};", tests.join(", ")));
+        self.writeln(&format!("static const char *const boron_test_names[] = {{ {} }};", names.join(", ")));
+        self.writeln(HARNESS);
+
+        Ok(self.code.to_owned())
     }
 
     /// Compiles a list of expressions into a C header file.
-    pub fn compile_lib(&mut self, name: String, expressions: Vec<Expression>) -> String {
+    pub fn compile_lib(&mut self, name: String, expressions: Vec<Expression>) -> Result<String, Error> {
         // Get current time
         let now = Local::now();
         let datetime: String = format!(
@@ -736,8 +1557,8 @@ impl Emitter {
         self.writeln(&datetime);
         self.writeln("");
 
-        // Only emit structs and functions
-        let (includes, structs, functions, _) = self.emit_block(expressions, None, false);
+        // Top-level code only contains declarations, so it is safe to emit in a header
+        let (includes, structs, functions, globals) = self.emit_block(desugar(expressions)?, None)?;
 
         let header_guard_start = format!("#ifndef {}\n#define {}", &name, &name);
         let header_guard_end = "#endif".to_string();
@@ -749,20 +1570,100 @@ impl Emitter {
         // Emit #include statements
         self.writeln("#include <stdio.h>");
         self.writeln("#include <stdbool.h>");
+        self.writeln("#include <stdlib.h>");
+        self.writeln("#include <string.h>");
         self.writeln("");
         self.writeln("// Additional includes begin here");
         self.writeln(&includes);
         self.writeln("");
+        self.writeln(RUNTIME);
         
         // Emit header (functions + structs)
         self.writeln("// Structs begin here");
         self.writeln(&structs);
+        self.writeln("// Globals begin here");
+        self.writeln(&globals);
         self.writeln("// Functions begin here");
         self.writeln(&functions);
 
         // Emit header guard
         self.writeln(&header_guard_end);
 
-        self.code.to_owned()
+        Ok(self.code.to_owned())
     }
-}
\ No newline at end of file
+}
+
+
+/// Resolves the path of a module (without extension), locating the standard library if necessary.
+pub fn resolve_module(module: String) -> Result<String, Error> {
+    let top = module.split("/").collect::<Vec<&str>>();
+    match top[0] {
+        "std" => {
+            // Get the current working directory
+            let current_path = match env::current_dir() {
+                Ok(p) => p.as_path().to_owned(),
+                Err(_) => return Err(Error::CouldNotGetCurrentDir),
+            };
+            for path in current_path.ancestors() {
+                if path.to_path_buf().join(".boron-std").is_dir() {
+                    // Get the rest of the path (everything after `.boron-std`)
+                    let mut rest_of_path = top[1..].iter().map(OsStr::new).collect::<Vec<&OsStr>>();
+                    rest_of_path.insert(0, OsStr::new(".boron-std"));
+                    
+                    let output =  path
+                        .iter()
+                        .chain(rest_of_path)
+                        .map(|x| match x.to_str() {
+                            Some(s) => Ok(s.to_string()),
+                            None => Err(Error::CouldNotEmit ("use".to_string())),
+                        })
+                        .collect::<Result<Vec<String>, Error>>()?;
+                    if output[0] == "/" {
+                        return Ok(format!("/{}", output[1..].join("/")));
+                    } else {
+                        return Ok(output.join("/"));
+                    }
+                }
+            }
+            Err(Error::CouldNotReadFile (module))
+        },
+        _ => Ok(module.to_owned())
+    }
+}
+
+
+/// Replaces each top-level `use` with the declarations of the module it names, for
+/// backends that compile a whole program at once.  Each module is included only once.
+///
+/// The program is taken to be in the current directory, and each module it uses is
+/// found relative to the module that uses it.
+pub fn include_modules(expressions: Vec<Expression>) -> Result<Vec<Expression>, Error> {
+    include(expressions, "", &mut HashSet::new())
+}
+
+
+/// Includes the modules used by a list of expressions from the file `from`, skipping those already included.
+fn include(expressions: Vec<Expression>, from: &str, included: &mut HashSet<String>) -> Result<Vec<Expression>, Error> {
+    let mut expanded = Vec::new();
+    for expression in expressions {
+        match expression {
+            Expression::Use (m) => {
+                let filename = locate(&m, from)?;
+                if !included.insert(filename.to_owned()) {
+                    continue;
+                }
+                let code = match read_to_string(&filename) {
+                    Ok(c) => c,
+                    Err(_) => return Err(Error::CouldNotReadFile (filename)),
+                };
+                let mut tokenizer = Tokenizer::new(&code);
+                let module = Parser::new().parse_all(&mut tokenizer)?;
+                expanded.extend(include(module, &filename, included)?);
+            },
+            // Tests are only compiled by `boron test`
+            Expression::Test { .. } => (),
+            _ => expanded.push(expression),
+        }
+    }
+    Ok(expanded)
+}
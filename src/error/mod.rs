@@ -1,20 +1,45 @@
 //! Provides error handling for the Boron compiler.
 
-use std::process::exit;
+use std::{
+    fmt,
+    process::exit,
+};
 
 use colored::*;
 
 use crate::version::VERSION;
 
+use crate::parser::MAX_DEPTH;
+
 /// Outlines different errors thrown by the Boron compiler.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
     NoFileProvided,
     UnexpectedCliFlag (String),
+    ExpectedTarget,
+    UnknownTarget (String),
+    ExpectedEmitKind,
+    UnknownEmitKind (String),
+    ExpectedJobs,
+    InvalidJobs (String),
+    LibraryNotSupported (String),
     CouldNotReadFile (String),
+    MissingModule (String, String, usize, usize),
+    ImportCycle (String),
+    ExpectedBoronFile (String),
     CouldNotCreate (String),
     CouldNotWriteFile (String),
     CouldNotEmit (String),
+    InvalidScope (usize),
     CouldNotParse (String),
+    UnexpectedToken (String, usize, usize),
+    NestingTooDeep (usize, usize),
+    FormatChangedProgram,
+    UnexpectedCharacter (char, usize, usize),
+    InvalidNumber (String, usize, usize),
+    UnterminatedChar (usize, usize),
+    UnterminatedString (usize, usize),
+    UnterminatedComment (usize, usize),
     InvalidOperator (String),
     UnexpectedEof (String),
     ExpectedIdentifier (String),
@@ -26,96 +51,305 @@ pub enum Error {
     ExpectedOpenBrace (String),
     ExpectedAssignment (String),
     ExpectedReturnType (String),
-    ExpectedSingleQuote (String),
     ExpectedStruct (String),
+    ExpectedTestName (String),
+    DuplicateField (String),
     FoundBareStruct,
     TooManyLeadingKeywords (String),
     UndeclaredVariable (String),
+    WrongArgumentCount (String),
+    ExpectedReferenceArgument (String),
+    ExpectedPointer (String),
+    ExpectedNullable (String),
+    ExpectedNew (String),
+    UseAfterMove (String),
+    DeferOutsideFunction,
+    TestInsideFunction,
+    NonConstantInitializer (String),
+    IntegerOverflow (usize, usize),
+    DivisionByZero (usize, usize),
+    StatementOutsideFunction,
+    CouldNotInterpret (String),
+    UndeclaredFunction (String),
+    NullDereference (String),
+    StackOverflow (String),
+    CouldNotCompile (String),
+    CouldNotRunCompiler (String),
+    InvalidBytecode (String),
     CouldNotGetCurrentDir,
 }
 
 
+/// Reports an error and exits, for use by the command line interface.
 pub fn throw(e: Error) -> ! {
+    exit_with(e, 0);
+}
+
+
+/// Reports an error that stopped a program while it was running, and exits with a failing status.
+pub fn abort(e: Error) -> ! {
+    exit_with(e, 1);
+}
+
+
+/// Reports an error and exits with the given status.
+fn exit_with(e: Error, status: i32) -> ! {
     println!("{}", "The Boron Compiler".truecolor(102, 153, 204).bold());
     println!("Version {}", VERSION);
-    println!("");
-
-    match e {
-        Error::NoFileProvided => {
-            println!("{}: No input file specified.", "Error".bold().red());
-        },
-        Error::UnexpectedCliFlag (s) => {
-            println!("{}: Got unexpected CLI flag {}", "Error".bold().red(), s);
-        },
-        Error::CouldNotReadFile (s) => {
-            println!("{}: Could not read input file {}", "Error".bold().red(), s);
-        },
-        Error::CouldNotCreate (s) => {
-            println!("{}: Could not open output file {}", "Error".bold().red(), s);
-        },
-        Error::CouldNotWriteFile (s) => {
-            println!("{}: Could not write to output file {}", "Error".bold().red(), s);
-        },
-        Error::CouldNotEmit (s) => {
-            println!("{}: Could not emit code near token {}", "Error".bold().red(), s);
-        },
-        Error::CouldNotParse (s) => {
-            println!("{}: Could not parse code near token {}", "Error".bold().red(), s);
-        },
-        Error::InvalidOperator (s) => {
-            println!("{}: Could not parse code near invalid operator {}", "Error".bold().red(), s);
-        },
-        Error::UnexpectedEof (s) => {
-            println!("{}: File unexpectedly terminates near token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedIdentifier (s) => {
-            println!("{}: Expected identifier, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedDatatypeKeyword (s) => {
-            println!("{}: Expected datatype, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedBoolean (s) => {
-            println!("{}: Expected boolean type, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedLiteral (s) => {
-            println!("{}: Expected literal, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedOpenParen (s) => {
-            println!("{}: Expected opening parenthesis, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedCloseParen (s) => {
-            println!("{}: Expected closing parenthesis, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedOpenBrace (s) => {
-            println!("{}: Expected open curly brace '{{', got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedAssignment (s) => {
-            println!("{}: Expected assignment ':', got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedReturnType (s) => {
-            println!("{}: Expected function return type, got token {}", "Error".bold().red(), s);
-        },
-        Error::ExpectedSingleQuote (s) => {
-            println!("{}: Expected single quote ', got token {}", "Error".bold().red(), s);
-        },
-        Error::FoundBareStruct => {
-            println!("{}: Could not parse bare struct initialization.\nTry naming your struct", "Error".bold().red());
-        },
-        Error::ExpectedStruct (s) => {
-            println!("{}: Expected struct, got variable {}", "Error".bold().red(), s);
-        },
-        Error::TooManyLeadingKeywords (s) => {
-            println!("{}: Too many leading keywords: {}", "Error".bold().red(), s);
-        },
-        Error::UndeclaredVariable (s) => {
-            println!("{}: Found undeclared variable {}", "Error".bold().red(), s);
-        },
-        Error::CouldNotGetCurrentDir => {
-            println!("{}: Could not determine working directory", "Error".bold().red());
-        },
-    };
-    
+    println!();
+
+    report(&e);
+
     println!("Compiler exiting");
 
-    exit(0);
-}
\ No newline at end of file
+    exit(status);
+}
+
+
+/// Prints a description of an error.
+pub fn report(e: &Error) {
+    println!("{}: {}", "Error".bold().red(), e);
+}
+
+
+impl Error {
+    /// Returns the line and column at which this error occurred, if it records one.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            Error::UnexpectedCharacter (_, l, col)
+            | Error::InvalidNumber (_, l, col)
+            | Error::UnterminatedChar (l, col)
+            | Error::UnterminatedString (l, col)
+            | Error::UnexpectedToken (_, l, col)
+            | Error::NestingTooDeep (l, col)
+            | Error::UnterminatedComment (l, col)
+            | Error::IntegerOverflow (l, col)
+            | Error::DivisionByZero (l, col) => Some((*l, *col)),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the variant of this error, such as `UndeclaredVariable`.
+    pub fn name(&self) -> String {
+        let debug = format!("{:?}", self);
+        match debug.split_once('(') {
+            Some((name, _)) => name.to_string(),
+            None => debug,
+        }
+    }
+
+    /// Returns the name of the variable, function or struct this error is about, if any.
+    pub fn subject(&self) -> Option<&str> {
+        match self {
+            Error::ExpectedStruct (s)
+            | Error::UndeclaredVariable (s)
+            | Error::WrongArgumentCount (s)
+            | Error::ExpectedReferenceArgument (s)
+            | Error::ExpectedPointer (s)
+            | Error::ExpectedNullable (s)
+            | Error::ExpectedNew (s)
+            | Error::UseAfterMove (s)
+            | Error::NonConstantInitializer (s)
+            | Error::UndeclaredFunction (s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+
+/// Describes an error without the `Error:` label.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoFileProvided => {
+                write!(f, "No input file specified.")
+            },
+            Error::UnexpectedCliFlag (s) => {
+                write!(f, "Got unexpected CLI flag {}", s)
+            },
+            Error::ExpectedTarget => {
+                write!(f, "Expected a target after --target")
+            },
+            Error::UnknownTarget (s) => {
+                write!(f, "Got unknown target {}", s)
+            },
+            Error::ExpectedEmitKind => {
+                write!(f, "Expected an intermediate form after --emit")
+            },
+            Error::UnknownEmitKind (s) => {
+                write!(f, "Got unknown intermediate form {}", s)
+            },
+            Error::ExpectedJobs => {
+                write!(f, "Expected a number of jobs after -j")
+            },
+            Error::InvalidJobs (s) => {
+                write!(f, "Expected a positive number of jobs, got {}", s)
+            },
+            Error::LibraryNotSupported (s) => {
+                write!(f, "The {} target cannot compile libraries", s)
+            },
+            Error::CouldNotReadFile (s) => {
+                write!(f, "Could not read input file {}", s)
+            },
+            Error::MissingModule (m, s, l, col) => {
+                write!(f, "Could not find module {}, used in {} at line {}, column {}", m, s, l, col)
+            },
+            Error::ImportCycle (s) => {
+                write!(f, "Found modules that use each other: {}", s)
+            },
+            Error::ExpectedBoronFile (s) => {
+                write!(f, "Expected a Boron source file ending in .brn, got {}", s)
+            },
+            Error::CouldNotCreate (s) => {
+                write!(f, "Could not open output file {}", s)
+            },
+            Error::CouldNotWriteFile (s) => {
+                write!(f, "Could not write to output file {}", s)
+            },
+            Error::CouldNotEmit (s) => {
+                write!(f, "Could not emit code near token {}", s)
+            },
+            Error::InvalidScope (id) => {
+                write!(f, "Found reference to nonexistent scope {}\nThis is a bug in the Boron compiler", id)
+            },
+            Error::CouldNotParse (s) => {
+                write!(f, "Could not parse code near token {}", s)
+            },
+            Error::UnexpectedToken (s, l, col) => {
+                write!(f, "Unexpected token {} at line {}, column {}", s, l, col)
+            },
+            Error::NestingTooDeep (l, col) => {
+                write!(f, "Expression nested too deeply at line {}, column {}\nAt most {} levels of nesting are allowed", l, col, MAX_DEPTH)
+            },
+            Error::FormatChangedProgram => {
+                write!(f, "Could not format the program without changing its meaning")
+            },
+            Error::UnexpectedCharacter (c, l, col) => {
+                write!(f, "Unexpected character {:?} at line {}, column {}", c, l, col)
+            },
+            Error::InvalidNumber (s, l, col) => {
+                write!(f, "Invalid numeric literal {} at line {}, column {}", s, l, col)
+            },
+            Error::UnterminatedChar (l, col) => {
+                write!(f, "Unterminated character literal at line {}, column {}", l, col)
+            },
+            Error::UnterminatedString (l, col) => {
+                write!(f, "Unterminated string literal at line {}, column {}", l, col)
+            },
+            Error::UnterminatedComment (l, col) => {
+                write!(f, "Unterminated block comment starting at line {}, column {}", l, col)
+            },
+            Error::InvalidOperator (s) => {
+                write!(f, "Could not parse code near invalid operator {}", s)
+            },
+            Error::UnexpectedEof (s) => {
+                write!(f, "File unexpectedly terminates near token {}", s)
+            },
+            Error::ExpectedIdentifier (s) => {
+                write!(f, "Expected identifier, got token {}", s)
+            },
+            Error::ExpectedDatatypeKeyword (s) => {
+                write!(f, "Expected datatype, got token {}", s)
+            },
+            Error::ExpectedBoolean (s) => {
+                write!(f, "Expected boolean type, got token {}", s)
+            },
+            Error::ExpectedLiteral (s) => {
+                write!(f, "Expected literal, got token {}", s)
+            },
+            Error::ExpectedOpenParen (s) => {
+                write!(f, "Expected opening parenthesis, got token {}", s)
+            },
+            Error::ExpectedCloseParen (s) => {
+                write!(f, "Expected closing parenthesis, got token {}", s)
+            },
+            Error::ExpectedOpenBrace (s) => {
+                write!(f, "Expected open curly brace '{{', got token {}", s)
+            },
+            Error::ExpectedAssignment (s) => {
+                write!(f, "Expected assignment ':', got token {}", s)
+            },
+            Error::ExpectedReturnType (s) => {
+                write!(f, "Expected function return type, got token {}", s)
+            },
+            Error::FoundBareStruct => {
+                write!(f, "Could not parse bare struct initialization.\nTry naming your struct")
+            },
+            Error::ExpectedStruct (s) => {
+                write!(f, "Expected struct, got variable {}", s)
+            },
+            Error::ExpectedTestName (s) => {
+                write!(f, "Expected test name in double quotes, got token {}", s)
+            },
+            Error::DuplicateField (s) => {
+                write!(f, "Found field {} more than once", s)
+            },
+            Error::TooManyLeadingKeywords (s) => {
+                write!(f, "Too many leading keywords: {}", s)
+            },
+            Error::UndeclaredVariable (s) => {
+                write!(f, "Found undeclared variable {}", s)
+            },
+            Error::WrongArgumentCount (s) => {
+                write!(f, "Wrong number of arguments in call to function {}", s)
+            },
+            Error::ExpectedPointer (s) => {
+                write!(f, "Expected heap pointer declared with `own` or `opt`, got {}", s)
+            },
+            Error::ExpectedNullable (s) => {
+                write!(f, "Cannot assign `null` to {}, which is not declared with `opt`", s)
+            },
+            Error::ExpectedNew (s) => {
+                write!(f, "Heap pointer {} must be allocated with `new`", s)
+            },
+            Error::UseAfterMove (s) => {
+                write!(f, "Heap pointer {} was moved to an `own` pointer and can no longer be used", s)
+            },
+            Error::DeferOutsideFunction => {
+                write!(f, "Found `defer` outside of a function")
+            },
+            Error::TestInsideFunction => {
+                write!(f, "Found `test` inside a block\nTests may only be declared at the top level of a file")
+            },
+            Error::NonConstantInitializer (s) => {
+                write!(f, "The initializer of {} cannot be evaluated at compile time\nConstants, statics and top-level variables must be initialized with constant expressions", s)
+            },
+            Error::IntegerOverflow (l, col) => {
+                write!(f, "Integer overflow in constant expression at line {}, column {}", l, col)
+            },
+            Error::DivisionByZero (l, col) => {
+                write!(f, "Division by zero at line {}, column {}", l, col)
+            },
+            Error::CouldNotInterpret (s) => {
+                write!(f, "Could not interpret {}", s)
+            },
+            Error::UndeclaredFunction (s) => {
+                write!(f, "Found undeclared function {}", s)
+            },
+            Error::NullDereference (s) => {
+                write!(f, "Dereferenced null pointer in {}", s)
+            },
+            Error::StackOverflow (s) => {
+                write!(f, "Stack overflow in call to function {}", s)
+            },
+            Error::CouldNotCompile (s) => {
+                write!(f, "Could not compile {} to bytecode", s)
+            },
+            Error::CouldNotRunCompiler (s) => {
+                write!(f, "Could not run the C compiler {}\nSet $CC to choose a compiler", s)
+            },
+            Error::InvalidBytecode (s) => {
+                write!(f, "Invalid bytecode: {}", s)
+            },
+            Error::StatementOutsideFunction => {
+                write!(f, "Found statement outside of a function\nOnly declarations may appear at the top level of a file")
+            },
+            Error::ExpectedReferenceArgument (s) => {
+                write!(f, "Expected a variable to pass by reference to function {}", s)
+            },
+            Error::CouldNotGetCurrentDir => {
+                write!(f, "Could not determine working directory")
+            },
+        }
+    }
+}
@@ -0,0 +1,790 @@
+//! A source formatter for the Boron programming language.
+//!
+//! The formatter pretty-prints the parsed program in a canonical layout.  It
+//! walks the lossless token stream alongside the parse tree, which carries
+//! comments, blank lines, and the order of struct fields over from the
+//! source.  The output is parsed again and must give the same parse tree.
+
+
+use std::{
+    cmp::min,
+    collections::HashMap,
+};
+
+use crate::parser::{
+    Expression,
+    Parser,
+    Passing,
+    Token,
+    TokenType,
+    Tokenizer,
+};
+
+use crate::error::Error;
+
+
+/// The text of one level of indentation.
+const INDENT: &str = "    ";
+
+/// The precedence of any expression that is not an infix operation.
+const ATOM: u8 = u8::MAX;
+
+
+/// Holds a comment on its own line.
+struct Comment<'a> {
+    text: &'a str,
+    // Whether a blank line comes before the comment
+    blank_line: bool,
+}
+
+
+/// Holds a significant token of the source along with the comments around it.
+struct SourceToken<'a> {
+    token: Token<'a>,
+    // Comments on their own lines before the token
+    leading: Vec<Comment<'a>>,
+    // Comments following the token on the same line
+    trailing: Vec<&'a str>,
+    // Whether a blank line comes directly before the token
+    blank_line: bool,
+}
+
+/// Provides functions for the `SourceToken` struct.
+impl SourceToken<'_> {
+    /// Checks if a blank line comes before the token or its first leading comment.
+    fn blank_line_before(&self) -> bool {
+        match self.leading.first() {
+            Some(c) => c.blank_line,
+            None => self.blank_line,
+        }
+    }
+}
+
+
+/// Formats a Boron program.
+pub fn format(source: &str) -> Result<String, Error> {
+    let expressions = parse(source)?;
+    let mut formatter = Formatter::new(source)?;
+    formatter.program(&expressions);
+    let output = formatter.finish();
+
+    // Formatting must never change the meaning of a program
+    if parse(&output)? != expressions {
+        return Err(Error::FormatChangedProgram);
+    }
+    Ok(output)
+}
+
+
+/// Parses a program, leaving out the locations of operators, which formatting may move.
+fn parse(source: &str) -> Result<Vec<Expression>, Error> {
+    let mut tokenizer = Tokenizer::new(source);
+    let mut expressions = Parser::new().parse_all(&mut tokenizer)?;
+    for expression in expressions.iter_mut() {
+        clear_spans(expression);
+    }
+    Ok(expressions)
+}
+
+
+/// Resets the locations stored in an expression and the expressions within it.
+fn clear_spans(expression: &mut Expression) {
+    match expression {
+        Expression::UnaryOp { expr, span, .. } => {
+            *span = Default::default();
+            clear_spans(expr);
+        },
+        Expression::BinOp { left, right, span, .. } => {
+            *span = Default::default();
+            clear_spans(left);
+            clear_spans(right);
+        },
+        Expression::StructInit { variables }
+        | Expression::New { variables, .. } => variables.values_mut().for_each(clear_spans),
+        Expression::Assignment { value, .. }
+        | Expression::Const { value, .. }
+        | Expression::Static { value, .. }
+        | Expression::Reassignment { value, .. }
+        | Expression::Return (value)
+        | Expression::Defer (value) => clear_spans(value),
+        Expression::FnCall { args, .. } => args.iter_mut().for_each(clear_spans),
+        Expression::While { condition, body }
+        | Expression::If { condition, body } => {
+            clear_spans(condition);
+            body.iter_mut().for_each(clear_spans);
+        },
+        Expression::IfElse { condition, body_true, body_false } => {
+            clear_spans(condition);
+            body_true.iter_mut().for_each(clear_spans);
+            body_false.iter_mut().for_each(clear_spans);
+        },
+        Expression::TernaryIfElse { condition, body_true, body_false } => {
+            clear_spans(condition);
+            clear_spans(body_true);
+            clear_spans(body_false);
+        },
+        Expression::FnDeclaration { body, .. }
+        | Expression::Test { body, .. } => body.iter_mut().for_each(clear_spans),
+        Expression::Assert { condition, span } => {
+            *span = Default::default();
+            clear_spans(condition);
+        },
+        Expression::AssertEq { left, right, span } => {
+            *span = Default::default();
+            clear_spans(left);
+            clear_spans(right);
+        },
+        _ => (),
+    }
+}
+
+
+/// Gets the precedence of an expression's outermost infix operator.
+///
+/// An expression parsed after an operator of the same or higher precedence
+/// must be parenthesized.
+fn binding(expression: &Expression) -> u8 {
+    match expression {
+        Expression::BinOp { op, .. } => (*op).into(),
+        Expression::TernaryIfElse { .. } => TokenType::TernaryIf.into(),
+        Expression::Reassignment { .. } => TokenType::Assignment.into(),
+        _ => ATOM,
+    }
+}
+
+
+/// Gets the precedence above which an operator following an expression would be parsed into it.
+///
+/// An expression followed by an operator of higher precedence must be parenthesized.
+fn tail(expression: &Expression) -> u8 {
+    let (precedence, last) = match expression {
+        Expression::BinOp { op, right, .. } => ((*op).into(), right),
+        Expression::UnaryOp { op, expr, .. } => ((*op).into(), expr),
+        Expression::TernaryIfElse { body_false, .. } => (TokenType::TernaryElse.into(), body_false),
+        Expression::Reassignment { value, .. } => (TokenType::Assignment.into(), value),
+        _ => return ATOM,
+    };
+    // A parenthesized operand cannot take in the operator
+    if binding(last) <= precedence {
+        precedence
+    } else {
+        min(precedence, tail(last))
+    }
+}
+
+
+/// Gets the token type of a word in a datatype.
+fn datatype_token(word: &str) -> TokenType {
+    match word {
+        "int" | "flt" | "bln" | "chr" => TokenType::Type,
+        "own" => TokenType::Own,
+        "opt" => TokenType::Opt,
+        _ => TokenType::Identifier,
+    }
+}
+
+
+/// Writes a character as a character literal.
+fn char_literal(c: char) -> String {
+    match c {
+        '\n' => "'\\n'".to_string(),
+        '\t' => "'\\t'".to_string(),
+        '\r' => "'\\r'".to_string(),
+        '\0' => "'\\0'".to_string(),
+        '\\' => "'\\\\'".to_string(),
+        '\'' => "'\\''".to_string(),
+        _ => format!("'{}'", c),
+    }
+}
+
+
+/// Writes text as a string literal.
+fn str_literal(s: &str) -> String {
+    let mut literal = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            '\0' => literal.push_str("\\0"),
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+
+/// Holds the state of formatting a program.
+struct Formatter<'a> {
+    source: Vec<SourceToken<'a>>,
+    // Comments after the last token of the source
+    end: Vec<Comment<'a>>,
+    // Index of the next source token to be written
+    cursor: usize,
+    output: String,
+    // Text and indentation of the line being written
+    line: String,
+    line_indent: usize,
+    indent: usize,
+    // Whether a comment has broken the current statement across lines
+    continued: bool,
+}
+
+/// Provides the layout of each kind of expression.
+impl<'a> Formatter<'a> {
+    /// Constructs a formatter over the tokens and comments of the source.
+    fn new(source: &'a str) -> Result<Self, Error> {
+        let mut tokens: Vec<SourceToken> = Vec::new();
+        let mut leading = Vec::new();
+        let mut blank_line = false;
+        // Whether no newline has been seen since the last significant token
+        let mut same_line = false;
+
+        for token in Tokenizer::lossless(source) {
+            let token = token?;
+            match token.get_type() {
+                TokenType::Whitespace => {
+                    let newlines = token.as_str().matches('\n').count();
+                    if newlines > 0 {
+                        same_line = false;
+                    }
+                    if newlines > 1 {
+                        blank_line = true;
+                    }
+                },
+                TokenType::Comment
+                | TokenType::DocComment => {
+                    match tokens.last_mut() {
+                        Some(t) if same_line => t.trailing.push(token.as_str()),
+                        _ => leading.push(Comment {
+                            text: token.as_str(),
+                            blank_line,
+                        }),
+                    }
+                    blank_line = false;
+                    if token.as_str().contains('\n') {
+                        same_line = false;
+                    }
+                },
+                _ => {
+                    tokens.push(SourceToken {
+                        token,
+                        leading: std::mem::take(&mut leading),
+                        trailing: Vec::new(),
+                        blank_line,
+                    });
+                    blank_line = false;
+                    same_line = true;
+                },
+            }
+        }
+
+        Ok(Self {
+            source: tokens,
+            end: leading,
+            cursor: 0,
+            output: String::new(),
+            line: String::new(),
+            line_indent: 0,
+            indent: 0,
+            continued: false,
+        })
+    }
+
+    /// Writes text to the current line, separated from what precedes it by a space if `space` is set.
+    fn write(&mut self, text: &str, space: bool) {
+        if self.line.is_empty() {
+            self.line_indent = self.indent + self.continued as usize;
+        } else if space {
+            self.line.push(' ');
+        }
+        self.line.push_str(text);
+    }
+
+    /// Ends the current line, if anything has been written to it.
+    fn newline(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        for _ in 0..self.line_indent {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(self.line.trim_end());
+        self.output.push('\n');
+        self.line.clear();
+    }
+
+    /// Ends the current line and leaves a blank line, unless this is the start of the output or of a block.
+    fn blank_line(&mut self) {
+        self.newline();
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") && !self.output.ends_with("{\n") {
+            self.output.push('\n');
+        }
+    }
+
+    /// Writes a comment that follows a token on the same line.
+    fn trailing_comment(&mut self, comment: &str) {
+        self.write(comment, true);
+        // Anything after a line comment must go on the next line
+        if !comment.starts_with("#[") {
+            self.newline();
+            self.continued = true;
+        }
+    }
+
+    /// Writes a comment on its own line.
+    fn leading_comment(&mut self, comment: &str) {
+        if !self.line.is_empty() {
+            self.newline();
+            self.continued = true;
+        }
+        self.write(comment, false);
+        self.newline();
+    }
+
+    /// Writes the comments before the next source token.
+    ///
+    /// A blank line before the first comment is left to `statement`, since it
+    /// is only kept between statements.
+    fn flush_leading(&mut self) {
+        // Blank lines are never left before a closing brace
+        let (comments, blank_line) = match self.source.get_mut(self.cursor) {
+            Some(t) => (std::mem::take(&mut t.leading), t.blank_line && !t.token.check(TokenType::CloseBrace)),
+            None => return,
+        };
+        self.comments(comments);
+        if blank_line && self.line.is_empty() {
+            self.blank_line();
+        }
+    }
+
+    /// Writes comments on their own lines, keeping blank lines between them.
+    fn comments(&mut self, comments: Vec<Comment>) {
+        for (i, comment) in comments.iter().enumerate() {
+            if i > 0 && comment.blank_line {
+                self.blank_line();
+            }
+            self.leading_comment(comment.text);
+        }
+    }
+
+    /// Writes the comments after a source token.
+    fn flush_trailing(&mut self, index: usize) {
+        for comment in std::mem::take(&mut self.source[index].trailing) {
+            self.trailing_comment(comment);
+        }
+    }
+
+    /// Checks if a source token is one the formatter may leave out.
+    fn is_droppable(&self, index: usize) -> bool {
+        let token = self.source[index].token;
+        matches!(token.get_type(), TokenType::OpenParen | TokenType::CloseParen | TokenType::FnDeclaration | TokenType::FnReturnType)
+            || token.as_str() == "nul"
+    }
+
+    /// Writes a token, along with any comments attached to it in the source.
+    fn token(&mut self, token_type: TokenType, text: &str, space: bool) {
+        // Find the token in the source, passing over parentheses and other tokens left out of the output
+        let mut index = self.cursor;
+        let found = loop {
+            match self.source.get(index) {
+                Some(t) if t.token.get_type() == token_type
+                    && (t.token.as_str() == text || matches!(token_type, TokenType::Int | TokenType::Float | TokenType::Str)) => break true,
+                Some(_) if self.is_droppable(index) => index += 1,
+                _ => break false,
+            }
+        };
+        // Parentheses added by the formatter have no counterpart in the source
+        if !found {
+            self.write(text, space);
+            return;
+        }
+
+        while self.cursor < index {
+            self.flush_leading();
+            self.flush_trailing(self.cursor);
+            self.cursor += 1;
+        }
+        self.flush_leading();
+        self.write(text, space);
+        self.flush_trailing(index);
+        self.cursor += 1;
+    }
+
+    /// Writes the tokens of a datatype, such as `own Point`.
+    fn datatype(&mut self, datatype: &str, space: bool) {
+        for (i, word) in datatype.split_whitespace().enumerate() {
+            self.token(datatype_token(word), word, space || i > 0);
+        }
+    }
+
+    /// Writes a whole program, leaving the rest of the comments at the end.
+    fn program(&mut self, expressions: &[Expression]) {
+        let mut previous: Option<&Expression> = None;
+        for expression in expressions {
+            // Declarations spanning several lines are set apart by blank lines
+            let multiline = |e: &Expression| matches!(e, Expression::FnDeclaration { .. } | Expression::Struct { .. } | Expression::Test { .. });
+            if previous.is_some_and(|p| multiline(p) || multiline(expression)) {
+                self.blank_line();
+            }
+            self.statement(expression);
+            previous = Some(expression);
+        }
+
+        // Write comments left over from tokens that were never written
+        while self.cursor < self.source.len() {
+            self.flush_leading();
+            self.flush_trailing(self.cursor);
+            self.cursor += 1;
+        }
+        self.newline();
+        let end = std::mem::take(&mut self.end);
+        if end.first().is_some_and(|c| c.blank_line) {
+            self.blank_line();
+        }
+        self.comments(end);
+    }
+
+    /// Finishes formatting, returning the output.
+    fn finish(mut self) -> String {
+        self.newline();
+        let trimmed = self.output.trim_end().len();
+        self.output.truncate(trimmed);
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output
+    }
+
+    /// Writes a statement on its own line, keeping a blank line before it if the source has one.
+    fn statement(&mut self, expression: &Expression) {
+        if self.source.get(self.cursor).is_some_and(|t| t.blank_line_before()) {
+            self.blank_line();
+        }
+        self.expression(expression, false);
+        self.newline();
+        self.continued = false;
+    }
+
+    /// Writes a block of statements between curly braces.
+    fn block(&mut self, body: &[Expression]) {
+        self.token(TokenType::OpenBrace, "{", true);
+        let has_comments = !self.line.ends_with('{')
+            || self.source.get(self.cursor).is_some_and(|t| !t.leading.is_empty());
+        if body.is_empty() && !has_comments {
+            self.token(TokenType::CloseBrace, "}", false);
+            return;
+        }
+
+        self.newline();
+        self.continued = false;
+        self.indent += 1;
+        for expression in body {
+            self.statement(expression);
+        }
+        self.close_block();
+        self.indent -= 1;
+        self.newline();
+        self.token(TokenType::CloseBrace, "}", false);
+    }
+
+    /// Writes the comments before a closing brace, which belong inside its block.
+    fn close_block(&mut self) {
+        match self.source.get(self.cursor) {
+            Some(t) if t.token.check(TokenType::CloseBrace) && !t.leading.is_empty() => {
+                if t.blank_line_before() {
+                    self.blank_line();
+                }
+                self.flush_leading();
+            },
+            _ => (),
+        }
+    }
+
+    /// Writes an expression, in parentheses if `parenthesize` is set.
+    fn operand(&mut self, expression: &Expression, parenthesize: bool, space: bool) {
+        if parenthesize {
+            self.token(TokenType::OpenParen, "(", space);
+            self.expression(expression, false);
+            self.token(TokenType::CloseParen, ")", false);
+        } else {
+            self.expression(expression, space);
+        }
+    }
+
+    /// Chooses the next field to write, following the order of the source.
+    ///
+    /// `offset` is the position of the field name relative to the next source token.
+    fn next_field(&self, remaining: &mut Vec<String>, offset: usize) -> String {
+        let name = self.source.get(self.cursor + offset).map(|t| t.token.as_str());
+        // Fields not found in the source are written in alphabetical order
+        let index = remaining.iter().position(|f| Some(f.as_str()) == name).unwrap_or(0);
+        remaining.remove(index)
+    }
+
+    /// Writes the fields of a struct initialization, such as `{ x 1 y 2 }`.
+    fn fields(&mut self, variables: &HashMap<String, Expression>) {
+        self.token(TokenType::OpenBrace, "{", true);
+        let mut remaining = variables.keys().cloned().collect::<Vec<String>>();
+        remaining.sort();
+        while !remaining.is_empty() {
+            let field = self.next_field(&mut remaining, 0);
+            self.token(TokenType::Identifier, &field, true);
+            self.expression(&variables[&field], true);
+        }
+        self.token(TokenType::CloseBrace, "}", !variables.is_empty());
+    }
+
+    /// Writes an expression.
+    fn expression(&mut self, expression: &Expression, space: bool) {
+        match expression {
+            Expression::Int (i) => self.token(TokenType::Int, &i.to_string(), space),
+            Expression::Float (f) => {
+                let mut text = f.to_string();
+                if !text.contains('.') {
+                    text.push_str(".0");
+                }
+                self.token(TokenType::Float, &text, space);
+            },
+            Expression::Bool (b) => self.token(TokenType::Bool, &b.to_string(), space),
+            Expression::Char (c) => self.token(TokenType::Char, &char_literal(*c), space),
+            Expression::Null => self.token(TokenType::Null, "null", space),
+            Expression::Identifier (i) => self.token(TokenType::Identifier, i, space),
+            Expression::Type (t) => self.datatype(t, space),
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                let text = match o {
+                    TokenType::Not => "!",
+                    _ => "-",
+                };
+                self.token(*o, text, space);
+                self.operand(e, binding(e) <= (*o).into(), false);
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => {
+                let text = match o {
+                    TokenType::Plus => "+",
+                    TokenType::Minus => "-",
+                    TokenType::Multiply => "*",
+                    TokenType::Divide => "/",
+                    TokenType::Greater => ">",
+                    TokenType::Less => "<",
+                    TokenType::GreaterEqual => ">=",
+                    TokenType::LessEqual => "<=",
+                    _ => "=",
+                };
+                let precedence: u8 = (*o).into();
+                self.operand(l, tail(l) < precedence, space);
+                self.token(*o, text, true);
+                self.operand(r, binding(r) <= precedence, true);
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                self.datatype(d, space);
+                self.token(TokenType::Identifier, i, true);
+            },
+            Expression::Struct {
+                identifier: i,
+                variables: v,
+                ..
+            } => {
+                self.token(TokenType::Struct, "struct", space);
+                self.token(TokenType::Identifier, i, true);
+                self.token(TokenType::OpenBrace, "{", true);
+                self.newline();
+                self.continued = false;
+                self.indent += 1;
+                let mut remaining = v.keys().cloned().collect::<Vec<String>>();
+                remaining.sort();
+                while !remaining.is_empty() {
+                    // Pointer fields have two words in their datatype
+                    let pointer = self.source.get(self.cursor)
+                        .is_some_and(|t| matches!(t.token.get_type(), TokenType::Own | TokenType::Opt));
+                    let field = self.next_field(&mut remaining, if pointer { 2 } else { 1 });
+                    self.datatype(&v[&field], false);
+                    self.token(TokenType::Identifier, &field, true);
+                    self.newline();
+                    self.continued = false;
+                }
+                self.close_block();
+                self.indent -= 1;
+                self.newline();
+                self.token(TokenType::CloseBrace, "}", false);
+            },
+            Expression::StructInit {
+                variables: v,
+            } => self.fields(v),
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                self.token(TokenType::New, "new", space);
+                self.token(TokenType::Identifier, d, true);
+                self.fields(v);
+            },
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.declaration(TokenType::Let, "let", d, i, v, space),
+            Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.declaration(TokenType::Const, "const", d, i, v, space),
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.declaration(TokenType::Static, "static", d, i, v, space),
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => {
+                self.token(TokenType::Identifier, i, space);
+                self.token(TokenType::Assignment, ":", false);
+                self.operand(v, binding(v) <= TokenType::Assignment.into(), true);
+            },
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => {
+                self.token(TokenType::Identifier, n, space);
+                self.token(TokenType::OpenParen, "(", false);
+                for (i, arg) in a.iter().enumerate() {
+                    self.expression(arg, i > 0);
+                }
+                self.token(TokenType::CloseParen, ")", false);
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                self.token(TokenType::While, "while", space);
+                self.operand(c, binding(c) <= TokenType::While.into(), true);
+                self.block(b);
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                self.token(TokenType::If, "if", space);
+                self.expression(c, true);
+                self.block(b);
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                self.token(TokenType::If, "if", space);
+                self.expression(c, true);
+                self.block(t);
+                self.token(TokenType::Else, "else", true);
+                self.block(f);
+            },
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let precedence: u8 = TokenType::TernaryIf.into();
+                self.operand(c, tail(c) < precedence, space);
+                self.token(TokenType::TernaryIf, "?", true);
+                self.operand(t, binding(t) <= precedence, true);
+                self.token(TokenType::TernaryElse, "|", true);
+                self.operand(f, binding(f) <= precedence, true);
+            },
+            Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                body: b,
+                constant: c,
+                ..
+            } => {
+                if *c {
+                    self.token(TokenType::Const, "const", space);
+                }
+                self.token(TokenType::Identifier, i, space || *c);
+                // A function with neither arguments nor a return type still needs `::`
+                if !a.is_empty() || r == "nul" {
+                    self.token(TokenType::FnDeclaration, "::", true);
+                }
+                for (name, datatype, passing) in a {
+                    if *passing == Passing::Reference {
+                        self.token(TokenType::Ref, "ref", true);
+                    }
+                    self.datatype(datatype, true);
+                    self.token(TokenType::Identifier, name, true);
+                }
+                if r != "nul" {
+                    self.token(TokenType::FnReturnType, "->", true);
+                    self.datatype(r, true);
+                }
+                self.block(b);
+            },
+            Expression::Return (e) => {
+                self.token(TokenType::Return, "return", space);
+                self.expression(e, true);
+            },
+            Expression::Defer (e) => {
+                self.token(TokenType::Defer, "defer", space);
+                self.expression(e, true);
+            },
+            Expression::Use (u) => {
+                self.token(TokenType::Use, "use", space);
+                self.token(TokenType::Identifier, &u.replace('/', "."), true);
+            },
+            Expression::Test {
+                name: n,
+                body: b,
+            } => {
+                self.token(TokenType::Test, "test", space);
+                self.token(TokenType::Str, &str_literal(n), true);
+                self.block(b);
+            },
+            Expression::Assert {
+                condition: c,
+                ..
+            } => {
+                self.token(TokenType::Assert, "assert", space);
+                self.token(TokenType::OpenParen, "(", false);
+                self.expression(c, false);
+                self.token(TokenType::CloseParen, ")", false);
+            },
+            Expression::AssertEq {
+                left: l,
+                right: r,
+                ..
+            } => {
+                self.token(TokenType::Assert, "assert_eq", space);
+                self.token(TokenType::OpenParen, "(", false);
+                self.expression(l, false);
+                self.expression(r, true);
+                self.token(TokenType::CloseParen, ")", false);
+            },
+        }
+    }
+
+    /// Writes a `let`, `const` or `static` declaration.
+    fn declaration(&mut self, keyword_type: TokenType, keyword: &str, datatype: &str, identifier: &str, value: &Expression, space: bool) {
+        self.token(keyword_type, keyword, space);
+        self.datatype(datatype, true);
+        self.token(TokenType::Identifier, identifier, true);
+        self.token(TokenType::Assignment, ":", false);
+        self.operand(value, binding(value) <= TokenType::Assignment.into(), true);
+    }
+}
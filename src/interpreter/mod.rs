@@ -0,0 +1,885 @@
+//! A tree-walking interpreter for the Boron programming language.
+//!
+//! The interpreter executes parsed expressions directly, with the same
+//! semantics as the C emitted by the Boron compiler.  It serves both as a
+//! quick way to run small programs and as a reference implementation
+//! that the output of the C backend can be tested against.
+
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::read_to_string,
+    rc::Rc,
+};
+
+use crate::parser::{
+    Expression,
+    Parser,
+    Passing,
+    Span,
+    TokenType,
+    Tokenizer,
+};
+
+use crate::modules::locate;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::error::Error;
+
+
+/// The deepest chain of function calls that will be interpreted.
+const MAX_DEPTH: usize = 10_000;
+
+
+/// Holds a value that may be shared, such as a variable passed by `ref`.
+pub type Cell = Rc<RefCell<Value>>;
+
+
+/// Enumerates values that can be held by variables at runtime.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Int (i32),
+    Float (f32),
+    Bool (bool),
+    Char (char),
+    // Struct held by value, with a cell for each field
+    Struct (String, HashMap<String, Cell>),
+    // Heap pointer to a struct, or `null`
+    Pointer (Option<Cell>),
+    Void,
+}
+
+/// Provides functions for the `Value` enum.
+impl Value {
+    /// Copies this value, including every field of a struct.
+    ///
+    /// Pointers are copied shallowly, so that the copy points to the same struct.
+    pub fn deep_copy(&self) -> Value {
+        match self {
+            Value::Struct (name, fields) => Value::Struct (
+                name.to_owned(),
+                fields.iter()
+                    .map(|(f, c)| (f.to_owned(), Rc::new(RefCell::new(c.borrow().deep_copy()))))
+                    .collect(),
+            ),
+            _ => self.to_owned(),
+        }
+    }
+
+    /// Checks if this value is true when used as a condition, as in C.
+    pub fn is_truthy(&self) -> Result<bool, Error> {
+        match self {
+            Value::Int (i) => Ok(*i != 0),
+            Value::Float (f) => Ok(*f != 0.0),
+            Value::Bool (b) => Ok(*b),
+            Value::Char (c) => Ok(*c != '\0'),
+            Value::Pointer (p) => Ok(p.is_some()),
+            Value::Struct (_, _)
+            | Value::Void => Err(Error::CouldNotInterpret ("condition".to_string())),
+        }
+    }
+
+    /// Gets the name of the Boron type of this value.
+    pub fn get_type(&self) -> String {
+        match self {
+            Value::Int (_) => "int".to_string(),
+            Value::Float (_) => "flt".to_string(),
+            Value::Bool (_) => "bln".to_string(),
+            Value::Char (_) => "chr".to_string(),
+            Value::Struct (name, _) => name.to_owned(),
+            Value::Pointer (Some(p)) => format!("own {}", p.borrow().get_type()),
+            Value::Pointer (None) => "null".to_string(),
+            Value::Void => "nul".to_string(),
+        }
+    }
+
+    /// Converts this value to the given datatype, as C does on assignment.
+    pub fn coerce(self, datatype: &str) -> Value {
+        match (datatype, self) {
+            ("int", Value::Float (f)) => Value::Int (f as i32),
+            ("int", Value::Char (c)) => Value::Int (c as i32),
+            ("int", Value::Bool (b)) => Value::Int (b as i32),
+            ("flt", Value::Int (i)) => Value::Float (i as f32),
+            ("flt", Value::Char (c)) => Value::Float (c as u32 as f32),
+            ("flt", Value::Bool (b)) => Value::Float (b as i32 as f32),
+            ("bln", v @ (Value::Int (_) | Value::Float (_) | Value::Char (_))) => Value::Bool (v.is_truthy() == Ok(true)),
+            ("chr", Value::Int (i)) => Value::Char (char::from(i as u8)),
+            (_, v) => v,
+        }
+    }
+
+    /// Describes this value, including the fields of structs, as shown by the REPL.
+    pub fn describe(&self) -> String {
+        match self {
+            Value::Char (c) => format!("{:?}", c),
+            Value::Struct (name, fields) => {
+                // Sort the fields so that structs are always described the same way
+                let mut fields = fields.iter()
+                    .map(|(f, c)| format!("{} {}", f, c.borrow().describe()))
+                    .collect::<Vec<String>>();
+                fields.sort();
+                format!("{} {{ {} }}", name, fields.join(" "))
+            },
+            Value::Pointer (Some(p)) => format!("new {}", p.borrow().describe()),
+            Value::Pointer (None) => "null".to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Converts a new value to the type of this value, for reassignment.
+    pub fn coerce_like(&self, value: Value) -> Value {
+        match self {
+            Value::Int (_)
+            | Value::Float (_)
+            | Value::Bool (_)
+            | Value::Char (_) => value.coerce(&self.get_type()),
+            _ => value,
+        }
+    }
+
+    /// Prints this value on its own line, which `print` only allows for primitives.
+    pub fn print(&self) -> Result<(), Error> {
+        match self {
+            Value::Struct (_, _)
+            | Value::Pointer (_) => Err(Error::CouldNotInterpret ("print".to_string())),
+            _ => {
+                println!("{}", self);
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Formats a value the way `print` shows it, or as described for structs and pointers.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int (i) => write!(f, "{}", i),
+            // C promotes floats to doubles when printing them with `%f`
+            Value::Float (x) => write!(f, "{:.6}", *x as f64),
+            Value::Bool (b) => write!(f, "{}", b),
+            Value::Char (c) => write!(f, "{}", c),
+            Value::Void => Ok(()),
+            Value::Struct (_, _)
+            | Value::Pointer (_) => write!(f, "{}", self.describe()),
+        }
+    }
+}
+
+
+/// Describes how control leaves a statement.
+enum Flow {
+    Next,
+    Return (Value),
+}
+
+
+/// Holds the declaration of a function.
+#[derive(Clone)]
+struct Function {
+    arguments: Vec<(String, String, Passing)>,
+    return_type: String,
+    body: Vec<Expression>,
+}
+
+
+/// Holds the variables of a block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, Cell>,
+    // Variables declared `own`, which take the pointers assigned to them
+    owners: HashSet<String>,
+    // Pointers that have been moved out of, which can no longer be used
+    moved: HashSet<String>,
+}
+
+
+/// Holds the scopes of a function call.
+struct Frame {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+
+/// Executes Boron programs.
+#[derive(Default)]
+pub struct Interpreter {
+    structs: HashMap<String, HashMap<String, String>>,
+    functions: HashMap<String, Function>,
+    globals: HashMap<String, Cell>,
+    // Static variables, keyed by `function.variable`
+    statics: HashMap<String, Cell>,
+    frames: Vec<Frame>,
+    // File being loaded, against whose directory `use` paths are resolved
+    file: String,
+}
+
+/// Provides an interface for the interpreter.
+impl Interpreter {
+    /// Constructs a new interpreter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the top-level declarations of a program read from a file.
+    pub fn load_file(&mut self, filename: &str, expressions: Vec<Expression>) -> Result<(), Error> {
+        self.file = filename.to_string();
+        self.load(expressions)
+    }
+
+    /// Loads the top-level declarations of a program.
+    ///
+    /// Functions and structs are registered first, so they may be used before they are declared.
+    pub fn load(&mut self, expressions: Vec<Expression>) -> Result<(), Error> {
+        let expressions = desugar(expressions)?;
+        for expression in expressions.iter() {
+            self.declare(expression);
+        }
+
+        for expression in expressions {
+            match expression {
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. } => (),
+                Expression::Use (_)
+                | Expression::Assignment { .. }
+                | Expression::Const { .. }
+                | Expression::Static { .. } => {
+                    self.exec(&expression)?;
+                },
+                _ => return Err(Error::StatementOutsideFunction),
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the `main` function of a loaded program and returns its exit code.
+    pub fn run(&mut self) -> Result<i32, Error> {
+        match self.call("main", Vec::new())? {
+            Value::Int (i) => Ok(i),
+            _ => Ok(0),
+        }
+    }
+
+    /// Executes a statement entered at the top level, such as in the REPL.
+    ///
+    /// Returns the value of a bare expression, or `None` for any other statement.
+    /// Variables declared this way are globals.
+    pub fn exec_statement(&mut self, statement: &Expression) -> Result<Option<Value>, Error> {
+        for statement in desugar(vec![statement.to_owned()])? {
+            if is_bare(&statement) {
+                return Ok(Some(self.eval(&statement)?));
+            }
+            self.exec(&statement)?;
+        }
+        Ok(None)
+    }
+
+    /// Abandons any function calls in progress, such as after an error.
+    pub fn reset(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Infers the type of an expression without evaluating it.
+    pub fn infer(&self, expr: &Expression) -> Result<String, Error> {
+        match expr {
+            Expression::Int (_) => Ok("int".to_string()),
+            Expression::Float (_) => Ok("flt".to_string()),
+            Expression::Bool (_) => Ok("bln".to_string()),
+            Expression::Char (_) => Ok("chr".to_string()),
+            Expression::Null => Ok("null".to_string()),
+            Expression::Identifier (i) => Ok(self.lvalue(i)?.borrow().get_type()),
+            Expression::UnaryOp {
+                op: TokenType::Not,
+                ..
+            } => Ok("bln".to_string()),
+            Expression::UnaryOp {
+                expr: e,
+                ..
+            } => match self.infer(e)?.as_str() {
+                "flt" => Ok("flt".to_string()),
+                _ => Ok("int".to_string()),
+            },
+            Expression::BinOp {
+                left: l,
+                op: TokenType::Plus | TokenType::Minus | TokenType::Multiply | TokenType::Divide,
+                right: r,
+                ..
+            } => if self.infer(l)? == "flt" || self.infer(r)? == "flt" {
+                Ok("flt".to_string())
+            } else {
+                Ok("int".to_string())
+            },
+            Expression::BinOp { .. } => Ok("bln".to_string()),
+            Expression::TernaryIfElse {
+                body_true: t,
+                ..
+            } => Ok(self.infer(t)?),
+            Expression::New {
+                datatype: d,
+                ..
+            } => Ok(format!("own {}", d)),
+            Expression::FnCall {
+                name: n,
+                ..
+            } => match self.functions.get(n) {
+                Some(f) => Ok(f.return_type.to_owned()),
+                None if n == "print" || n == "drop" => Ok("nul".to_string()),
+                None => Err(Error::UndeclaredFunction (n.to_string())),
+            },
+            _ => Err(Error::CouldNotInterpret (format!("{:?}", expr))),
+        }
+    }
+
+    /// Registers a struct or function declaration.
+    fn declare(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Struct {
+                identifier: i,
+                variables: v,
+                ..
+            } => {
+                self.structs.insert(i.to_owned(), v.to_owned());
+            },
+            Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                body: b,
+                ..
+            } => {
+                let function = Function {
+                    arguments: a.to_owned(),
+                    return_type: r.to_owned(),
+                    body: b.to_owned(),
+                };
+                self.functions.insert(i.to_owned(), function);
+            },
+            _ => (),
+        }
+    }
+
+    /// Loads the declarations of a Boron module.
+    fn load_module(&mut self, module: &str) -> Result<(), Error> {
+        let filename = locate(module, &self.file)?;
+        let code = match read_to_string(&filename) {
+            Ok(c) => c,
+            Err(_) => return Err(Error::CouldNotReadFile (filename)),
+        };
+
+        let mut tokenizer = Tokenizer::new(&code);
+        let expressions = Parser::new().parse_all(&mut tokenizer)?;
+
+        // Modules are loaded at the top level, even when `use`d inside a function
+        let frames = std::mem::take(&mut self.frames);
+        let file = std::mem::replace(&mut self.file, filename);
+        self.load(expressions)?;
+        self.frames = frames;
+        self.file = file;
+        Ok(())
+    }
+
+    /// Binds a variable in the innermost scope, or as a global at the top level.
+    fn bind(&mut self, name: String, cell: Cell) {
+        match self.frames.last_mut().and_then(|f| f.scopes.last_mut()) {
+            Some(scope) => {
+                // A redeclared variable holds a new value
+                scope.owners.remove(&name);
+                scope.moved.remove(&name);
+                scope.variables.insert(name, cell);
+            },
+            None => {
+                self.globals.insert(name, cell);
+            },
+        }
+    }
+
+    /// Looks up a variable, searching the scopes of the current function and then the globals.
+    fn lookup(&self, name: &String) -> Option<Cell> {
+        if let Some(frame) = self.frames.last() {
+            for scope in frame.scopes.iter().rev() {
+                if let Some(c) = scope.variables.get(name) {
+                    return Some(c.to_owned());
+                }
+            }
+        }
+        self.globals.get(name).cloned()
+    }
+
+    /// Finds the scope of the current function that declares a variable.
+    fn scope_of(&mut self, name: &String) -> Option<&mut Scope> {
+        self.frames.last_mut()?.scopes.iter_mut().rev().find(|s| s.variables.contains_key(name))
+    }
+
+    /// Checks if a variable of the current function is in one of the given sets of its scope.
+    fn marked(&self, name: &String, set: fn(&Scope) -> &HashSet<String>) -> bool {
+        match self.frames.last().and_then(|f| f.scopes.iter().rev().find(|s| s.variables.contains_key(name))) {
+            Some(scope) => set(scope).contains(name),
+            None => false,
+        }
+    }
+
+    /// Moves a heap pointer out of the variable it is read from when it is stored in an `own` variable,
+    /// so that the variable cannot be used or dropped afterwards.
+    fn move_into(&mut self, owner: bool, value: &Expression, new: &Value) {
+        if let (true, Expression::Identifier (id), Value::Pointer (_)) = (owner, value, new) {
+            if let Some(scope) = self.scope_of(id) {
+                scope.moved.insert(id.to_owned());
+            }
+        }
+    }
+
+    /// Finds the cell holding a variable or struct field (`point.x`), following heap pointers.
+    fn lvalue(&self, path: &String) -> Result<Cell, Error> {
+        let (base, fields) = split_path(path);
+        let base = base.to_string();
+        let mut cell = match self.lookup(&base) {
+            Some(c) => c,
+            None => return Err(Error::UndeclaredVariable (path.to_owned())),
+        };
+        if self.marked(&base, |s| &s.moved) {
+            return Err(Error::UseAfterMove (base));
+        }
+
+        for field in fields {
+            let next = match &*cell.borrow() {
+                Value::Struct (_, f) => f.get(field).cloned(),
+                Value::Pointer (Some(p)) => match &*p.borrow() {
+                    Value::Struct (_, f) => f.get(field).cloned(),
+                    _ => None,
+                },
+                Value::Pointer (None) => return Err(Error::NullDereference (path.to_owned())),
+                _ => None,
+            };
+            cell = match next {
+                Some(c) => c,
+                None => return Err(Error::UndeclaredVariable (path.to_owned())),
+            };
+        }
+
+        Ok(cell)
+    }
+
+    /// Constructs the zero value of a datatype, as used for uninitialized variables and fields.
+    fn zero(&self, datatype: &str) -> Result<Value, Error> {
+        match datatype {
+            "int" => Ok(Value::Int (0)),
+            "flt" => Ok(Value::Float (0.0)),
+            "bln" => Ok(Value::Bool (false)),
+            "chr" => Ok(Value::Char ('\0')),
+            "nul" => Ok(Value::Void),
+            _ if datatype.starts_with("own ") || datatype.starts_with("opt ") => Ok(Value::Pointer (None)),
+            _ => {
+                let structure = match self.structs.get(datatype) {
+                    Some(s) => s,
+                    None => return Err(Error::UndeclaredVariable (datatype.to_string())),
+                };
+                let fields = structure.iter()
+                    .map(|(f, t)| Ok((f.to_owned(), Rc::new(RefCell::new(self.zero(t)?)))))
+                    .collect::<Result<_, Error>>()?;
+                Ok(Value::Struct (datatype.to_string(), fields))
+            },
+        }
+    }
+
+    /// Constructs a struct from an initialization (`{ x 1 y 2 }`).
+    fn init_struct(&mut self, datatype: &str, variables: &HashMap<String, Expression>) -> Result<Value, Error> {
+        let value = self.zero(datatype)?;
+        if let Value::Struct (_, fields) = &value {
+            for (varname, variable) in variables.iter() {
+                let field = match fields.get(varname) {
+                    Some(f) => f,
+                    None => return Err(Error::UndeclaredVariable (format!("{}.{}", datatype, varname))),
+                };
+                let new = self.eval(variable)?;
+                let new = field.borrow().coerce_like(new);
+                *field.borrow_mut() = new;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Declares a variable with an initial value.
+    fn assign(&mut self, datatype: &str, identifier: &String, value: &Expression) -> Result<Cell, Error> {
+        let value = match value {
+            Expression::StructInit {
+                variables: v,
+            } => {
+                if datatype.starts_with("own ") || datatype.starts_with("opt ") {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                self.init_struct(datatype, v)?
+            },
+            _ => {
+                let new = self.eval(value)?.coerce(datatype);
+                if matches!(new, Value::Pointer (None)) && !datatype.starts_with("opt ") {
+                    return Err(Error::ExpectedNullable (identifier.to_string()));
+                }
+                self.move_into(datatype.starts_with("own "), value, &new);
+                new
+            },
+        };
+        let cell = Rc::new(RefCell::new(value));
+        self.bind(identifier.to_owned(), cell.to_owned());
+        if datatype.starts_with("own ") {
+            if let Some(scope) = self.frames.last_mut().and_then(|f| f.scopes.last_mut()) {
+                scope.owners.insert(identifier.to_owned());
+            }
+        }
+        Ok(cell)
+    }
+
+    /// Executes a block in a new scope.
+    fn exec_block(&mut self, body: &[Expression]) -> Result<Flow, Error> {
+        self.push_scope();
+        let mut flow = Flow::Next;
+        for statement in body {
+            flow = self.exec(statement)?;
+            if let Flow::Return (_) = flow {
+                break;
+            }
+        }
+        self.pop_scope();
+        Ok(flow)
+    }
+
+    /// Opens a new block scope in the current function.
+    fn push_scope(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.scopes.push(Scope::default());
+        }
+    }
+
+    /// Closes the innermost block scope of the current function.
+    fn pop_scope(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.scopes.pop();
+        }
+    }
+
+    /// Executes a single statement.
+    fn exec(&mut self, statement: &Expression) -> Result<Flow, Error> {
+        match statement {
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } | Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                self.assign(d, i, v)?;
+            },
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => match self.frames.last() {
+                // A static variable is initialized only once, and keeps its value between calls
+                Some(frame) => {
+                    let key = format!("{}.{}", frame.name, i);
+                    match self.statics.get(&key) {
+                        Some(c) => {
+                            let cell = c.to_owned();
+                            self.bind(i.to_owned(), cell);
+                        },
+                        None => {
+                            let cell = self.assign(d, i, v)?;
+                            self.statics.insert(key, cell);
+                        },
+                    }
+                },
+                None => {
+                    self.assign(d, i, v)?;
+                },
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                let value = self.zero(d)?;
+                self.bind(i.to_owned(), Rc::new(RefCell::new(value)));
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => match &**v {
+                Expression::StructInit {
+                    variables: fields,
+                } => {
+                    for (varname, variable) in fields.iter() {
+                        let cell = self.lvalue(&format!("{}.{}", i, varname))?;
+                        let new = self.eval(variable)?;
+                        let new = cell.borrow().coerce_like(new);
+                        *cell.borrow_mut() = new;
+                    }
+                },
+                _ => {
+                    let new = self.eval(v)?;
+                    // Assigning to a moved pointer gives it a value again
+                    if let Some(scope) = self.scope_of(i) {
+                        scope.moved.remove(i);
+                    }
+                    let cell = self.lvalue(i)?;
+                    let new = cell.borrow().coerce_like(new);
+                    self.move_into(self.marked(i, |s| &s.owners), v, &new);
+                    *cell.borrow_mut() = new;
+                },
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                while self.eval(c)?.is_truthy()? {
+                    if let Flow::Return (v) = self.exec_block(b)? {
+                        return Ok(Flow::Return (v));
+                    }
+                }
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                if self.eval(c)?.is_truthy()? {
+                    return self.exec_block(b);
+                }
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                return if self.eval(c)?.is_truthy()? {
+                    Ok(self.exec_block(t)?)
+                } else {
+                    Ok(self.exec_block(f)?)
+                };
+            },
+            Expression::Return (v) => return Ok(Flow::Return (self.eval(v)?)),
+            Expression::Struct { .. }
+            | Expression::FnDeclaration { .. } => self.declare(statement),
+            Expression::Use (m) => self.load_module(m)?,
+            _ => {
+                self.eval(statement)?;
+            },
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Evaluates an expression into a value.
+    fn eval(&mut self, expr: &Expression) -> Result<Value, Error> {
+        match expr {
+            Expression::Int (i) => Ok(Value::Int (*i)),
+            Expression::Float (f) => Ok(Value::Float (*f)),
+            Expression::Bool (b) => Ok(Value::Bool (*b)),
+            Expression::Char (c) => Ok(Value::Char (*c)),
+            Expression::Null => Ok(Value::Pointer (None)),
+            // Reading a struct copies it, just as assigning a struct does in C
+            Expression::Identifier (i) => Ok(self.lvalue(i)?.borrow().deep_copy()),
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                let value = self.eval(e)?;
+                Ok(unop(*o, value)?)
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                span: s,
+            } => {
+                let left = self.eval(l)?;
+                let right = self.eval(r)?;
+                Ok(binop(*o, left, right, s)?)
+            },
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => if self.eval(c)?.is_truthy()? {
+                Ok(self.eval(t)?)
+            } else {
+                Ok(self.eval(f)?)
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => Ok(Value::Pointer (Some(Rc::new(RefCell::new(self.init_struct(d, v)?))))),
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => match n.as_str() {
+                "print" => {
+                    for arg in a {
+                        self.eval(arg)?.print()?;
+                    }
+                    Ok(Value::Void)
+                },
+                "drop" => {
+                    for arg in a {
+                        let id = match arg {
+                            Expression::Identifier (id) => id,
+                            _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+                        };
+                        let cell = self.lvalue(id)?;
+                        if !matches!(*cell.borrow(), Value::Pointer (_)) {
+                            return Err(Error::ExpectedPointer (id.to_owned()));
+                        }
+                        *cell.borrow_mut() = Value::Pointer (None);
+                    }
+                    Ok(Value::Void)
+                },
+                _ => Ok(self.call(n, a.to_vec())?),
+            },
+            _ => Err(Error::CouldNotInterpret (format!("{:?}", expr))),
+        }
+    }
+
+    /// Calls a function, passing each argument according to the function's signature.
+    fn call(&mut self, name: &str, args: Vec<Expression>) -> Result<Value, Error> {
+        let function = match self.functions.get(name) {
+            Some(f) => f.to_owned(),
+            None => return Err(Error::UndeclaredFunction (name.to_string())),
+        };
+        if function.arguments.len() != args.len() {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(Error::StackOverflow (name.to_string()));
+        }
+
+        // Evaluate the arguments in the caller's scope
+        let mut parameters = Scope::default();
+        for ((param, datatype, passing), arg) in function.arguments.iter().zip(args.iter()) {
+            let cell = match passing {
+                // A reference shares the caller's variable
+                Passing::Reference => match arg {
+                    Expression::Identifier (id) => self.lvalue(id)?,
+                    _ => return Err(Error::ExpectedReferenceArgument (name.to_string())),
+                },
+                // A struct passed by constant reference may be given as a heap pointer
+                Passing::ConstReference => match self.eval(arg)? {
+                    Value::Pointer (Some(p)) => Rc::new(RefCell::new(p.borrow().deep_copy())),
+                    Value::Pointer (None) => return Err(Error::NullDereference (param.to_owned())),
+                    v => Rc::new(RefCell::new(v)),
+                },
+                Passing::Value => Rc::new(RefCell::new(self.eval(arg)?.coerce(datatype))),
+            };
+            if datatype.starts_with("own ") {
+                parameters.owners.insert(param.to_owned());
+            }
+            parameters.variables.insert(param.to_owned(), cell);
+        }
+
+        self.frames.push(Frame {
+            name: name.to_string(),
+            scopes: vec![parameters],
+        });
+        let flow = self.exec_block(&function.body)?;
+        self.frames.pop();
+
+        match flow {
+            Flow::Return (v) => Ok(v.coerce(&function.return_type)),
+            Flow::Next => Ok(Value::Void),
+        }
+    }
+}
+
+
+/// Checks if an expression produces a value, rather than being a statement.
+pub fn is_bare(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::Int (_)
+        | Expression::Float (_)
+        | Expression::Bool (_)
+        | Expression::Char (_)
+        | Expression::Null
+        | Expression::Identifier (_)
+        | Expression::UnaryOp { .. }
+        | Expression::BinOp { .. }
+        | Expression::TernaryIfElse { .. }
+        | Expression::New { .. }
+        | Expression::FnCall { .. }
+    )
+}
+
+
+/// Evaluates a unary operation.
+pub fn unop(op: TokenType, value: Value) -> Result<Value, Error> {
+    match (op, value) {
+        (TokenType::Minus, Value::Int (i)) => Ok(Value::Int (i.wrapping_neg())),
+        (TokenType::Minus, Value::Float (f)) => Ok(Value::Float (-f)),
+        (TokenType::Minus, Value::Char (c)) => Ok(Value::Int (-(c as i32))),
+        (TokenType::Not, v) => Ok(Value::Bool (!v.is_truthy()?)),
+        _ => Err(Error::CouldNotInterpret ("unary operation".to_string())),
+    }
+}
+
+
+/// Evaluates a binary operation, promoting operands as C does.
+pub fn binop(op: TokenType, left: Value, right: Value, span: &Span) -> Result<Value, Error> {
+    // Pointers may only be compared with each other
+    if let (Value::Pointer (l), Value::Pointer (r)) = (&left, &right) {
+        let equal = match (l, r) {
+            (Some(l), Some(r)) => Rc::ptr_eq(l, r),
+            (None, None) => true,
+            _ => false,
+        };
+        return match op {
+            TokenType::Equal => Ok(Value::Bool (equal)),
+            _ => return Err(Error::CouldNotInterpret ("pointer comparison".to_string())),
+        };
+    }
+
+    // Any floating-point operand promotes the operation to floating-point
+    if matches!(left, Value::Float (_)) || matches!(right, Value::Float (_)) {
+        return match (left.coerce("flt"), right.coerce("flt")) {
+            (Value::Float (l), Value::Float (r)) => match op {
+                TokenType::Plus => Ok(Value::Float (l + r)),
+                TokenType::Minus => Ok(Value::Float (l - r)),
+                TokenType::Multiply => Ok(Value::Float (l * r)),
+                TokenType::Divide => Ok(Value::Float (l / r)),
+                _ => Ok(compare(op, l, r)?),
+            },
+            _ => return Err(Error::CouldNotInterpret ("binary operation".to_string())),
+        };
+    }
+
+    // Otherwise, chars and bools are promoted to integers
+    match (left.coerce("int"), right.coerce("int")) {
+        (Value::Int (l), Value::Int (r)) => match op {
+            TokenType::Plus => Ok(Value::Int (l.wrapping_add(r))),
+            TokenType::Minus => Ok(Value::Int (l.wrapping_sub(r))),
+            TokenType::Multiply => Ok(Value::Int (l.wrapping_mul(r))),
+            TokenType::Divide => {
+                if r == 0 {
+                    return Err(Error::DivisionByZero (span.line, span.column));
+                }
+                Ok(Value::Int (l.wrapping_div(r)))
+            },
+            _ => Ok(compare(op, l, r)?),
+        },
+        _ => Err(Error::CouldNotInterpret ("binary operation".to_string())),
+    }
+}
+
+
+/// Evaluates a comparison between two values.
+fn compare<T: PartialOrd>(op: TokenType, left: T, right: T) -> Result<Value, Error> {
+    let result = match op {
+        TokenType::Greater => left > right,
+        TokenType::Less => left < right,
+        TokenType::Equal => left == right,
+        TokenType::GreaterEqual => left >= right,
+        TokenType::LessEqual => left <= right,
+        _ => return Err(Error::CouldNotInterpret ("operation".to_string())),
+    };
+    Ok(Value::Bool (result))
+}
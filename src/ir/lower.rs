@@ -0,0 +1,815 @@
+//! Lowers the parsed program into the IR.
+
+
+use std::collections::HashMap;
+
+use crate::parser::{
+    Expression,
+    Passing,
+    TokenType,
+};
+
+use crate::emitter::include_modules;
+
+use crate::consteval::Evaluator;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::error::Error;
+
+use super::{
+    Argument,
+    BinaryOp,
+    Block,
+    Constant,
+    Function,
+    Global,
+    Initializer,
+    Instruction,
+    Operand,
+    Place,
+    Program,
+    Temp,
+    Terminator,
+    Type,
+    UnaryOp,
+    Value,
+    Variable,
+};
+
+
+/// Holds the variables of a block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, (Variable, Type)>,
+}
+
+
+/// Holds the parameters and return type of a function.
+#[derive(Clone)]
+struct Signature {
+    arguments: Vec<(Type, Passing)>,
+    return_type: Type,
+}
+
+
+/// Lowers a program, including the modules it uses, into the IR.
+pub fn lower(expressions: Vec<Expression>) -> Result<Program, Error> {
+    Lowerer::default().lower(desugar(include_modules(expressions)?)?)
+}
+
+
+/// Holds the state of lowering a program.
+#[derive(Default)]
+struct Lowerer {
+    structs: HashMap<String, Vec<(String, Type)>>,
+    functions: HashMap<String, Signature>,
+    globals: HashMap<String, Type>,
+    // Values of top-level constants and declarations of `const` functions, for evaluating initializers
+    constants: HashMap<String, Expression>,
+    const_fns: HashMap<String, Expression>,
+    program: Program,
+    // State of the function being lowered
+    function: Option<Function>,
+    block: usize,
+    scopes: Vec<Scope>,
+}
+
+/// Provides the lowering of each kind of expression.
+impl Lowerer {
+    /// Lowers a whole program.
+    fn lower(mut self, expressions: Vec<Expression>) -> Result<Program, Error> {
+        // Register every struct and function first, so they may be used before they are declared
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct {
+                    identifier: i,
+                    variables: v,
+                    ..
+                } => {
+                    let mut fields = v.iter()
+                        .map(|(f, t)| (f.to_owned(), Type::parse(t)))
+                        .collect::<Vec<(String, Type)>>();
+                    fields.sort_by(|a, b| a.0.cmp(&b.0));
+                    self.structs.insert(i.to_owned(), fields.to_owned());
+                    self.program.structs.push((i.to_owned(), fields));
+                },
+                Expression::FnDeclaration {
+                    identifier: i,
+                    arguments: a,
+                    return_type: r,
+                    constant: c,
+                    ..
+                } => {
+                    let signature = Signature {
+                        arguments: a.iter().map(|(_, t, p)| (Type::parse(t), *p)).collect(),
+                        return_type: Type::parse(r),
+                    };
+                    self.functions.insert(i.to_owned(), signature);
+                    if *c {
+                        self.const_fns.insert(i.to_owned(), expression.to_owned());
+                    }
+                },
+                _ => (),
+            }
+        }
+        self.program.structs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. } => (),
+                Expression::Assignment {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } | Expression::Static {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    self.global(d, i, v)?;
+                },
+                Expression::Const {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let value = self.global(d, i, v)?;
+                    self.constants.insert(i.to_owned(), value);
+                },
+                _ => return Err(Error::StatementOutsideFunction),
+            }
+        }
+
+        for expression in expressions.iter() {
+            if let Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                body: b,
+                ..
+            } = expression {
+                self.function(i, a, r, b)?;
+            }
+        }
+        Ok(self.program)
+    }
+
+    /// Adds a global variable with a constant initializer, returning the initializer as a literal.
+    fn global(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<Expression, Error> {
+        let datatype = Type::parse(datatype);
+        let value = match value {
+            Expression::StructInit { .. } => {
+                if datatype.pointee().is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                value.to_owned()
+            },
+            Expression::Null if !matches!(datatype, Type::Opt (_)) => return Err(Error::ExpectedNullable (identifier.to_string())),
+            _ => self.evaluate(value, identifier)?,
+        };
+        let initializer = self.initializer(&value, &datatype, identifier)?;
+        self.globals.insert(identifier.to_string(), datatype.to_owned());
+        self.program.globals.push(Global {
+            name: identifier.to_string(),
+            datatype,
+            value: initializer,
+        });
+        Ok(value)
+    }
+
+    /// Evaluates a constant expression into a literal.
+    fn evaluate(&self, value: &Expression, identifier: &str) -> Result<Expression, Error> {
+        let constants = |n: &String| Ok(self.constants.get(n).cloned());
+        let functions = |n: &String| Ok(self.const_fns.get(n).cloned());
+        match Evaluator::new(&constants, &functions).evaluate(value)? {
+            Some(v) => Ok(v),
+            None => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Converts a literal or struct initialization into the initial value of a global of the given type.
+    fn initializer(&self, value: &Expression, datatype: &Type, identifier: &str) -> Result<Initializer, Error> {
+        let constant = match (datatype, value) {
+            (Type::Struct (s), Expression::StructInit {
+                variables: v,
+            }) => {
+                let fields = self.fields(s, identifier)?;
+                for f in v.keys() {
+                    if !fields.iter().any(|(n, _)| n == f) {
+                        return Err(Error::UndeclaredVariable (format!("{}.{}", identifier, f)));
+                    }
+                }
+                return Ok(Initializer::Struct (fields.iter()
+                    .map(|(f, t)| match v.get(f) {
+                        Some(e) => Ok((f.to_owned(), self.initializer(&self.evaluate(e, identifier)?, t, identifier)?)),
+                        None => Ok((f.to_owned(), self.zero(t, identifier)?)),
+                    })
+                    .collect::<Result<_, Error>>()?));
+            },
+            (_, Expression::Int (i)) => Constant::Int (*i),
+            (_, Expression::Float (f)) => Constant::Float (*f),
+            (_, Expression::Bool (b)) => Constant::Bool (*b),
+            (_, Expression::Char (c)) => Constant::Char (*c),
+            (_, Expression::Null) => Constant::Null,
+            _ => return Err(Error::NonConstantInitializer (identifier.to_string())),
+        };
+        match super::passes::convert(constant, datatype) {
+            Some(c) => Ok(Initializer::Constant (c)),
+            None => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Gets the zero initial value of a type.
+    fn zero(&self, datatype: &Type, identifier: &str) -> Result<Initializer, Error> {
+        match (datatype, Constant::zero(datatype)) {
+            (_, Some(c)) => Ok(Initializer::Constant (c)),
+            (Type::Struct (s), None) => Ok(Initializer::Struct (self.fields(s, identifier)?.iter()
+                .map(|(f, t)| Ok((f.to_owned(), self.zero(t, identifier)?)))
+                .collect::<Result<_, Error>>()?)),
+            _ => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Gets the fields of a struct.
+    fn fields(&self, datatype: &str, path: &str) -> Result<Vec<(String, Type)>, Error> {
+        match self.structs.get(datatype) {
+            Some(f) => Ok(f.to_owned()),
+            None => Err(Error::UndeclaredVariable (path.to_string())),
+        }
+    }
+
+    /// Gets the type of a struct field.
+    fn field(&self, datatype: &str, field: &str, path: &str) -> Result<Type, Error> {
+        match self.fields(datatype, path)?.into_iter().find(|(f, _)| f == field) {
+            Some((_, t)) => Ok(t),
+            None => Err(Error::UndeclaredVariable (path.to_string())),
+        }
+    }
+
+    /// Gets the function being lowered.
+    fn current(&mut self) -> Result<&mut Function, Error> {
+        match self.function.as_mut() {
+            Some(f) => Ok(f),
+            None => Err(Error::StatementOutsideFunction),
+        }
+    }
+
+    /// Lowers a function declaration.
+    fn function(&mut self, identifier: &str, arguments: &[(String, String, Passing)], return_type: &str, body: &[Expression]) -> Result<(), Error> {
+        self.function = Some(Function {
+            name: identifier.to_string(),
+            params: Vec::new(),
+            return_type: Type::parse(return_type),
+            locals: Vec::new(),
+            temps: Vec::new(),
+            blocks: Vec::new(),
+        });
+        self.scopes = vec![Scope::default()];
+        self.block = self.new_block()?;
+        for (name, datatype, passing) in arguments {
+            let local = self.declare(name, Type::parse(datatype))?;
+            self.current()?.params.push((local, *passing));
+        }
+
+        self.block(body)?;
+
+        // Falling off the end of a function returns the zero value of its return type
+        let datatype = Type::parse(return_type);
+        let value = match (&datatype, Constant::zero(&datatype)) {
+            (_, Some(c)) => Some(Operand::Constant (c)),
+            (Type::Struct (_), None) => {
+                let local = self.local("result", datatype.to_owned())?;
+                let place = Place {
+                    variable: Variable::Local (local),
+                    fields: Vec::new(),
+                };
+                self.push(Instruction::Zero (place.to_owned()))?;
+                Some(self.assign(Value::Load (place), datatype)?)
+            },
+            _ => None,
+        };
+        self.terminate(Terminator::Return (value))?;
+
+        self.scopes.clear();
+        if let Some(function) = self.function.take() {
+            self.program.functions.push(function);
+        }
+        Ok(())
+    }
+
+    /// Adds an empty block, returning its index.  Its terminator is replaced when the block is finished.
+    fn new_block(&mut self) -> Result<usize, Error> {
+        let blocks = &mut self.current()?.blocks;
+        blocks.push(Block {
+            instructions: Vec::new(),
+            terminator: Terminator::Return (None),
+        });
+        Ok(blocks.len() - 1)
+    }
+
+    /// Appends an instruction to the current block.
+    fn push(&mut self, instruction: Instruction) -> Result<(), Error> {
+        let block = self.block;
+        self.current()?.blocks[block].instructions.push(instruction);
+        Ok(())
+    }
+
+    /// Ends the current block, continuing in a new block that nothing jumps to yet.
+    fn terminate(&mut self, terminator: Terminator) -> Result<(), Error> {
+        let block = self.block;
+        self.current()?.blocks[block].terminator = terminator;
+        self.block = self.new_block()?;
+        Ok(())
+    }
+
+    /// Ends the current block, continuing in the given block.
+    fn jump_to(&mut self, terminator: Terminator, next: usize) -> Result<(), Error> {
+        let block = self.block;
+        self.current()?.blocks[block].terminator = terminator;
+        self.block = next;
+        Ok(())
+    }
+
+    /// Assigns a value to a new temporary of the given type.
+    fn assign(&mut self, value: Value, datatype: Type) -> Result<Operand, Error> {
+        let temps = &mut self.current()?.temps;
+        temps.push(datatype);
+        let temp = Temp (temps.len() - 1);
+        self.push(Instruction::Assign (temp, value))?;
+        Ok(Operand::Temp (temp))
+    }
+
+    /// Adds a local with a name unique within the function.
+    fn local(&mut self, name: &str, datatype: Type) -> Result<usize, Error> {
+        let locals = &mut self.current()?.locals;
+        let count = locals.iter().filter(|(n, _)| n == name || n.starts_with(&format!("{}.", name))).count();
+        let unique = match count {
+            0 => name.to_string(),
+            _ => format!("{}.{}", name, count),
+        };
+        locals.push((unique, datatype));
+        Ok(locals.len() - 1)
+    }
+
+    /// Declares a variable in the innermost scope.
+    fn declare(&mut self, name: &str, datatype: Type) -> Result<usize, Error> {
+        if let Type::Struct (s) = &datatype {
+            self.fields(s, name)?;
+        }
+        let local = self.local(name, datatype.to_owned())?;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.variables.insert(name.to_string(), (Variable::Local (local), datatype));
+        }
+        Ok(local)
+    }
+
+    /// Looks up a variable, searching the scopes of the current function and then the globals.
+    fn lookup(&self, name: &str) -> Option<(Variable, Type)> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(v) = scope.variables.get(name) {
+                return Some(v.to_owned());
+            }
+        }
+        self.globals.get(name).map(|t| (Variable::Global (name.to_string()), t.to_owned()))
+    }
+
+    /// Resolves a variable or struct field (`point.x`) into a place and its type.
+    fn place(&self, path: &str) -> Result<(Place, Type), Error> {
+        let (base, fields) = split_path(path);
+        let (variable, mut datatype) = match self.lookup(base) {
+            Some(v) => v,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        let mut names = Vec::new();
+        for field in fields {
+            let structure = match &datatype {
+                Type::Struct (s)
+                | Type::Own (s)
+                | Type::Opt (s) => s.to_owned(),
+                _ => return Err(Error::UndeclaredVariable (path.to_string())),
+            };
+            datatype = self.field(&structure, field, path)?;
+            names.push(field.to_string());
+        }
+        let place = Place {
+            variable,
+            fields: names,
+        };
+        Ok((place, datatype))
+    }
+
+    /// Converts an operand to another type, as C does on assignment.
+    fn convert(&mut self, operand: Operand, from: &Type, to: &Type) -> Result<Operand, Error> {
+        match (from, to) {
+            _ if from == to => Ok(operand),
+            (Type::Null, t) if t.pointee().is_some() => Ok(operand),
+            (f, t) if f.pointee().is_some() && f.pointee() == t.pointee() => Ok(operand),
+            (f, Type::Bln) if f.pointee().is_some() => self.assign(Value::Convert (operand), Type::Bln),
+            (f, t) if f.is_scalar() && t.is_scalar() => self.assign(Value::Convert (operand), to.to_owned()),
+            _ => Err(Error::CouldNotEmit (format!("{} as {}", from, to))),
+        }
+    }
+
+    /// Lowers a block in a new scope.
+    fn block(&mut self, body: &[Expression]) -> Result<(), Error> {
+        self.scopes.push(Scope::default());
+        for statement in body {
+            self.statement(statement)?;
+        }
+
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Lowers a struct initialization (`{ x 1 y 2 }`) into a place, zeroing any other fields.
+    fn init_fields(&mut self, place: &Place, datatype: &str, variables: &HashMap<String, Expression>) -> Result<(), Error> {
+        self.push(Instruction::Zero (place.to_owned()))?;
+        // Sort the fields so that they are always initialized in the same order
+        let mut fields = variables.iter().collect::<Vec<(&String, &Expression)>>();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, value) in fields {
+            let fieldtype = self.field(datatype, name, &format!("{}.{}", datatype, name))?;
+            let (operand, from) = self.expression(value)?;
+            let operand = self.convert(operand, &from, &fieldtype)?;
+            let mut field = place.to_owned();
+            field.fields.push(name.to_owned());
+            self.push(Instruction::Store (field, operand))?;
+        }
+        Ok(())
+    }
+
+    /// Lowers the declaration of a local variable with an initial value.
+    fn assignment(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<(), Error> {
+        let datatype = Type::parse(datatype);
+        match (value, &datatype) {
+            (Expression::StructInit { .. }, t) if t.pointee().is_some() => return Err(Error::ExpectedNew (identifier.to_string())),
+            (Expression::StructInit {
+                variables: v,
+            }, Type::Struct (s)) => {
+                let local = self.declare(identifier, datatype.to_owned())?;
+                let place = Place {
+                    variable: Variable::Local (local),
+                    fields: Vec::new(),
+                };
+                self.init_fields(&place, s, v)?;
+            },
+            (Expression::Null, t) if !matches!(t, Type::Opt (_)) => return Err(Error::ExpectedNullable (identifier.to_string())),
+            _ => {
+                // Lower the value before declaring the variable, which it may not refer to
+                let (operand, from) = self.expression(value)?;
+                let operand = self.convert(operand, &from, &datatype)?;
+                let local = self.declare(identifier, datatype)?;
+                let place = Place {
+                    variable: Variable::Local (local),
+                    fields: Vec::new(),
+                };
+                self.push(Instruction::Store (place, operand))?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Lowers a condition into a `bln` operand.
+    fn condition(&mut self, condition: &Expression) -> Result<Operand, Error> {
+        let (operand, datatype) = self.expression(condition)?;
+        self.convert(operand, &datatype, &Type::Bln)
+    }
+
+    /// Lowers a single statement.
+    fn statement(&mut self, statement: &Expression) -> Result<(), Error> {
+        match statement {
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } | Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.assignment(d, i, v)?,
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                // A static variable is a global initialized once, when the program starts
+                let name = format!("{}.{}", self.current()?.name, i);
+                self.global(d, &name, v)?;
+                let datatype = Type::parse(d);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.variables.insert(i.to_owned(), (Variable::Global (name), datatype));
+                }
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                let local = self.declare(i, Type::parse(d))?;
+                self.push(Instruction::Zero (Place {
+                    variable: Variable::Local (local),
+                    fields: Vec::new(),
+                }))?;
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => match &**v {
+                Expression::StructInit {
+                    variables: fields,
+                } => {
+                    let mut fields = fields.iter().collect::<Vec<(&String, &Expression)>>();
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                    for (name, value) in fields {
+                        let (operand, from) = self.expression(value)?;
+                        let (place, datatype) = self.place(&format!("{}.{}", i, name))?;
+                        let operand = self.convert(operand, &from, &datatype)?;
+                        self.push(Instruction::Store (place, operand))?;
+                    }
+                },
+                _ => {
+                    let (operand, from) = self.expression(v)?;
+                    let (place, datatype) = self.place(i)?;
+                    let operand = self.convert(operand, &from, &datatype)?;
+                    self.push(Instruction::Store (place, operand))?;
+                },
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                let test = self.new_block()?;
+                let body = self.new_block()?;
+                let exit = self.new_block()?;
+                self.jump_to(Terminator::Jump (test), test)?;
+                let condition = self.condition(c)?;
+                self.jump_to(Terminator::Branch (condition, body, exit), body)?;
+                self.block(b)?;
+                self.jump_to(Terminator::Jump (test), exit)?;
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                let condition = self.condition(c)?;
+                let then = self.new_block()?;
+                let exit = self.new_block()?;
+                self.jump_to(Terminator::Branch (condition, then, exit), then)?;
+                self.block(b)?;
+                self.jump_to(Terminator::Jump (exit), exit)?;
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let condition = self.condition(c)?;
+                let then = self.new_block()?;
+                let otherwise = self.new_block()?;
+                let exit = self.new_block()?;
+                self.jump_to(Terminator::Branch (condition, then, otherwise), then)?;
+                self.block(t)?;
+                self.jump_to(Terminator::Jump (exit), otherwise)?;
+                self.block(f)?;
+                self.jump_to(Terminator::Jump (exit), exit)?;
+            },
+            Expression::Return (v) => {
+                let (operand, from) = self.expression(v)?;
+                let return_type = self.current()?.return_type.to_owned();
+                let value = match return_type {
+                    Type::Nul => None,
+                    _ => Some(self.convert(operand, &from, &return_type)?),
+                };
+                self.terminate(Terminator::Return (value))?;
+            },
+            Expression::Struct { .. }
+            | Expression::FnDeclaration { .. }
+            | Expression::Use (_) => return Err(Error::CouldNotEmit (format!("declaration inside {}", self.current()?.name))),
+            _ => {
+                self.expression(statement)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Lowers an expression, returning the operand holding its value and its type.
+    fn expression(&mut self, expr: &Expression) -> Result<(Operand, Type), Error> {
+        Ok(match expr {
+            Expression::Int (i) => (Operand::Constant (Constant::Int (*i)), Type::Int),
+            Expression::Float (f) => (Operand::Constant (Constant::Float (*f)), Type::Flt),
+            Expression::Bool (b) => (Operand::Constant (Constant::Bool (*b)), Type::Bln),
+            Expression::Char (c) => (Operand::Constant (Constant::Char (*c)), Type::Chr),
+            Expression::Null => (Operand::Constant (Constant::Null), Type::Null),
+            Expression::Identifier (i) => {
+                let (place, datatype) = self.place(i)?;
+                // Scalar constants are substituted for their values
+                if let (Variable::Global (g), true) = (&place.variable, place.fields.is_empty()) {
+                    if let Some(value) = self.constants.get(g) {
+                        if let Initializer::Constant (c) = self.initializer(value, &datatype, g)? {
+                            return Ok((Operand::Constant (c), datatype));
+                        }
+                    }
+                }
+                (self.assign(Value::Load (place), datatype.to_owned())?, datatype)
+            },
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                let (operand, from) = self.expression(e)?;
+                let (op, datatype) = match (o, &from) {
+                    (TokenType::Not, _) => (UnaryOp::Not, Type::Bln),
+                    (_, Type::Flt) => (UnaryOp::Neg, Type::Flt),
+                    _ => (UnaryOp::Neg, Type::Int),
+                };
+                let operand = self.convert(operand, &from, &datatype)?;
+                (self.assign(Value::Unary (op, operand), datatype.to_owned())?, datatype)
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => self.binary(*o, l, r)?,
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let condition = self.condition(c)?;
+                let then = self.new_block()?;
+                let otherwise = self.new_block()?;
+                let exit = self.new_block()?;
+                self.jump_to(Terminator::Branch (condition, then, otherwise), then)?;
+                let (t, t_type) = self.expression(t)?;
+                let t_end = self.block;
+                self.block = otherwise;
+                let (f, f_type) = self.expression(f)?;
+                let f_end = self.block;
+
+                // Each arm stores its value, converted to the type of the whole expression, in a local
+                let datatype = join(&t_type, &f_type);
+                if datatype == Type::Nul {
+                    for end in [t_end, f_end] {
+                        self.block = end;
+                        self.jump_to(Terminator::Jump (exit), exit)?;
+                    }
+                    return Ok((Operand::Constant (Constant::Null), Type::Nul));
+                }
+                let local = self.local("ternary", datatype.to_owned())?;
+                let place = Place {
+                    variable: Variable::Local (local),
+                    fields: Vec::new(),
+                };
+                for (end, operand, from) in [(t_end, t, t_type), (f_end, f, f_type)] {
+                    self.block = end;
+                    let operand = self.convert(operand, &from, &datatype)?;
+                    self.push(Instruction::Store (place.to_owned(), operand))?;
+                    self.jump_to(Terminator::Jump (exit), exit)?;
+                }
+                (self.assign(Value::Load (place), datatype.to_owned())?, datatype)
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                let mut fields = v.iter().collect::<Vec<(&String, &Expression)>>();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                let mut operands = Vec::new();
+                for (name, value) in fields {
+                    let fieldtype = self.field(d, name, &format!("{}.{}", d, name))?;
+                    let (operand, from) = self.expression(value)?;
+                    operands.push((name.to_owned(), self.convert(operand, &from, &fieldtype)?));
+                }
+                let datatype = Type::Own (d.to_owned());
+                (self.assign(Value::New (d.to_owned(), operands), datatype.to_owned())?, datatype)
+            },
+            Expression::StructInit { .. } => return Err(Error::FoundBareStruct),
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => match n.as_str() {
+                "print" => {
+                    for arg in a {
+                        let (operand, datatype) = self.expression(arg)?;
+                        if !datatype.is_scalar() {
+                            return Err(Error::CouldNotEmit (format!("print of {}", datatype)));
+                        }
+                        self.push(Instruction::Print (operand))?;
+                    }
+                    (Operand::Constant (Constant::Null), Type::Nul)
+                },
+                "drop" => {
+                    for arg in a {
+                        let id = match arg {
+                            Expression::Identifier (id) => id,
+                            _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+                        };
+                        let (place, datatype) = self.place(id)?;
+                        if datatype.pointee().is_none() {
+                            return Err(Error::ExpectedPointer (id.to_owned()));
+                        }
+                        self.push(Instruction::Drop (place))?;
+                    }
+                    (Operand::Constant (Constant::Null), Type::Nul)
+                },
+                _ => self.call(n, a)?,
+            },
+            _ => return Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        })
+    }
+
+    /// Lowers a binary operation, converting both operands to a common type as C does.
+    fn binary(&mut self, op: TokenType, left: &Expression, right: &Expression) -> Result<(Operand, Type), Error> {
+        let op = match op {
+            TokenType::Plus => BinaryOp::Add,
+            TokenType::Minus => BinaryOp::Sub,
+            TokenType::Multiply => BinaryOp::Mul,
+            TokenType::Divide => BinaryOp::Div,
+            TokenType::Greater => BinaryOp::Gt,
+            TokenType::Less => BinaryOp::Lt,
+            TokenType::GreaterEqual => BinaryOp::Ge,
+            TokenType::LessEqual => BinaryOp::Le,
+            TokenType::Equal => BinaryOp::Eq,
+            _ => return Err(Error::InvalidOperator (format!("{:?}", op))),
+        };
+        let (l, l_type) = self.expression(left)?;
+        let (r, r_type) = self.expression(right)?;
+
+        // Pointers may only be compared for equality, and are not converted
+        let pointers = [&l_type, &r_type].iter().any(|t| t.pointee().is_some() || **t == Type::Null);
+        if pointers {
+            if op != BinaryOp::Eq {
+                return Err(Error::InvalidOperator (format!("{:?}", op)));
+            }
+            return Ok((self.assign(Value::Binary (op, l, r), Type::Bln)?, Type::Bln));
+        }
+
+        let datatype = match l_type == Type::Flt || r_type == Type::Flt {
+            true => Type::Flt,
+            false => Type::Int,
+        };
+        let l = self.convert(l, &l_type, &datatype)?;
+        let r = self.convert(r, &r_type, &datatype)?;
+        let result = match op.is_comparison() {
+            true => Type::Bln,
+            false => datatype,
+        };
+        Ok((self.assign(Value::Binary (op, l, r), result.to_owned())?, result))
+    }
+
+    /// Lowers a function call, passing each argument according to the function's signature.
+    fn call(&mut self, name: &str, args: &[Expression]) -> Result<(Operand, Type), Error> {
+        let signature = match self.functions.get(name) {
+            Some(s) => s.to_owned(),
+            None => return Err(Error::UndeclaredFunction (name.to_string())),
+        };
+        if signature.arguments.len() != args.len() {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+
+        let mut arguments = Vec::new();
+        for ((datatype, passing), arg) in signature.arguments.iter().zip(args.iter()) {
+            let argument = match (passing, arg) {
+                (Passing::Value, _) => {
+                    let (operand, from) = self.expression(arg)?;
+                    Argument::Value (self.convert(operand, &from, datatype)?)
+                },
+                (_, Expression::Identifier (id)) => Argument::Reference (self.place(id)?.0),
+                (Passing::Reference, _) => return Err(Error::ExpectedReferenceArgument (name.to_string())),
+                (Passing::ConstReference, _) => Argument::Value (self.expression(arg)?.0),
+            };
+            arguments.push(argument);
+        }
+
+        let return_type = signature.return_type;
+        match return_type {
+            Type::Nul => {
+                self.push(Instruction::Call (None, name.to_string(), arguments))?;
+                Ok((Operand::Constant (Constant::Null), Type::Nul))
+            },
+            _ => {
+                let temps = &mut self.current()?.temps;
+                temps.push(return_type.to_owned());
+                let temp = Temp (temps.len() - 1);
+                self.push(Instruction::Call (Some(temp), name.to_string(), arguments))?;
+                Ok((Operand::Temp (temp), return_type))
+            },
+        }
+    }
+}
+
+
+/// Gets the type of a ternary expression whose arms have the given types.
+fn join(left: &Type, right: &Type) -> Type {
+    match (left, right) {
+        _ if left == right => left.to_owned(),
+        (Type::Null, _) => right.to_owned(),
+        (_, Type::Null) => left.to_owned(),
+        (l, r) if l.pointee().is_some() && l.pointee() == r.pointee() => Type::Opt (l.pointee().unwrap_or_default().to_string()),
+        (Type::Flt, _) | (_, Type::Flt) => Type::Flt,
+        _ => Type::Int,
+    }
+}
@@ -0,0 +1,444 @@
+//! A typed intermediate representation of Boron programs.
+//!
+//! Lowering starts from the desugared program, resolves names, makes every
+//! conversion explicit, and turns structured control flow into basic blocks.  Each
+//! temporary is assigned exactly once, while variables are read and written
+//! through loads and stores.  The passes in `passes` optimize the result.
+
+
+pub mod lower;
+pub mod passes;
+
+pub use lower::lower;
+
+use std::fmt;
+
+use crate::parser::Passing;
+
+
+/// Enumerates the types of values in the IR.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Flt,
+    Bln,
+    Chr,
+    Nul,
+    // Type of the `null` literal, which converts to any pointer
+    Null,
+    Struct (String),
+    // Owning and nullable heap pointers
+    Own (String),
+    Opt (String),
+}
+
+impl Type {
+    /// Parses a Boron type name (`int`, `Point`, `own Node`).
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "int" => Type::Int,
+            "flt" => Type::Flt,
+            "bln" => Type::Bln,
+            "chr" => Type::Chr,
+            "nul" => Type::Nul,
+            "null" => Type::Null,
+            _ => match (name.strip_prefix("own "), name.strip_prefix("opt ")) {
+                (Some(s), _) => Type::Own (s.to_string()),
+                (_, Some(s)) => Type::Opt (s.to_string()),
+                _ => Type::Struct (name.to_string()),
+            },
+        }
+    }
+
+    /// Gets the struct a pointer type points to.
+    pub fn pointee(&self) -> Option<&str> {
+        match self {
+            Type::Own (s)
+            | Type::Opt (s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Checks if this is a scalar type, which may be converted to other scalars.
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, Type::Int | Type::Flt | Type::Bln | Type::Chr)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Flt => write!(f, "flt"),
+            Type::Bln => write!(f, "bln"),
+            Type::Chr => write!(f, "chr"),
+            Type::Nul => write!(f, "nul"),
+            Type::Null => write!(f, "null"),
+            Type::Struct (s) => write!(f, "{}", s),
+            Type::Own (s) => write!(f, "own {}", s),
+            Type::Opt (s) => write!(f, "opt {}", s),
+        }
+    }
+}
+
+
+/// Enumerates constant values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constant {
+    Int (i32),
+    Float (f32),
+    Bool (bool),
+    Char (char),
+    Null,
+}
+
+impl Constant {
+    /// Gets the zero value of a scalar or pointer type.
+    pub fn zero(datatype: &Type) -> Option<Self> {
+        match datatype {
+            Type::Int => Some(Constant::Int (0)),
+            Type::Flt => Some(Constant::Float (0.0)),
+            Type::Bln => Some(Constant::Bool (false)),
+            Type::Chr => Some(Constant::Char ('\0')),
+            Type::Null
+            | Type::Own (_)
+            | Type::Opt (_) => Some(Constant::Null),
+            Type::Nul
+            | Type::Struct (_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Int (i) => write!(f, "{}", i),
+            Constant::Float (x) => write!(f, "{:?}", x),
+            Constant::Bool (b) => write!(f, "{}", b),
+            Constant::Char (c) => write!(f, "{:?}", c),
+            Constant::Null => write!(f, "null"),
+        }
+    }
+}
+
+
+/// Identifies a temporary, which is assigned exactly once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Temp (pub usize);
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+
+/// Enumerates the operands of instructions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Constant (Constant),
+    Temp (Temp),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Constant (c) => write!(f, "{}", c),
+            Operand::Temp (t) => write!(f, "{}", t),
+        }
+    }
+}
+
+
+/// Enumerates the variables that places are based on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Variable {
+    // Index into the function's locals
+    Local (usize),
+    Global (String),
+}
+
+
+/// Describes a variable or a field of one, following heap pointers (`list.next.value`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+    pub variable: Variable,
+    pub fields: Vec<String>,
+}
+
+
+/// Enumerates unary operators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+
+/// Enumerates binary operators.  Both operands have the same type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+}
+
+impl BinaryOp {
+    /// Checks if this operator is a comparison, which gives a `bln`.
+    pub fn is_comparison(&self) -> bool {
+        !matches!(self, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div)
+    }
+}
+
+
+/// Enumerates the values that may be assigned to a temporary.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unary (UnaryOp, Operand),
+    Binary (BinaryOp, Operand, Operand),
+    // Converts a scalar to the temporary's type
+    Convert (Operand),
+    Load (Place),
+    // Allocates a zeroed struct on the heap, then sets the given fields
+    New (String, Vec<(String, Operand)>),
+}
+
+
+/// Enumerates the ways arguments are passed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Argument {
+    Value (Operand),
+    // The address of a variable, for `ref` and struct parameters
+    Reference (Place),
+}
+
+
+/// Enumerates the instructions within a basic block.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Assign (Temp, Value),
+    Store (Place, Operand),
+    // Sets a variable to the zero value of its type
+    Zero (Place),
+    // Calls a function, keeping its result unless it returns `nul`
+    Call (Option<Temp>, String, Vec<Argument>),
+    Print (Operand),
+    // Frees a heap pointer and sets it to `null`
+    Drop (Place),
+}
+
+
+/// Enumerates the instructions that end a basic block.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Terminator {
+    Jump (usize),
+    // Jumps to the first block if the `bln` operand is true, and to the second otherwise
+    Branch (Operand, usize, usize),
+    Return (Option<Operand>),
+}
+
+
+/// Holds a straight-line sequence of instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+
+/// Holds a function whose body is a list of basic blocks, the first being its entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    // Indices of the locals holding the parameters, and how they are passed
+    pub params: Vec<(usize, Passing)>,
+    pub return_type: Type,
+    // Names (unique within the function) and types of locals, and types of temporaries
+    pub locals: Vec<(String, Type)>,
+    pub temps: Vec<Type>,
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    /// Gets the type of an operand.
+    pub fn type_of(&self, operand: &Operand) -> Type {
+        match operand {
+            Operand::Constant (Constant::Int (_)) => Type::Int,
+            Operand::Constant (Constant::Float (_)) => Type::Flt,
+            Operand::Constant (Constant::Bool (_)) => Type::Bln,
+            Operand::Constant (Constant::Char (_)) => Type::Chr,
+            Operand::Constant (Constant::Null) => Type::Null,
+            Operand::Temp (t) => self.temps[t.0].to_owned(),
+        }
+    }
+
+    /// Formats a place, naming locals with `$` and globals with `@`.
+    fn place(&self, place: &Place) -> String {
+        let mut name = match &place.variable {
+            Variable::Local (l) => format!("${}", self.locals[*l].0),
+            Variable::Global (g) => format!("@{}", g),
+        };
+        for field in place.fields.iter() {
+            name.push('.');
+            name.push_str(field);
+        }
+        name
+    }
+}
+
+
+/// Enumerates the initial values of globals.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Initializer {
+    Constant (Constant),
+    // Every field of a struct, in order
+    Struct (Vec<(String, Initializer)>),
+}
+
+impl fmt::Display for Initializer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Initializer::Constant (c) => write!(f, "{}", c),
+            Initializer::Struct (fields) => {
+                let fields = fields.iter()
+                    .map(|(n, i)| format!("{} {}", n, i))
+                    .collect::<Vec<String>>();
+                write!(f, "{{ {} }}", fields.join(" "))
+            },
+        }
+    }
+}
+
+
+/// Holds a global variable, including static variables (named `function.variable`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub datatype: Type,
+    pub value: Initializer,
+}
+
+
+/// Holds a whole program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    // Fields of each struct, sorted by name
+    pub structs: Vec<(String, Vec<(String, Type)>)>,
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+
+/// Runs every optimization pass over a program.
+pub fn optimize(mut program: Program) -> Program {
+    for function in program.functions.iter_mut() {
+        // Each pass may expose more work for the other, so run them until nothing changes
+        loop {
+            let before = function.to_owned();
+            passes::propagate_constants(function);
+            passes::eliminate_dead_code(function);
+            if *function == before {
+                break;
+            }
+        }
+    }
+    // Calls in branches removed above no longer keep their callees alive
+    passes::remove_unused_functions(&mut program);
+    program
+}
+
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self.params.iter()
+            .map(|(l, p)| {
+                let (name, datatype) = &self.locals[*l];
+                match p {
+                    Passing::Value => format!("${} {}", name, datatype),
+                    Passing::ConstReference => format!("${} &{}", name, datatype),
+                    Passing::Reference => format!("${} ref {}", name, datatype),
+                }
+            })
+            .collect::<Vec<String>>();
+        writeln!(f, "fn {}({}) -> {} {{", self.name, params.join(", "), self.return_type)?;
+
+        for (index, (name, datatype)) in self.locals.iter().enumerate() {
+            if !self.params.iter().any(|(l, _)| *l == index) {
+                writeln!(f, "    let ${} {}", name, datatype)?;
+            }
+        }
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "  bb{}:", index)?;
+            for instruction in block.instructions.iter() {
+                let line = match instruction {
+                    Instruction::Assign (t, v) => {
+                        let value = match v {
+                            Value::Unary (o, a) => format!("{} {}", format!("{:?}", o).to_lowercase(), a),
+                            Value::Binary (o, a, b) => format!("{} {} {}", format!("{:?}", o).to_lowercase(), a, b),
+                            Value::Convert (a) => format!("convert {} {}", self.type_of(a), a),
+                            Value::Load (p) => format!("load {}", self.place(p)),
+                            Value::New (s, fields) => {
+                                let fields = fields.iter()
+                                    .map(|(n, o)| format!(" {} {}", n, o))
+                                    .collect::<String>();
+                                format!("new {} {{{} }}", s, fields)
+                            },
+                        };
+                        format!("{} {} = {}", t, self.temps[t.0], value)
+                    },
+                    Instruction::Store (p, o) => format!("store {} {}", self.place(p), o),
+                    Instruction::Zero (p) => format!("zero {}", self.place(p)),
+                    Instruction::Call (t, name, args) => {
+                        let args = args.iter()
+                            .map(|a| match a {
+                                Argument::Value (o) => o.to_string(),
+                                Argument::Reference (p) => format!("&{}", self.place(p)),
+                            })
+                            .collect::<Vec<String>>();
+                        match t {
+                            Some(t) => format!("{} {} = call {}({})", t, self.temps[t.0], name, args.join(", ")),
+                            None => format!("call {}({})", name, args.join(", ")),
+                        }
+                    },
+                    Instruction::Print (o) => format!("print {}", o),
+                    Instruction::Drop (p) => format!("drop {}", self.place(p)),
+                };
+                writeln!(f, "    {}", line)?;
+            }
+            let terminator = match &block.terminator {
+                Terminator::Jump (b) => format!("jump bb{}", b),
+                Terminator::Branch (o, t, e) => format!("branch {} bb{} bb{}", o, t, e),
+                Terminator::Return (Some(o)) => format!("return {}", o),
+                Terminator::Return (None) => "return".to_string(),
+            };
+            writeln!(f, "    {}", terminator)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, fields) in self.structs.iter() {
+            let fields = fields.iter()
+                .map(|(n, t)| format!(" {} {}", t, n))
+                .collect::<String>();
+            writeln!(f, "struct {} {{{} }}", name, fields)?;
+        }
+        for global in self.globals.iter() {
+            writeln!(f, "global @{} {} = {}", global.name, global.datatype, global.value)?;
+        }
+        for function in self.functions.iter() {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,531 @@
+//! Optimization passes over the IR.
+//!
+//! Each pass leaves the program's observable behaviour unchanged.  Folding
+//! never hides a runtime error, so integer overflow and division by zero are
+//! left to happen when the program runs.
+
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use crate::parser::Passing;
+
+use super::{
+    Argument,
+    BinaryOp,
+    Block,
+    Constant,
+    Function,
+    Instruction,
+    Operand,
+    Place,
+    Program,
+    Temp,
+    Terminator,
+    Type,
+    UnaryOp,
+    Value,
+    Variable,
+};
+
+
+/// Converts a constant to another type, as the `Convert` instruction does.
+///
+/// Returns `None` if the conversion cannot be done at compile time.
+pub fn convert(constant: Constant, to: &Type) -> Option<Constant> {
+    // Characters are signed bytes, as in C
+    let byte = |c: char| c as u8 as i8 as i32;
+    let converted = match (constant, to) {
+        (Constant::Int (i), Type::Int) => Constant::Int (i),
+        (Constant::Float (f), Type::Int) if f.is_finite() && f > i32::MIN as f32 - 1.0 && f < i32::MAX as f32 => Constant::Int (f as i32),
+        (Constant::Bool (b), Type::Int) => Constant::Int (b as i32),
+        (Constant::Char (c), Type::Int) => Constant::Int (byte(c)),
+        (Constant::Int (i), Type::Flt) => Constant::Float (i as f32),
+        (Constant::Float (f), Type::Flt) => Constant::Float (f),
+        (Constant::Bool (b), Type::Flt) => Constant::Float (b as i32 as f32),
+        (Constant::Char (c), Type::Flt) => Constant::Float (byte(c) as f32),
+        (Constant::Int (i), Type::Bln) => Constant::Bool (i != 0),
+        (Constant::Float (f), Type::Bln) => Constant::Bool (f != 0.0),
+        (Constant::Bool (b), Type::Bln) => Constant::Bool (b),
+        (Constant::Char (c), Type::Bln) => Constant::Bool (c != '\0'),
+        (Constant::Null, Type::Bln) => Constant::Bool (false),
+        (Constant::Int (i), Type::Chr) => Constant::Char (i as u8 as char),
+        (Constant::Float (f), Type::Chr) if f.is_finite() && f.abs() < 128.0 => Constant::Char (f as i32 as u8 as char),
+        (Constant::Bool (b), Type::Chr) => Constant::Char (b as u8 as char),
+        (Constant::Char (c), Type::Chr) => Constant::Char (c),
+        (Constant::Null, _) if to.pointee().is_some() => Constant::Null,
+        _ => return None,
+    };
+    Some(converted)
+}
+
+
+/// Evaluates a value whose operands are constants, or returns `None` if it must be computed at runtime.
+fn fold(value: &Value, datatype: &Type) -> Option<Constant> {
+    let folded = match value {
+        Value::Unary (UnaryOp::Neg, Operand::Constant (Constant::Int (i))) => Constant::Int (i.checked_neg()?),
+        Value::Unary (UnaryOp::Neg, Operand::Constant (Constant::Float (f))) => Constant::Float (-f),
+        Value::Unary (UnaryOp::Not, Operand::Constant (Constant::Bool (b))) => Constant::Bool (!b),
+        Value::Binary (op, Operand::Constant (Constant::Int (a)), Operand::Constant (Constant::Int (b))) => match op {
+            BinaryOp::Add => Constant::Int (a.checked_add(*b)?),
+            BinaryOp::Sub => Constant::Int (a.checked_sub(*b)?),
+            BinaryOp::Mul => Constant::Int (a.checked_mul(*b)?),
+            BinaryOp::Div => Constant::Int (a.checked_div(*b)?),
+            BinaryOp::Gt => Constant::Bool (a > b),
+            BinaryOp::Lt => Constant::Bool (a < b),
+            BinaryOp::Ge => Constant::Bool (a >= b),
+            BinaryOp::Le => Constant::Bool (a <= b),
+            BinaryOp::Eq => Constant::Bool (a == b),
+        },
+        Value::Binary (op, Operand::Constant (Constant::Float (a)), Operand::Constant (Constant::Float (b))) => match op {
+            BinaryOp::Add => Constant::Float (a + b),
+            BinaryOp::Sub => Constant::Float (a - b),
+            BinaryOp::Mul => Constant::Float (a * b),
+            BinaryOp::Div if *b != 0.0 => Constant::Float (a / b),
+            BinaryOp::Div => return None,
+            BinaryOp::Gt => Constant::Bool (a > b),
+            BinaryOp::Lt => Constant::Bool (a < b),
+            BinaryOp::Ge => Constant::Bool (a >= b),
+            BinaryOp::Le => Constant::Bool (a <= b),
+            BinaryOp::Eq => Constant::Bool (a == b),
+        },
+        Value::Binary (BinaryOp::Eq, Operand::Constant (Constant::Null), Operand::Constant (Constant::Null)) => Constant::Bool (true),
+        Value::Convert (Operand::Constant (c)) => convert(*c, datatype)?,
+        _ => return None,
+    };
+    // `NaN` is never equal to itself, so it would keep the optimizer from seeing that nothing changed
+    match folded {
+        Constant::Float (f) if f.is_nan() => None,
+        _ => Some(folded),
+    }
+}
+
+
+/// Calls a closure on every operand read by an instruction.
+fn for_each_operand(instruction: &mut Instruction, f: &mut impl FnMut(&mut Operand)) {
+    match instruction {
+        Instruction::Assign (_, value) => match value {
+            Value::Unary (_, a)
+            | Value::Convert (a) => f(a),
+            Value::Binary (_, a, b) => {
+                f(a);
+                f(b);
+            },
+            Value::Load (_) => (),
+            Value::New (_, fields) => {
+                for (_, a) in fields.iter_mut() {
+                    f(a);
+                }
+            },
+        },
+        Instruction::Store (_, a)
+        | Instruction::Print (a) => f(a),
+        Instruction::Call (_, _, args) => {
+            for arg in args.iter_mut() {
+                if let Argument::Value (a) = arg {
+                    f(a);
+                }
+            }
+        },
+        Instruction::Zero (_)
+        | Instruction::Drop (_) => (),
+    }
+}
+
+
+/// Calls a closure on every place used by an instruction, with whether the place is read.
+fn for_each_place(instruction: &mut Instruction, f: &mut impl FnMut(&mut Place, bool)) {
+    match instruction {
+        Instruction::Assign (_, Value::Load (p)) => f(p, true),
+        Instruction::Assign (_, _)
+        | Instruction::Print (_) => (),
+        Instruction::Store (p, _)
+        | Instruction::Zero (p) => f(p, false),
+        // A dropped pointer is read to be freed
+        Instruction::Drop (p) => f(p, true),
+        // A reference may be read or written by the callee
+        Instruction::Call (_, _, args) => {
+            for arg in args.iter_mut() {
+                if let Argument::Reference (p) = arg {
+                    f(p, true);
+                }
+            }
+        },
+    }
+}
+
+
+/// Gets the local a place refers to as a whole, if it is a local without fields.
+fn whole_local(place: &Place) -> Option<usize> {
+    match (&place.variable, place.fields.is_empty()) {
+        (Variable::Local (l), true) => Some(*l),
+        _ => None,
+    }
+}
+
+
+/// Finds the locals whose storage may be reached from outside the function's own loads and stores.
+///
+/// These are locals passed by reference, and parameters that are themselves references.
+fn escaping_locals(function: &Function) -> HashSet<usize> {
+    let mut escaping = function.params.iter()
+        .filter(|(_, p)| *p != Passing::Value)
+        .map(|(l, _)| *l)
+        .collect::<HashSet<usize>>();
+    for block in function.blocks.iter() {
+        for instruction in block.instructions.iter() {
+            if let Instruction::Call (_, _, args) = instruction {
+                for arg in args {
+                    if let Argument::Reference (Place { variable: Variable::Local (l), .. }) = arg {
+                        escaping.insert(*l);
+                    }
+                }
+            }
+        }
+    }
+    escaping
+}
+
+
+/// Propagates constants and copies through temporaries and through locals within each block,
+/// folding operations on constants and turning branches on constants into jumps.
+pub fn propagate_constants(function: &mut Function) {
+    let escaping = escaping_locals(function);
+    let mut known = HashMap::<Temp, Operand>::new();
+
+    for block in function.blocks.iter_mut() {
+        // Values of locals are only tracked within a block, since other blocks may jump in
+        let mut locals = HashMap::<usize, Operand>::new();
+        for instruction in block.instructions.iter_mut() {
+            for_each_operand(instruction, &mut |operand| substitute(operand, &known));
+            match instruction {
+                Instruction::Assign (t, Value::Load (p)) => {
+                    if let Some(o) = whole_local(p).and_then(|l| locals.get(&l)) {
+                        known.insert(*t, *o);
+                    }
+                },
+                Instruction::Assign (t, value) => {
+                    if let Some(c) = fold(value, &function.temps[t.0]) {
+                        known.insert(*t, Operand::Constant (c));
+                    }
+                },
+                Instruction::Store (p, operand) => match whole_local(p).filter(|l| !escaping.contains(l)) {
+                    Some(l) => {
+                        locals.insert(l, *operand);
+                    },
+                    None => forget(&mut locals, p),
+                },
+                Instruction::Zero (p) => match whole_local(p).filter(|l| !escaping.contains(l)) {
+                    Some(l) => match Constant::zero(&function.locals[l].1) {
+                        Some(c) => {
+                            locals.insert(l, Operand::Constant (c));
+                        },
+                        None => forget(&mut locals, p),
+                    },
+                    None => forget(&mut locals, p),
+                },
+                // Dropping a pointer sets it to `null`
+                Instruction::Drop (p) => match whole_local(p).filter(|l| !escaping.contains(l)) {
+                    Some(l) => {
+                        locals.insert(l, Operand::Constant (Constant::Null));
+                    },
+                    None => forget(&mut locals, p),
+                },
+                Instruction::Call (_, _, _)
+                | Instruction::Print (_) => (),
+            }
+        }
+        if let Terminator::Branch (operand, _, _)
+        | Terminator::Return (Some(operand)) = &mut block.terminator {
+            substitute(operand, &known);
+        }
+        if let Terminator::Branch (Operand::Constant (Constant::Bool (b)), then, otherwise) = block.terminator {
+            block.terminator = Terminator::Jump (if b { then } else { otherwise });
+        }
+    }
+
+    // Temporaries may be used in blocks that come before the one defining them
+    for block in function.blocks.iter_mut() {
+        for instruction in block.instructions.iter_mut() {
+            for_each_operand(instruction, &mut |operand| substitute(operand, &known));
+        }
+        if let Terminator::Branch (operand, _, _)
+        | Terminator::Return (Some(operand)) = &mut block.terminator {
+            substitute(operand, &known);
+        }
+    }
+}
+
+
+/// Replaces a temporary with its known value.
+fn substitute(operand: &mut Operand, known: &HashMap<Temp, Operand>) {
+    // A temporary may be known to equal another whose value is known in turn
+    while let Operand::Temp (t) = operand {
+        match known.get(t) {
+            Some(o) if o != operand => *operand = *o,
+            _ => break,
+        }
+    }
+}
+
+
+/// Stops tracking the value of the local a place is based on.
+fn forget(locals: &mut HashMap<usize, Operand>, place: &Place) {
+    if let Variable::Local (l) = place.variable {
+        locals.remove(&l);
+    }
+}
+
+
+/// Removes unreachable blocks, merges straight-line blocks, and removes unused temporaries,
+/// stores to locals that are never read, and the locals themselves.
+pub fn eliminate_dead_code(function: &mut Function) {
+    skip_empty_blocks(function);
+    remove_unreachable_blocks(function);
+    merge_blocks(function);
+    remove_unreachable_blocks(function);
+    remove_unused_temps(function);
+    remove_dead_stores(function);
+    remove_unused_locals(function);
+}
+
+
+/// Gets the blocks a terminator may jump to.
+fn successors(terminator: &Terminator) -> Vec<usize> {
+    match terminator {
+        Terminator::Jump (b) => vec![*b],
+        Terminator::Branch (_, t, e) => vec![*t, *e],
+        Terminator::Return (_) => Vec::new(),
+    }
+}
+
+
+/// Redirects jumps to empty blocks that only jump elsewhere.
+fn skip_empty_blocks(function: &mut Function) {
+    let target = |function: &Function, mut block: usize| {
+        // Follow a chain of empty blocks, stopping at a loop of them
+        let mut seen = HashSet::new();
+        while let Block {
+            instructions,
+            terminator: Terminator::Jump (next),
+        } = &function.blocks[block] {
+            if !instructions.is_empty() || block == 0 || !seen.insert(block) {
+                break;
+            }
+            block = *next;
+        }
+        block
+    };
+    for index in 0..function.blocks.len() {
+        let terminator = match &function.blocks[index].terminator {
+            Terminator::Jump (b) => Terminator::Jump (target(function, *b)),
+            Terminator::Branch (o, t, e) => Terminator::Branch (*o, target(function, *t), target(function, *e)),
+            Terminator::Return (r) => Terminator::Return (*r),
+        };
+        function.blocks[index].terminator = terminator;
+    }
+}
+
+
+/// Removes blocks that cannot be reached from the entry block.
+fn remove_unreachable_blocks(function: &mut Function) {
+    let mut reachable = vec![false; function.blocks.len()];
+    let mut stack = vec![0];
+    while let Some(block) = stack.pop() {
+        if !reachable[block] {
+            reachable[block] = true;
+            stack.extend(successors(&function.blocks[block].terminator));
+        }
+    }
+
+    let mut renumbered = Vec::new();
+    let mut next = 0;
+    for r in reachable.iter() {
+        renumbered.push(next);
+        if *r {
+            next += 1;
+        }
+    }
+    let blocks = std::mem::take(&mut function.blocks);
+    for (block, r) in blocks.into_iter().zip(reachable) {
+        if !r {
+            continue;
+        }
+        let terminator = match block.terminator {
+            Terminator::Jump (b) => Terminator::Jump (renumbered[b]),
+            Terminator::Branch (o, t, e) => Terminator::Branch (o, renumbered[t], renumbered[e]),
+            Terminator::Return (r) => Terminator::Return (r),
+        };
+        function.blocks.push(Block {
+            instructions: block.instructions,
+            terminator,
+        });
+    }
+}
+
+
+/// Appends each block that is only reached by a jump from one other block to that block.
+fn merge_blocks(function: &mut Function) {
+    loop {
+        let mut predecessors = vec![0; function.blocks.len()];
+        for block in function.blocks.iter() {
+            for s in successors(&block.terminator) {
+                predecessors[s] += 1;
+            }
+        }
+        let merge = function.blocks.iter().enumerate().find_map(|(index, block)| match block.terminator {
+            Terminator::Jump (next) if next != index && next != 0 && predecessors[next] == 1 => Some((index, next)),
+            _ => None,
+        });
+        let (index, next) = match merge {
+            Some(m) => m,
+            None => break,
+        };
+        // The merged block is left unreachable, to be removed afterwards
+        let instructions = std::mem::take(&mut function.blocks[next].instructions);
+        let terminator = std::mem::replace(&mut function.blocks[next].terminator, Terminator::Jump (next));
+        function.blocks[index].instructions.extend(instructions);
+        function.blocks[index].terminator = terminator;
+    }
+}
+
+
+/// Checks if computing a value can have no effect besides defining its temporary.
+fn is_pure(value: &Value) -> bool {
+    match value {
+        // Division by zero must still happen at runtime
+        Value::Binary (BinaryOp::Div, _, divisor) => matches!(
+            divisor,
+            Operand::Constant (Constant::Int (i)) if *i != 0 && *i != -1
+        ) || matches!(divisor, Operand::Constant (Constant::Float (_))),
+        _ => true,
+    }
+}
+
+
+/// Removes assignments to temporaries that are never used.
+fn remove_unused_temps(function: &mut Function) {
+    loop {
+        let mut used = HashSet::new();
+        for block in function.blocks.iter_mut() {
+            for instruction in block.instructions.iter_mut() {
+                for_each_operand(instruction, &mut |operand| {
+                    if let Operand::Temp (t) = operand {
+                        used.insert(*t);
+                    }
+                });
+            }
+            if let Terminator::Branch (Operand::Temp (t), _, _)
+            | Terminator::Return (Some(Operand::Temp (t))) = block.terminator {
+                used.insert(t);
+            }
+        }
+
+        let mut changed = false;
+        for block in function.blocks.iter_mut() {
+            block.instructions.retain(|instruction| match instruction {
+                Instruction::Assign (t, value) if !used.contains(t) && is_pure(value) => {
+                    changed = true;
+                    false
+                },
+                _ => true,
+            });
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+
+/// Removes stores to locals that are never read.
+fn remove_dead_stores(function: &mut Function) {
+    let escaping = escaping_locals(function);
+    let mut read = HashSet::new();
+    for block in function.blocks.iter_mut() {
+        for instruction in block.instructions.iter_mut() {
+            for_each_place(instruction, &mut |place, reads| {
+                if let (Variable::Local (l), true) = (&place.variable, reads) {
+                    read.insert(*l);
+                }
+            });
+        }
+    }
+
+    for block in function.blocks.iter_mut() {
+        block.instructions.retain(|instruction| match instruction {
+            Instruction::Store (Place { variable: Variable::Local (l), .. }, _)
+            | Instruction::Zero (Place { variable: Variable::Local (l), .. }) => read.contains(l) || escaping.contains(l),
+            _ => true,
+        });
+    }
+}
+
+
+/// Removes locals that are no longer mentioned, renumbering the rest.
+fn remove_unused_locals(function: &mut Function) {
+    let mut used = vec![false; function.locals.len()];
+    for (l, _) in function.params.iter() {
+        used[*l] = true;
+    }
+    for block in function.blocks.iter_mut() {
+        for instruction in block.instructions.iter_mut() {
+            for_each_place(instruction, &mut |place, _| {
+                if let Variable::Local (l) = place.variable {
+                    used[l] = true;
+                }
+            });
+        }
+    }
+
+    let mut renumbered = Vec::new();
+    let mut next = 0;
+    for u in used.iter() {
+        renumbered.push(next);
+        if *u {
+            next += 1;
+        }
+    }
+    let locals = std::mem::take(&mut function.locals);
+    function.locals = locals.into_iter().zip(used.iter()).filter(|(_, u)| **u).map(|(l, _)| l).collect();
+    for (l, _) in function.params.iter_mut() {
+        *l = renumbered[*l];
+    }
+    for block in function.blocks.iter_mut() {
+        for instruction in block.instructions.iter_mut() {
+            for_each_place(instruction, &mut |place, _| {
+                if let Variable::Local (l) = &mut place.variable {
+                    *l = renumbered[*l];
+                }
+            });
+        }
+    }
+}
+
+
+/// Removes functions that cannot be called from `main`.  Libraries, which have no `main`, are left unchanged.
+pub fn remove_unused_functions(program: &mut Program) {
+    if !program.functions.iter().any(|f| f.name == "main") {
+        return;
+    }
+
+    let mut reachable = HashSet::new();
+    let mut stack = vec!["main".to_string()];
+    while let Some(name) = stack.pop() {
+        if !reachable.insert(name.to_owned()) {
+            continue;
+        }
+        if let Some(function) = program.functions.iter().find(|f| f.name == name) {
+            for block in function.blocks.iter() {
+                for instruction in block.instructions.iter() {
+                    if let Instruction::Call (_, callee, _) = instruction {
+                        stack.push(callee.to_owned());
+                    }
+                }
+            }
+        }
+    }
+    program.functions.retain(|f| reachable.contains(&f.name));
+}
@@ -0,0 +1,29 @@
+//! A simple compiler for the Boron programming language.
+//! 
+//! This library exposes the stages of the Boron compiler (tokenizer,
+//! parser, formatter, token and syntax tree dumps, constant evaluator, desugaring shared by the backends, typed IR and its optimizer, C, LLVM, WebAssembly, and x86-64 emitters,
+//! interpreter, bytecode VM, REPL, language server, UI test expectations, build cache, and module resolution) so that they can be driven by the `boron` binary, benchmarks, and other tools.
+
+
+pub mod tokenizer;
+pub mod parser;
+pub mod formatter;
+pub mod dump;
+pub mod emitter;
+pub mod llvm;
+pub mod wasm;
+pub mod x86_64;
+pub mod backend;
+pub mod ir;
+pub mod consteval;
+pub mod desugar;
+pub mod interpreter;
+pub mod bytecode;
+pub mod repl;
+pub mod pipeline;
+pub mod cache;
+pub mod modules;
+pub mod lsp;
+pub mod ui;
+pub mod version;
+pub mod error;
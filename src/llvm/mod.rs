@@ -0,0 +1,1125 @@
+//! Emits textual LLVM IR (`.ll`) for the Boron compiler.
+//!
+//! Unlike the C emitter, this emitter resolves every type itself: local
+//! variables live in `alloca` slots, structs become LLVM struct types with
+//! their fields in alphabetical order, and `print` calls `printf`.
+
+
+use std::collections::HashMap;
+
+use crate::parser::{
+    Expression,
+    Passing,
+    TokenType,
+};
+
+use crate::emitter::include_modules;
+
+use crate::consteval::Evaluator;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::error::Error;
+
+
+/// Declarations, format strings, and runtime support emitted into every module.
+const PRELUDE: &str = "declare i32 @printf(i8*, ...)
+declare i8* @malloc(i64)
+declare void @free(i8*)
+declare i64 @write(i32, i8*, i64)
+declare void @exit(i32)
+
+@.str.int = private unnamed_addr constant [4 x i8] c\"%d\\0A\\00\"
+@.str.flt = private unnamed_addr constant [4 x i8] c\"%f\\0A\\00\"
+@.str.chr = private unnamed_addr constant [4 x i8] c\"%c\\0A\\00\"
+@.str.true = private unnamed_addr constant [6 x i8] c\"true\\0A\\00\"
+@.str.false = private unnamed_addr constant [7 x i8] c\"false\\0A\\00\"
+@.str.nul = private unnamed_addr constant [2 x i8] c\"\\0A\\00\"
+@.str.oom = private unnamed_addr constant [15 x i8] c\"Out of memory\\0A\\00\"
+
+define private i8* @boron.new(i64 %size) {
+entry:
+  %pointer = call i8* @malloc(i64 %size)
+  %failed = icmp eq i8* %pointer, null
+  br i1 %failed, label %oom, label %ok
+oom:
+  %written = call i64 @write(i32 2, i8* getelementptr inbounds ([15 x i8], [15 x i8]* @.str.oom, i64 0, i64 0), i64 14)
+  call void @exit(i32 1)
+  unreachable
+ok:
+  ret i8* %pointer
+}
+";
+
+
+/// Holds a value computed by the emitted code.
+#[derive(Clone, Debug)]
+struct Operand {
+    // LLVM constant or register holding the value
+    value: String,
+    // Boron type of the value
+    datatype: String,
+}
+
+impl Operand {
+    fn new(value: String, datatype: &str) -> Self {
+        Self {
+            value,
+            datatype: datatype.to_string(),
+        }
+    }
+}
+
+
+/// Holds the address and Boron type of a variable.
+#[derive(Clone, Debug)]
+struct Variable {
+    pointer: String,
+    datatype: String,
+}
+
+
+/// Holds the variables of a block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, Variable>,
+}
+
+
+/// Holds the parameters and return type of a function.
+#[derive(Clone)]
+struct Signature {
+    arguments: Vec<(String, Passing)>,
+    return_type: String,
+}
+
+
+/// Emits LLVM IR for a Boron program.
+#[derive(Default)]
+pub struct Emitter {
+    // Fields of each struct, sorted by name
+    structs: HashMap<String, Vec<(String, String)>>,
+    functions: HashMap<String, Signature>,
+    globals: HashMap<String, Variable>,
+    // Values of top-level constants and declarations of `const` functions, for evaluating initializers
+    constants: HashMap<String, Expression>,
+    const_fns: HashMap<String, Expression>,
+    // Struct types, global variables, and function definitions emitted so far
+    types: Vec<String>,
+    variables: Vec<String>,
+    definitions: Vec<String>,
+    // State of the function being emitted
+    function: String,
+    return_type: String,
+    allocas: Vec<String>,
+    body: Vec<String>,
+    scopes: Vec<Scope>,
+    // Counter used to give registers and labels unique names
+    next: usize,
+    // Label of the current basic block, and whether it has been terminated
+    block: String,
+    terminated: bool,
+}
+
+/// Provides an interface for the LLVM emitter.
+impl Emitter {
+    /// Constructs a new LLVM emitter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compiles a program, including the modules it uses, into an LLVM module.
+    pub fn compile_exe(&mut self, expressions: Vec<Expression>) -> Result<String, Error> {
+        let expressions = desugar(include_modules(expressions)?)?;
+
+        // Register every struct and function first, so they may be used before they are declared
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct {
+                    identifier: i,
+                    variables: v,
+                    ..
+                } => {
+                    let mut fields = v.iter()
+                        .map(|(f, t)| (f.to_owned(), t.to_owned()))
+                        .collect::<Vec<(String, String)>>();
+                    fields.sort();
+                    self.structs.insert(i.to_owned(), fields);
+                },
+                Expression::FnDeclaration {
+                    identifier: i,
+                    arguments: a,
+                    return_type: r,
+                    constant: c,
+                    ..
+                } => {
+                    let signature = Signature {
+                        arguments: a.iter().map(|(_, t, p)| (t.to_owned(), *p)).collect(),
+                        return_type: r.to_owned(),
+                    };
+                    self.functions.insert(i.to_owned(), signature);
+                    if *c {
+                        self.const_fns.insert(i.to_owned(), expression.to_owned());
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        let mut names = self.structs.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+        for name in names {
+            let fields = self.structs[&name].iter()
+                .map(|(_, t)| self.ty(t))
+                .collect::<Result<Vec<String>, Error>>()?;
+            self.types.push(format!("{} = type {{ {} }}", quote('%', &name), fields.join(", ")));
+        }
+
+        // Emit globals before functions, which may refer to them
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. } => (),
+                Expression::Assignment {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } | Expression::Static {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let pointer = quote('@', i);
+                    self.emit_global(&pointer, d, i, v)?;
+                    self.globals.insert(i.to_owned(), Variable {
+                        pointer,
+                        datatype: d.to_owned(),
+                    });
+                },
+                Expression::Const {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let pointer = quote('@', i);
+                    let value = self.emit_global(&pointer, d, i, v)?;
+                    self.constants.insert(i.to_owned(), value);
+                    self.globals.insert(i.to_owned(), Variable {
+                        pointer,
+                        datatype: d.to_owned(),
+                    });
+                },
+                _ => return Err(Error::StatementOutsideFunction),
+            }
+        }
+
+        for expression in expressions.iter() {
+            if let Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                body: b,
+                ..
+            } = expression {
+                self.emit_function(i, a, r, b)?;
+            }
+        }
+
+        let mut output = String::new();
+        output.push_str("; ModuleID = 'boron'\nsource_filename = \"boron\"\n\n");
+        for section in [&self.types, &self.variables] {
+            if !section.is_empty() {
+                output.push_str(&section.join("\n"));
+                output.push_str("\n\n");
+            }
+        }
+        output.push_str(PRELUDE);
+        for definition in self.definitions.iter() {
+            output.push('\n');
+            output.push_str(definition);
+        }
+        Ok(output)
+    }
+
+    /// Gets the LLVM type of a Boron type.
+    fn ty(&self, datatype: &str) -> Result<String, Error> {
+        match datatype {
+            "int" => Ok("i32".to_string()),
+            "flt" => Ok("float".to_string()),
+            "bln" => Ok("i1".to_string()),
+            "chr" => Ok("i8".to_string()),
+            "nul" => Ok("void".to_string()),
+            "null" => Ok("i8*".to_string()),
+            _ => match pointee(datatype) {
+                Some(s) => Ok(format!("{}*", self.ty(s)?)),
+                None if self.structs.contains_key(datatype) => Ok(quote('%', datatype)),
+                None => Err(Error::UndeclaredVariable (datatype.to_string())),
+            },
+        }
+    }
+
+    /// Gets the LLVM constant holding the zero value of a Boron type.
+    fn zero(&self, datatype: &str) -> String {
+        match datatype {
+            "int" | "chr" => "0".to_string(),
+            "flt" => float(0.0),
+            "bln" => "false".to_string(),
+            _ if pointee(datatype).is_some() => "null".to_string(),
+            _ => "zeroinitializer".to_string(),
+        }
+    }
+
+    /// Gets the index and type of a struct field.
+    fn field(&self, datatype: &str, field: &str, path: &str) -> Result<(usize, String), Error> {
+        let fields = match self.structs.get(datatype) {
+            Some(f) => f,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        match fields.iter().position(|(f, _)| f == field) {
+            Some(i) => Ok((i, fields[i].1.to_owned())),
+            None => Err(Error::UndeclaredVariable (path.to_string())),
+        }
+    }
+
+    /// Emits a global variable with a constant initializer, returning the initializer as a literal.
+    fn emit_global(&mut self, pointer: &str, datatype: &str, identifier: &str, value: &Expression) -> Result<Expression, Error> {
+        let value = match value {
+            Expression::StructInit { .. } => {
+                if pointee(datatype).is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                value.to_owned()
+            },
+            Expression::Null if !datatype.starts_with("opt ") => return Err(Error::ExpectedNullable (identifier.to_string())),
+            _ => self.evaluate(value, identifier)?,
+        };
+        let constant = self.constant(&value, datatype, identifier)?;
+        self.variables.push(format!("{} = global {} {}", pointer, self.ty(datatype)?, constant));
+        Ok(value)
+    }
+
+    /// Evaluates a constant expression into a literal.
+    fn evaluate(&self, value: &Expression, identifier: &str) -> Result<Expression, Error> {
+        let constants = |n: &String| Ok(self.constants.get(n).cloned());
+        let functions = |n: &String| Ok(self.const_fns.get(n).cloned());
+        match Evaluator::new(&constants, &functions).evaluate(value)? {
+            Some(v) => Ok(v),
+            None => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Converts a literal or struct initialization into an LLVM constant of the given type.
+    fn constant(&self, value: &Expression, datatype: &str, identifier: &str) -> Result<String, Error> {
+        match (datatype, value) {
+            ("int", Expression::Int (i)) => Ok(i.to_string()),
+            ("int", Expression::Float (f)) => Ok((*f as i32).to_string()),
+            ("int", Expression::Char (c)) => Ok((*c as u8 as i8 as i32).to_string()),
+            ("int", Expression::Bool (b)) => Ok((*b as i32).to_string()),
+            ("flt", Expression::Int (i)) => Ok(float(*i as f32)),
+            ("flt", Expression::Float (f)) => Ok(float(*f)),
+            ("bln", Expression::Bool (b)) => Ok(b.to_string()),
+            ("bln", Expression::Int (i)) => Ok((*i != 0).to_string()),
+            ("chr", Expression::Char (c)) => Ok((*c as u8 as i8).to_string()),
+            ("chr", Expression::Int (i)) => Ok((*i as u8 as i8).to_string()),
+            (d, Expression::Null) if pointee(d).is_some() => Ok("null".to_string()),
+            (d, Expression::StructInit {
+                variables: v,
+            }) if self.structs.contains_key(d) => {
+                let fields = self.structs[d].iter()
+                    .map(|(f, t)| {
+                        let constant = match v.get(f) {
+                            Some(e) => self.constant(&self.evaluate(e, identifier)?, t, identifier)?,
+                            None => self.zero(t),
+                        };
+                        Ok(format!("{} {}", self.ty(t)?, constant))
+                    })
+                    .collect::<Result<Vec<String>, Error>>()?;
+                for f in v.keys() {
+                    self.field(d, f, &format!("{}.{}", identifier, f))?;
+                }
+                Ok(format!("{{ {} }}", fields.join(", ")))
+            },
+            _ => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Emits a function definition.
+    fn emit_function(&mut self, identifier: &str, arguments: &[(String, String, Passing)], return_type: &str, body: &[Expression]) -> Result<(), Error> {
+        self.function = identifier.to_string();
+        self.return_type = return_type.to_string();
+        self.allocas = Vec::new();
+        self.body = Vec::new();
+        self.block = "entry".to_string();
+        self.terminated = false;
+
+        // Parameters passed by value are copied into local variables, while references are used directly
+        let mut params = Vec::new();
+        let mut scope = Scope::default();
+        for (name, datatype, passing) in arguments {
+            let register = quote('%', &format!("{}.arg", name));
+            let pointer = match passing {
+                Passing::Value => {
+                    params.push(format!("{} {}", self.ty(datatype)?, register));
+                    let pointer = self.alloca(name, datatype)?;
+                    self.instr(format!("store {} {}, {}* {}", self.ty(datatype)?, register, self.ty(datatype)?, pointer));
+                    pointer
+                },
+                Passing::ConstReference
+                | Passing::Reference => {
+                    params.push(format!("{}* {}", self.ty(datatype)?, register));
+                    register
+                },
+            };
+            scope.variables.insert(name.to_owned(), Variable {
+                pointer,
+                datatype: datatype.to_owned(),
+            });
+        }
+        self.scopes = vec![scope];
+
+        self.emit_block(body)?;
+
+        // Falling off the end of a function returns the zero value of its return type
+        if !self.terminated {
+            let ret = match return_type {
+                "nul" => "ret void".to_string(),
+                _ => format!("ret {} {}", self.ty(return_type)?, self.zero(return_type)),
+            };
+            self.terminate(ret);
+        }
+        self.scopes.clear();
+
+        let mut definition = format!("define {} {}({}) {{\nentry:\n", self.ty(return_type)?, quote('@', identifier), params.join(", "));
+        for line in self.allocas.iter().chain(self.body.iter()) {
+            definition.push_str(line);
+            definition.push('\n');
+        }
+        definition.push_str("}\n");
+        self.definitions.push(definition);
+        Ok(())
+    }
+
+    /// Returns a fresh number for naming a register or label.
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    /// Reserves a stack slot for a local variable, returning its address.
+    fn alloca(&mut self, name: &str, datatype: &str) -> Result<String, Error> {
+        let pointer = quote('%', &format!("{}.{}", name, self.fresh()));
+        self.allocas.push(format!("  {} = alloca {}", pointer, self.ty(datatype)?));
+        Ok(pointer)
+    }
+
+    /// Emits an instruction, starting a new (unreachable) block if the current one has been terminated.
+    fn instr(&mut self, instruction: String) {
+        if self.terminated {
+            let label = format!("dead.{}", self.fresh());
+            self.body.push(format!("{}:", label));
+            self.block = label;
+            self.terminated = false;
+        }
+        self.body.push(format!("  {}", instruction));
+    }
+
+    /// Emits an instruction that produces a value, returning the register holding it.
+    fn temp(&mut self, instruction: String) -> String {
+        let register = format!("%t{}", self.fresh());
+        self.instr(format!("{} = {}", register, instruction));
+        register
+    }
+
+    /// Emits an instruction that ends the current block.
+    fn terminate(&mut self, instruction: String) {
+        self.instr(instruction);
+        self.terminated = true;
+    }
+
+    /// Branches to a label, unless the current block has already been terminated.
+    fn branch(&mut self, label: &str) {
+        if !self.terminated {
+            self.terminate(format!("br label %{}", label));
+        }
+    }
+
+    /// Starts a new block, falling through into it from the current block if necessary.
+    fn label(&mut self, label: &str) {
+        self.branch(label);
+        self.body.push(format!("{}:", label));
+        self.block = label.to_string();
+        self.terminated = false;
+    }
+
+    /// Binds a variable in the innermost scope.
+    fn bind(&mut self, name: &str, variable: Variable) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.variables.insert(name.to_string(), variable);
+        }
+    }
+
+    /// Looks up a variable, searching the scopes of the current function and then the globals.
+    fn lookup(&self, name: &str) -> Option<Variable> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(v) = scope.variables.get(name) {
+                return Some(v.to_owned());
+            }
+        }
+        self.globals.get(name).cloned()
+    }
+
+    /// Gets the Boron type of a variable or struct field (`point.x`).
+    fn path_type(&self, path: &str) -> Result<String, Error> {
+        let (base, fields) = split_path(path);
+        let mut datatype = match self.lookup(base) {
+            Some(v) => v.datatype,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        for field in fields {
+            let structure = pointee(&datatype).unwrap_or(&datatype).to_string();
+            datatype = self.field(&structure, field, path)?.1;
+        }
+        Ok(datatype)
+    }
+
+    /// Emits the address of a variable or struct field (`point.x`), following heap pointers.
+    fn emit_lvalue(&mut self, path: &str) -> Result<Variable, Error> {
+        let (base, fields) = split_path(path);
+        let mut variable = match self.lookup(base) {
+            Some(v) => v,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        for field in fields {
+            let structure = match pointee(&variable.datatype) {
+                Some(s) => {
+                    let s = s.to_string();
+                    let ty = self.ty(&variable.datatype)?;
+                    variable.pointer = self.temp(format!("load {}, {}* {}", ty, ty, variable.pointer));
+                    s
+                },
+                None => variable.datatype.to_owned(),
+            };
+            let (index, datatype) = self.field(&structure, field, path)?;
+            let ty = self.ty(&structure)?;
+            variable = Variable {
+                pointer: self.temp(format!(
+                    "getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
+                    ty, ty, variable.pointer, index,
+                )),
+                datatype,
+            };
+        }
+        Ok(variable)
+    }
+
+    /// Emits a store of a value into a variable, converting it to the variable's type.
+    fn emit_store(&mut self, value: Operand, variable: &Variable) -> Result<(), Error> {
+        let value = self.convert(value, &variable.datatype)?;
+        let ty = self.ty(&variable.datatype)?;
+        self.instr(format!("store {} {}, {}* {}", ty, value, ty, variable.pointer));
+        Ok(())
+    }
+
+    /// Converts a value to another type, as C does on assignment.
+    fn convert(&mut self, operand: Operand, datatype: &str) -> Result<String, Error> {
+        let from = operand.datatype.as_str();
+        let v = operand.value;
+        if from == datatype || (pointee(from).is_some() && pointee(from) == pointee(datatype)) {
+            return Ok(v);
+        }
+        match (from, datatype) {
+            ("null", d) if pointee(d).is_some() => Ok("null".to_string()),
+            ("int", "flt") => Ok(self.temp(format!("sitofp i32 {} to float", v))),
+            ("chr", "flt") => Ok(self.temp(format!("sitofp i8 {} to float", v))),
+            ("bln", "flt") => Ok(self.temp(format!("uitofp i1 {} to float", v))),
+            ("flt", "int") => Ok(self.temp(format!("fptosi float {} to i32", v))),
+            ("flt", "chr") => Ok(self.temp(format!("fptosi float {} to i8", v))),
+            ("chr", "int") => Ok(self.temp(format!("sext i8 {} to i32", v))),
+            ("bln", "int") => Ok(self.temp(format!("zext i1 {} to i32", v))),
+            ("bln", "chr") => Ok(self.temp(format!("zext i1 {} to i8", v))),
+            ("int", "chr") => Ok(self.temp(format!("trunc i32 {} to i8", v))),
+            ("int" | "chr" | "flt", "bln") => Ok(self.truth(Operand::new(v, from))?),
+            _ => Err(Error::CouldNotEmit (format!("{} as {}", from, datatype))),
+        }
+    }
+
+    /// Converts a value into an `i1` for use as a condition.
+    fn truth(&mut self, operand: Operand) -> Result<String, Error> {
+        let v = operand.value;
+        match operand.datatype.as_str() {
+            "bln" => Ok(v),
+            "int" => Ok(self.temp(format!("icmp ne i32 {}, 0", v))),
+            "chr" => Ok(self.temp(format!("icmp ne i8 {}, 0", v))),
+            "flt" => Ok(self.temp(format!("fcmp une float {}, {}", v, float(0.0)))),
+            d if pointee(d).is_some() => {
+                let ty = self.ty(d)?;
+                Ok(self.temp(format!("icmp ne {} {}, null", ty, v)))
+            },
+            _ => Err(Error::CouldNotEmit ("condition".to_string())),
+        }
+    }
+
+    /// Emits a block in a new scope.
+    fn emit_block(&mut self, body: &[Expression]) -> Result<(), Error> {
+        self.scopes.push(Scope::default());
+        for statement in body {
+            self.emit_statement(statement)?;
+        }
+
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Declares a local variable with an initial value.
+    fn emit_assignment(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<(), Error> {
+        let pointer = match value {
+            Expression::StructInit {
+                variables: v,
+            } => {
+                if pointee(datatype).is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                let pointer = self.alloca(identifier, datatype)?;
+                self.emit_fields(&pointer, datatype, v)?;
+                pointer
+            },
+            _ => {
+                if matches!(value, Expression::Null) && !datatype.starts_with("opt ") {
+                    return Err(Error::ExpectedNullable (identifier.to_string()));
+                }
+                // Evaluate the value before declaring the variable, which it may not refer to
+                let value = self.emit_expr(value)?;
+                let pointer = self.alloca(identifier, datatype)?;
+                self.emit_store(value, &Variable {
+                    pointer: pointer.to_owned(),
+                    datatype: datatype.to_string(),
+                })?;
+                pointer
+            },
+        };
+        self.bind(identifier, Variable {
+            pointer,
+            datatype: datatype.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Initializes a struct in place from a struct initialization (`{ x 1 y 2 }`), zeroing any other fields.
+    fn emit_fields(&mut self, pointer: &str, datatype: &str, variables: &HashMap<String, Expression>) -> Result<(), Error> {
+        let ty = self.ty(datatype)?;
+        self.instr(format!("store {} zeroinitializer, {}* {}", ty, ty, pointer));
+        // Sort the fields so that they are always initialized in the same order
+        let mut fields = variables.iter().collect::<Vec<(&String, &Expression)>>();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (varname, variable) in fields {
+            let (index, fieldtype) = self.field(datatype, varname, &format!("{}.{}", datatype, varname))?;
+            let value = self.emit_expr(variable)?;
+            let field = self.temp(format!(
+                "getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
+                ty, ty, pointer, index,
+            ));
+            self.emit_store(value, &Variable {
+                pointer: field,
+                datatype: fieldtype,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Emits a single statement.
+    fn emit_statement(&mut self, statement: &Expression) -> Result<(), Error> {
+        match statement {
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } | Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.emit_assignment(d, i, v)?,
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                // A static variable is a global initialized once, when the program is loaded
+                let pointer = quote('@', &format!("{}.{}", self.function, i));
+                self.emit_global(&pointer, d, i, v)?;
+                self.bind(i, Variable {
+                    pointer,
+                    datatype: d.to_owned(),
+                });
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                let pointer = self.alloca(i, d)?;
+                let ty = self.ty(d)?;
+                self.instr(format!("store {} {}, {}* {}", ty, self.zero(d), ty, pointer));
+                self.bind(i, Variable {
+                    pointer,
+                    datatype: d.to_owned(),
+                });
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => match &**v {
+                Expression::StructInit {
+                    variables: fields,
+                } => {
+                    let mut fields = fields.iter().collect::<Vec<(&String, &Expression)>>();
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                    for (varname, variable) in fields {
+                        let value = self.emit_expr(variable)?;
+                        let field = self.emit_lvalue(&format!("{}.{}", i, varname))?;
+                        self.emit_store(value, &field)?;
+                    }
+                },
+                _ => {
+                    let value = self.emit_expr(v)?;
+                    let variable = self.emit_lvalue(i)?;
+                    self.emit_store(value, &variable)?;
+                },
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                let n = self.fresh();
+                let (cond, body, end) = (format!("while.cond.{}", n), format!("while.body.{}", n), format!("while.end.{}", n));
+                self.label(&cond);
+                let condition = self.emit_expr(c)?;
+                let condition = self.truth(condition)?;
+                self.terminate(format!("br i1 {}, label %{}, label %{}", condition, body, end));
+                self.label(&body);
+                self.emit_block(b)?;
+                self.branch(&cond);
+                self.label(&end);
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                let n = self.fresh();
+                let (then, end) = (format!("if.then.{}", n), format!("if.end.{}", n));
+                let condition = self.emit_expr(c)?;
+                let condition = self.truth(condition)?;
+                self.terminate(format!("br i1 {}, label %{}, label %{}", condition, then, end));
+                self.label(&then);
+                self.emit_block(b)?;
+                self.label(&end);
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let n = self.fresh();
+                let (then, otherwise, end) = (format!("if.then.{}", n), format!("if.else.{}", n), format!("if.end.{}", n));
+                let condition = self.emit_expr(c)?;
+                let condition = self.truth(condition)?;
+                self.terminate(format!("br i1 {}, label %{}, label %{}", condition, then, otherwise));
+                self.label(&then);
+                self.emit_block(t)?;
+                self.branch(&end);
+                self.label(&otherwise);
+                self.emit_block(f)?;
+                self.label(&end);
+            },
+            Expression::Return (v) => {
+                let value = self.emit_expr(v)?;
+                let return_type = self.return_type.to_owned();
+                let ret = match return_type.as_str() {
+                    "nul" => "ret void".to_string(),
+                    _ => {
+                        let value = self.convert(value, &return_type)?;
+                        format!("ret {} {}", self.ty(&return_type)?, value)
+                    },
+                };
+                self.terminate(ret);
+            },
+            Expression::Struct { .. }
+            | Expression::FnDeclaration { .. }
+            | Expression::Use (_) => return Err(Error::CouldNotEmit (format!("declaration inside {}", self.function))),
+            _ => {
+                self.emit_expr(statement)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Infers the Boron type of an expression without emitting it.
+    fn infer(&self, expr: &Expression) -> Result<String, Error> {
+        match expr {
+            Expression::Int (_) => Ok("int".to_string()),
+            Expression::Float (_) => Ok("flt".to_string()),
+            Expression::Bool (_) => Ok("bln".to_string()),
+            Expression::Char (_) => Ok("chr".to_string()),
+            Expression::Null => Ok("null".to_string()),
+            Expression::Identifier (i) => Ok(self.path_type(i)?),
+            Expression::UnaryOp {
+                op: TokenType::Not,
+                ..
+            } => Ok("bln".to_string()),
+            Expression::UnaryOp {
+                expr: e,
+                ..
+            } => Ok(arithmetic(&self.infer(e)?, "int")),
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => match o {
+                TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Multiply
+                | TokenType::Divide => Ok(arithmetic(&self.infer(l)?, &self.infer(r)?)),
+                _ => Ok("bln".to_string()),
+            },
+            Expression::TernaryIfElse {
+                body_true: t,
+                body_false: f,
+                ..
+            } => {
+                let (t, f) = (self.infer(t)?, self.infer(f)?);
+                match (t.as_str(), f.as_str()) {
+                    _ if t == f => Ok(t),
+                    ("null", _) => Ok(f),
+                    (_, "null") => Ok(t),
+                    _ if pointee(&t).is_some() && pointee(&t) == pointee(&f) => Ok(format!("opt {}", pointee(&t).unwrap_or_default())),
+                    _ => Ok(arithmetic(&t, &f)),
+                }
+            },
+            Expression::New {
+                datatype: d,
+                ..
+            } => Ok(format!("own {}", d)),
+            Expression::FnCall {
+                name: n,
+                ..
+            } => match n.as_str() {
+                "print" | "drop" => Ok("nul".to_string()),
+                _ => match self.functions.get(n) {
+                    Some(s) => Ok(s.return_type.to_owned()),
+                    None => Err(Error::UndeclaredFunction (n.to_string())),
+                },
+            },
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
+    }
+
+    /// Emits an expression, returning the value it computes.
+    fn emit_expr(&mut self, expr: &Expression) -> Result<Operand, Error> {
+        match expr {
+            Expression::Int (i) => Ok(Operand::new(i.to_string(), "int")),
+            Expression::Float (f) => Ok(Operand::new(float(*f), "flt")),
+            Expression::Bool (b) => Ok(Operand::new(b.to_string(), "bln")),
+            Expression::Char (c) => Ok(Operand::new((*c as u8 as i8).to_string(), "chr")),
+            Expression::Null => Ok(Operand::new("null".to_string(), "null")),
+            // Reading a struct copies it, just as assigning a struct does in C
+            Expression::Identifier (i) => {
+                let variable = self.emit_lvalue(i)?;
+                let ty = self.ty(&variable.datatype)?;
+                let value = self.temp(format!("load {}, {}* {}", ty, ty, variable.pointer));
+                Ok(Operand::new(value, &variable.datatype))
+            },
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                let value = self.emit_expr(e)?;
+                match o {
+                    TokenType::Not => {
+                        let value = self.truth(value)?;
+                        Ok(Operand::new(self.temp(format!("xor i1 {}, true", value)), "bln"))
+                    },
+                    _ => match value.datatype.as_str() {
+                        "flt" => Ok(Operand::new(self.temp(format!("fneg float {}", value.value)), "flt")),
+                        _ => {
+                            let value = self.convert(value, "int")?;
+                            Ok(Operand::new(self.temp(format!("sub i32 0, {}", value)), "int"))
+                        },
+                    },
+                }
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => {
+                let left = self.emit_expr(l)?;
+                let right = self.emit_expr(r)?;
+                Ok(self.emit_binop(*o, left, right)?)
+            },
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let datatype = self.infer(expr)?;
+                let n = self.fresh();
+                let (then, otherwise, end) = (format!("ternary.then.{}", n), format!("ternary.else.{}", n), format!("ternary.end.{}", n));
+                let condition = self.emit_expr(c)?;
+                let condition = self.truth(condition)?;
+                self.terminate(format!("br i1 {}, label %{}, label %{}", condition, then, otherwise));
+
+                // Each arm is converted to the type of the whole expression before leaving its block
+                let mut incoming = Vec::new();
+                for (label, arm) in [(&then, t), (&otherwise, f)] {
+                    self.label(label);
+                    let value = self.emit_expr(arm)?;
+                    let value = self.convert(value, &datatype)?;
+                    incoming.push(format!("[ {}, %{} ]", value, self.block));
+                    self.branch(&end);
+                }
+                self.label(&end);
+                let value = self.temp(format!("phi {} {}", self.ty(&datatype)?, incoming.join(", ")));
+                Ok(Operand::new(value, &datatype))
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                if !self.structs.contains_key(d) {
+                    return Err(Error::UndeclaredVariable (d.to_owned()));
+                }
+                let ty = self.ty(d)?;
+                let size = self.temp(format!("ptrtoint {}* getelementptr ({}, {}* null, i32 1) to i64", ty, ty, ty));
+                let raw = self.temp(format!("call i8* @boron.new(i64 {})", size));
+                let pointer = self.temp(format!("bitcast i8* {} to {}*", raw, ty));
+                self.emit_fields(&pointer, d, v)?;
+                Ok(Operand::new(pointer, &format!("own {}", d)))
+            },
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => match n.as_str() {
+                "print" => {
+                    for arg in a {
+                        self.emit_print(arg)?;
+                    }
+                    Ok(Operand::new(String::new(), "nul"))
+                },
+                "drop" => {
+                    for arg in a {
+                        let id = match arg {
+                            Expression::Identifier (id) => id,
+                            _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+                        };
+                        let variable = self.emit_lvalue(id)?;
+                        if pointee(&variable.datatype).is_none() {
+                            return Err(Error::ExpectedPointer (id.to_owned()));
+                        }
+                        let ty = self.ty(&variable.datatype)?;
+                        let pointer = self.temp(format!("load {}, {}* {}", ty, ty, variable.pointer));
+                        let raw = self.temp(format!("bitcast {} {} to i8*", ty, pointer));
+                        self.instr(format!("call void @free(i8* {})", raw));
+                        self.instr(format!("store {} null, {}* {}", ty, ty, variable.pointer));
+                    }
+                    Ok(Operand::new(String::new(), "nul"))
+                },
+                _ => Ok(self.emit_call(n, a)?),
+            },
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
+    }
+
+    /// Emits a binary operation, promoting its operands as C does.
+    fn emit_binop(&mut self, op: TokenType, left: Operand, right: Operand) -> Result<Operand, Error> {
+        let (l, r) = (left.datatype.as_str(), right.datatype.as_str());
+
+        // Pointers may only be compared for equality
+        if pointee(l).is_some() || pointee(r).is_some() || l == "null" || r == "null" {
+            if op != TokenType::Equal {
+                return Err(Error::InvalidOperator (format!("{:?}", op)));
+            }
+            let ty = self.ty(if l == "null" { r } else { l })?;
+            let value = self.temp(format!("icmp eq {} {}, {}", ty, left.value, right.value));
+            return Ok(Operand::new(value, "bln"));
+        }
+
+        // Characters and booleans are compared as themselves, and promoted to `int` otherwise
+        let datatype = match (l, r, op) {
+            ("chr", "chr", o) | ("bln", "bln", o) if is_comparison(o) => l.to_string(),
+            _ => arithmetic(l, r),
+        };
+        let left = self.convert(left, &datatype)?;
+        let right = self.convert(right, &datatype)?;
+        let ty = self.ty(&datatype)?;
+
+        let float = datatype == "flt";
+        let instruction = match (op, float) {
+            (TokenType::Plus, false) => "add",
+            (TokenType::Minus, false) => "sub",
+            (TokenType::Multiply, false) => "mul",
+            (TokenType::Divide, false) => "sdiv",
+            (TokenType::Plus, true) => "fadd",
+            (TokenType::Minus, true) => "fsub",
+            (TokenType::Multiply, true) => "fmul",
+            (TokenType::Divide, true) => "fdiv",
+            (TokenType::Greater, false) => "icmp sgt",
+            (TokenType::Less, false) => "icmp slt",
+            (TokenType::Equal, false) => "icmp eq",
+            (TokenType::GreaterEqual, false) => "icmp sge",
+            (TokenType::LessEqual, false) => "icmp sle",
+            (TokenType::Greater, true) => "fcmp ogt",
+            (TokenType::Less, true) => "fcmp olt",
+            (TokenType::Equal, true) => "fcmp oeq",
+            (TokenType::GreaterEqual, true) => "fcmp oge",
+            (TokenType::LessEqual, true) => "fcmp ole",
+            _ => return Err(Error::InvalidOperator (format!("{:?}", op))),
+        };
+        let value = self.temp(format!("{} {} {}, {}", instruction, ty, left, right));
+        match is_comparison(op) {
+            true => Ok(Operand::new(value, "bln")),
+            false => Ok(Operand::new(value, &datatype)),
+        }
+    }
+
+    /// Emits a call to `printf` for a single value.
+    fn emit_print(&mut self, arg: &Expression) -> Result<(), Error> {
+        let value = self.emit_expr(arg)?;
+        let (format, args) = match value.datatype.as_str() {
+            "int" => ("int", format!(", i32 {}", value.value)),
+            // C promotes floats to doubles and chars to ints when passing them to `printf`
+            "flt" => ("flt", format!(", double {}", self.temp(format!("fpext float {} to double", value.value)))),
+            "chr" => ("chr", format!(", i32 {}", self.temp(format!("sext i8 {} to i32", value.value)))),
+            "bln" => {
+                let string = self.temp(format!(
+                    "select i1 {}, i8* {}, i8* {}",
+                    value.value, string("true", 6), string("false", 7),
+                ));
+                self.temp(format!("call i32 (i8*, ...) @printf(i8* {})", string));
+                return Ok(());
+            },
+            "nul" => ("nul", String::new()),
+            _ => return Err(Error::CouldNotEmit (format!("print of {}", value.datatype))),
+        };
+        let length = if format == "nul" { 2 } else { 4 };
+        self.temp(format!("call i32 (i8*, ...) @printf(i8* {}{})", string(format, length), args));
+        Ok(())
+    }
+
+    /// Emits a function call, passing each argument according to the function's signature.
+    fn emit_call(&mut self, name: &str, args: &[Expression]) -> Result<Operand, Error> {
+        let signature = match self.functions.get(name) {
+            Some(s) => s.to_owned(),
+            None => return Err(Error::UndeclaredFunction (name.to_string())),
+        };
+        if signature.arguments.len() != args.len() {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+
+        let mut arguments = Vec::new();
+        for ((datatype, passing), arg) in signature.arguments.iter().zip(args.iter()) {
+            let ty = self.ty(datatype)?;
+            let argument = match (passing, arg) {
+                (Passing::Value, _) => {
+                    let value = self.emit_expr(arg)?;
+                    self.convert(value, datatype)?
+                },
+                (_, Expression::Identifier (id)) => {
+                    let variable = self.emit_lvalue(id)?;
+                    // A heap pointer already refers to its struct, so it is passed as-is
+                    match pointee(&variable.datatype) {
+                        Some(s) if s == datatype => {
+                            let pointer_ty = self.ty(&variable.datatype)?;
+                            self.temp(format!("load {}, {}* {}", pointer_ty, pointer_ty, variable.pointer))
+                        },
+                        _ => variable.pointer,
+                    }
+                },
+                (Passing::Reference, _) => return Err(Error::ExpectedReferenceArgument (name.to_string())),
+                // Any other struct passed by constant reference is copied into a temporary
+                (Passing::ConstReference, _) => {
+                    let value = self.emit_expr(arg)?;
+                    match pointee(&value.datatype) {
+                        Some(_) => value.value,
+                        None => {
+                            let pointer = self.alloca("arg", datatype)?;
+                            self.emit_store(value, &Variable {
+                                pointer: pointer.to_owned(),
+                                datatype: datatype.to_owned(),
+                            })?;
+                            pointer
+                        },
+                    }
+                },
+            };
+            let ty = match passing {
+                Passing::Value => ty,
+                _ => format!("{}*", ty),
+            };
+            arguments.push(format!("{} {}", ty, argument));
+        }
+
+        let call = format!("call {} {}({})", self.ty(&signature.return_type)?, quote('@', name), arguments.join(", "));
+        match signature.return_type.as_str() {
+            "nul" => {
+                self.instr(call);
+                Ok(Operand::new(String::new(), "nul"))
+            },
+            _ => Ok(Operand::new(self.temp(call), &signature.return_type)),
+        }
+    }
+}
+
+
+/// Gets the struct a pointer type (`own Node` or `opt Node`) points to.
+fn pointee(datatype: &str) -> Option<&str> {
+    datatype.strip_prefix("own ").or_else(|| datatype.strip_prefix("opt "))
+}
+
+
+/// Gets the type of an arithmetic operation on two types, promoting as C does.
+fn arithmetic(left: &str, right: &str) -> String {
+    match left == "flt" || right == "flt" {
+        true => "flt".to_string(),
+        false => "int".to_string(),
+    }
+}
+
+
+/// Checks if an operator is a comparison.
+fn is_comparison(op: TokenType) -> bool {
+    matches!(
+        op,
+        TokenType::Greater
+        | TokenType::Less
+        | TokenType::Equal
+        | TokenType::GreaterEqual
+        | TokenType::LessEqual
+    )
+}
+
+
+/// Formats a global (`@`) or local (`%`) identifier, quoting names that LLVM would not accept bare.
+fn quote(sigil: char, name: &str) -> String {
+    match name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        true => format!("{}{}", sigil, name),
+        false => format!("{}\"{}\"", sigil, name),
+    }
+}
+
+
+/// Formats a floating-point constant exactly, as LLVM requires.
+fn float(f: f32) -> String {
+    format!("0x{:016X}", (f as f64).to_bits())
+}
+
+
+/// Gets a pointer to the first character of a string constant from the prelude.
+fn string(name: &str, length: usize) -> String {
+    format!(
+        "getelementptr inbounds ([{} x i8], [{} x i8]* @.str.{}, i64 0, i64 0)",
+        length, length, name,
+    )
+}
@@ -0,0 +1,547 @@
+//! Indexes the declarations in a Boron document for the language server.
+//!
+//! The index is built from the document's tokens rather than its parse tree, so
+//! that hover, go-to-definition and completion keep working while the document
+//! is being edited and does not parse.  Variables, structs and function
+//! signatures are recorded in an `Environment`, with one scope per block.
+
+
+use std::collections::{HashMap, HashSet};
+
+use unicode_ident::is_xid_continue;
+
+use crate::emitter::{
+    Environment,
+    Signature,
+    Variable,
+};
+
+use crate::error::Error;
+
+use crate::parser::{
+    Passing,
+    Span,
+    Token,
+    TokenType,
+    Tokenizer,
+};
+
+use crate::pipeline::span_at;
+
+
+/// Limits how many unexpected characters are skipped while indexing a document.
+const MAX_SKIPPED: usize = 64;
+
+
+/// Enumerates the kinds of declaration in a Boron program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Function,
+    Struct,
+    Field,
+}
+
+
+/// Describes a declaration in a document.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // The declaration as it is written in Boron
+    pub detail: String,
+    // Doc comment written before the declaration
+    pub doc: Option<String>,
+    // Span of the declared name
+    pub span: Span,
+    // Scope a variable is declared in
+    scope: usize,
+    // Struct a field belongs to
+    owner: Option<String>,
+}
+
+
+/// Locates a block within a document.
+struct Block {
+    parent: Option<usize>,
+    // Byte offsets of the start and end of the block
+    start: usize,
+    end: usize,
+}
+
+
+/// Records an identifier that appears in a document.
+struct Name {
+    text: String,
+    span: Span,
+    // Whether the identifier is followed by `(`
+    called: bool,
+}
+
+
+/// Indexes the blocks, declarations and identifiers of a document.
+pub struct Analysis {
+    source: String,
+    environment: Environment,
+    // Blocks of the document, by scope ID in `environment`
+    blocks: Vec<Block>,
+    symbols: Vec<Symbol>,
+    names: Vec<Name>,
+}
+
+
+/// Provides functions for the `Analysis` struct.
+impl Analysis {
+    /// Indexes a document.
+    pub fn new(source: &str) -> Self {
+        let mut analysis = Self {
+            source: source.to_string(),
+            environment: Environment::new(),
+            blocks: Vec::new(),
+            symbols: Vec::new(),
+            names: Vec::new(),
+        };
+        let global = analysis.open(None, 0);
+        analysis.blocks[global].end = source.len();
+
+        // Tokens scanned before an error are still indexed
+        let text = skip_unexpected(source);
+        let tokens = Tokenizer::new(&text)
+            .map_while(Result::ok)
+            .collect::<Vec<_>>();
+
+        for (i, token) in tokens.iter().enumerate() {
+            if token.check(TokenType::Identifier) {
+                analysis.names.push(Name {
+                    text: token.get_value(),
+                    span: token.get_span(),
+                    called: check(&tokens, i + 1, TokenType::OpenParen),
+                });
+            }
+        }
+
+        let mut stack = vec![global];
+        let mut i = 0;
+        while i < tokens.len() {
+            let scope = stack[stack.len() - 1];
+            let token = tokens[i];
+            i = match token.get_type() {
+                TokenType::Struct => analysis.declare_struct(&tokens, i, global),
+                TokenType::Let
+                | TokenType::Const
+                | TokenType::Static => analysis.declare_variable(&tokens, i, scope),
+                TokenType::Identifier if check(&tokens, i + 1, TokenType::FnDeclaration)
+                || check(&tokens, i + 1, TokenType::FnReturnType) => {
+                    analysis.declare_function(&tokens, i, global, &mut stack)
+                },
+                TokenType::OpenBrace => {
+                    let block = analysis.open(Some(scope), token.get_span().start);
+                    stack.push(block);
+                    i + 1
+                },
+                TokenType::CloseBrace => {
+                    // The global scope is never closed
+                    if stack.len() > 1 {
+                        analysis.blocks[scope].end = token.get_span().end;
+                        stack.pop();
+                    }
+                    i + 1
+                },
+                _ => i + 1,
+            };
+        }
+
+        analysis
+    }
+
+    /// Opens a block beginning at the given byte offset, which runs to the end of the document until it is closed.
+    ///
+    /// Every scope of the environment is opened here, so registering names in one cannot fail.
+    fn open(&mut self, parent: Option<usize>, start: usize) -> usize {
+        let id = self.environment.add(parent);
+        self.blocks.push(Block {
+            parent,
+            start,
+            end: self.source.len(),
+        });
+        id
+    }
+
+    /// Indexes a struct declaration, returning the index of the token after it.
+    fn declare_struct(&mut self, tokens: &[Token], i: usize, global: usize) -> usize {
+        let name = match tokens.get(i + 1) {
+            Some(t) if t.check(TokenType::Identifier) => *t,
+            _ => return i + 1,
+        };
+        if !check(tokens, i + 2, TokenType::OpenBrace) {
+            return i + 2;
+        }
+
+        let mut structure = HashMap::new();
+        let mut fields = Vec::new();
+        let mut j = i + 3;
+        while let Some((datatype, k)) = datatype(tokens, j) {
+            let field = match tokens.get(k) {
+                Some(t) if t.check(TokenType::Identifier) => *t,
+                _ => break,
+            };
+            structure.insert(field.get_value(), Variable::from(&datatype));
+            fields.push(format!("{} {}", datatype, field.as_str()));
+            self.symbols.push(Symbol {
+                name: field.get_value(),
+                kind: SymbolKind::Field,
+                detail: format!("{} {}.{}", datatype, name.as_str(), field.as_str()),
+                doc: tokens[j].get_doc(),
+                span: field.get_span(),
+                scope: global,
+                owner: Some(name.get_value()),
+            });
+            j = k + 1;
+        }
+        if check(tokens, j, TokenType::CloseBrace) {
+            j += 1;
+        }
+
+        let detail = match fields.len() {
+            0 => format!("struct {} {{}}", name.as_str()),
+            _ => format!("struct {} {{\n    {}\n}}", name.as_str(), fields.join("\n    ")),
+        };
+        let _ = self.environment.register_struct(global, name.get_value(), structure);
+        self.symbols.push(Symbol {
+            name: name.get_value(),
+            kind: SymbolKind::Struct,
+            detail,
+            doc: tokens[i].get_doc(),
+            span: name.get_span(),
+            scope: global,
+            owner: None,
+        });
+
+        j
+    }
+
+    /// Indexes a `let`, `const` or `static` declaration, returning the index of the token after it.
+    fn declare_variable(&mut self, tokens: &[Token], i: usize, scope: usize) -> usize {
+        let (datatype, j) = match datatype(tokens, i + 1) {
+            Some(d) => d,
+            None => return i + 1,
+        };
+        let name = match tokens.get(j) {
+            Some(t) if t.check(TokenType::Identifier) => *t,
+            _ => return j,
+        };
+
+        let _ = self.environment.register(scope, name.get_value(), Variable::from(&datatype));
+        self.symbols.push(Symbol {
+            name: name.get_value(),
+            kind: SymbolKind::Variable,
+            detail: format!("{} {} {}", tokens[i].as_str(), datatype, name.as_str()),
+            doc: tokens[i].get_doc(),
+            span: name.get_span(),
+            scope,
+            owner: None,
+        });
+
+        j + 1
+    }
+
+    /// Indexes a function declaration and opens its body, returning the index of the token after the `{`.
+    ///
+    /// The body's block begins at the function's name so that its arguments are in scope.
+    fn declare_function(&mut self, tokens: &[Token], i: usize, global: usize, stack: &mut Vec<usize>) -> usize {
+        let name = tokens[i];
+        let body = self.open(Some(global), name.get_span().start);
+
+        let mut arguments = Vec::new();
+        let mut written = Vec::new();
+        let mut j = i + 1;
+        if check(tokens, j, TokenType::FnDeclaration) {
+            j += 1;
+            loop {
+                let by_ref = check(tokens, j, TokenType::Ref);
+                let start = if by_ref { j + 1 } else { j };
+                let (datatype, k) = match datatype(tokens, start) {
+                    Some(d) => d,
+                    None => break,
+                };
+                let argument = match tokens.get(k) {
+                    Some(t) if t.check(TokenType::Identifier) => *t,
+                    _ => break,
+                };
+
+                // Pointers are passed by value, transferring ownership to the callee
+                let passing = match tokens[start].get_type() {
+                    _ if by_ref => Passing::Reference,
+                    TokenType::Identifier => Passing::ConstReference,
+                    _ => Passing::Value,
+                };
+                let declaration = match by_ref {
+                    true => format!("ref {} {}", datatype, argument.as_str()),
+                    false => format!("{} {}", datatype, argument.as_str()),
+                };
+
+                let _ = self.environment.register(body, argument.get_value(), Variable::from(&datatype));
+                self.symbols.push(Symbol {
+                    name: argument.get_value(),
+                    kind: SymbolKind::Variable,
+                    detail: declaration.to_owned(),
+                    doc: None,
+                    span: argument.get_span(),
+                    scope: body,
+                    owner: None,
+                });
+                arguments.push((Variable::from(&datatype), passing));
+                written.push(declaration);
+                j = k + 1;
+            }
+        }
+
+        let mut return_type = None;
+        if check(tokens, j, TokenType::FnReturnType) {
+            if let Some((datatype, k)) = datatype(tokens, j + 1) {
+                return_type = Some(datatype);
+                j = k;
+            } else {
+                j += 1;
+            }
+        }
+
+        let detail = match (written.is_empty(), &return_type) {
+            (true, Some(r)) => format!("{} -> {}", name.as_str(), r),
+            (true, None) => format!("{} ::", name.as_str()),
+            (false, Some(r)) => format!("{} :: {} -> {}", name.as_str(), written.join(" "), r),
+            (false, None) => format!("{} :: {}", name.as_str(), written.join(" ")),
+        };
+        let signature = Signature {
+            arguments,
+            return_type: Variable::from(&return_type.unwrap_or_else(|| "nul".to_string())),
+        };
+        let _ = self.environment.register_fn(global, name.get_value(), signature);
+        self.symbols.push(Symbol {
+            name: name.get_value(),
+            kind: SymbolKind::Function,
+            detail,
+            doc: name.get_doc(),
+            span: name.get_span(),
+            scope: global,
+            owner: None,
+        });
+
+        if check(tokens, j, TokenType::OpenBrace) {
+            stack.push(body);
+            j + 1
+        } else {
+            // Without a body, the function's block ends with its declaration
+            self.blocks[body].end = tokens[j.min(tokens.len()) - 1].get_span().end;
+            j
+        }
+    }
+
+    /// Finds the innermost block containing a byte offset.
+    fn scope_at(&self, offset: usize) -> usize {
+        self.blocks.iter()
+            .enumerate()
+            .filter(|(_, b)| b.start <= offset && offset <= b.end)
+            .max_by_key(|(id, b)| (b.start, *id))
+            .map(|(id, _)| id)
+            .unwrap_or(0)
+    }
+
+    /// Finds the variable with the given name that is visible at a byte offset.
+    ///
+    /// Local variables are visible only after they are declared.
+    fn variable(&self, name: &str, scope: usize, offset: usize) -> Option<&Symbol> {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let found = self.symbols.iter()
+                .rev()
+                .find(|s| s.kind == SymbolKind::Variable
+                    && s.scope == id
+                    && s.name == name
+                    && (self.blocks[id].parent.is_none() || s.span.start <= offset));
+            if found.is_some() {
+                return found;
+            }
+            current = self.blocks[id].parent;
+        }
+        None
+    }
+
+    /// Finds the declaration of a function, struct or field by name.
+    fn declaration(&self, kind: SymbolKind, name: &str, owner: Option<&String>) -> Option<&Symbol> {
+        self.symbols.iter()
+            .find(|s| s.kind == kind && s.name == name && s.owner.as_ref() == owner)
+    }
+
+    /// Gets the type of a variable or of a chain of fields such as `a.b.c`.
+    fn type_of(&self, segments: &[&str], scope: usize, offset: usize) -> Option<Variable> {
+        let (first, rest) = segments.split_first()?;
+        let symbol = self.variable(first, scope, offset)?;
+        let mut variable = self.environment.lookup(symbol.scope, &symbol.name).ok()?;
+
+        for segment in rest {
+            let structure = variable.get_struct()?;
+            if !self.environment.check_struct(0, structure).ok()? {
+                return None;
+            }
+            variable = self.environment.lookup_struct(0, structure).ok()?.get(*segment)?.to_owned();
+        }
+
+        Some(variable)
+    }
+
+    /// Finds the declaration of the name at a byte offset.
+    ///
+    /// In a chain of fields such as `a.b.c`, this is the declaration of the field under
+    /// the offset.  A method call such as `a.f(b)` refers to the function `f`.
+    pub fn definition(&self, offset: usize) -> Option<&Symbol> {
+        let name = self.names.iter()
+            .find(|n| n.span.start <= offset && offset <= n.span.end)?;
+        let segments = name.text.split('.').collect::<Vec<&str>>();
+        let index = name.text[..offset - name.span.start].matches('.').count();
+        let scope = self.scope_at(offset);
+        let last = index == segments.len() - 1;
+
+        if index == 0 {
+            let variable = match name.called && last {
+                true => None,
+                false => self.variable(segments[0], scope, offset),
+            };
+            return variable
+                .or_else(|| self.declaration(SymbolKind::Function, segments[0], None))
+                .or_else(|| self.declaration(SymbolKind::Struct, segments[0], None));
+        }
+
+        let owner = self.type_of(&segments[..index], scope, offset);
+        let field = match owner.as_ref().and_then(Variable::get_struct) {
+            Some(s) => self.declaration(SymbolKind::Field, segments[index], Some(s)),
+            None => None,
+        };
+        match field {
+            Some(f) => Some(f),
+            None if name.called && last => self.declaration(SymbolKind::Function, segments[index], None),
+            None => None,
+        }
+    }
+
+    /// Describes the name at a byte offset in Markdown.
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let symbol = self.definition(offset)?;
+        let mut contents = format!("```boron\n{}\n```", symbol.detail);
+        if let Some(doc) = &symbol.doc {
+            contents.push_str("\n\n");
+            contents.push_str(doc);
+        }
+        Some(contents)
+    }
+
+    /// Lists the declarations that may be written at a byte offset.
+    ///
+    /// After a `.`, these are the fields of the struct before it and the functions that
+    /// take it as their first argument.  Otherwise, they are the functions and the
+    /// variables in scope.
+    pub fn completions(&self, offset: usize) -> Vec<&Symbol> {
+        let before = &self.source[..offset];
+        let word = match before.char_indices().rev().find(|(_, c)| !is_name_continue(*c)) {
+            Some((i, c)) => &before[i + c.len_utf8()..],
+            None => before,
+        };
+        let scope = self.scope_at(offset);
+
+        if let Some((base, _)) = word.rsplit_once('.') {
+            let segments = base.split('.').collect::<Vec<&str>>();
+            let owner = match self.type_of(&segments, scope, offset) {
+                Some(v) => match v.get_struct() {
+                    Some(s) => s.to_owned(),
+                    None => return Vec::new(),
+                },
+                None => return Vec::new(),
+            };
+
+            let fields = self.symbols.iter()
+                .filter(|s| s.kind == SymbolKind::Field && s.owner.as_ref() == Some(&owner));
+            let methods = self.symbols.iter()
+                .filter(|s| s.kind == SymbolKind::Function)
+                .filter(|s| match self.environment.lookup_fn(0, &s.name).ok().flatten() {
+                    Some(signature) => match signature.arguments.first() {
+                        Some((argument, _)) => argument.get_struct() == Some(&owner),
+                        None => false,
+                    },
+                    None => false,
+                });
+            return fields.chain(methods).collect();
+        }
+
+        let functions = self.symbols.iter()
+            .filter(|s| s.kind == SymbolKind::Function);
+
+        // Inner variables shadow outer ones with the same name
+        let mut seen = HashSet::new();
+        let mut variables = Vec::new();
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let visible = self.symbols.iter()
+                .rev()
+                .filter(|s| s.kind == SymbolKind::Variable && s.scope == id)
+                .filter(|s| self.blocks[id].parent.is_none() || s.span.start < offset);
+            for symbol in visible {
+                if seen.insert(&symbol.name) {
+                    variables.push(symbol);
+                }
+            }
+            current = self.blocks[id].parent;
+        }
+
+        functions.chain(variables).collect()
+    }
+}
+
+
+/// Checks if the token at the given index has the given type.
+fn check(tokens: &[Token], i: usize, token_type: TokenType) -> bool {
+    match tokens.get(i) {
+        Some(t) => t.check(token_type),
+        None => false,
+    }
+}
+
+
+/// Reads a datatype beginning at the given index, returning it and the index of the token after it.
+fn datatype(tokens: &[Token], i: usize) -> Option<(String, usize)> {
+    let token = tokens.get(i)?;
+    match token.get_type() {
+        TokenType::Type
+        | TokenType::Identifier => Some((token.get_value(), i + 1)),
+        TokenType::Own
+        | TokenType::Opt => match tokens.get(i + 1) {
+            Some(t) if t.check(TokenType::Identifier) => Some((format!("{} {}", token.as_str(), t.as_str()), i + 2)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+/// Checks if a character may appear in a name, including the `.` between fields.
+fn is_name_continue(c: char) -> bool {
+    c == '_' || c == '.' || is_xid_continue(c)
+}
+
+
+/// Replaces each character the tokenizer does not expect with spaces.
+///
+/// This skips, for example, the `.` typed before a field name is completed, so
+/// that the rest of the document can still be indexed.  Byte offsets are unchanged.
+fn skip_unexpected(source: &str) -> String {
+    let mut text = source.to_string();
+    for _ in 0..MAX_SKIPPED {
+        match Tokenizer::new(&text).find_map(Result::err) {
+            Some(Error::UnexpectedCharacter (c, line, column)) => {
+                let span = span_at(&text, line, column);
+                text.replace_range(span.start..span.end, &" ".repeat(c.len_utf8()));
+            },
+            _ => break,
+        }
+    }
+    text
+}
@@ -0,0 +1,354 @@
+//! A language server for Boron, which speaks the Language Server Protocol over standard input and output.
+//!
+//! The server publishes the first error in each open document as it changes, shows
+//! the declaration of the name under the cursor on hover, jumps to the declarations
+//! of variables, functions, structs and fields, and completes struct fields after
+//! `.` as well as function and variable names.
+
+
+pub mod analysis;
+
+
+use std::{
+    collections::HashMap,
+    io::{
+        self,
+        BufRead,
+        Write,
+    },
+    panic,
+    process::exit,
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+
+pub use analysis::{
+    Analysis,
+    Symbol,
+    SymbolKind,
+};
+
+use crate::pipeline;
+
+use crate::version::VERSION;
+
+
+/// JSON-RPC error code for a request with a method the server does not know.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code for a request with missing or malformed parameters.
+const INVALID_PARAMS: i64 = -32602;
+
+/// JSON-RPC error code for a request sent after the server was shut down.
+const INVALID_REQUEST: i64 = -32600;
+
+/// JSON-RPC error code for a request the server failed while handling.
+const INTERNAL_ERROR: i64 = -32603;
+
+
+/// Holds the state of a language server session.
+#[derive(Default)]
+pub struct Server {
+    // Text of each open document, by URI
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+
+/// Provides functions for the `Server` struct.
+impl Server {
+    /// Constructs a new language server.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves requests from standard input until the client asks the server to exit.
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        let stdout = io::stdout();
+
+        while let Some(message) = read_message(&mut input) {
+            if message["method"] == "exit" {
+                break;
+            }
+
+            // A bug in the compiler should not end the session
+            let replies = match panic::catch_unwind(panic::AssertUnwindSafe(|| self.handle(&message))) {
+                Ok(r) => r,
+                Err(_) => match message.get("id") {
+                    Some(id) => vec![error(id.to_owned(), INTERNAL_ERROR, "The server failed to handle this request")],
+                    None => Vec::new(),
+                },
+            };
+
+            for reply in replies {
+                let mut output = stdout.lock();
+                if write_message(&mut output, &reply).is_err() {
+                    return;
+                }
+            }
+        }
+
+        // Exiting without a shutdown request indicates an error
+        exit(if self.shutdown { 0 } else { 1 });
+    }
+
+    /// Handles one message from the client, returning the messages to send in reply.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message["method"].as_str() {
+            Some(m) => m,
+            // The client is responding to a request, which the server never sends
+            None => return Vec::new(),
+        };
+        let params = &message["params"];
+
+        // Notifications have no ID and are never answered
+        let id = match message.get("id") {
+            Some(id) => id.to_owned(),
+            None => return self.notify(method, params),
+        };
+
+        if self.shutdown {
+            return vec![error(id, INVALID_REQUEST, "The server has been shut down")];
+        }
+
+        let result = match method {
+            "initialize" => Some(initialize()),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Value::Null)
+            },
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            _ => return vec![error(id, METHOD_NOT_FOUND, &format!("Unknown method {}", method))],
+        };
+
+        match result {
+            Some(r) => vec![json!({"jsonrpc": "2.0", "id": id, "result": r})],
+            None => vec![error(id, INVALID_PARAMS, &format!("Invalid parameters to {}", method))],
+        }
+    }
+
+    /// Handles a notification from the client.
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(u) => u.to_string(),
+            None => return Vec::new(),
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_owned(), text.to_string());
+            },
+            // Documents are always synchronized in full, so the last change holds the whole text
+            "textDocument/didChange" => match params["contentChanges"].as_array().and_then(|c| c.last()) {
+                Some(change) => {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    self.documents.insert(uri.to_owned(), text.to_string());
+                },
+                None => return Vec::new(),
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish(&uri, Vec::new())];
+            },
+            _ => return Vec::new(),
+        }
+
+        vec![self.diagnose(&uri)]
+    }
+
+    /// Checks a document, returning a notification that publishes its diagnostics.
+    fn diagnose(&self, uri: &str) -> Value {
+        let text = match self.documents.get(uri) {
+            Some(t) => t,
+            None => return publish(uri, Vec::new()),
+        };
+
+        let diagnostics = match pipeline::check(text) {
+            Ok(_) => Vec::new(),
+            Err(d) => vec![json!({
+                "range": range(text, d.span.start, d.span.end),
+                "severity": 1,
+                "source": "boron",
+                "message": d.message,
+            })],
+        };
+
+        publish(uri, diagnostics)
+    }
+
+    /// Finds the document and byte offset a request refers to.
+    fn locate<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a String, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = self.documents.get(uri)?;
+        let line = params["position"]["line"].as_u64()? as usize;
+        let character = params["position"]["character"].as_u64()? as usize;
+        Some((uri, text, offset(text, line, character)))
+    }
+
+    /// Describes the name under the cursor.
+    fn hover(&self, params: &Value) -> Option<Value> {
+        let (_, text, offset) = self.locate(params)?;
+        Some(match Analysis::new(text).hover(offset) {
+            Some(contents) => json!({"contents": {"kind": "markdown", "value": contents}}),
+            None => Value::Null,
+        })
+    }
+
+    /// Finds the declaration of the name under the cursor.
+    fn definition(&self, params: &Value) -> Option<Value> {
+        let (uri, text, offset) = self.locate(params)?;
+        Some(match Analysis::new(text).definition(offset) {
+            Some(symbol) => json!({
+                "uri": uri,
+                "range": range(text, symbol.span.start, symbol.span.end),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    /// Lists the completions at the cursor.
+    fn completion(&self, params: &Value) -> Option<Value> {
+        let (_, text, offset) = self.locate(params)?;
+        let analysis = Analysis::new(text);
+        let items = analysis.completions(offset)
+            .into_iter()
+            .map(|symbol| json!({
+                "label": symbol.name,
+                "kind": completion_kind(symbol.kind),
+                "detail": symbol.detail,
+            }))
+            .collect::<Vec<Value>>();
+        Some(Value::Array(items))
+    }
+}
+
+
+/// Describes the capabilities of the server in reply to `initialize`.
+fn initialize() -> Value {
+    json!({
+        "capabilities": {
+            // Documents are sent in full on every change
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "completionProvider": {
+                "triggerCharacters": ["."],
+            },
+        },
+        "serverInfo": {
+            "name": "boron",
+            "version": VERSION,
+        },
+    })
+}
+
+
+/// Constructs a reply reporting that a request failed.
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": code, "message": message},
+    })
+}
+
+
+/// Constructs a notification publishing the diagnostics of a document.
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+
+/// Converts a kind of declaration into an LSP completion item kind.
+fn completion_kind(kind: SymbolKind) -> u8 {
+    match kind {
+        SymbolKind::Function => 3,
+        SymbolKind::Field => 5,
+        SymbolKind::Variable => 6,
+        SymbolKind::Struct => 22,
+    }
+}
+
+
+/// Converts a line and a character offset in UTF-16 code units into a byte offset.
+pub fn offset(text: &str, line: usize, character: usize) -> usize {
+    let start: usize = text.split_inclusive('\n')
+        .take(line)
+        .map(str::len)
+        .sum();
+
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+
+/// Converts a byte offset into an LSP position, counting characters in UTF-16 code units.
+pub fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let start = match before.rfind('\n') {
+        Some(i) => i + 1,
+        None => 0,
+    };
+    let character: usize = before[start..].chars().map(char::len_utf16).sum();
+    json!({"line": line, "character": character})
+}
+
+
+/// Converts a pair of byte offsets into an LSP range.
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json!({"start": position(text, start), "end": position(text, end)})
+}
+
+
+/// Reads a message framed by a `Content-Length` header, or `None` at the end of the input.
+pub fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+
+    // A malformed message is ignored rather than ending the session
+    Some(serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+
+/// Writes a message framed by a `Content-Length` header.
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
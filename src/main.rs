@@ -11,24 +11,46 @@
 
 
 use std::{
-    fs::{rename, read_to_string, OpenOptions},
+    fs::{remove_file, rename, read, read_to_string, write, OpenOptions},
     env,
     io::Write,
     ffi::OsStr,
+    path::{Path, PathBuf},
+    process::{self, exit, Command},
+    panic,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
 use walkdir::WalkDir;
 
-pub mod tokenizer;
-pub mod parser;
-pub mod emitter;
-pub mod version;
-pub mod error;
+use boron_lang::{
+    tokenizer::Tokenizer,
+    parser::Parser,
+    parser::Expression,
+    backend::{self, Backend},
+    emitter::Emitter,
+    interpreter::Interpreter,
+    bytecode::{Program, Vm},
+    repl::Repl,
+    lsp::Server,
+    pipeline,
+    ui::{self, Outcome},
+    cache::Cache,
+    modules,
+    ir,
+    formatter,
+    dump,
+    error::{Error, abort, report, throw},
+};
+
+
+/// The stack size of the interpreter thread, which bounds how deeply interpreted code can recurse.
+const INTERPRETER_STACK_SIZE: usize = 1 << 30;
+
 
-use tokenizer::Tokenizer;
-use parser::Parser;
-use emitter::Emitter;
-use error::{Error, throw};
+/// Lists the intermediate forms that can be printed with `--emit`.
+const EMIT_KINDS: &[&str] = &["tokens", "ast", "tree", "ir", "c"];
 
 
 /// Enumerates the types of processes that the Boron compiler can execute.
@@ -38,6 +60,13 @@ pub enum Process {
     Exe,
     Build,
     BuildStd,
+    Interp,
+    Repl,
+    Run,
+    Emit,
+    Fmt,
+    Lsp,
+    Test,
 }
 
 
@@ -45,6 +74,23 @@ pub enum Process {
 pub struct Args {
     filename: Option<String>,
     process: Process,
+    target: String,
+    emit: String,
+    // Whether `fmt` only checks that files are formatted
+    check: bool,
+    // Whether `test` checks files against their UI annotations, and whether it rewrites them
+    ui: bool,
+    bless: bool,
+    // Whether a directory build recompiles every file, ignoring the cache
+    force: bool,
+    // Number of files a directory build compiles at once
+    jobs: usize,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Args {
@@ -53,6 +99,13 @@ impl Args {
         Self {
             filename: None,
             process: Process::Exe,
+            target: backend::BACKENDS[0].0.to_string(),
+            emit: EMIT_KINDS[0].to_string(),
+            check: false,
+            ui: false,
+            bless: false,
+            force: false,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
         }
     }
 
@@ -81,6 +134,89 @@ impl Args {
         self.process = Process::BuildStd;
     }
 
+    /// Marks this as an interpreted run.
+    pub fn mark_interp(&mut self) {
+        self.process = Process::Interp;
+    }
+
+    /// Marks this as an interactive session.
+    pub fn mark_repl(&mut self) {
+        self.process = Process::Repl;
+    }
+
+    /// Marks this as a run of a compiled bytecode program.
+    pub fn mark_run(&mut self) {
+        self.process = Process::Run;
+    }
+
+    /// Sets the target by name.
+    pub fn set_target(&mut self, t: String) {
+        if backend::find(&t).is_none() {
+            throw(Error::UnknownTarget (t));
+        }
+        self.target = t;
+    }
+
+    /// Marks this as a run of the formatter.
+    pub fn mark_fmt(&mut self) {
+        self.process = Process::Fmt;
+    }
+
+    /// Marks this as a language server session.
+    pub fn mark_lsp(&mut self) {
+        self.process = Process::Lsp;
+    }
+
+    /// Marks this as a run of the tests.
+    pub fn mark_test(&mut self) {
+        self.process = Process::Test;
+    }
+
+    /// Marks this as a run of the UI tests.
+    pub fn mark_ui(&mut self) {
+        self.ui = true;
+    }
+
+    /// Marks this as rewriting the expectations of UI tests to match their outcomes.
+    pub fn mark_bless(&mut self) {
+        self.bless = true;
+    }
+
+    /// Marks this as a directory build that recompiles every file.
+    pub fn mark_force(&mut self) {
+        self.force = true;
+    }
+
+    /// Sets the number of files a directory build compiles at once.
+    pub fn set_jobs(&mut self, j: String) {
+        self.jobs = match j.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => throw(Error::InvalidJobs (j)),
+        };
+    }
+
+    /// Marks this as a check that files are formatted, without changing them.
+    pub fn mark_check(&mut self) {
+        self.check = true;
+    }
+
+    /// Marks this as printing an intermediate form of the program.
+    pub fn set_emit(&mut self, e: String) {
+        if !EMIT_KINDS.contains(&e.as_str()) {
+            throw(Error::UnknownEmitKind (e));
+        }
+        self.process = Process::Emit;
+        self.emit = e;
+    }
+
+    /// Gets the backend for the target.
+    pub fn get_backend(&self) -> Box<dyn Backend> {
+        match backend::find(&self.target) {
+            Some(b) => b,
+            None => throw(Error::UnknownTarget (self.target.to_owned())),
+        }
+    }
+
     /// Gets whether or not this is a library.
     pub fn get_process(&self) -> Process {
         self.process
@@ -96,12 +232,16 @@ impl Args {
 
     /// Gets the library name (without extension) from the CLI args.
     pub fn get_libname(&self) -> String {
-        let mut f = match &self.filename {
-            Some(f) => f.to_owned(),
-            None => throw(Error::NoFileProvided),
-        };
-        f.truncate(f.len() - 4);
-        f.to_ascii_uppercase().replace("-", "_").replace("/", "_").to_owned()
+        self.get_stem().to_ascii_uppercase().replace("-", "_").replace("/", "_").to_owned()
+    }
+
+    /// Gets the filename from the CLI args without its `.brn` extension.
+    pub fn get_stem(&self) -> String {
+        let f = self.get_filename();
+        match f.strip_suffix(".brn") {
+            Some(stem) => stem.to_string(),
+            None => throw(Error::ExpectedBoronFile (f)),
+        }
     }
 }
 
@@ -110,15 +250,49 @@ fn main() {
     // Sets up a CLI args struct.
     let mut args = Args::new();
 
-    for arg in env::args() {
+    // Skip the name of the executable
+    let mut cli = env::args().enumerate().skip(1);
+    while let Some((index, arg)) = cli.next() {
         if arg.starts_with("--") {
             match arg.as_str() {
                 "--lib" => args.mark_lib(),
                 "--exe" => args.mark_exe(), // NOTE: this is marked by default
                 "--build" => args.mark_build(),
                 "--build-std" => args.mark_build_std(),
+                "--target" => match cli.next() {
+                    Some((_, t)) => args.set_target(t),
+                    None => throw(Error::ExpectedTarget),
+                },
+                "--check" => args.mark_check(),
+                "--ui" => args.mark_ui(),
+                "--bless" => args.mark_bless(),
+                "--force" => args.mark_force(),
+                "--emit" => match cli.next() {
+                    Some((_, e)) => args.set_emit(e),
+                    None => throw(Error::ExpectedEmitKind),
+                },
                 _ => throw(Error::UnexpectedCliFlag (arg)),
             }
+        } else if arg == "-j" {
+            match cli.next() {
+                Some((_, j)) => args.set_jobs(j),
+                None => throw(Error::ExpectedJobs),
+            }
+        } else if index == 1 && arg == "interp" {
+            // Subcommands must come first (`boron interp file.brn`)
+            args.mark_interp();
+        } else if index == 1 && arg == "repl" {
+            args.mark_repl();
+        } else if index == 1 && arg == "compile" {
+            args.mark_exe();
+        } else if index == 1 && arg == "run" {
+            args.mark_run();
+        } else if index == 1 && arg == "fmt" {
+            args.mark_fmt();
+        } else if index == 1 && arg == "lsp" {
+            args.mark_lsp();
+        } else if index == 1 && arg == "test" {
+            args.mark_test();
         } else {
             args.set_filename(arg);
         }
@@ -129,88 +303,240 @@ fn main() {
         Process::Exe => compile_exe(args),
         Process::Build => build(args),
         Process::BuildStd => build_std(args),
+        Process::Interp => interp(args),
+        Process::Repl => repl(),
+        Process::Run => run(args),
+        Process::Emit => emit(args),
+        Process::Fmt => fmt(args),
+        Process::Lsp => Server::new().run(),
+        Process::Test if args.ui => test_ui(args),
+        Process::Test => test(args),
     };
 }
 
 
-fn compile_lib(args: Args) {
-    let code = match read_to_string(&args.get_filename()) {
+/// Gets the result of a compiler stage, reporting its error and exiting if it failed.
+fn or_throw<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(t) => t,
+        Err(e) => throw(e),
+    }
+}
+
+
+fn read_file(args: &Args) -> String {
+    match read_to_string(args.get_filename()) {
         Ok(c) => c,
         Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
-    };
+    }
+}
 
-    let mut tokenizer = Tokenizer::new(code);
 
-    let parser = Parser::new();
-    let expressions = parser.parse_all(&mut tokenizer);
+fn parse_file(args: &Args) -> Vec<Expression> {
+    let code = read_file(args);
 
-    let mut emitter = Emitter::new();
+    let mut tokenizer = Tokenizer::new(&code);
+
+    let parser = Parser::new();
+    or_throw(parser.parse_all(&mut tokenizer))
+}
 
-    let mut output_filename = args.get_filename();
-    output_filename.truncate(output_filename.len() - 4);
-    output_filename.push_str(".h");
 
-    let output = emitter.compile_lib(args.get_libname(), expressions);
+fn write_output(args: &Args, extension: &str, output: &[u8]) {
+    let mut output_filename = args.get_stem();
+    output_filename.push('.');
+    output_filename.push_str(extension);
 
     // Open a file for output
     let mut output_file = match OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
-        .open(output_filename.to_owned())
+        .open(&output_filename)
     {
         Ok(f) => f,
         Err(_) => throw(Error::CouldNotCreate (output_filename.to_owned())),
     };
 
-    match output_file.write_all(output.as_bytes()) {
+    match output_file.write_all(output) {
         Ok(_) => (),
         Err(_) => throw(Error::CouldNotWriteFile (output_filename.to_owned())),
     }
 }
 
 
-fn compile_exe(args: Args) {
-    let code = match read_to_string(&args.get_filename()) {
-        Ok(c) => c,
-        Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
+/// Compiles each Boron file in a directory as a library, skipping files the cache shows are unchanged.
+///
+/// Stale files are compiled at once on a pool of worker threads, but their output is
+/// written and their errors are reported in the order of their filenames, so a build
+/// does the same thing however many jobs it runs.
+fn compile_dir(args: &Args) {
+    // Walk the given directory
+    let mut filenames: Vec<String> = Vec::new();
+    for entry in WalkDir::new(args.get_filename()).sort_by_file_name() {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
+        };
+        if entry.path().extension() == Some(OsStr::new("brn")) {
+            filenames.push(entry.path().display().to_string());
+        }
+    }
+
+    let backend = args.get_backend();
+    let extension = match backend.lib_extension() {
+        Some(e) => e,
+        None => throw(Error::LibraryNotSupported (backend.name().to_string())),
+    };
+    let mut cache = match args.force {
+        true => Cache::empty(&args.get_filename(), &args.target),
+        false => Cache::open(&args.get_filename(), &args.target),
     };
 
-    let mut tokenizer = Tokenizer::new(code);
+    // Decide what to rebuild before recording anything, so that dependents of changed files are stale too
+    let mut stale = Vec::new();
+    for filename in &filenames {
+        let mut filename_args = Args::new();
+        filename_args.set_filename(filename.to_owned());
+        filename_args.target = args.target.to_owned();
+        let output = format!("{}.{}", filename_args.get_stem(), extension);
+        if !cache.is_fresh(filename) || !Path::new(&output).exists() {
+            stale.push(filename_args);
+        }
+    }
 
-    let parser = Parser::new();
-    let expressions = parser.parse_all(&mut tokenizer);
+    let mut failure = None;
+    for (filename_args, result) in stale.iter().zip(compile_all(&stale, args.jobs)) {
+        match result {
+            Ok(compiled) => {
+                or_throw(cache.record(&filename_args.get_filename(), &compiled.code, &compiled.uses));
+                write_output(filename_args, extension, &compiled.output);
+            },
+            Err(e) => if failure.is_none() {
+                failure = Some(e);
+            },
+        }
+    }
 
-    let mut emitter = Emitter::new();
+    // Files that compiled are not compiled again, even if another file failed
+    cache.retain(&filenames);
+    or_throw(cache.save());
+    if let Some(e) = failure {
+        throw(e);
+    }
+}
 
-    let mut output_filename = args.get_filename();
-    output_filename.truncate(output_filename.len() - 4);
-    output_filename.push_str(".c");
 
-    let output = emitter.compile_exe(expressions);
+/// Describes a file compiled by a directory build.
+struct Compiled {
+    code: String,
+    // `use` statements of the file, which the cache records
+    uses: Vec<Expression>,
+    output: Vec<u8>,
+}
 
-    // Open a file for output
-    let mut output_file = match OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(output_filename.to_owned())
-    {
-        Ok(f) => f,
-        Err(_) => throw(Error::CouldNotCreate (output_filename.to_owned())),
+
+/// Compiles files as libraries on a pool of worker threads, returning each
+/// compiled file, or the error it threw, in the order of the files.
+fn compile_all(files: &[Args], jobs: usize) -> Vec<Result<Compiled, Error>> {
+    let next = AtomicUsize::new(0);
+    let mut results = thread::scope(|s| {
+        let workers = (0..jobs.min(files.len()))
+            .map(|_| s.spawn(|| {
+                let mut compiled = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(index) else { break };
+
+                    // Errors are returned rather than printed, so that workers never write to the terminal
+                    compiled.push((index, compile_file(file)));
+                }
+                compiled
+            }))
+            .collect::<Vec<_>>();
+
+        // A worker only fails to join if the compiler itself panicked, so pass the panic on
+        workers.into_iter()
+            .flat_map(|w| w.join().unwrap_or_else(|p| panic::resume_unwind(p)))
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+
+/// Compiles a file of a directory build as a library.
+fn compile_file(file: &Args) -> Result<Compiled, Error> {
+    let code = match read_to_string(file.get_filename()) {
+        Ok(c) => c,
+        Err(_) => return Err(Error::CouldNotReadFile (file.get_filename())),
     };
+    let mut tokenizer = Tokenizer::new(&code);
+    let expressions = Parser::new().parse_all(&mut tokenizer)?;
+    let uses = expressions.iter()
+        .filter(|e| matches!(e, Expression::Use (_)))
+        .cloned()
+        .collect();
+    let output = file.get_backend().compile_lib(file.get_libname(), expressions)?;
+    Ok(Compiled { code, uses, output })
+}
 
-    match output_file.write_all(output.as_bytes()) {
-        Ok(_) => (),
-        Err(_) => throw(Error::CouldNotWriteFile (output_filename.to_owned())),
+
+fn compile_lib(args: Args) {
+    let backend = args.get_backend();
+    let extension = match backend.lib_extension() {
+        Some(e) => e,
+        None => throw(Error::LibraryNotSupported (backend.name().to_string())),
+    };
+
+    let expressions = parse_file(&args);
+    let output = or_throw(backend.compile_lib(args.get_libname(), expressions));
+    write_output(&args, extension, &output);
+}
+
+
+fn compile_exe(args: Args) {
+    let backend = args.get_backend();
+
+    // Find every module the program uses before compiling anything, so that a missing module or a cycle stops the build
+    let (mut expressions, modules) = or_throw(modules::resolve(&args.get_filename(), &read_file(&args)));
+
+    // Backends that compile libraries include the header of each module, so compile the modules first
+    if let Some(extension) = backend.lib_extension() {
+        for module in modules {
+            let mut module_args = Args::new();
+            module_args.set_filename(module.filename);
+            let output = or_throw(backend.compile_lib(module_args.get_libname(), module.expressions));
+            write_output(&module_args, extension, &output);
+        }
+    } else {
+        // Other backends compile the modules along with the program
+        expressions = modules::flatten(expressions, modules);
     }
+
+    let output = or_throw(backend.compile_exe(expressions));
+    write_output(&args, backend.extension(), &output);
 }
 
 
-fn build(args: Args) {
-    // Walk the given directory
+fn emit(args: Args) {
+    let code = read_file(&args);
+    match args.emit.as_str() {
+        "tokens" => print!("{}", or_throw(dump::tokens(&code))),
+        "ast" => print!("{}", or_throw(dump::ast(&code))),
+        "tree" => print!("{}", or_throw(dump::tree(&code))),
+        "ir" => print!("{}", ir::optimize(or_throw(ir::lower(parse_file(&args))))),
+        "c" => print!("{}", or_throw(Emitter::new().compile_exe(parse_file(&args)))),
+        _ => throw(Error::UnknownEmitKind (args.emit.to_owned())),
+    }
+}
+
+
+fn fmt(args: Args) {
+    // Format a single file, or every Boron file in a directory
     let mut filenames: Vec<String> = Vec::new();
-    for entry in WalkDir::new(&args.get_filename()) {
+    for entry in WalkDir::new(args.get_filename()) {
         let entry = match entry {
             Ok(e) => e,
             Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
@@ -220,34 +546,321 @@ fn build(args: Args) {
         }
     }
 
+    // A file that cannot be parsed is reported, and the remaining files are still formatted
+    let mut unformatted = Vec::new();
+    let mut failed = Vec::new();
     for filename in filenames {
-        let mut filename_args = Args::new();
-        filename_args.set_filename(filename);
-        filename_args.mark_lib();
-        compile_lib(filename_args);
+        let code = match read_to_string(&filename) {
+            Ok(c) => c,
+            Err(_) => throw(Error::CouldNotReadFile (filename)),
+        };
+        let formatted = match formatter::format(&code) {
+            Ok(f) => f,
+            Err(e) => {
+                println!("could not format {}", filename);
+                report(&e);
+                failed.push(filename);
+                continue;
+            },
+        };
+        if formatted == code {
+            continue;
+        }
+
+        if args.check {
+            println!("{} is not formatted", filename);
+            unformatted.push(filename);
+        } else if write(&filename, formatted).is_err() {
+            throw(Error::CouldNotWriteFile (filename));
+        }
+    }
+
+    if !unformatted.is_empty() || !failed.is_empty() {
+        exit(1);
     }
 }
 
 
-fn build_std(args: Args) {
-    // Walk the given directory
+fn test(args: Args) {
+    let filenames = test_files(&args);
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    let mut passed = 0;
+    let mut failed = 0;
+    for (index, filename) in filenames.iter().enumerate() {
+        let code = match read_to_string(filename) {
+            Ok(c) => c,
+            Err(_) => throw(Error::CouldNotReadFile (filename.to_owned())),
+        };
+        let mut tokenizer = Tokenizer::new(&code);
+        // A file that cannot be parsed counts as one failure, and the remaining files are still tested
+        let expressions = match Parser::new().parse_all(&mut tokenizer) {
+            Ok(e) => e,
+            Err(e) => {
+                println!("\ncould not parse {}", filename);
+                report(&e);
+                failed += 1;
+                continue;
+            },
+        };
+
+        let count = expressions.iter()
+            .filter(|e| matches!(e, Expression::Test { .. }))
+            .count();
+        if count == 0 {
+            continue;
+        }
+        println!("\nrunning {} {} in {}", count, if count == 1 { "test" } else { "tests" }, filename);
+
+        let harness = test_binary("test", index);
+        let compiled = Emitter::new().compile_tests(filename, expressions)
+            .map(|c| compile_c(&compiler, filename, c.as_bytes(), &harness));
+        if let Err(e) = &compiled {
+            report(e);
+        }
+        if compiled != Ok(true) {
+            println!("could not compile the tests in {}", filename);
+            failed += count;
+            continue;
+        }
+
+        // The harness exits with the number of tests that failed
+        let failures = match Command::new(&harness).status() {
+            Ok(s) => s.code().map_or(count, |c| (c as usize).min(count)),
+            Err(_) => count,
+        };
+        let _ = remove_file(&harness);
+        passed += count - failures;
+        failed += failures;
+    }
+
+    let result = if failed == 0 { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed", result, passed, failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+
+fn test_ui(args: Args) {
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    let mut passed = 0;
+    let mut failed = 0;
+    for (index, filename) in test_files(&args).iter().enumerate() {
+        let code = match read_to_string(filename) {
+            Ok(c) => c,
+            Err(_) => throw(Error::CouldNotReadFile (filename.to_owned())),
+        };
+
+        let (actual, message) = match observe(&compiler, filename, index, &code) {
+            Ok(o) => o,
+            Err(e) => {
+                println!("ui {} ... FAILED\n    {}", filename, e);
+                failed += 1;
+                continue;
+            },
+        };
+
+        if args.bless {
+            let blessed = ui::bless(&code, &actual);
+            if blessed == code {
+                println!("ui {} ... ok", filename);
+            } else if write(filename, blessed).is_ok() {
+                println!("ui {} ... blessed", filename);
+            } else {
+                throw(Error::CouldNotWriteFile (filename.to_owned()));
+            }
+            passed += 1;
+            continue;
+        }
+
+        let report = ui::diff(&ui::expectations(&code), &actual);
+        if report.is_empty() {
+            println!("ui {} ... ok", filename);
+            passed += 1;
+            continue;
+        }
+        println!("ui {} ... FAILED", filename);
+        for line in report {
+            println!("    {}", line);
+        }
+        if let Some(m) = message {
+            println!("    note: {}", m.replace('\n', "\n    "));
+        }
+        failed += 1;
+    }
+
+    let result = if failed == 0 { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed", result, passed, failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+
+/// Compiles a file, and runs it if it declares `main`, returning what happened.
+///
+/// The message of any compile error is returned alongside the outcome.  An error
+/// is returned if the emitted C cannot be compiled or run.
+fn observe(compiler: &str, filename: &str, index: usize, code: &str) -> Result<(Outcome, Option<String>), String> {
+    let failure = |d: pipeline::Diagnostic| {
+        let outcome = Outcome {
+            error: Some(d.kind),
+            stdout: Vec::new(),
+        };
+        let message = format!("{} at line {}, column {}", d.message, d.span.line, d.span.column);
+        Ok((outcome, Some(message)))
+    };
+
+    let expressions = match pipeline::check(code).and_then(|_| pipeline::parse(code)) {
+        Ok(e) => e,
+        Err(d) => return failure(d),
+    };
+    let runnable = expressions.iter()
+        .any(|e| matches!(e, Expression::FnDeclaration { identifier, .. } if identifier == "main"));
+    if !runnable {
+        return Ok((Outcome::default(), None));
+    }
+
+    let output = match pipeline::compile(code, &backend::C) {
+        Ok(o) => o,
+        Err(d) => return failure(d),
+    };
+    let binary = test_binary("ui", index);
+    if !compile_c(compiler, filename, &output, &binary) {
+        return Err(format!("could not compile the C emitted for {}", filename));
+    }
+    let run = Command::new(&binary).output();
+    let _ = remove_file(&binary);
+    match run {
+        Ok(o) => {
+            let stdout = String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect();
+            Ok((Outcome { error: None, stdout }, None))
+        },
+        Err(_) => Err(format!("could not run {}", filename)),
+    }
+}
+
+
+/// Lists the Boron files to test, in order, from the given directory or the current one.
+fn test_files(args: &Args) -> Vec<String> {
+    let root = args.filename.to_owned().unwrap_or(".".to_string());
     let mut filenames: Vec<String> = Vec::new();
-    for entry in WalkDir::new(&args.get_filename()) {
+    for entry in WalkDir::new(&root).sort_by_file_name() {
         let entry = match entry {
             Ok(e) => e,
-            Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
+            Err(_) => throw(Error::CouldNotReadFile (root)),
         };
         if entry.path().extension() == Some(OsStr::new("brn")) {
             filenames.push(entry.path().display().to_string());
         }
     }
+    filenames
+}
 
-    for filename in filenames {
-        let mut filename_args = Args::new();
-        filename_args.set_filename(filename);
-        filename_args.mark_lib();
-        compile_lib(filename_args);
+
+/// Gets a path in the temporary directory for a test binary, so that it never overwrites a program's output.
+fn test_binary(kind: &str, index: usize) -> PathBuf {
+    env::temp_dir().join(format!("boron-{}-{}-{}", kind, process::id(), index))
+}
+
+
+/// Compiles C emitted for a Boron file into a binary, returning whether it succeeded.
+fn compile_c(compiler: &str, filename: &str, code: &[u8], binary: &Path) -> bool {
+    let source = binary.with_extension("c");
+    if write(&source, code).is_err() {
+        throw(Error::CouldNotWriteFile (source.display().to_string()));
+    }
+
+    // Headers of used modules are found next to the Boron file
+    let directory = match Path::new(filename).parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let compiled = match Command::new(compiler)
+        .arg(&source)
+        .arg("-o")
+        .arg(binary)
+        .arg("-I")
+        .arg(&directory)
+        .status()
+    {
+        Ok(s) => s.success(),
+        Err(_) => throw(Error::CouldNotRunCompiler (compiler.to_string())),
+    };
+    let _ = remove_file(&source);
+    compiled
+}
+
+
+fn run(args: Args) {
+    let bytes = match read(args.get_filename()) {
+        Ok(b) => b,
+        Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
+    };
+
+    let vm = or_throw(Program::from_bytes(&bytes).and_then(Vm::new));
+    match vm.run() {
+        Ok(s) => exit(s),
+        Err(e) => abort(e),
+    }
+}
+
+
+fn interp(args: Args) {
+    let code = match read_to_string(args.get_filename()) {
+        Ok(c) => c,
+        Err(_) => throw(Error::CouldNotReadFile (args.get_filename())),
+    };
+
+    // Interpreted recursion needs a much larger stack than the main thread provides
+    let filename = args.get_filename();
+    let handle = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let mut tokenizer = Tokenizer::new(&code);
+
+            let parser = Parser::new();
+            let expressions = parser.parse_all(&mut tokenizer)?;
+
+            let mut interpreter = Interpreter::new();
+            interpreter.load_file(&filename, expressions)?;
+            interpreter.run()
+        });
+
+    // A program that fails while it runs exits with a failing status, like a compiled program would
+    let status = match handle.map(|h| h.join()) {
+        Ok(Ok(Ok(s))) => s,
+        Ok(Ok(Err(e))) => abort(e),
+        _ => abort(Error::CouldNotInterpret (args.get_filename())),
+    };
+    exit(status);
+}
+
+
+fn repl() {
+    // Interpreted recursion needs a much larger stack than the main thread provides
+    let handle = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(|| Repl::new().run());
+
+    // A panic inside the REPL stops the session with a failing status
+    match handle.map(|h| h.join()) {
+        Ok(Ok(())) => (),
+        _ => abort(Error::CouldNotInterpret ("input".to_string())),
     }
+}
+
+
+fn build(args: Args) {
+    compile_dir(&args);
+}
+
+
+fn build_std(args: Args) {
+    compile_dir(&args);
 
     match rename(args.get_filename().as_str(), ".boron-std") {
         Ok(_) => (),
@@ -0,0 +1,116 @@
+//! Finds every module a program uses, directly or through other modules.
+//!
+//! Modules are found by following the `use` statements of a program, then those
+//! of each module it uses, and so on.  Each `use` names a path relative to the
+//! directory of the file it is written in, just as the `#include` it compiles to
+//! in C does.  They are returned in dependency order, so
+//! that each module comes after every module it uses.  A module that cannot be
+//! found is reported at the `use` statement that names it, and modules that use
+//! each other are reported with the whole chain of `use` statements between them.
+
+
+use std::{
+    fs::read_to_string,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::emitter::resolve_module;
+
+use crate::error::Error;
+
+use crate::parser::{
+    Expression,
+    Parser,
+    Tokenizer,
+};
+
+
+/// Describes a module used by a program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    // Source file of the module, such as `shapes/square.brn`
+    pub filename: String,
+    pub expressions: Vec<Expression>,
+}
+
+
+/// Parses a program and finds the modules it uses, directly or indirectly, in dependency order.
+///
+/// Returns the expressions of the program itself, which is not one of the modules.
+pub fn resolve(filename: &str, source: &str) -> Result<(Vec<Expression>, Vec<Module>), Error> {
+    let mut modules = Vec::new();
+    let expressions = visit(normalize(filename), source, &mut vec![normalize(filename)], &mut modules)?;
+    Ok((expressions, modules))
+}
+
+
+/// Puts the declarations of each module before those of the program, for backends that compile a
+/// whole program at once, leaving out `use` statements and tests.
+pub fn flatten(expressions: Vec<Expression>, modules: Vec<Module>) -> Vec<Expression> {
+    modules.into_iter()
+        .flat_map(|m| m.expressions)
+        .chain(expressions)
+        .filter(|e| !matches!(e, Expression::Use (_) | Expression::Test { .. }))
+        .collect()
+}
+
+
+/// Adds the modules used by a file to a list, after the modules they use, and returns the expressions of the file.
+///
+/// `chain` holds the file being visited and each file whose `use` led to it.
+fn visit(filename: String, source: &str, chain: &mut Vec<String>, modules: &mut Vec<Module>) -> Result<Vec<Expression>, Error> {
+    let mut tokenizer = Tokenizer::new(source);
+    let expressions = Parser::new().parse_spanned(&mut tokenizer)?;
+
+    for (span, expression) in &expressions {
+        let Expression::Use (m) = expression else { continue };
+        let missing = || Error::MissingModule (m.to_owned(), filename.to_owned(), span.line, span.column);
+
+        let module = match locate(m, &filename) {
+            Ok(path) => path,
+            Err(_) => return Err(missing()),
+        };
+        if let Some(start) = chain.iter().position(|f| *f == module) {
+            let mut cycle = chain[start..].to_vec();
+            cycle.push(module);
+            return Err(Error::ImportCycle (cycle.join(" -> ")));
+        }
+        if modules.iter().any(|d: &Module| d.filename == module) {
+            continue;
+        }
+
+        let code = match read_to_string(&module) {
+            Ok(c) => c,
+            Err(_) => return Err(missing()),
+        };
+        chain.push(module.to_owned());
+        let expressions = visit(module.to_owned(), &code, chain, modules)?;
+        chain.pop();
+        modules.push(Module {
+            filename: module,
+            expressions,
+        });
+    }
+
+    Ok(expressions.into_iter().map(|(_, e)| e).collect())
+}
+
+
+/// Finds the source file of a module named by a `use` statement in the file `from`.
+pub fn locate(module: &str, from: &str) -> Result<String, Error> {
+    let path = format!("{}.brn", resolve_module(module.to_string())?);
+    // Modules of the standard library resolve to absolute paths, which `join` keeps as they are
+    let directory = Path::new(from).parent().unwrap_or(Path::new(""));
+    Ok(normalize(&directory.join(path).display().to_string()))
+}
+
+
+/// Writes a filename without `.` components, so that `./a.brn` and `a.brn` name the same file.
+pub fn normalize(filename: &str) -> String {
+    Path::new(filename)
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect::<PathBuf>()
+        .display()
+        .to_string()
+}
@@ -0,0 +1,66 @@
+//! Provides a parselet for assertions.
+
+
+use crate::parser::{
+    Parser,
+    Expression,
+    Token,
+    TokenType,
+    Tokenizer,
+    prefix::PrefixParselet,
+};
+
+use crate::error::Error;
+
+
+/// Provides a prefix parselet for assertions.
+pub struct AssertParselet;
+
+impl PrefixParselet for AssertParselet {
+    /// Parses an `assert` or `assert_eq` statement into an expression.
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let next = match tokenizer.next().transpose()? {
+            Some(n) => n,
+            None => return Err(Error::UnexpectedEof (token.get_value())),
+        };
+        if next.get_type() != TokenType::OpenParen {
+            return Err(Error::ExpectedOpenParen (next.get_value()));
+        }
+
+        let mut args: Vec<Expression> = Vec::new();
+
+        // Until we find a closing parenthesis, parse each expression
+        loop {
+            let t = match tokenizer.peek()? {
+                Some(t) => t,
+                None => return Err(Error::UnexpectedEof (token.get_value())),
+            };
+            if t.get_type() == TokenType::CloseParen {
+                tokenizer.next();
+                break;
+            }
+
+            let expr: Expression = match parser.parse(0, tokenizer)? {
+                Some(e) => e,
+                None => return Err(Error::CouldNotParse (t.get_value())),
+            };
+            args.push(expr);
+        }
+
+        // The location of the keyword is reported when the assertion fails
+        let span = token.get_span();
+        let mut args = args.into_iter();
+        match (token.as_str(), args.next(), args.next(), args.next()) {
+            ("assert", Some(condition), None, None) => Ok(Expression::Assert {
+                condition: Box::new(condition),
+                span,
+            }),
+            ("assert_eq", Some(left), Some(right), None) => Ok(Expression::AssertEq {
+                left: Box::new(left),
+                right: Box::new(right),
+                span,
+            }),
+            _ => Err(Error::WrongArgumentCount (token.get_value())),
+        }
+    }
+}
@@ -8,12 +8,10 @@ use crate::parser::{
     TokenType,
     Tokenizer,
     prefix::PrefixParselet,
+    parse_datatype,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for assignments.
@@ -21,53 +19,85 @@ pub struct AssignmentParselet;
 
 impl PrefixParselet for AssignmentParselet {
     /// Parses an assignment into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        if token.get_type() != TokenType::Let {
-            throw(Error::CouldNotParse (token.get_value()));
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        // This is an assignment, a constant, or a static variable
+        if !matches!(token.get_type(), TokenType::Let | TokenType::Const | TokenType::Static) {
+            return Err(Error::CouldNotParse (token.get_value()));
+        }
+
+        // `const name :: ...` declares a function that can be evaluated at compile time
+        if token.get_type() == TokenType::Const && is_fn_declaration(tokenizer)? {
+            return match parser.parse(0, tokenizer)? {
+                Some(Expression::FnDeclaration { identifier, arguments, return_type, body, doc, .. }) => Ok(Expression::FnDeclaration {
+                    identifier,
+                    arguments,
+                    return_type,
+                    body,
+                    doc,
+                    constant: true,
+                }),
+                _ => Err(Error::CouldNotParse (token.get_value())),
+            };
         }
-        // This is an assignment
 
         // Get the datatype keyword
-        let next = match tokenizer.next() {
+        let next = match tokenizer.next().transpose()? {
             Some(n) => n,
-            None => throw(Error::UnexpectedEof (token.get_value())),
-        };
-        let d = match next.get_type() {
-            TokenType::Type | TokenType::Identifier => next.get_value(),
-            _ => throw(Error::ExpectedDatatypeKeyword (next.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
+        let d = parse_datatype(tokenizer, next)?;
 
         // Get the identifier name
-        let next = match tokenizer.next() {
+        let next = match tokenizer.next().transpose()? {
             Some(n) => n,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
         let id = match next.get_type() {
             TokenType::Identifier => next.get_value(),
-            _ => throw(Error::ExpectedIdentifier (next.get_value())),
+            _ => return Err(Error::ExpectedIdentifier (next.get_value())),
         };
 
         // Consume the assignment token
-        let next = match tokenizer.next() {
+        let next = match tokenizer.next().transpose()? {
             Some(n) => n,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
         let _ = match next.get_type() {
             TokenType::Assignment => next.get_value(),
-            _ => throw(Error::ExpectedAssignment (next.get_value())),
+            _ => return Err(Error::ExpectedAssignment (next.get_value())),
         };
 
         // Evaluate the right hand side of the assignment
-        let right_hand_side: Expression = match parser.parse(next.get_type().into(), tokenizer) {
+        let right_hand_side: Expression = match parser.parse(next.get_type().into(), tokenizer)? {
             Some(r) => r,
-            None => throw(Error::CouldNotParse (id)),
+            None => return Err(Error::CouldNotParse (id)),
         };
 
         // Place the right hand side into an instance of `Expression`
-        Expression::Assignment {
-            datatype: d,
-            identifier: id,
-            value: Box::new(right_hand_side),
-        }
+        Ok(match token.get_type() {
+            TokenType::Const => Expression::Const {
+                datatype: d,
+                identifier: id,
+                value: Box::new(right_hand_side),
+            },
+            TokenType::Static => Expression::Static {
+                datatype: d,
+                identifier: id,
+                value: Box::new(right_hand_side),
+            },
+            _ => Expression::Assignment {
+                datatype: d,
+                identifier: id,
+                value: Box::new(right_hand_side),
+            },
+        })
     }
-}
\ No newline at end of file
+}
+
+/// Checks if the upcoming tokens begin a function declaration (`name ::` or `name ->`).
+fn is_fn_declaration(tokenizer: &mut Tokenizer) -> Result<bool, Error> {
+    let name = tokenizer.look_ahead(0)?.map(|t| t.get_type());
+    let next = tokenizer.look_ahead(1)?.map(|t| t.get_type());
+    Ok(name == Some(TokenType::Identifier)
+        && matches!(next, Some(TokenType::FnDeclaration | TokenType::FnReturnType)))
+}
@@ -10,10 +10,7 @@ use crate::parser::{
     infix::InfixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for binary operations.
@@ -21,10 +18,10 @@ pub struct BinOpParselet;
 
 impl InfixParselet for BinOpParselet {
     /// Parses a binary operation into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Expression {
-        let right = match parser.parse(token.get_type().into(), tokenizer) {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Result<Expression, Error> {
+        let right = match parser.parse(token.get_type().into(), tokenizer)? {
             Some(r) => r,
-            None => throw(Error::CouldNotParse (token.get_value())),
+            None => return Err(Error::CouldNotParse (token.get_value())),
         };
 
         match token.get_type() {
@@ -40,13 +37,14 @@ impl InfixParselet for BinOpParselet {
             => {
                 // No problem!
             },
-            _ => throw(Error::InvalidOperator (token.get_value())), // Error: invalid binary operation
+            _ => return Err(Error::InvalidOperator (token.get_value())), // Error: invalid binary operation
         };
 
-        Expression::BinOp {
+        Ok(Expression::BinOp {
             left: Box::new(left),
             op: token.get_type(),
             right: Box::new(right),
-        }
+            span: token.get_span(),
+        })
     }
 }
\ No newline at end of file
@@ -8,12 +8,10 @@ use crate::parser::{
     TokenType,
     Tokenizer,
     prefix::PrefixParselet,
+    parse_datatype,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for datatype keywords.
@@ -21,26 +19,31 @@ pub struct DatatypeParselet;
 
 impl PrefixParselet for DatatypeParselet {
     /// Parses a datatype keyword into an expression.
-    fn parse(&self, _parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        if token.get_type() == TokenType::Type {
-            // Wait to discard the token... maybe it's important
-            let identifier = match tokenizer.peek() {
-                Some(i) => i,
-                None => throw(Error::UnexpectedEof (token.get_value())),
-            };
-
-            if identifier.get_type() == TokenType::Identifier {
-                // Discard the token from the stream
-                tokenizer.next();
-                Expression::Declaration {
-                    identifier: identifier.get_value(),
-                    datatype: token.get_value()
+    fn parse(&self, _parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        match token.get_type() {
+            TokenType::Type
+            | TokenType::Own
+            | TokenType::Opt => {
+                let datatype = parse_datatype(tokenizer, token)?;
+
+                // Wait to discard the token... maybe it's important
+                let identifier = match tokenizer.peek()? {
+                    Some(i) => i,
+                    None => return Err(Error::UnexpectedEof (datatype)),
+                };
+
+                if identifier.get_type() == TokenType::Identifier {
+                    // Discard the token from the stream
+                    tokenizer.next();
+                    Ok(Expression::Declaration {
+                        identifier: identifier.get_value(),
+                        datatype,
+                    })
+                } else {
+                    Err(Error::ExpectedIdentifier (datatype))
                 }
-            } else {
-                throw(Error::ExpectedIdentifier (token.get_value()));
-            }
-        } else {
-            throw(Error::ExpectedDatatypeKeyword (token.get_value()));
+            },
+            _ => Err(Error::ExpectedDatatypeKeyword (token.get_value())),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,28 @@
+//! Provides a parselet for defer statements.
+
+
+use crate::parser::{
+    Parser,
+    Expression,
+    Token,
+    Tokenizer,
+    prefix::PrefixParselet,
+};
+
+use crate::error::Error;
+
+
+/// Provides a prefix parselet for defer statements.
+pub struct DeferParselet;
+
+impl PrefixParselet for DeferParselet {
+    /// Parses a defer statement into an expression.
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let expr = match parser.parse(token.get_type().into(), tokenizer)? {
+            Some(d) => d,
+            None => return Err(Error::UnexpectedEof (token.get_value())),
+        };
+
+        Ok(Expression::Defer (Box::new(expr)))
+    }
+}
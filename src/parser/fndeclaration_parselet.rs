@@ -3,16 +3,15 @@
 use crate::parser::{
     Parser,
     Expression,
+    Passing,
     Token,
     TokenType,
     Tokenizer,
     infix::InfixParselet,
+    parse_datatype,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for function declarations.
@@ -20,111 +19,108 @@ pub struct FnDeclarationParselet;
 
 impl InfixParselet for FnDeclarationParselet {
     /// Parses a function declaration into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Expression {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Result<Expression, Error> {
         // Get the function name
         let name = match left {
             Expression::Identifier (s) => s,
-            _ => throw(Error::ExpectedIdentifier ("before function declaration".to_string())),
+            _ => return Err(Error::ExpectedIdentifier ("before function declaration".to_string())),
         };
 
-        let mut args: Vec<(String, String)> = Vec::new();
+        let mut args: Vec<(String, String, Passing)> = Vec::new();
 
         // If this function has any arguments, `token.get_type() == TokenType::FnDeclaration`.
         if token.get_type() != TokenType::FnReturnType {
             // Parse each argument to the function
-            while let Some(t) = tokenizer.peek() {
+            while let Some(t) = tokenizer.peek()? {
                 if t.get_type() == TokenType::FnReturnType
                 || t.get_type() == TokenType::OpenBrace
                 {
                     break;
                 }
 
-                // Parse each type and variable name
+                // Parse each passing mode, type and variable name
+
+                // Parse the passing mode
+                let by_ref = t.get_type() == TokenType::Ref;
+                if by_ref {
+                    tokenizer.next();
+                }
 
                 // Parse the type
-                // It's ok to use `unwrap` here because we know that there's at least one more
-                // token left in the tokenizer
-                let option_argtype = tokenizer.next().unwrap();
-                let argtype = match option_argtype.get_type() {
-                    TokenType::Type
-                    | TokenType::Identifier => option_argtype.get_value(),
-                    _ => throw(Error::ExpectedIdentifier (option_argtype.get_value())),
+                let option_argtype = match tokenizer.next().transpose()? {
+                    Some(t) => t,
+                    None => return Err(Error::UnexpectedEof (t.get_value())),
+                };
+                // Pointers are passed by value, transferring ownership to the callee
+                let (argtype, passing) = match option_argtype.get_type() {
+                    TokenType::Type => (option_argtype.get_value(), Passing::Value),
+                    TokenType::Identifier => (option_argtype.get_value(), Passing::ConstReference),
+                    TokenType::Own
+                    | TokenType::Opt => (parse_datatype(tokenizer, option_argtype)?, Passing::Value),
+                    _ => return Err(Error::ExpectedIdentifier (option_argtype.get_value())),
+                };
+                let passing = if by_ref {
+                    Passing::Reference
+                } else {
+                    passing
                 };
 
                 // Parse the variable name
-                let option_arg = match tokenizer.next() {
+                let option_arg = match tokenizer.next().transpose()? {
                     Some(t) => t,
-                    None => throw(Error::UnexpectedEof (argtype)),
+                    None => return Err(Error::UnexpectedEof (argtype)),
                 };
                 let arg = match option_arg.get_type() {
                     TokenType::Identifier => option_arg.get_value(),
-                    _ => throw(Error::ExpectedIdentifier (option_arg.get_value())),
+                    _ => return Err(Error::ExpectedIdentifier (option_arg.get_value())),
                 };
 
-                args.push((arg, argtype));
+                args.push((arg, argtype, passing));
             }
         }
 
         // Parse the return type
-        let peek = match tokenizer.peek() {
-            Some(n) => n,
-            None => throw(Error::UnexpectedEof (token.get_value())),
-        };
-        let return_type = match peek.get_type() {
-            TokenType::FnReturnType => {
-                // Consume the -> token
-                // It's ok to use unwrap here because we know there's at least
-                // one more token in the stream
-                tokenizer.next().unwrap();
-
-                let next = match tokenizer.next() {
-                    Some(n) => n,
-                    None => throw(Error::UnexpectedEof (token.get_value())),
-                };
-                let value = match next.get_type() {
-                    TokenType::Type
-                    | TokenType::Identifier => {
-                        tokenizer.next();
-                        next.get_value()
-                    },
-                    _ => throw(Error::ExpectedDatatypeKeyword (peek.get_value())),
-                };
-                value.to_owned()
-            },
-            TokenType::OpenBrace => {
+        // If the function takes no arguments, the `->` token has already been consumed
+        let has_return_type = match tokenizer.peek()? {
+            Some(t) if t.get_type() == TokenType::FnReturnType => {
                 tokenizer.next();
-                "nul".to_string()
+                true
             },
-            TokenType::Type => {
-                tokenizer.next();
-                // Consume the open brace as well
-                tokenizer.next();
-                peek.get_value()
-            },
-            _ => throw(Error::ExpectedReturnType (peek.get_value())),
+            _ => token.get_type() == TokenType::FnReturnType,
         };
-        
-        // Parse the function body
-        let mut body: Vec<Expression> = Vec::new();
-        // Until we find a closing curly brace, parse each expression in the loop
-        while let Some(t) = tokenizer.peek() {
-            if t.get_type() == TokenType::CloseBrace {
-                tokenizer.next();
-                break;
+        let return_type = if has_return_type {
+            let next = match tokenizer.next().transpose()? {
+                Some(n) => n,
+                None => return Err(Error::UnexpectedEof (token.get_value())),
+            };
+            match next.get_type() {
+                TokenType::Type
+                | TokenType::Identifier
+                | TokenType::Own
+                | TokenType::Opt => parse_datatype(tokenizer, next)?,
+                _ => return Err(Error::ExpectedReturnType (next.get_value())),
             }
+        } else {
+            "nul".to_string()
+        };
 
-            let expr: Expression = match parser.parse(t.get_type().into(), tokenizer) {
-                Some(e) => e,
-                None => throw(Error::CouldNotParse (t.get_value())),
-            };
-            body.push(expr);
-        }
+        // Consume the opening brace
+        match tokenizer.next().transpose()? {
+            Some(t) if t.get_type() == TokenType::OpenBrace => (),
+            Some(t) => return Err(Error::ExpectedOpenBrace (t.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
+        };
 
-        Expression::FnDeclaration {
+        // Parse the function body
+        let body = parser.parse_block(tokenizer)?;
+
+        Ok(Expression::FnDeclaration {
             identifier: name,
             arguments: args,
             return_type,
             body,
-        }
+            doc: None,
+            constant: false,
+        })
     }
 }
\ No newline at end of file
@@ -9,10 +9,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for identifiers.
@@ -20,10 +17,10 @@ pub struct IdentifierParselet;
 
 impl PrefixParselet for IdentifierParselet {
     /// Parses an identifier into an expression.
-    fn parse(&self, _parser: &Parser, _tokenizer: &mut Tokenizer, token: Token) -> Expression {
+    fn parse(&self, _parser: &Parser, _tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
         if !token.check(TokenType::Identifier) {
-            throw(Error::ExpectedIdentifier (token.get_value()));
+            return Err(Error::ExpectedIdentifier (token.get_value()));
         }
-        Expression::Identifier (token.get_value())
+        Ok(Expression::Identifier (token.get_value()))
     }
 }
\ No newline at end of file
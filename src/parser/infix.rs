@@ -8,8 +8,10 @@ use crate::parser::{
     Tokenizer,
 };
 
+use crate::error::Error;
+
 
 /// Defines shared behavior for infix parselets.
 pub trait InfixParselet {
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Expression;
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Result<Expression, Error>;
 }
\ No newline at end of file
@@ -10,10 +10,9 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::tokenizer::unescape_char;
+
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for literals.
@@ -21,8 +20,8 @@ pub struct LiteralParselet;
 
 impl PrefixParselet for LiteralParselet {
     /// Parses a literal into an expression.
-    fn parse(&self, _parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        match token.get_type() {
+    fn parse(&self, _parser: &Parser, _tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        Ok(match token.get_type() {
             TokenType::Int => {
                 // It's ok to use `unwrap` here because our tokenizer checked that this could
                 // be parsed into an `i32`.
@@ -39,34 +38,19 @@ impl PrefixParselet for LiteralParselet {
                 let bln: bool = match token.get_value().as_str() {
                     "true" => true,
                     "false" => false,
-                    _ => throw(Error::ExpectedBoolean (token.get_value())),
+                    _ => return Err(Error::ExpectedBoolean (token.get_value())),
                 };
                 Expression::Bool (bln)
             },
-            TokenType::SingleQuote => {
-                let next = match tokenizer.next() {
-                    Some(n) => n,
-                    None => throw(Error::UnexpectedEof (token.get_value())),
-                };
-                if next.get_value().as_str().len() != 1 {
-                    throw(Error::ExpectedLiteral (next.get_value()));
-                }
-
-                // It's ok to use `unwrap` here because we just checked that there is exactly
-                // one character in the string.
-                let chr: char = next.get_value().chars().nth(0).unwrap();
-                
-                let next = match tokenizer.peek() {
-                    Some(n) => n,
-                    None => throw(Error::UnexpectedEof (token.get_value())),
-                };
-                match next.get_type() {
-                    TokenType::SingleQuote => tokenizer.next(),
-                    _ => throw(Error::ExpectedSingleQuote (token.get_value())),
+            TokenType::Null => Expression::Null,
+            TokenType::Char => {
+                let chr: char = match unescape_char(&token.get_value()) {
+                    Some(c) => c,
+                    None => return Err(Error::ExpectedLiteral (token.get_value())),
                 };
                 Expression::Char (chr)
             },
-            _ => throw(Error::ExpectedLiteral (token.get_value())),
-        }
+            _ => return Err(Error::ExpectedLiteral (token.get_value())),
+        })
     }
 }
\ No newline at end of file
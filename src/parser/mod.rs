@@ -20,10 +20,22 @@ pub mod struct_parselet;
 pub mod structinit_parselet;
 pub mod fndeclaration_parselet;
 pub mod return_parselet;
+pub mod defer_parselet;
 pub mod use_parselet;
+pub mod new_parselet;
+pub mod test_parselet;
+pub mod assert_parselet;
 
 
-use std::collections::HashMap;
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+};
+
+use serde::{
+    Serialize,
+    Serializer,
+};
 
 use infix::InfixParselet;
 use prefix::PrefixParselet;
@@ -44,17 +56,45 @@ use struct_parselet::StructParselet;
 use structinit_parselet::StructInitParselet;
 use fndeclaration_parselet::FnDeclarationParselet;
 use return_parselet::ReturnParselet;
+use defer_parselet::DeferParselet;
 use use_parselet::UseParselet;
+use new_parselet::NewParselet;
+use test_parselet::TestParselet;
+use assert_parselet::AssertParselet;
 
 pub use crate::tokenizer::{
+    Span,
     Token,
     TokenType,
     Tokenizer,
 };
 
+use crate::error::Error;
+
+
+/// The deepest nesting of expressions that will be parsed.
+///
+/// Later stages walk the syntax tree recursively, so this bounds the stack they use.
+pub const MAX_DEPTH: usize = 256;
+
+
+/// Enumerates the ways in which a function argument can be passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Passing {
+    // Passed by value (primitive types such as `int x`)
+    Value,
+    // Passed by constant reference (struct types such as `Point p`)
+    ConstReference,
+    // Passed by mutable reference (any type marked `ref`, such as `ref int x`)
+    Reference,
+}
+
 
 /// Defines possible expressions in Boron.
-#[derive(Clone, Debug)]
+///
+/// Expressions serialize in serde's externally tagged form, such as `{"Int": 1}`,
+/// with the fields of maps in order and arguments as `name`, `datatype` and `passing`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Expression {
     // 32-bit integer
     Int (i32),
@@ -64,6 +104,8 @@ pub enum Expression {
     Bool (bool),
     // Character
     Char (char),
+    // Null pointer
+    Null,
     // Variable or function name
     Identifier (String),
     // Datatype keyword
@@ -72,12 +114,16 @@ pub enum Expression {
     UnaryOp {
         op: TokenType,
         expr: Box<Expression>,
+        // Location of the operator
+        span: Span,
     },
     // Binary operation
     BinOp {
         left: Box<Expression>,
         op: TokenType,
         right: Box<Expression>,
+        // Location of the operator
+        span: Span,
     },
     // Variable declaration
     Declaration {
@@ -87,10 +133,22 @@ pub enum Expression {
     // Struct declaration
     Struct {
         identifier: String,
+        #[serde(serialize_with = "ordered")]
         variables: HashMap<String, String>,
+        // Doc comments attached to the struct and to each of its fields
+        doc: Option<String>,
+        #[serde(serialize_with = "ordered")]
+        docs: HashMap<String, String>,
     },
     // Struct initialization
     StructInit {
+        #[serde(serialize_with = "ordered")]
+        variables: HashMap<String, Expression>,
+    },
+    // Heap-allocated struct initialization
+    New {
+        datatype: String,
+        #[serde(serialize_with = "ordered")]
         variables: HashMap<String, Expression>,
     },
     // Variable assignment
@@ -99,6 +157,18 @@ pub enum Expression {
         identifier: String,
         value: Box<Expression>,
     },
+    // Compile-time constant declaration
+    Const {
+        datatype: String,
+        identifier: String,
+        value: Box<Expression>,
+    },
+    // Static variable declaration
+    Static {
+        datatype: String,
+        identifier: String,
+        value: Box<Expression>,
+    },
     // Variable reassignment
     Reassignment {
         identifier: String,
@@ -134,14 +204,114 @@ pub enum Expression {
     // Function declaration
     FnDeclaration {
         identifier: String,
-        arguments: Vec<(String, String)>,
+        #[serde(serialize_with = "named_arguments")]
+        arguments: Vec<(String, String, Passing)>,
         return_type: String,
         body: Vec<Expression>,
+        // Doc comment attached to the function
+        doc: Option<String>,
+        // Whether the function may be evaluated at compile time
+        constant: bool,
     },
     // Return statement
     Return (Box<Expression>),
+    // Defer statement
+    Defer (Box<Expression>),
     // Use statement
     Use (String),
+    // Unit test, which is only compiled by `boron test`
+    Test {
+        name: String,
+        body: Vec<Expression>,
+    },
+    // Assertion that a condition holds
+    Assert {
+        condition: Box<Expression>,
+        // Location of the `assert` keyword
+        span: Span,
+    },
+    // Assertion that two values are equal
+    AssertEq {
+        left: Box<Expression>,
+        right: Box<Expression>,
+        // Location of the `assert_eq` keyword
+        span: Span,
+    },
+}
+
+
+/// Provides functions for the `Expression` enum.
+impl Expression {
+    /// Attaches a doc comment to a struct or function declaration.
+    /// Doc comments preceding any other expression are discarded.
+    pub fn with_doc(self, doc: String) -> Self {
+        match self {
+            Expression::Struct { identifier, variables, doc: _, docs } => Expression::Struct {
+                identifier,
+                variables,
+                doc: Some(doc),
+                docs,
+            },
+            Expression::FnDeclaration { identifier, arguments, return_type, body, doc: _, constant } => Expression::FnDeclaration {
+                identifier,
+                arguments,
+                return_type,
+                body,
+                doc: Some(doc),
+                constant,
+            },
+            _ => self,
+        }
+    }
+}
+
+
+/// Serializes a map with its keys in order, so that the output does not vary between runs.
+fn ordered<S: Serializer, V: Serialize>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+
+/// Describes a function argument for serialization.
+#[derive(Serialize)]
+struct Argument<'a> {
+    name: &'a str,
+    datatype: &'a str,
+    passing: Passing,
+}
+
+
+/// Serializes the arguments of a function declaration as objects with named fields.
+fn named_arguments<S: Serializer>(arguments: &[(String, String, Passing)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(arguments.iter().map(|(name, datatype, passing)| Argument {
+        name,
+        datatype,
+        passing: *passing,
+    }))
+}
+
+
+/// Parses a datatype beginning with the given token.
+///
+/// Pointer types are written with a leading `own` (an owning pointer) or `opt`
+/// (a nullable owning pointer) and are returned as, for example, `"own Point"`.
+pub fn parse_datatype(tokenizer: &mut Tokenizer, token: Token) -> Result<String, Error> {
+    match token.get_type() {
+        TokenType::Type
+        | TokenType::Identifier => Ok(token.get_value()),
+        TokenType::Own
+        | TokenType::Opt => {
+            let next = match tokenizer.next().transpose()? {
+                Some(n) => n,
+                None => return Err(Error::UnexpectedEof (token.get_value())),
+            };
+            match next.get_type() {
+                TokenType::Identifier => Ok(format!("{} {}", token.get_value(), next.get_value())),
+                _ => Err(Error::ExpectedStruct (next.get_value())),
+            }
+        },
+        _ => Err(Error::ExpectedDatatypeKeyword (token.get_value())),
+    }
 }
 
 
@@ -175,6 +345,14 @@ impl From<TokenType> for u8 {
 pub struct Parser {
     prefix_parselets: HashMap<TokenType, Box<dyn PrefixParselet>>,
     infix_parselets: HashMap<TokenType, Box<dyn InfixParselet>>,
+    // Nesting depth of the expression being parsed
+    depth: Cell<usize>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Parser {
@@ -185,21 +363,30 @@ impl Parser {
 
         // Declarative grammar begins here.
         prefix_parselets.insert(TokenType::Type, Box::new(DatatypeParselet {}));
+        prefix_parselets.insert(TokenType::Own, Box::new(DatatypeParselet {}));
+        prefix_parselets.insert(TokenType::Opt, Box::new(DatatypeParselet {}));
         prefix_parselets.insert(TokenType::Identifier, Box::new(IdentifierParselet {}));
         prefix_parselets.insert(TokenType::While, Box::new(WhileParselet {}));
         prefix_parselets.insert(TokenType::If, Box::new(IfElseParselet {}));
         prefix_parselets.insert(TokenType::Int, Box::new(LiteralParselet {}));
         prefix_parselets.insert(TokenType::Float, Box::new(LiteralParselet {}));
         prefix_parselets.insert(TokenType::Bool, Box::new(LiteralParselet {}));
-        prefix_parselets.insert(TokenType::SingleQuote, Box::new(LiteralParselet {}));
+        prefix_parselets.insert(TokenType::Null, Box::new(LiteralParselet {}));
+        prefix_parselets.insert(TokenType::Char, Box::new(LiteralParselet {}));
         prefix_parselets.insert(TokenType::OpenParen, Box::new(ParenParselet {}));
         prefix_parselets.insert(TokenType::Minus, Box::new(UnaryOpParselet {}));
         prefix_parselets.insert(TokenType::Not, Box::new(UnaryOpParselet {}));
         prefix_parselets.insert(TokenType::Struct, Box::new(StructParselet {}));
         prefix_parselets.insert(TokenType::Return, Box::new(ReturnParselet {}));
+        prefix_parselets.insert(TokenType::Defer, Box::new(DeferParselet {}));
         prefix_parselets.insert(TokenType::Use, Box::new(UseParselet {}));
         prefix_parselets.insert(TokenType::Let, Box::new(AssignmentParselet {}));
+        prefix_parselets.insert(TokenType::Const, Box::new(AssignmentParselet {}));
+        prefix_parselets.insert(TokenType::Static, Box::new(AssignmentParselet {}));
         prefix_parselets.insert(TokenType::OpenBrace, Box::new(StructInitParselet {}));
+        prefix_parselets.insert(TokenType::New, Box::new(NewParselet {}));
+        prefix_parselets.insert(TokenType::Test, Box::new(TestParselet {}));
+        prefix_parselets.insert(TokenType::Assert, Box::new(AssertParselet {}));
         infix_parselets.insert(TokenType::Assignment, Box::new(ReassignmentParselet {}));
         infix_parselets.insert(TokenType::OpenParen, Box::new(OpenParenParselet {}));
         infix_parselets.insert(TokenType::Plus, Box::new(BinOpParselet {}));
@@ -218,64 +405,142 @@ impl Parser {
         Self {
             prefix_parselets,
             infix_parselets,
+            depth: Cell::new(0),
         }
     }
 
     /// Gets the precedence of the given token.
-    fn get_precedence(&self, tokenizer: &mut Tokenizer) -> u8 {
-        let token = match tokenizer.peek() {
+    fn get_precedence(&self, tokenizer: &mut Tokenizer) -> Result<u8, Error> {
+        let token = match tokenizer.peek()? {
             Some(t) => t,
-            None => return 0,
+            None => return Ok(0),
         };
 
-        token.get_type().into()
+        Ok(token.get_type().into())
     }
 
     /// Parses the token stream and returns an expression, if possible.
-    pub fn parse(&self, precedence: u8, tokenizer: &mut Tokenizer) -> Option<Expression> {
+    pub fn parse(&self, precedence: u8, tokenizer: &mut Tokenizer) -> Result<Option<Expression>, Error> {
         // Get the next token from the token stream.
-        let token = match tokenizer.next() {
+        let token = match tokenizer.next().transpose()? {
             Some(t) => t,
-            None => return None,
+            None => return Ok(None),
         };
+        let doc = token.get_doc();
+
+        let depth = self.depth.get();
+        self.deepen(&token)?;
 
         // Get the proper prefix parselet from the type of the given token.
-        let parselet: &Box<dyn PrefixParselet> = match self.prefix_parselets.get(&token.get_type()) {
-            Some(p) => p,
-            None => return None,
+        // A token that cannot begin an expression must not silently end the program.
+        let parselet: &dyn PrefixParselet = match self.prefix_parselets.get(&token.get_type()) {
+            Some(p) => p.as_ref(),
+            None => {
+                let span = token.get_span();
+                return Err(Error::UnexpectedToken (token.get_value(), span.line, span.column));
+            },
         };
 
-        let mut left: Expression = parselet.parse(self, tokenizer, token);
+        let left: Expression = parselet.parse(self, tokenizer, token)?;
+        let mut left = self.parse_infix(precedence, tokenizer, left)?;
+        self.depth.set(depth);
 
-        while precedence < self.get_precedence(tokenizer) {
-            let token = match tokenizer.peek() {
+        // Attach any doc comment preceding this expression
+        if let Some(d) = doc {
+            left = left.with_doc(d);
+        }
+
+        Ok(Some(left))
+    }
+
+    /// Applies each operator that binds more tightly than the given precedence to an expression.
+    fn parse_infix(&self, precedence: u8, tokenizer: &mut Tokenizer, mut left: Expression) -> Result<Expression, Error> {
+        while precedence < self.get_precedence(tokenizer)? {
+            let token = match tokenizer.peek()? {
                 Some(t) => t,
                 None => break,
             };
-    
+
             // Get the proper infix parselet from the type of the given token,
             // or return the current expression.
-            let parselet: &Box<dyn InfixParselet> = match self.infix_parselets.get(&token.get_type()) {
-                Some(p) => p,
+            let parselet: &dyn InfixParselet = match self.infix_parselets.get(&token.get_type()) {
+                Some(p) => p.as_ref(),
                 None => break,
             };
-    
+
             tokenizer.next();
-    
-            left = parselet.parse(self, tokenizer, left, token);
+
+            // Each operator applied to `left` nests it one level deeper
+            self.deepen(&token)?;
+            left = parselet.parse(self, tokenizer, left, token)?;
         }
-        
-        Some(left)
+
+        Ok(left)
+    }
+
+    /// Enters one more level of nesting, returning an error at the given token if it is too deep.
+    fn deepen(&self, token: &Token) -> Result<(), Error> {
+        let depth = self.depth.get() + 1;
+        if depth > MAX_DEPTH {
+            let span = token.get_span();
+            return Err(Error::NestingTooDeep (span.line, span.column));
+        }
+        self.depth.set(depth);
+        Ok(())
+    }
+
+    /// Parses the statements of a block, up to and including its closing brace.
+    ///
+    /// The opening brace must already have been consumed.
+    pub fn parse_block(&self, tokenizer: &mut Tokenizer) -> Result<Vec<Expression>, Error> {
+        let mut body: Vec<Expression> = Vec::new();
+
+        // Until we find a closing curly brace, parse each expression in the block
+        while let Some(t) = tokenizer.peek()? {
+            if t.get_type() == TokenType::CloseBrace {
+                tokenizer.next();
+                break;
+            }
+
+            match self.parse(t.get_type().into(), tokenizer)? {
+                Some(e) => body.push(e),
+                None => return Err(Error::CouldNotParse (t.get_value())),
+            }
+        }
+
+        Ok(body)
     }
 
     /// Parses the program into a list of expressions.
-    pub fn parse_all(&self, tokenizer: &mut Tokenizer) -> Vec<Expression> {
+    pub fn parse_all(&self, tokenizer: &mut Tokenizer) -> Result<Vec<Expression>, Error> {
+        // A previous parse may have been abandoned partway through by an error
+        self.depth.set(0);
         let mut expressions = Vec::new();
 
-        while let Some(e) = self.parse(0, tokenizer) {
+        while let Some(e) = self.parse(0, tokenizer)? {
             expressions.push(e);
         }
 
-        expressions
+        Ok(expressions)
+    }
+
+    /// Parses the program into a list of expressions, each with the span of its source text.
+    pub fn parse_spanned(&self, tokenizer: &mut Tokenizer) -> Result<Vec<(Span, Expression)>, Error> {
+        self.depth.set(0);
+        let mut expressions = Vec::new();
+
+        while let Some(first) = tokenizer.peek()? {
+            let e = match self.parse(0, tokenizer)? {
+                Some(e) => e,
+                None => break,
+            };
+            let span = match tokenizer.consumed_span() {
+                Some(last) => first.get_span().to(last),
+                None => first.get_span(),
+            };
+            expressions.push((span, e));
+        }
+
+        Ok(expressions)
     }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+//! Provides a parselet for heap allocations.
+
+
+use crate::parser::{
+    Parser,
+    Expression,
+    Token,
+    TokenType,
+    Tokenizer,
+    prefix::PrefixParselet,
+    structinit_parselet::StructInitParselet,
+};
+
+use crate::error::Error;
+
+
+/// Provides a prefix parselet for heap allocations.
+pub struct NewParselet;
+
+impl PrefixParselet for NewParselet {
+    /// Parses a heap allocation (`new Point { x 1 y 2 }`) into an expression.
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        if token.get_type() != TokenType::New {
+            return Err(Error::CouldNotParse (token.get_value()));
+        }
+
+        // Get the name of the struct being allocated
+        let next = match tokenizer.next().transpose()? {
+            Some(n) => n,
+            None => return Err(Error::UnexpectedEof (token.get_value())),
+        };
+        let datatype = match next.get_type() {
+            TokenType::Identifier => next.get_value(),
+            _ => return Err(Error::ExpectedStruct (next.get_value())),
+        };
+
+        // Parse the struct initialization
+        let brace = match tokenizer.next().transpose()? {
+            Some(b) => b,
+            None => return Err(Error::UnexpectedEof (datatype)),
+        };
+        let variables = match StructInitParselet.parse(parser, tokenizer, brace)? {
+            Expression::StructInit { variables } => variables,
+            _ => return Err(Error::CouldNotParse (datatype)),
+        };
+
+        Ok(Expression::New {
+            datatype,
+            variables,
+        })
+    }
+}
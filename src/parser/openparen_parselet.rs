@@ -10,10 +10,7 @@ use crate::parser::{
     infix::InfixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for function calls.
@@ -21,7 +18,7 @@ pub struct OpenParenParselet;
 
 impl InfixParselet for OpenParenParselet {
     /// Parses a function call into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Expression {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Result<Expression, Error> {
         if let Expression::Identifier (i) = left {
             // `i` is the name of the function being called
             let fn_name: String = i;
@@ -29,27 +26,28 @@ impl InfixParselet for OpenParenParselet {
             let mut args: Vec<Expression> = Vec::new();
 
             // Until we find a closing parenthesis, parse each expression
-            while let Some(t) = tokenizer.peek() {
+            while let Some(t) = tokenizer.peek()? {
                 if t.get_type() == TokenType::CloseParen {
                     tokenizer.next();
                     break;
                 }
 
-                let expr: Expression = match parser.parse(token.get_type().into(), tokenizer) {
+                // Each argument is a whole expression, such as `n - 1`
+                let expr: Expression = match parser.parse(0, tokenizer)? {
                     Some(e) => e,
                     None => {
-                        throw(Error::CouldNotParse (token.get_value()));
+                        return Err(Error::CouldNotParse (token.get_value()));
                     },
                 };
                 args.push(expr);
             }
 
-            Expression::FnCall {
+            Ok(Expression::FnCall {
                 name: fn_name,
                 args,
-            }
+            })
         } else {
-            throw(Error::ExpectedIdentifier (token.get_value()));
+            Err(Error::ExpectedIdentifier (token.get_value()))
         }
     }
 }
\ No newline at end of file
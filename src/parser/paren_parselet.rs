@@ -10,10 +10,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for parenthetical expressions.
@@ -21,32 +18,31 @@ pub struct ParenParselet;
 
 impl PrefixParselet for ParenParselet {
     /// Parses a parenthetical into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
         if token.check(TokenType::OpenParen) {
-            let expr = match parser.parse(token.get_type().into(), tokenizer) {
+            // Parentheses group a whole expression, so parse it at the lowest precedence
+            let expr = match parser.parse(0, tokenizer)? {
                 Some(e) => e,
-                None => throw(Error::CouldNotParse (token.get_value())),
+                None => return Err(Error::CouldNotParse (token.get_value())),
             };
                         
-            let next = tokenizer.peek();
+            let next = tokenizer.peek()?;
 
             match next {
                 Some(t) => {
                     if t.get_type() == TokenType::CloseParen {
                         // Consume the token
-                        // It's ok to use `unwrap` here because we just checked that
-                        // the tokenizer has at least one more token to yield
-                        tokenizer.next().unwrap();
+                        tokenizer.next();
                     } else {
-                        throw(Error::ExpectedCloseParen (t.get_value()));
+                        return Err(Error::ExpectedCloseParen (t.get_value()));
                     }
                 },
-                None => throw(Error::UnexpectedEof (token.get_value())),
+                None => return Err(Error::UnexpectedEof (token.get_value())),
             }
 
-            expr
+            Ok(expr)
         } else {
-            throw(Error::ExpectedOpenParen (token.get_value()));
+            Err(Error::ExpectedOpenParen (token.get_value()))
         }
     }
 }
\ No newline at end of file
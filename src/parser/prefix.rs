@@ -8,8 +8,10 @@ use crate::parser::{
     Tokenizer,
 };
 
+use crate::error::Error;
+
 
 /// Defines shared behavior for prefix parselets.
 pub trait PrefixParselet {
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression;
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error>;
 }
\ No newline at end of file
@@ -9,10 +9,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for return statements.
@@ -20,12 +17,12 @@ pub struct ReturnParselet;
 
 impl PrefixParselet for ReturnParselet {
     /// Parses a return statement into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        let expr = match parser.parse(token.get_type().into(), tokenizer) {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let expr = match parser.parse(token.get_type().into(), tokenizer)? {
             Some(r) => r,
-            None => throw(Error::CouldNotParse (token.get_value())),
+            None => return Err(Error::CouldNotParse (token.get_value())),
         };
 
-        Expression::Return (Box::new(expr))
+        Ok(Expression::Return (Box::new(expr)))
     }
 }
\ No newline at end of file
@@ -12,10 +12,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for structure definitions.
@@ -23,57 +20,66 @@ pub struct StructParselet;
 
 impl PrefixParselet for StructParselet {
     /// Parses a structure definition into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
         if token.get_type() != TokenType::Struct {
-            throw(Error::CouldNotParse (token.get_value()));
+            return Err(Error::CouldNotParse (token.get_value()));
         }
 
-        let name = match tokenizer.peek() {
+        let name = match tokenizer.peek()? {
             Some(t) => t,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
         match name.get_type() {
             TokenType::Identifier => tokenizer.next(),
-            _ => throw(Error::ExpectedIdentifier (name.get_value())),
+            _ => return Err(Error::ExpectedIdentifier (name.get_value())),
         };
 
-        let next = match tokenizer.peek() {
+        let next = match tokenizer.peek()? {
             Some(t) => t,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
         match next.get_type() {
             // This is a struct declaration.
             TokenType::OpenBrace => {
                 tokenizer.next();
                 let mut body: HashMap<String, String> = HashMap::new();
+                let mut docs: HashMap<String, String> = HashMap::new();
 
                 // Until we find a closing curly brace, parse each variable
-                while let Some(t) = tokenizer.peek() {
+                while let Some(t) = tokenizer.peek()? {
                     if t.get_type() == TokenType::CloseBrace {
                         tokenizer.next();
                         break;
                     }
         
-                    let expr: Expression = match parser.parse(t.get_type().into(), tokenizer) {
+                    let expr: Expression = match parser.parse(t.get_type().into(), tokenizer)? {
                         Some(e) => e,
-                        None => throw(Error::CouldNotParse (t.get_value())),
+                        None => return Err(Error::CouldNotParse (t.get_value())),
                     };
                     if let Expression::Declaration {
                         datatype: d,
                         identifier: i,
                     } = expr {
+                        if body.contains_key(&i) {
+                            return Err(Error::DuplicateField (i));
+                        }
+                        if let Some(doc) = t.get_doc() {
+                            docs.insert(i.to_owned(), doc);
+                        }
                         body.insert(i, d);
                     } else {
-                        throw(Error::CouldNotParse (t.get_value()));
+                        return Err(Error::CouldNotParse (t.get_value()));
                     }
                 }
         
-                Expression::Struct {
+                Ok(Expression::Struct {
                     identifier: name.get_value(),
                     variables: body,
-                }
+                    doc: None,
+                    docs,
+                })
             },
-            _ => throw(Error::ExpectedOpenBrace (next.get_value())),
+            _ => Err(Error::ExpectedOpenBrace (next.get_value())),
         }
     }
 }
\ No newline at end of file
@@ -11,10 +11,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for struct initializations.
@@ -22,33 +19,38 @@ pub struct StructInitParselet;
 
 impl PrefixParselet for StructInitParselet {
     /// Parses a struct initialization into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
         if token.check(TokenType::OpenBrace) {
             let mut variables: HashMap<String, Expression> = HashMap::new();
 
             // Until we find a closing curly brace, parse each variable
-            while let Some(t) = tokenizer.peek() {
+            while let Some(t) = tokenizer.peek()? {
                 if t.get_type() == TokenType::CloseBrace {
                     tokenizer.next();
                     break;
                 }
 
-                // It's ok to use `unwrap` here because we just checked that there is
-                // at least one more token left in the tokenizer.
-                let varname: String = tokenizer.next().unwrap().get_value();
-                let expr: Expression = match parser.parse(t.get_type().into(), tokenizer) {
+                let varname: String = match tokenizer.next().transpose()? {
+                    Some(v) => v.get_value(),
+                    None => return Err(Error::UnexpectedEof (t.get_value())),
+                };
+                let expr: Expression = match parser.parse(t.get_type().into(), tokenizer)? {
                     Some(e) => e,
-                    None => throw(Error::CouldNotParse (t.get_value())),
+                    None => return Err(Error::CouldNotParse (t.get_value())),
                 };
 
+                // A repeated field would silently drop all but its last value
+                if variables.contains_key(&varname) {
+                    return Err(Error::DuplicateField (varname));
+                }
                 variables.insert(varname, expr);
             }
 
-            Expression::StructInit {
+            Ok(Expression::StructInit {
                 variables,
-            }
+            })
         } else {
-            throw(Error::ExpectedOpenBrace (token.get_value()));
+            Err(Error::ExpectedOpenBrace (token.get_value()))
         }
     }
 }
\ No newline at end of file
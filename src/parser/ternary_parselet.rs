@@ -10,10 +10,7 @@ use crate::parser::{
     infix::InfixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for the ternary conditional.
@@ -21,34 +18,34 @@ pub struct TernaryParselet;
 
 impl InfixParselet for TernaryParselet {
     /// Parses a ternary conditional into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Expression {
-        let if_expr: Expression = match parser.parse(token.get_type().into(), tokenizer) {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, left: Expression, token: Token) -> Result<Expression, Error> {
+        let if_expr: Expression = match parser.parse(token.get_type().into(), tokenizer)? {
             Some(e) => e,
             None => {
-                throw(Error::CouldNotParse (token.get_value()));
+                return Err(Error::CouldNotParse (token.get_value()));
             },
         };
 
-        let ternary_else = match tokenizer.next() {
+        let ternary_else = match tokenizer.next().transpose()? {
             Some(t) => t,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
 
         if ternary_else.get_type() != TokenType::TernaryElse {
-            throw(Error::CouldNotParse (ternary_else.get_value()));
+            return Err(Error::CouldNotParse (ternary_else.get_value()));
         }
 
-        let else_expr: Expression = match parser.parse(ternary_else.get_type().into(), tokenizer) {
+        let else_expr: Expression = match parser.parse(ternary_else.get_type().into(), tokenizer)? {
             Some(e) => e,
             None => {
-                throw(Error::CouldNotParse (token.get_value()));
+                return Err(Error::CouldNotParse (token.get_value()));
             },
         };
 
-        Expression::TernaryIfElse {
+        Ok(Expression::TernaryIfElse {
             condition: Box::new(left),
             body_true: Box::new(if_expr),
             body_false: Box::new(else_expr),
-        }
+        })
     }
 }
\ No newline at end of file
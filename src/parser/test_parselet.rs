@@ -0,0 +1,53 @@
+//! Provides a parselet for test declarations.
+
+
+use crate::parser::{
+    Parser,
+    Expression,
+    Token,
+    TokenType,
+    Tokenizer,
+    prefix::PrefixParselet,
+};
+
+use crate::tokenizer::unescape_str;
+
+use crate::error::Error;
+
+
+/// Provides a prefix parselet for test declarations.
+pub struct TestParselet;
+
+impl PrefixParselet for TestParselet {
+    /// Parses a test declaration into an expression.
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let next = match tokenizer.next().transpose()? {
+            Some(n) => n,
+            None => return Err(Error::UnexpectedEof (token.get_value())),
+        };
+        let name = match next.get_type() {
+            TokenType::Str => match unescape_str(next.as_str()) {
+                Some(n) => n,
+                None => return Err(Error::ExpectedTestName (next.get_value())),
+            },
+            _ => return Err(Error::ExpectedTestName (next.get_value())),
+        };
+
+        let next = match tokenizer.peek()? {
+            Some(t) => t,
+            None => return Err(Error::UnexpectedEof (token.get_value())),
+        };
+
+        match next.get_type() {
+            TokenType::OpenBrace => tokenizer.next(),
+            _ => return Err(Error::ExpectedOpenBrace (next.get_value())),
+        };
+
+        let body = parser.parse_block(tokenizer)?;
+
+        Ok(Expression::Test {
+            name,
+            body,
+        })
+    }
+}
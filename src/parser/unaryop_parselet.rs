@@ -9,10 +9,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for unary operations.
@@ -20,15 +17,16 @@ pub struct UnaryOpParselet;
 
 impl PrefixParselet for UnaryOpParselet {
     /// Parses a unary operation into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        let expr = match parser.parse(token.get_type().into(), tokenizer) {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let expr = match parser.parse(token.get_type().into(), tokenizer)? {
             Some(e) => e,
-            None => throw(Error::CouldNotParse (token.get_value())),
+            None => return Err(Error::CouldNotParse (token.get_value())),
         };
 
-        Expression::UnaryOp {
+        Ok(Expression::UnaryOp {
             op: token.get_type(),
             expr: Box::new(expr),
-        }
+            span: token.get_span(),
+        })
     }
 }
\ No newline at end of file
@@ -10,10 +10,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for use statements.
@@ -21,16 +18,16 @@ pub struct UseParselet;
 
 impl PrefixParselet for UseParselet {
     /// Parses a use statement into an expression.
-    fn parse(&self, _parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        let next = match tokenizer.next() {
+    fn parse(&self, _parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let next = match tokenizer.next().transpose()? {
             Some(n) => n,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
         let identifier = match next.get_type() {
             TokenType::Identifier => next.get_value(),
-            _ => throw(Error::ExpectedIdentifier (next.get_value())),
+            _ => return Err(Error::ExpectedIdentifier (next.get_value())),
         };
         
-        Expression::Use (identifier.replace(".", "/"))
+        Ok(Expression::Use (identifier.replace(".", "/")))
     }
 }
\ No newline at end of file
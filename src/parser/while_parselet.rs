@@ -10,10 +10,7 @@ use crate::parser::{
     prefix::PrefixParselet,
 };
 
-use crate::error::{
-    throw,
-    Error,
-};
+use crate::error::Error;
 
 
 /// Provides a prefix parselet for while loops.
@@ -21,41 +18,27 @@ pub struct WhileParselet;
 
 impl PrefixParselet for WhileParselet {
     /// Parses a while loop into an expression.
-    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Expression {
-        let condition: Expression = match parser.parse(token.get_type().into(), tokenizer) {
+    fn parse(&self, parser: &Parser, tokenizer: &mut Tokenizer, token: Token) -> Result<Expression, Error> {
+        let condition: Expression = match parser.parse(token.get_type().into(), tokenizer)? {
             Some(c) => c,
-            None => throw(Error::CouldNotParse (token.get_value())),
+            None => return Err(Error::CouldNotParse (token.get_value())),
         };
 
-        let next = match tokenizer.peek() {
+        let next = match tokenizer.peek()? {
             Some(t) => t,
-            None => throw(Error::UnexpectedEof (token.get_value())),
+            None => return Err(Error::UnexpectedEof (token.get_value())),
         };
 
         match next.get_type() {
             TokenType::OpenBrace => tokenizer.next(),
-            _ => throw(Error::ExpectedOpenBrace (next.get_value())),
+            _ => return Err(Error::ExpectedOpenBrace (next.get_value())),
         };
 
-        let mut body: Vec<Expression> = Vec::new();
-
-        // Until we find a closing curly brace, parse each expression in the loop
-        while let Some(t) = tokenizer.peek() {
-            if t.get_type() == TokenType::CloseBrace {
-                tokenizer.next();
-                break;
-            }
-
-            let expr: Expression = match parser.parse(t.get_type().into(), tokenizer) {
-                Some(e) => e,
-                None => throw(Error::CouldNotParse (t.get_value())),
-            };
-            body.push(expr);
-        }
+        let body = parser.parse_block(tokenizer)?;
 
-        Expression::While {
+        Ok(Expression::While {
             condition: Box::new(condition),
             body,
-        }
+        })
     }
 }
\ No newline at end of file
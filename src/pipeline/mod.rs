@@ -0,0 +1,165 @@
+//! Runs the Boron compiler as a library, without exiting the process.
+//!
+//! The `boron` binary reports an error and exits as soon as one is returned.  These
+//! functions instead turn the error into a `Diagnostic` that locates it in the
+//! source, so that editors and other long-running tools can keep going.
+
+
+use crate::backend::Backend;
+
+use crate::ir;
+
+use crate::emitter::Emitter;
+
+use crate::error::Error;
+
+use crate::parser::{
+    Expression,
+    Parser,
+    Span,
+    TokenType,
+    Tokenizer,
+};
+
+
+/// Describes an error found while compiling a source string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    // Name of the kind of error, such as `UndeclaredVariable`
+    pub kind: String,
+    // Part of the source the error was found in
+    pub span: Span,
+}
+
+
+/// Parses a source string into a list of expressions.
+pub fn parse(source: &str) -> Result<Vec<Expression>, Diagnostic> {
+    let mut tokenizer = Tokenizer::new(source);
+    let parser = Parser::new();
+
+    match parser.parse_all(&mut tokenizer) {
+        Ok(expressions) => Ok(expressions),
+        Err(e) => {
+            // Parse errors are found at the last token the parser looked at
+            let span = match locate(source, &e) {
+                Some(s) => s,
+                None => tokenizer.last_span().unwrap_or_default(),
+            };
+            Err(diagnose(e, span))
+        },
+    }
+}
+
+
+/// Checks a source string for errors by compiling it to C and lowering it into the typed IR.
+///
+/// Lowering checks that every variable and function is declared, which the C emitter
+/// leaves to the C compiler.  Constructs that the IR does not support are not errors.
+pub fn check(source: &str) -> Result<(), Diagnostic> {
+    let expressions = parse(source)?;
+
+    let result = match Emitter::new().compile_exe(expressions.to_owned()) {
+        Ok(_) => match ir::lower(expressions) {
+            Err(Error::CouldNotEmit (_)) => Ok(()),
+            result => result.map(|_| ()),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let span = locate(source, &e).unwrap_or_default();
+            Err(diagnose(e, span))
+        },
+    }
+}
+
+
+/// Compiles a source string into the contents of an output file with the given backend.
+pub fn compile(source: &str, backend: &dyn Backend) -> Result<Vec<u8>, Diagnostic> {
+    let expressions = parse(source)?;
+
+    match backend.compile_exe(expressions) {
+        Ok(output) => Ok(output),
+        Err(e) => {
+            let span = locate(source, &e).unwrap_or_default();
+            Err(diagnose(e, span))
+        },
+    }
+}
+
+
+/// Constructs a diagnostic from an error.
+fn diagnose(e: Error, span: Span) -> Diagnostic {
+    Diagnostic {
+        message: e.to_string(),
+        kind: e.name(),
+        span,
+    }
+}
+
+
+/// Finds where in the source an error was found, if the error says.
+///
+/// Errors about a variable, function or struct are placed at its first use,
+/// or at its first mention if it is never used.
+fn locate(source: &str, e: &Error) -> Option<Span> {
+    if let Some((line, column)) = e.location() {
+        return Some(span_at(source, line, column));
+    }
+
+    let subject = e.subject()?;
+
+    let tokenizer = Tokenizer::new(source);
+    let tokens = match tokenizer.collect::<Result<Vec<_>, Error>>() {
+        Ok(t) => t,
+        Err(_) => return None,
+    };
+
+    let mentions = tokens.iter()
+        .enumerate()
+        .filter(|(_, t)| t.check(TokenType::Identifier) && t.as_str() == subject);
+
+    let mut first = None;
+    for (i, token) in mentions {
+        // A name followed by `::` or `->` is being declared rather than used
+        let declared = match tokens.get(i + 1) {
+            Some(next) => next.check(TokenType::FnDeclaration) || next.check(TokenType::FnReturnType),
+            None => false,
+        };
+        if !declared {
+            return Some(token.get_span());
+        }
+        first.get_or_insert(token.get_span());
+    }
+
+    first
+}
+
+
+/// Gets the span of the character at the given line and column, both starting at 1.
+pub fn span_at(source: &str, line: usize, column: usize) -> Span {
+    let start: usize = source.split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+
+    let start = match source[start..].char_indices().nth(column.saturating_sub(1)) {
+        Some((i, _)) => start + i,
+        None => source.len(),
+    };
+
+    let end = match source[start..].chars().next() {
+        Some(c) if c != '\n' => start + c.len_utf8(),
+        _ => start,
+    };
+
+    Span {
+        start,
+        end,
+        line,
+        column,
+    }
+}
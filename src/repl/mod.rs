@@ -0,0 +1,245 @@
+//! An interactive read-eval-print loop for the Boron programming language.
+
+
+use std::io::{
+    self,
+    BufRead,
+    Write,
+};
+
+use crate::parser::{
+    Expression,
+    Parser,
+    Tokenizer,
+};
+
+use crate::interpreter::{
+    is_bare,
+    Interpreter,
+    Value,
+};
+
+use crate::emitter::Emitter;
+
+use crate::desugar::desugar;
+
+use crate::version::VERSION;
+
+use crate::error::{
+    report,
+    Error,
+};
+
+
+/// Describes the commands accepted by the REPL.
+const HELP: &str = "Enter declarations, statements, or expressions to evaluate them.
+Input continues onto the next line while braces are unbalanced.
+
+:type <expr>    Show the type of an expression
+:ast <expr>     Show the parsed form of an expression
+:c <expr>       Show the C emitted for an expression
+:help           Show this message
+:quit           Exit the REPL";
+
+
+/// Holds the state of an interactive session.
+#[derive(Default)]
+pub struct Repl {
+    interpreter: Interpreter,
+    parser: Parser,
+    // Structs, functions, and modules entered so far
+    declarations: Vec<Expression>,
+    // Statements entered so far, which `:c` emits as the body of a function
+    statements: Vec<Expression>,
+}
+
+/// Provides an interface for the REPL.
+impl Repl {
+    /// Constructs a new REPL session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads and evaluates input from standard input until it ends.
+    pub fn run(&mut self) {
+        println!("Boron {} (type :help for help)", VERSION);
+
+        let stdin = io::stdin();
+        let mut buffer = String::new();
+        loop {
+            if buffer.is_empty() {
+                print!(">>> ");
+            } else {
+                print!("... ");
+            }
+            let _ = io::stdout().flush();
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => buffer.push_str(&line),
+            }
+
+            // Keep reading until every brace has been closed
+            if depth(&buffer) > 0 {
+                continue;
+            }
+
+            let input = std::mem::take(&mut buffer);
+            if !self.handle(input.trim()) {
+                break;
+            }
+        }
+        println!();
+    }
+
+    /// Handles a complete input, returning `false` if the session should end.
+    pub fn handle(&mut self, input: &str) -> bool {
+        if input.is_empty() {
+            return true;
+        }
+
+        let (command, rest) = match input.strip_prefix(':') {
+            Some(c) => c.split_once(char::is_whitespace).unwrap_or((c, "")),
+            None => {
+                self.execute(input);
+                return true;
+            },
+        };
+
+        let result = match command {
+            "type" => self.show_types(rest),
+            "ast" => self.show_ast(rest),
+            "c" => self.show_c(rest),
+            "help" => {
+                println!("{}", HELP);
+                Ok(())
+            },
+            "quit" | "q" => return false,
+            _ => {
+                println!("Unknown command :{} (type :help for help)", command);
+                Ok(())
+            },
+        };
+        if let Err(e) = result {
+            report(&e);
+        }
+        true
+    }
+
+    /// Shows the type of each expression in an input.
+    fn show_types(&self, input: &str) -> Result<(), Error> {
+        for expression in desugar(self.parse(input)?)? {
+            println!("{}", self.interpreter.infer(&expression)?);
+        }
+        Ok(())
+    }
+
+    /// Shows the parsed form of each expression in an input.
+    fn show_ast(&self, input: &str) -> Result<(), Error> {
+        for expression in self.parse(input)? {
+            println!("{:#?}", expression);
+        }
+        Ok(())
+    }
+
+    /// Shows the C emitted for each expression in an input, given everything entered so far.
+    fn show_c(&self, input: &str) -> Result<(), Error> {
+        for expression in self.parse(input)? {
+            let mut emitter = Emitter::new();
+            println!("{}", emitter.compile_expression(
+                self.declarations.to_owned(),
+                self.statements.to_owned(),
+                &expression,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Parses and executes an input, printing the value of each bare expression.
+    fn execute(&mut self, input: &str) {
+        let expressions = match self.parse(input) {
+            Ok(e) => e,
+            Err(e) => return report(&e),
+        };
+
+        for expression in expressions {
+            let value = match self.interpreter.exec_statement(&expression) {
+                Ok(v) => v,
+                Err(e) => {
+                    report(&e);
+                    // Stop at the first error, abandoning any calls it interrupted
+                    self.interpreter.reset();
+                    return;
+                },
+            };
+
+            match value {
+                Some(Value::Void) => (),
+                Some(v) => println!("{}: {}", v.describe(), v.get_type()),
+                None => self.remember(expression),
+            }
+        }
+    }
+
+    /// Records a declaration or statement so that `:c` can refer to it.
+    fn remember(&mut self, expression: Expression) {
+        match expression {
+            Expression::Struct {
+                identifier: ref i,
+                ..
+            } | Expression::FnDeclaration {
+                identifier: ref i,
+                ..
+            } => {
+                // Redefining a struct or function replaces the old definition
+                let name = i.to_owned();
+                self.declarations.retain(|d| !matches!(
+                    d,
+                    Expression::Struct { identifier, .. }
+                    | Expression::FnDeclaration { identifier, .. } if *identifier == name
+                ));
+                self.declarations.push(expression);
+            },
+            Expression::Use (_) => self.declarations.push(expression),
+            _ if !is_bare(&expression) => self.statements.push(expression),
+            _ => (),
+        }
+    }
+
+    /// Parses an input into a list of expressions.
+    fn parse(&self, input: &str) -> Result<Vec<Expression>, Error> {
+        let mut tokenizer = Tokenizer::new(input);
+        self.parser.parse_all(&mut tokenizer)
+    }
+}
+
+
+/// Counts the braces left open in an input, ignoring those in comments and character literals.
+fn depth(input: &str) -> isize {
+    let mut depth = 0;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '#' => {
+                // Skip the rest of the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            },
+            '\'' => {
+                // Skip the character, which may be escaped, and the closing quote
+                if chars.next() == Some('\\') {
+                    chars.next();
+                }
+                chars.next();
+            },
+            _ => (),
+        }
+    }
+    depth
+}
@@ -1,260 +1,583 @@
 //! A simple tokenizer for the Boron compiler.
+//!
+//! The tokenizer borrows its source string and scans tokens lazily, only as
+//! far ahead as the parser asks it to look.  Tokens are small `Copy` values
+//! that refer back into the source by byte offset.
 
 
 pub mod token;
 
 
+use std::collections::VecDeque;
+
+use unicode_ident::{
+    is_xid_start,
+    is_xid_continue,
+};
+
 pub use token::{
+    Span,
     Token,
     TokenType,
 };
 
+use crate::error::Error;
+
 
-/// Creates a character stream.
+/// Creates a character stream over a borrowed source string.
 #[derive(Clone)]
-pub struct CharStream {
-    source: Vec<char>,
+pub struct CharStream<'a> {
+    source: &'a str,
     index: usize,
+    line: usize,
+    column: usize,
 }
 
 /// Provides functions for the `CharStream` struct.
-impl CharStream {
+impl<'a> CharStream<'a> {
     /// Constructs a new character stream from a given string.
-    pub fn new(src: String) -> Self {
+    pub fn new(src: &'a str) -> Self {
         Self {
-            source: src.chars().collect::<Vec<char>>(),
+            source: src,
             index: 0,
+            line: 1,
+            column: 1,
         }
     }
 
     /// Gets the next character in the stream without advancing the stream.
     pub fn peek(&self) -> Option<char> {
-        // If beyond the end of the source string, return EOF.
-        if self.index >= self.source.len() {
-            None
+        self.source[self.index..].chars().next()
+    }
+
+    /// Gets the nth character ahead without advancing the stream.
+    pub fn look_ahead(&self, n: usize) -> Option<char> {
+        self.source[self.index..].chars().nth(n)
+    }
+
+    /// Gets the next character in the stream and advances the stream.
+    pub fn next_char(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.index += character.len_utf8();
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            Some(self.source[self.index])
+            self.column += 1;
         }
+        Some(character)
     }
 
-    /// Gets the next character in the stream and advances the stream.
-    pub fn next(&mut self) -> Option<char> {
-        let character = self.peek();
-        self.index += 1;
-        character
+    /// Advances the stream while the given predicate holds.
+    pub fn eat_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            self.next_char();
+        }
+    }
+
+    /// Gets the byte offset of the next character in the stream.
+    pub fn offset(&self) -> usize {
+        self.index
+    }
+
+    /// Gets the line and column of the next character in the stream.
+    pub fn location(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    /// Gets the source text between the given byte offset and the current position.
+    pub fn slice_from(&self, start: usize) -> &'a str {
+        &self.source[start..self.index]
     }
 }
 
 
 /// Provides an abstraction over tokenization behavior.
+///
+/// Tokens are scanned on demand and buffered only as far as `peek`
+/// and `look_ahead` require.
 #[derive(Clone)]
-pub struct Tokenizer {
-    tokenstream: Vec<Token>,
-    index: usize,
+pub struct Tokenizer<'a> {
+    charstream: CharStream<'a>,
+    buffer: VecDeque<Token<'a>>,
+    lossless: bool,
+    // Span of the most recently scanned token
+    last: Option<Span>,
+    // Span of the most recently consumed token
+    consumed: Option<Span>,
+    // Error that stopped the stream, returned again by every later call
+    error: Option<Error>,
+    // Whether iteration has yielded the error, after which it ends
+    reported: bool,
 }
 
 const WHITESPACE: &str = "\r\n\t ,";
-const SEPARATORS: &str = "\r\n\t ():,'";
+
+/// Checks if a character is whitespace.
+fn is_whitespace(c: char) -> bool {
+    WHITESPACE.contains(c)
+}
+
+/// Checks if a character may begin an identifier.
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || is_xid_start(c)
+}
+
+/// Checks if a character may continue an identifier.
+fn is_identifier_continue(c: char) -> bool {
+    c == '_' || is_xid_continue(c)
+}
+
+/// Recovers the character denoted by a character literal such as `'a'` or `'\\n'`.
+pub fn unescape_char(literal: &str) -> Option<char> {
+    let inner = literal.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let chr = match chars.next()? {
+        '\\' => match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            _ => return None,
+        },
+        c => c,
+    };
+    match chars.next() {
+        Some(_) => None,
+        None => Some(chr),
+    }
+}
+
+/// Recovers the text denoted by a string literal such as `"adds \"one\""`.
+pub fn unescape_str(literal: &str) -> Option<String> {
+    let inner = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        text.push(match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\\' => '\\',
+                '"' => '"',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(text)
+}
 
 /// Provides functions for the `Tokenizer` struct.
-impl Tokenizer {
+impl<'a> Tokenizer<'a> {
     /// Constructs a new token stream from a string.
-    pub fn new(string: String) -> Self {
-        let mut charstream = CharStream::new(string);
-
-        let mut tokenstream = Vec::new();
-        
-        while let Some(t) = Self::next_token(&mut charstream) {
-            tokenstream.push(t);
+    ///
+    /// Whitespace and comments are discarded, except for doc comments,
+    /// which are attached to the token that follows them.
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            charstream: CharStream::new(source),
+            buffer: VecDeque::new(),
+            lossless: false,
+            last: None,
+            consumed: None,
+            error: None,
+            reported: false,
         }
+    }
 
+    /// Constructs a lossless token stream from a string.
+    ///
+    /// Whitespace and comments are preserved as trivia tokens, so concatenating
+    /// the values of every token reproduces the original source exactly.
+    pub fn lossless(source: &'a str) -> Self {
         Self {
-            tokenstream,
-            index: 0,
+            charstream: CharStream::new(source),
+            buffer: VecDeque::new(),
+            lossless: true,
+            last: None,
+            consumed: None,
+            error: None,
+            reported: false,
         }
     }
 
-    /// Skips all whitespace (newlines, spaces, and comments)
-    fn skip_whitespace(charstream: &mut CharStream) {
-        while let Some(c) = charstream.peek() {
-            if WHITESPACE.contains(c) {
-                charstream.next();
-            } else if c == '#' {
-                while charstream.peek() != Some('\n') {
-                    charstream.next();
-                }
-            } else {
-                break;
+    /// Scans the next token that the parser should see, or `None` at the end of the source.
+    ///
+    /// Once an error is found, scanning stops and every later call returns the same error.
+    fn scan(&mut self) -> Result<Option<Token<'a>>, Error> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        match self.scan_token() {
+            Ok(Some(token)) => {
+                self.last = Some(token.get_span());
+                Ok(Some(token))
+            },
+            Ok(None) => Ok(None),
+            Err(e) => {
+                self.error = Some(e.clone());
+                Err(e)
+            },
+        }
+    }
+
+    /// Scans the next token, attaching any doc comment that precedes it.
+    fn scan_token(&mut self) -> Result<Option<Token<'a>>, Error> {
+        if self.lossless {
+            return Self::next_token(&mut self.charstream);
+        }
+
+        // Doc comments are collected as a single span of source text
+        let mut doc: Option<Span> = None;
+
+        loop {
+            let token = match Self::next_token(&mut self.charstream)? {
+                Some(t) => t,
+                None => return Ok(None),
+            };
+            match token.get_type() {
+                TokenType::DocComment => {
+                    doc = Some(match doc {
+                        Some(d) => d.to(token.get_span()),
+                        None => token.get_span(),
+                    });
+                },
+                TokenType::Whitespace
+                | TokenType::Comment => (),
+                _ => {
+                    return match doc {
+                        Some(d) => Ok(Some(token.with_doc(&self.charstream.source[d.start..d.end]))),
+                        None => Ok(Some(token)),
+                    };
+                },
             }
         }
     }
 
-    /// Yields the next token from the character stream.
-    fn next_token(charstream: &mut CharStream) -> Option<Token> {
-        // Skip whitespace
-        Self::skip_whitespace(charstream);
+    /// Scans a comment.
+    ///
+    /// Line comments begin with `#` and doc comments with `##`; both run until the
+    /// end of the line.  Block comments are delimited by `#[` and `]#` and may nest.
+    fn scan_comment(charstream: &mut CharStream<'a>, line: usize, column: usize) -> Result<TokenType, Error> {
+        match charstream.peek() {
+            Some('[') => {
+                let mut depth = 0;
+                let mut previous = '#';
+                while let Some(c) = charstream.next_char() {
+                    if previous == '#' && c == '[' {
+                        depth += 1;
+                        // Prevent `#[#` from being read as both an opener and a closer
+                        previous = '\0';
+                        continue;
+                    }
+                    if previous == ']' && c == '#' {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(TokenType::Comment);
+                        }
+                        previous = '\0';
+                        continue;
+                    }
+                    previous = c;
+                }
+                Err(Error::UnterminatedComment (line, column))
+            },
+            Some('#') => {
+                charstream.eat_while(|c| c != '\n');
+                Ok(TokenType::DocComment)
+            },
+            _ => {
+                charstream.eat_while(|c| c != '\n');
+                Ok(TokenType::Comment)
+            },
+        }
+    }
+
+    /// Yields the next token (including whitespace and comments) from the character stream.
+    fn next_token(charstream: &mut CharStream<'a>) -> Result<Option<Token<'a>>, Error> {
+        let start = charstream.offset();
+        let (line, column) = charstream.location();
 
-        let character = match charstream.next() {
+        let character = match charstream.next_char() {
             Some(c) => c,
-            None => return None,
+            None => return Ok(None),
         };
-        
-        let token = match character {
+
+        let token_type = match character {
             // EOF
-            '\0' => return None,
+            '\0' => return Ok(None),
+            // Whitespace
+            c if is_whitespace(c) => {
+                charstream.eat_while(is_whitespace);
+                TokenType::Whitespace
+            },
+            // Comment
+            '#' => Self::scan_comment(charstream, line, column)?,
             // Ternary if
-            '?' => Token::new(character.to_string(), TokenType::TernaryIf),
+            '?' => TokenType::TernaryIf,
             // Ternary else
-            '|' => Token::new(character.to_string(), TokenType::TernaryElse),
+            '|' => TokenType::TernaryElse,
             // Open parenthesis
-            '(' => Token::new(character.to_string(), TokenType::OpenParen),
+            '(' => TokenType::OpenParen,
             // Closing parenthesis
-            ')' => Token::new(character.to_string(), TokenType::CloseParen),
+            ')' => TokenType::CloseParen,
             // Open curly brace
-            '{' => Token::new(character.to_string(), TokenType::OpenBrace),
+            '{' => TokenType::OpenBrace,
             // Closing curly brace
-            '}' => Token::new(character.to_string(), TokenType::CloseBrace),
-            // Single quote
-            '\'' => Token::new(character.to_string(), TokenType::SingleQuote),
+            '}' => TokenType::CloseBrace,
+            // Character literal
+            '\'' => Self::scan_char(charstream, line, column)?,
+            // String literal
+            '"' => Self::scan_str(charstream, line, column)?,
             // Assignment or function declaration
             ':' => {
                 match charstream.peek() {
                     Some(':') => {
-                        charstream.next();
-                        Token::new("::".to_string(), TokenType::FnDeclaration)
+                        charstream.next_char();
+                        TokenType::FnDeclaration
                     },
-                    _ => Token::new(character.to_string(), TokenType::Assignment)
+                    _ => TokenType::Assignment,
                 }
             },
             // Plus
-            '+' => Token::new(character.to_string(), TokenType::Plus),
+            '+' => TokenType::Plus,
             // Minus or function return type
             '-' => {
                 match charstream.peek() {
                     Some('>') => {
-                        charstream.next();
-                        Token::new("->".to_string(), TokenType::FnReturnType)
+                        charstream.next_char();
+                        TokenType::FnReturnType
                     },
-                    _ => Token::new(character.to_string(), TokenType::Minus)
+                    _ => TokenType::Minus,
                 }
             },
             // Multiply
-            '*' => Token::new(character.to_string(), TokenType::Multiply),
+            '*' => TokenType::Multiply,
             // Divide
-            '/' => Token::new(character.to_string(), TokenType::Divide),
+            '/' => TokenType::Divide,
             // Not
-            '!' => Token::new(character.to_string(), TokenType::Not),
+            '!' => TokenType::Not,
             // Greater
             '>' => {
                 match charstream.peek() {
                     Some('=') => {
-                        charstream.next();
-                        Token::new(">=".to_string(), TokenType::GreaterEqual)
+                        charstream.next_char();
+                        TokenType::GreaterEqual
                     },
-                    _ => Token::new(character.to_string(), TokenType::Greater)
+                    _ => TokenType::Greater,
                 }
             },
             // Less
             '<' => {
                 match charstream.peek() {
                     Some('=') => {
-                        charstream.next();
-                        Token::new("<=".to_string(), TokenType::LessEqual)
+                        charstream.next_char();
+                        TokenType::LessEqual
                     },
-                    _ => Token::new(character.to_string(), TokenType::Less)
+                    _ => TokenType::Less,
                 }
             }
             // Equal
-            '=' => Token::new(character.to_string(), TokenType::Equal),
+            '=' => TokenType::Equal,
             // Integer or floating-point
-            '0'..='9' => {
-                let mut sofar = String::from(character);
-                while let Some(chr) = charstream.peek() {
-                    if !SEPARATORS.contains(chr) {
-                        sofar.push(chr);
-                        charstream.next();
-                    } else {
-                        break;
-                    }
-                }
+            '0'..='9' => Self::scan_number(charstream, start, line, column)?,
+            // Identifier or keyword
+            c if is_identifier_start(c) => Self::scan_identifier(charstream, start),
+            // Unrecognized character
+            _ => return Err(Error::UnexpectedCharacter (character, line, column)),
+        };
 
-                let mut token = Token::new(sofar.clone(), TokenType::Unknown);
+        let span = Span {
+            start,
+            end: charstream.offset(),
+            line,
+            column,
+        };
 
-                if let Ok(_) = str::parse::<i32>(&sofar) {
-                    token = Token::new(sofar, TokenType::Int);
-                } else if let Ok(_) = str::parse::<f32>(&sofar) {
-                    token = Token::new(sofar, TokenType::Float);
-                }
+        Ok(Some(Token::new(charstream.slice_from(start), token_type, span)))
+    }
 
-                token
-            },
-            // Identifier or type keyword
-            'A'..='z' => {
-                let mut sofar = String::from(character);
-                while let Some(chr) = charstream.peek() {
-                    if !SEPARATORS.contains(chr) {
-                        sofar.push(chr);
-                        charstream.next();
-                    } else {
-                        break;
-                    }
-                }
+    /// Scans the remainder of an integer or floating-point literal.
+    fn scan_number(charstream: &mut CharStream<'a>, start: usize, line: usize, column: usize) -> Result<TokenType, Error> {
+        let mut float = false;
 
-                let token = match sofar.as_str() {
-                    "int" => Token::new(sofar, TokenType::Type),
-                    "flt" => Token::new(sofar, TokenType::Type),
-                    "bln" => Token::new(sofar, TokenType::Type),
-                    "chr" => Token::new(sofar, TokenType::Type),
-                    "let" => Token::new(sofar, TokenType::Let),
-                    "use" => Token::new(sofar, TokenType::Use),
-                    "struct" => Token::new(sofar, TokenType::Struct),
-                    "true" => Token::new(sofar, TokenType::Bool),
-                    "false" => Token::new(sofar, TokenType::Bool),
-                    "while" => Token::new(sofar, TokenType::While),
-                    "if" => Token::new(sofar, TokenType::If),
-                    "else" => Token::new(sofar, TokenType::Else),
-                    "return" => Token::new(sofar, TokenType::Return),
-                    _ => Token::new(sofar, TokenType::Identifier),
-                };
-
-                token
+        while let Some(chr) = charstream.peek() {
+            if chr.is_ascii_digit() {
+                charstream.next_char();
+            } else if chr == '.' && !float {
+                float = true;
+                charstream.next_char();
+            } else {
+                break;
+            }
+        }
+
+        // A number running straight into an identifier (such as `3x`) is malformed
+        if charstream.peek().is_some_and(is_identifier_continue) {
+            charstream.eat_while(is_identifier_continue);
+            return Err(Error::InvalidNumber (charstream.slice_from(start).to_string(), line, column));
+        }
+
+        let sofar = charstream.slice_from(start);
+        if float {
+            match str::parse::<f32>(sofar) {
+                Ok(_) => Ok(TokenType::Float),
+                Err(_) => Err(Error::InvalidNumber (sofar.to_string(), line, column)),
+            }
+        } else {
+            match str::parse::<i32>(sofar) {
+                Ok(_) => Ok(TokenType::Int),
+                Err(_) => Err(Error::InvalidNumber (sofar.to_string(), line, column)),
+            }
+        }
+    }
+
+    /// Scans the remainder of an identifier or keyword.
+    ///
+    /// Identifiers may contain dots (as in `point.x` or `std.io`), provided
+    /// that each dot is followed by the start of another identifier.
+    fn scan_identifier(charstream: &mut CharStream<'a>, start: usize) -> TokenType {
+        while let Some(chr) = charstream.peek() {
+            if is_identifier_continue(chr)
+            || (chr == '.' && charstream.look_ahead(1).is_some_and(is_identifier_start))
+            {
+                charstream.next_char();
+            } else {
+                break;
+            }
+        }
+
+        match charstream.slice_from(start) {
+            "int" => TokenType::Type,
+            "flt" => TokenType::Type,
+            "bln" => TokenType::Type,
+            "chr" => TokenType::Type,
+            "let" => TokenType::Let,
+            "const" => TokenType::Const,
+            "static" => TokenType::Static,
+            "use" => TokenType::Use,
+            "struct" => TokenType::Struct,
+            "true" => TokenType::Bool,
+            "false" => TokenType::Bool,
+            "while" => TokenType::While,
+            "if" => TokenType::If,
+            "else" => TokenType::Else,
+            "return" => TokenType::Return,
+            "defer" => TokenType::Defer,
+            "test" => TokenType::Test,
+            "assert" => TokenType::Assert,
+            "assert_eq" => TokenType::Assert,
+            "ref" => TokenType::Ref,
+            "own" => TokenType::Own,
+            "opt" => TokenType::Opt,
+            "new" => TokenType::New,
+            "null" => TokenType::Null,
+            _ => TokenType::Identifier,
+        }
+    }
+
+    /// Scans the remainder of a character literal, validating escape sequences.
+    ///
+    /// The token keeps the literal as written (including quotes); use `unescape_char`
+    /// to recover the character it denotes.
+    fn scan_char(charstream: &mut CharStream<'a>, line: usize, column: usize) -> Result<TokenType, Error> {
+        match charstream.next_char() {
+            Some('\\') => {
+                match charstream.next_char() {
+                    Some('n' | 't' | 'r' | '0' | '\\' | '\'') => (),
+                    Some(c) => return Err(Error::UnexpectedCharacter (c, line, column + 2)),
+                    None => return Err(Error::UnterminatedChar (line, column)),
+                }
             },
-            // Unknown type
-            _ => Token::new(character.to_string(), TokenType::Unknown),
+            Some('\n') | None => return Err(Error::UnterminatedChar (line, column)),
+            Some(_) => (),
         };
 
-        Some(token)
+        match charstream.next_char() {
+            Some('\'') => Ok(TokenType::Char),
+            _ => Err(Error::UnterminatedChar (line, column)),
+        }
     }
 
-    /// Gets the next token and advances the stream.
-    pub fn next(&mut self) -> Option<Token> {
-        let token = self.peek();
-        self.index += 1;
-        token
+    /// Scans the remainder of a string literal, validating escape sequences.
+    ///
+    /// Strings name tests and may not span lines.  The token keeps the literal as
+    /// written (including quotes); use `unescape_str` to recover its text.
+    fn scan_str(charstream: &mut CharStream<'a>, line: usize, column: usize) -> Result<TokenType, Error> {
+        loop {
+            let (l, col) = charstream.location();
+            match charstream.next_char() {
+                Some('"') => return Ok(TokenType::Str),
+                Some('\\') => match charstream.next_char() {
+                    Some('n' | 't' | 'r' | '0' | '\\' | '"') => (),
+                    Some('\n') | None => return Err(Error::UnterminatedString (line, column)),
+                    Some(c) => return Err(Error::UnexpectedCharacter (c, l, col + 1)),
+                },
+                Some('\n') | None => return Err(Error::UnterminatedString (line, column)),
+                Some(_) => (),
+            }
+        }
+    }
+
+    /// Gets the span of the most recently scanned token.
+    ///
+    /// Since tokens are scanned only as far as the parser looks ahead, this locates a parse error.
+    pub fn last_span(&self) -> Option<Span> {
+        self.last
+    }
+
+    /// Gets the span of the most recently consumed token, which ends the expression just parsed.
+    pub fn consumed_span(&self) -> Option<Span> {
+        self.consumed
     }
 
     /// Gets the next token without advancing the stream.
-    pub fn peek(&self) -> Option<Token> {
-        if self.index >= self.tokenstream.len() {
-            None
-        } else {
-            Some(self.tokenstream[self.index].to_owned())
-        }
+    pub fn peek(&mut self) -> Result<Option<Token<'a>>, Error> {
+        self.look_ahead(0)
     }
 
     /// Gets the nth token ahead without advancing the stream.
-    pub fn look_ahead(&self, n: usize) -> Option<Token> {
-        let index = self.index + n;
-        if index >= self.tokenstream.len() {
-            None
-        } else {
-            Some(self.tokenstream[index].to_owned())
+    pub fn look_ahead(&mut self, n: usize) -> Result<Option<Token<'a>>, Error> {
+        while self.buffer.len() <= n {
+            match self.scan()? {
+                Some(token) => self.buffer.push_back(token),
+                None => return Ok(None),
+            }
         }
+        Ok(self.buffer.get(n).copied())
     }
+}
 
-    /// Yields all tokens in the stream.
-    /// This *does not* consume the token stream.
-    pub fn collect(&self) -> Vec<Token> {
-        self.tokenstream.to_owned()
+
+/// Allows the `Tokenizer` to be consumed one token at a time.
+///
+/// Each item is a token, or the error that stopped the stream, which ends the iteration.
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, Error>;
+
+    /// Gets the next token and advances the stream.
+    fn next(&mut self) -> Option<Result<Token<'a>, Error>> {
+        let token = match self.buffer.pop_front() {
+            Some(t) => t,
+            None => match self.scan() {
+                Ok(Some(t)) => t,
+                Ok(None) => return None,
+                Err(_) if self.reported => return None,
+                Err(e) => {
+                    self.reported = true;
+                    return Some(Err(e));
+                },
+            },
+        };
+        self.consumed = Some(token.get_span());
+        Some(Ok(token))
     }
-}
\ No newline at end of file
+}
@@ -1,22 +1,33 @@
 //! Provides abstractions over tokens.
 
 
+use serde::{
+    ser::SerializeStruct,
+    Serialize,
+    Serializer,
+};
+
+
 /// Enumerates token types available.
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Serialize)]
 pub enum TokenType {
     OpenParen,
     CloseParen,
     OpenBrace,
     CloseBrace,
-    SingleQuote,
     Int,
     Float,
+    Char,
+    Str,
     Bool,
     Let,
+    Const,
+    Static,
     Assignment,
     FnDeclaration,
     FnReturnType,
     Return,
+    Defer,
     Identifier,
     Plus,
     Minus,
@@ -36,28 +47,74 @@ pub enum TokenType {
     TernaryIf,
     TernaryElse,
     Use,
-    Unknown,
+    Test,
+    Assert,
+    Ref,
+    Own,
+    Opt,
+    New,
+    Null,
+    Whitespace,
+    Comment,
+    DocComment,
+}
+
+
+/// Locates a token within its source string.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize)]
+pub struct Span {
+    // Byte offsets of the first character and one past the last character
+    pub start: usize,
+    pub end: usize,
+    // Line and column (both starting at 1) of the first character
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Provides functions for the `Span` struct.
+impl Span {
+    /// Constructs a span covering this span and everything up to the end of another.
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+            column: self.column,
+        }
+    }
 }
 
 
 /// Defines an abstraction over tokens.
-#[derive(Clone, Debug)]
-pub struct Token {
-    val: String,
+///
+/// Tokens borrow their text from the source string, so they are cheap to copy.
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+    val: &'a str,
     t: TokenType,
+    span: Span,
+    doc: Option<&'a str>,
 }
 
 
 /// Provides functions for the `Token` struct.
-impl Token {
-    /// Constructs a new token from a string and a token type.
-    pub fn new(token_value: String, token_type: TokenType) -> Self {
+impl<'a> Token<'a> {
+    /// Constructs a new token from a string, a token type, and its location in the source.
+    pub fn new(token_value: &'a str, token_type: TokenType, span: Span) -> Self {
         Self {
             val: token_value,
             t: token_type,
+            span,
+            doc: None,
         }
     }
 
+    /// Attaches the source text of a run of doc comments to this token.
+    pub fn with_doc(mut self, doc: &'a str) -> Self {
+        self.doc = Some(doc);
+        self
+    }
+
     /// Checks if this token is of the given type.
     pub fn check(&self, token_type: TokenType) -> bool {
         self.t == token_type
@@ -68,8 +125,53 @@ impl Token {
         self.val.to_owned()
     }
 
+    /// Gets the string associated with this token without copying it.
+    pub fn as_str(&self) -> &'a str {
+        self.val
+    }
+
     /// Gets the type associated with this token.
     pub fn get_type(&self) -> TokenType {
         self.t
     }
-}
\ No newline at end of file
+
+    /// Gets the location of this token in the source.
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    /// Gets the doc comment attached to this token, if any, without the leading `##`s.
+    pub fn get_doc(&self) -> Option<String> {
+        let doc = self.doc?;
+        let lines = doc.lines()
+            .map(|l| l.trim())
+            .filter(|l| l.starts_with("##"))
+            .map(|l| {
+                let text = l.trim_start_matches('#');
+                text.strip_prefix(' ').unwrap_or(text).trim_end()
+            })
+            .collect::<Vec<&str>>();
+        Some(lines.join("\n"))
+    }
+
+    /// Checks if this token is whitespace or a comment.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.t, TokenType::Whitespace | TokenType::Comment | TokenType::DocComment)
+    }
+}
+
+
+/// Serializes a token as its type, text and location, along with its doc comment if it has one.
+impl Serialize for Token<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let doc = self.get_doc();
+        let mut token = serializer.serialize_struct("Token", if doc.is_some() { 4 } else { 3 })?;
+        token.serialize_field("type", &self.t)?;
+        token.serialize_field("value", self.val)?;
+        token.serialize_field("span", &self.span)?;
+        if let Some(d) = doc {
+            token.serialize_field("doc", &d)?;
+        }
+        token.end()
+    }
+}
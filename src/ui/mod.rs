@@ -0,0 +1,139 @@
+//! Checks what the compiler and compiled programs do against expectations written in the source.
+//!
+//! A UI test is a Boron file annotated with comments of two kinds:
+//!
+//! - `# expect-error: UndeclaredVariable` expects compiling the file to fail with
+//!   the named kind of error.
+//! - `# expect-stdout: 42` expects the program to print the line `42`.  Each of
+//!   these comments expects one more line, in order.
+//!
+//! A file without `expect-error` must compile, and if it declares `main` it is run
+//! and must print exactly the expected lines.  Blessing a file rewrites its
+//! annotations to describe what actually happened.
+
+
+/// Begins a comment naming the kind of error a file is expected to fail with.
+pub const EXPECT_ERROR: &str = "# expect-error:";
+
+/// Begins a comment holding a line a program is expected to print.
+pub const EXPECT_STDOUT: &str = "# expect-stdout:";
+
+
+/// Describes what compiling and running a program does, or is expected to do.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    // Kind of error reported by the compiler, such as `UndeclaredVariable`
+    pub error: Option<String>,
+    // Lines printed by the program
+    pub stdout: Vec<String>,
+}
+
+
+/// Reads the expected outcome of a program from its annotations.
+pub fn expectations(source: &str) -> Outcome {
+    let mut expected = Outcome::default();
+    for line in source.lines() {
+        let line = line.trim_start();
+        if let Some(error) = line.strip_prefix(EXPECT_ERROR) {
+            expected.error = Some(error.trim().to_string());
+        } else if let Some(stdout) = line.strip_prefix(EXPECT_STDOUT) {
+            // Only the space after the colon is part of the annotation
+            let stdout = stdout.strip_prefix(' ').unwrap_or(stdout);
+            expected.stdout.push(stdout.to_string());
+        }
+    }
+    expected
+}
+
+
+/// Checks if a line of source is an annotation.
+fn is_annotation(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with(EXPECT_ERROR) || line.starts_with(EXPECT_STDOUT)
+}
+
+
+/// Rewrites the annotations of a program to describe an outcome.
+///
+/// The new annotations take the place of the first old one, or go at the top
+/// of the file if it has none.  The rest of the file is left unchanged.
+pub fn bless(source: &str, actual: &Outcome) -> String {
+    let mut annotations = Vec::new();
+    if let Some(error) = &actual.error {
+        annotations.push(format!("{} {}", EXPECT_ERROR, error));
+    }
+    for line in &actual.stdout {
+        match line.is_empty() {
+            true => annotations.push(EXPECT_STDOUT.to_string()),
+            false => annotations.push(format!("{} {}", EXPECT_STDOUT, line)),
+        }
+    }
+
+    let lines = source.lines().collect::<Vec<&str>>();
+    let position = lines.iter().position(|l| is_annotation(l)).unwrap_or(0);
+
+    let mut blessed = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index == position {
+            blessed.append(&mut annotations);
+        }
+        if !is_annotation(line) {
+            blessed.push(line.to_string());
+        }
+    }
+    blessed.append(&mut annotations);
+
+    let mut output = blessed.join("\n");
+    if source.ends_with('\n') || source.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+
+/// Describes how an outcome differs from the expected one, or returns nothing if they agree.
+///
+/// Differences in output are shown line by line, with `-` before expected
+/// lines that are missing and `+` before lines that were not expected.
+pub fn diff(expected: &Outcome, actual: &Outcome) -> Vec<String> {
+    match (&expected.error, &actual.error) {
+        (Some(e), Some(a)) if e == a => return Vec::new(),
+        (Some(e), Some(a)) => return vec![format!("expected error {}, found error {}", e, a)],
+        (Some(e), None) => return vec![format!("expected error {}, but the file compiled", e)],
+        (None, Some(a)) => return vec![format!("expected no error, found error {}", a)],
+        (None, None) => (),
+    }
+
+    if expected.stdout == actual.stdout {
+        return Vec::new();
+    }
+
+    // Find the longest common subsequence of lines, working back from the end
+    let (old, new) = (&expected.stdout, &actual.stdout);
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = vec!["stdout differs from the expected output:".to_string()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
@@ -1,4 +1,4 @@
 //! Provides version information for the Boron compiler.
 
 
-pub const VERSION: &str = "0.26.0";
\ No newline at end of file
+pub const VERSION: &str = "0.51.0";
\ No newline at end of file
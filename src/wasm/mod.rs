@@ -0,0 +1,1262 @@
+//! Emits the WebAssembly text format (`.wat`) for the Boron compiler.
+//!
+//! Scalars are held in `i32` and `f32` locals, except those passed to `ref`
+//! parameters, which need an address.  Structs and those scalars live in a
+//! stack frame in linear memory, addressed by the `$fp` local, and heap
+//! structs are allocated from the end of linear memory.  Every function is
+//! exported, and `print` calls functions imported from the host.
+
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use crate::parser::{
+    Expression,
+    Passing,
+    TokenType,
+};
+
+use crate::emitter::include_modules;
+
+use crate::consteval::Evaluator;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::error::Error;
+
+
+/// The address of the first global variable.  Lower addresses are left unused, so that `null` is never a valid address.
+const DATA_BASE: u32 = 16;
+
+/// The size of the stack that holds each function's frame.
+const STACK_SIZE: u32 = 1 << 20;
+
+/// The size of a page of WebAssembly memory.
+const PAGE_SIZE: u32 = 1 << 16;
+
+
+/// Host functions imported by every module.
+const IMPORTS: &str = "  (import \"env\" \"print_int\" (func $boron.print_int (param i32)))
+  (import \"env\" \"print_flt\" (func $boron.print_flt (param f32)))
+  (import \"env\" \"print_bln\" (func $boron.print_bln (param i32)))
+  (import \"env\" \"print_chr\" (func $boron.print_chr (param i32)))
+";
+
+
+/// Runtime support emitted into every module.
+///
+/// Each heap block begins with its size and, once freed, a link to the next free block.
+/// Freed blocks are reused by allocations of the same size.
+const RUNTIME: &str = "  (func $boron.new (param $size i32) (result i32)
+    (local $block i32)
+    (local $previous i32)
+    (local.set $block (global.get $boron.free))
+    (block $done
+      (loop $search
+        (br_if $done (i32.eqz (local.get $block)))
+        (if (i32.eq (i32.load (local.get $block)) (local.get $size))
+          (then
+            (if (local.get $previous)
+              (then (i32.store offset=4 (local.get $previous) (i32.load offset=4 (local.get $block))))
+              (else (global.set $boron.free (i32.load offset=4 (local.get $block)))))
+            (return (i32.add (local.get $block) (i32.const 8)))))
+        (local.set $previous (local.get $block))
+        (local.set $block (i32.load offset=4 (local.get $block)))
+        (br $search)))
+    (local.set $block (global.get $boron.heap))
+    (global.set $boron.heap (i32.add (local.get $block) (i32.add (local.get $size) (i32.const 8))))
+    (if (i32.gt_u (global.get $boron.heap) (i32.mul (memory.size) (i32.const 65536)))
+      (then
+        (if (i32.eq
+              (memory.grow (i32.add (i32.shr_u (i32.sub (global.get $boron.heap) (i32.mul (memory.size) (i32.const 65536))) (i32.const 16)) (i32.const 1)))
+              (i32.const -1))
+          (then (unreachable)))))
+    (i32.store (local.get $block) (local.get $size))
+    (i32.add (local.get $block) (i32.const 8)))
+  (func $boron.free (param $pointer i32)
+    (local $block i32)
+    (if (i32.eqz (local.get $pointer)) (then (return)))
+    (local.set $block (i32.sub (local.get $pointer) (i32.const 8)))
+    (i32.store offset=4 (local.get $block) (global.get $boron.free))
+    (global.set $boron.free (local.get $block)))
+";
+
+
+/// Describes where a variable is stored.
+#[derive(Clone, Debug)]
+enum Place {
+    // Scalar held in a WebAssembly local
+    Local (String),
+    // Value held in linear memory, at the address computed by the given instructions
+    Memory (String),
+}
+
+
+/// Holds the place and Boron type of a variable.
+#[derive(Clone, Debug)]
+struct Variable {
+    place: Place,
+    datatype: String,
+}
+
+
+/// Holds the variables of a block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, Variable>,
+}
+
+
+/// Holds the parameters and return type of a function.
+#[derive(Clone)]
+struct Signature {
+    arguments: Vec<(String, Passing)>,
+    return_type: String,
+}
+
+
+/// Emits WebAssembly text for a Boron program.
+#[derive(Default)]
+pub struct Emitter {
+    // Fields of each struct, sorted by name
+    structs: HashMap<String, Vec<(String, String)>>,
+    functions: HashMap<String, Signature>,
+    globals: HashMap<String, Variable>,
+    // Values of top-level constants and declarations of `const` functions, for evaluating initializers
+    constants: HashMap<String, Expression>,
+    const_fns: HashMap<String, Expression>,
+    // Initial contents of global variables, and the address of the next one
+    data: Vec<String>,
+    data_end: u32,
+    definitions: Vec<String>,
+    // State of the function being emitted
+    function: String,
+    return_type: String,
+    locals: Vec<(String, &'static str)>,
+    body: Vec<String>,
+    depth: usize,
+    frame: u32,
+    scopes: Vec<Scope>,
+    // Scalar variables passed by reference, which must be stored in memory
+    escaping: HashSet<String>,
+    // Counter used to give locals and labels unique names
+    next: usize,
+}
+
+/// Provides an interface for the WebAssembly emitter.
+impl Emitter {
+    /// Constructs a new WebAssembly emitter.
+    pub fn new() -> Self {
+        Self {
+            data_end: DATA_BASE,
+            ..Self::default()
+        }
+    }
+
+    /// Compiles a program, including the modules it uses, into a WebAssembly module.
+    pub fn compile_exe(&mut self, expressions: Vec<Expression>) -> Result<String, Error> {
+        let expressions = desugar(include_modules(expressions)?)?;
+
+        // Register every struct and function first, so they may be used before they are declared
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct {
+                    identifier: i,
+                    variables: v,
+                    ..
+                } => {
+                    let mut fields = v.iter()
+                        .map(|(f, t)| (f.to_owned(), t.to_owned()))
+                        .collect::<Vec<(String, String)>>();
+                    fields.sort();
+                    self.structs.insert(i.to_owned(), fields);
+                },
+                Expression::FnDeclaration {
+                    identifier: i,
+                    arguments: a,
+                    return_type: r,
+                    constant: c,
+                    ..
+                } => {
+                    let signature = Signature {
+                        arguments: a.iter().map(|(_, t, p)| (t.to_owned(), *p)).collect(),
+                        return_type: r.to_owned(),
+                    };
+                    self.functions.insert(i.to_owned(), signature);
+                    if *c {
+                        self.const_fns.insert(i.to_owned(), expression.to_owned());
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        // Emit globals before functions, which may refer to them
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. } => (),
+                Expression::Assignment {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } | Expression::Static {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let variable = self.emit_global(d, i, v)?.0;
+                    self.globals.insert(i.to_owned(), variable);
+                },
+                Expression::Const {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let (variable, value) = self.emit_global(d, i, v)?;
+                    self.constants.insert(i.to_owned(), value);
+                    self.globals.insert(i.to_owned(), variable);
+                },
+                _ => return Err(Error::StatementOutsideFunction),
+            }
+        }
+
+        for expression in expressions.iter() {
+            if let Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                body: b,
+                ..
+            } = expression {
+                self.emit_function(i, a, r, b)?;
+            }
+        }
+
+        // The stack lies above the globals and grows down, and the heap lies above the stack
+        let stack_limit = align(self.data_end, 16);
+        let heap = stack_limit + STACK_SIZE;
+        let pages = heap / PAGE_SIZE + 1;
+
+        let mut output = String::new();
+        output.push_str("(module\n");
+        output.push_str(IMPORTS);
+        output.push_str(&format!("  (memory (export \"memory\") {})\n", pages));
+        output.push_str(&format!("  (global $boron.sp (mut i32) (i32.const {}))\n", heap));
+        output.push_str(&format!("  (global $boron.stack_limit i32 (i32.const {}))\n", stack_limit));
+        output.push_str(&format!("  (global $boron.heap (mut i32) (i32.const {}))\n", heap));
+        output.push_str("  (global $boron.free (mut i32) (i32.const 0))\n");
+        for data in self.data.iter() {
+            output.push_str(data);
+        }
+        output.push_str(RUNTIME);
+        for definition in self.definitions.iter() {
+            output.push_str(definition);
+        }
+        output.push_str(")\n");
+        Ok(output)
+    }
+
+    /// Gets the WebAssembly type of a Boron type, or `None` for `nul`.
+    fn ty(&self, datatype: &str) -> Result<Option<&'static str>, Error> {
+        match datatype {
+            "flt" => Ok(Some("f32")),
+            "nul" => Ok(None),
+            "int" | "bln" | "chr" | "null" => Ok(Some("i32")),
+            _ if pointee(datatype).is_some() || self.structs.contains_key(datatype) => Ok(Some("i32")),
+            _ => Err(Error::UndeclaredVariable (datatype.to_string())),
+        }
+    }
+
+    /// Gets the size in bytes of a Boron type in linear memory.
+    fn size(&self, datatype: &str) -> u32 {
+        match self.structs.get(datatype) {
+            Some(fields) => fields.iter().map(|(_, t)| self.size(t)).sum(),
+            None => 4,
+        }
+    }
+
+    /// Gets the offset and type of a struct field.
+    fn field(&self, datatype: &str, field: &str, path: &str) -> Result<(u32, String), Error> {
+        let fields = match self.structs.get(datatype) {
+            Some(f) => f,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        let mut offset = 0;
+        for (f, t) in fields {
+            if f == field {
+                return Ok((offset, t.to_owned()));
+            }
+            offset += self.size(t);
+        }
+        Err(Error::UndeclaredVariable (path.to_string()))
+    }
+
+    /// Reserves static memory for a global variable with a constant initializer.
+    ///
+    /// Returns the variable and its initializer as a literal.
+    fn emit_global(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<(Variable, Expression), Error> {
+        let value = match value {
+            Expression::StructInit { .. } => {
+                if pointee(datatype).is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                value.to_owned()
+            },
+            Expression::Null if !datatype.starts_with("opt ") => return Err(Error::ExpectedNullable (identifier.to_string())),
+            _ => self.evaluate(value, identifier)?,
+        };
+
+        let address = self.data_end;
+        self.ty(datatype)?;
+        let mut bytes = Vec::new();
+        self.constant(&value, datatype, identifier, &mut bytes)?;
+        self.data_end += bytes.len() as u32;
+        let escaped = bytes.iter().map(|b| format!("\\{:02x}", b)).collect::<String>();
+        self.data.push(format!("  (data (i32.const {}) \"{}\")\n", address, escaped));
+
+        let variable = Variable {
+            place: Place::Memory (format!("i32.const {}", address)),
+            datatype: datatype.to_string(),
+        };
+        Ok((variable, value))
+    }
+
+    /// Evaluates a constant expression into a literal.
+    fn evaluate(&self, value: &Expression, identifier: &str) -> Result<Expression, Error> {
+        let constants = |n: &String| Ok(self.constants.get(n).cloned());
+        let functions = |n: &String| Ok(self.const_fns.get(n).cloned());
+        match Evaluator::new(&constants, &functions).evaluate(value)? {
+            Some(v) => Ok(v),
+            None => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Encodes a literal or struct initialization of the given type as little-endian bytes.
+    fn constant(&self, value: &Expression, datatype: &str, identifier: &str, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let word = match (datatype, value) {
+            ("int", Expression::Int (i)) => *i as u32,
+            ("int", Expression::Float (f)) => *f as i32 as u32,
+            ("int", Expression::Char (c)) => *c as u8 as i8 as i32 as u32,
+            ("int", Expression::Bool (b)) => *b as u32,
+            ("flt", Expression::Int (i)) => (*i as f32).to_bits(),
+            ("flt", Expression::Float (f)) => f.to_bits(),
+            ("bln", Expression::Bool (b)) => *b as u32,
+            ("bln", Expression::Int (i)) => (*i != 0) as u32,
+            ("chr", Expression::Char (c)) => *c as u8 as i8 as i32 as u32,
+            ("chr", Expression::Int (i)) => *i as u8 as i8 as i32 as u32,
+            (d, Expression::Null) if pointee(d).is_some() => 0,
+            (d, Expression::StructInit {
+                variables: v,
+            }) if self.structs.contains_key(d) => {
+                for f in v.keys() {
+                    self.field(d, f, &format!("{}.{}", identifier, f))?;
+                }
+                for (f, t) in self.structs[d].iter() {
+                    match v.get(f) {
+                        Some(e) => self.constant(&self.evaluate(e, identifier)?, t, identifier, bytes)?,
+                        None => bytes.extend(vec![0; self.size(t) as usize]),
+                    }
+                }
+                return Ok(());
+            },
+            _ => return Err(Error::NonConstantInitializer (identifier.to_string())),
+        };
+        bytes.extend_from_slice(&word.to_le_bytes());
+        Ok(())
+    }
+
+    /// Emits a function definition.
+    fn emit_function(&mut self, identifier: &str, arguments: &[(String, String, Passing)], return_type: &str, body: &[Expression]) -> Result<(), Error> {
+        self.function = identifier.to_string();
+        self.return_type = return_type.to_string();
+        self.locals = vec![
+            ("$fp".to_string(), "i32"),
+            ("$scratch.i32".to_string(), "i32"),
+            ("$scratch.f32".to_string(), "f32"),
+        ];
+        self.body = Vec::new();
+        self.depth = 2;
+        self.frame = 0;
+        self.escaping = HashSet::new();
+        for statement in body {
+            self.find_escaping(statement);
+        }
+
+        // Parameters passed by value are locals, unless they escape, while references are addresses
+        let mut params = Vec::new();
+        let mut copies = Vec::new();
+        let mut scope = Scope::default();
+        for (name, datatype, passing) in arguments {
+            let param = format!("${}.arg", mangle(name));
+            let place = match passing {
+                Passing::Value => {
+                    // It's ok to use `unwrap` here because parameters cannot be `nul`
+                    params.push(format!("(param {} {})", param, self.ty(datatype)?.unwrap_or("i32")));
+                    match self.escaping.contains(name) {
+                        true => {
+                            let address = self.slot(datatype);
+                            copies.push((address.to_owned(), param, datatype.to_owned()));
+                            Place::Memory (address)
+                        },
+                        false => Place::Local (param),
+                    }
+                },
+                Passing::ConstReference
+                | Passing::Reference => {
+                    params.push(format!("(param {} i32)", param));
+                    Place::Memory (format!("local.get {}", param))
+                },
+            };
+            scope.variables.insert(name.to_owned(), Variable {
+                place,
+                datatype: datatype.to_owned(),
+            });
+        }
+        self.scopes = vec![scope];
+
+        for (address, param, datatype) in copies {
+            self.line(&format!("local.get {}", param));
+            self.emit_store(&Place::Memory (address), &datatype)?;
+        }
+        self.emit_block(body)?;
+        self.scopes.clear();
+
+        // Falling off the end of a function returns the zero value of its return type
+        self.emit_epilogue();
+        if let Some(t) = self.ty(return_type)? {
+            self.line(&format!("{}.const 0", t));
+        }
+
+        let mut definition = format!("  (func ${} (export \"{}\")", mangle(identifier), identifier);
+        for param in params {
+            definition.push(' ');
+            definition.push_str(&param);
+        }
+        if let Some(t) = self.ty(return_type)? {
+            definition.push_str(&format!(" (result {})", t));
+        }
+        definition.push('\n');
+        for (local, t) in self.locals.iter() {
+            definition.push_str(&format!("    (local {} {})\n", local, t));
+        }
+        let frame = format!("i32.const {}", self.frame);
+        for line in self.prologue().iter().chain(self.body.iter()) {
+            definition.push_str(&line.replace("i32.const @frame", &frame));
+            definition.push('\n');
+        }
+        definition.push_str("  )\n");
+        self.definitions.push(definition);
+        Ok(())
+    }
+
+    /// Builds the instructions that allocate a function's frame, trapping if the stack overflows.
+    fn prologue(&self) -> Vec<String> {
+        [
+            "global.get $boron.sp".to_string(),
+            format!("i32.const {}", self.frame),
+            "i32.sub".to_string(),
+            "local.tee $fp".to_string(),
+            "global.set $boron.sp".to_string(),
+            "local.get $fp".to_string(),
+            "global.get $boron.stack_limit".to_string(),
+            "i32.lt_u".to_string(),
+            "if".to_string(),
+            "  unreachable".to_string(),
+            "end".to_string(),
+        ].iter().map(|l| format!("    {}", l)).collect()
+    }
+
+    /// Emits the instructions that free a function's frame.
+    fn emit_epilogue(&mut self) {
+        // The frame's size is not known until the whole function has been emitted
+        self.line("local.get $fp");
+        self.line("i32.const @frame");
+        self.line("i32.add");
+        self.line("global.set $boron.sp");
+    }
+
+    /// Emits a line of code at the current indentation.  Code spanning several lines is indented as a whole.
+    fn line(&mut self, code: &str) {
+        for line in code.lines() {
+            self.body.push(format!("{}{}", "  ".repeat(self.depth), line));
+        }
+    }
+
+    /// Returns a fresh number for naming a local or label.
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    /// Declares a new local of the given WebAssembly type.
+    fn local(&mut self, name: &str, t: &'static str) -> String {
+        let local = format!("${}.{}", mangle(name), self.fresh());
+        self.locals.push((local.to_owned(), t));
+        local
+    }
+
+    /// Reserves space in the frame for a value, returning the instructions that compute its address.
+    fn slot(&mut self, datatype: &str) -> String {
+        let offset = self.frame;
+        self.frame += align(self.size(datatype), 4);
+        format!("local.get $fp\ni32.const {}\ni32.add", offset)
+    }
+
+    /// Binds a variable in the innermost scope.
+    fn bind(&mut self, name: &str, variable: Variable) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.variables.insert(name.to_string(), variable);
+        }
+    }
+
+    /// Looks up a variable, searching the scopes of the current function and then the globals.
+    fn lookup(&self, name: &str) -> Option<Variable> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(v) = scope.variables.get(name) {
+                return Some(v.to_owned());
+            }
+        }
+        self.globals.get(name).cloned()
+    }
+
+    /// Finds the scalar variables that a statement passes by reference.
+    fn find_escaping(&mut self, expr: &Expression) {
+        match expr {
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => {
+                if let Some(signature) = self.functions.get(n).cloned() {
+                    for ((_, passing), arg) in signature.arguments.iter().zip(a.iter()) {
+                        if let (Passing::Reference, Expression::Identifier (id)) = (passing, arg) {
+                            if !id.contains('.') {
+                                self.escaping.insert(id.to_owned());
+                            }
+                        }
+                    }
+                }
+                for arg in a {
+                    self.find_escaping(arg);
+                }
+            },
+            Expression::UnaryOp { expr: e, .. }
+            | Expression::Return (e)
+            | Expression::Assignment { value: e, .. }
+            | Expression::Const { value: e, .. }
+            | Expression::Static { value: e, .. }
+            | Expression::Reassignment { value: e, .. } => self.find_escaping(e),
+            Expression::BinOp { left: l, right: r, .. } => {
+                self.find_escaping(l);
+                self.find_escaping(r);
+            },
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                self.find_escaping(c);
+                self.find_escaping(t);
+                self.find_escaping(f);
+            },
+            Expression::StructInit { variables: v }
+            | Expression::New { variables: v, .. } => {
+                for e in v.values() {
+                    self.find_escaping(e);
+                }
+            },
+            Expression::While { condition: c, body: b }
+            | Expression::If { condition: c, body: b } => {
+                self.find_escaping(c);
+                for e in b {
+                    self.find_escaping(e);
+                }
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                self.find_escaping(c);
+                for e in t.iter().chain(f.iter()) {
+                    self.find_escaping(e);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Gets the Boron type of a variable or struct field (`point.x`).
+    fn path_type(&self, path: &str) -> Result<String, Error> {
+        let (base, fields) = split_path(path);
+        let mut datatype = match self.lookup(base) {
+            Some(v) => v.datatype,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        for field in fields {
+            let structure = pointee(&datatype).unwrap_or(&datatype).to_string();
+            datatype = self.field(&structure, field, path)?.1;
+        }
+        Ok(datatype)
+    }
+
+    /// Finds the place of a variable or struct field (`point.x`), following heap pointers.
+    fn lvalue(&self, path: &str) -> Result<Variable, Error> {
+        let (base, fields) = split_path(path);
+        let mut variable = match self.lookup(base) {
+            Some(v) => v,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        for field in fields {
+            let (address, structure) = match (pointee(&variable.datatype), &variable.place) {
+                (Some(s), _) => (self.load_code(&variable.place, &variable.datatype)?, s.to_string()),
+                (None, Place::Memory (a)) => (a.to_owned(), variable.datatype.to_owned()),
+                (None, Place::Local (_)) => return Err(Error::UndeclaredVariable (path.to_string())),
+            };
+            let (offset, datatype) = self.field(&structure, field, path)?;
+            variable = Variable {
+                place: Place::Memory (format!("{}\ni32.const {}\ni32.add", address, offset)),
+                datatype,
+            };
+        }
+        Ok(variable)
+    }
+
+    /// Builds the instructions that push the value of a variable.  Structs are represented by their address.
+    fn load_code(&self, place: &Place, datatype: &str) -> Result<String, Error> {
+        match place {
+            Place::Local (l) => Ok(format!("local.get {}", l)),
+            Place::Memory (a) if self.structs.contains_key(datatype) => Ok(a.to_owned()),
+            Place::Memory (a) => match self.ty(datatype)? {
+                Some("f32") => Ok(format!("{}\nf32.load", a)),
+                _ => Ok(format!("{}\ni32.load", a)),
+            },
+        }
+    }
+
+    /// Emits a store of the value on top of the stack into a variable.  Structs are copied.
+    fn emit_store(&mut self, place: &Place, datatype: &str) -> Result<(), Error> {
+        match place {
+            Place::Local (l) => self.line(&format!("local.set {}", l)),
+            Place::Memory (a) => {
+                // Set the value aside while computing the address
+                let t = self.ty(datatype)?.unwrap_or("i32");
+                self.line(&format!("local.set $scratch.{}", t));
+                self.line(a);
+                self.line(&format!("local.get $scratch.{}", t));
+                match self.structs.contains_key(datatype) {
+                    true => {
+                        self.line(&format!("i32.const {}", self.size(datatype)));
+                        self.line("memory.copy");
+                    },
+                    false => self.line(&format!("{}.store", t)),
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Emits the instructions that set a variable to the zero value of its type.
+    fn emit_zero(&mut self, place: &Place, datatype: &str) -> Result<(), Error> {
+        match place {
+            Place::Memory (a) if self.structs.contains_key(datatype) => {
+                self.line(a);
+                self.line("i32.const 0");
+                self.line(&format!("i32.const {}", self.size(datatype)));
+                self.line("memory.fill");
+            },
+            _ => {
+                let t = self.ty(datatype)?.unwrap_or("i32");
+                self.line(&format!("{}.const 0", t));
+                self.emit_store(place, datatype)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Converts the value on top of the stack to another type, as C does on assignment.
+    fn convert(&mut self, from: &str, datatype: &str) -> Result<(), Error> {
+        if from == datatype || (pointee(from).is_some() && pointee(from) == pointee(datatype)) {
+            return Ok(());
+        }
+        let instructions: &[&str] = match (from, datatype) {
+            ("null", d) if pointee(d).is_some() => &[],
+            ("int" | "chr", "flt") => &["f32.convert_i32_s"],
+            ("bln", "flt") => &["f32.convert_i32_u"],
+            ("flt", "int") => &["i32.trunc_f32_s"],
+            ("flt", "chr") => &["i32.trunc_f32_s", "i32.extend8_s"],
+            ("int", "chr") => &["i32.extend8_s"],
+            ("chr" | "bln", "int") | ("bln", "chr") => &[],
+            ("int" | "chr", "bln") => &["i32.const 0", "i32.ne"],
+            ("flt", "bln") => &["f32.const 0", "f32.ne"],
+            _ => return Err(Error::CouldNotEmit (format!("{} as {}", from, datatype))),
+        };
+        for instruction in instructions {
+            self.line(instruction);
+        }
+        Ok(())
+    }
+
+    /// Converts the value on top of the stack into an `i32` condition.
+    fn truth(&mut self, datatype: &str) -> Result<(), Error> {
+        match datatype {
+            "flt" => self.convert("flt", "bln")?,
+            _ if self.structs.contains_key(datatype) || datatype == "nul" => return Err(Error::CouldNotEmit ("condition".to_string())),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Emits a block in a new scope.
+    fn emit_block(&mut self, body: &[Expression]) -> Result<(), Error> {
+        self.scopes.push(Scope::default());
+        for statement in body {
+            self.emit_statement(statement)?;
+        }
+
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Declares a new local variable, in memory if it is a struct or is passed by reference.
+    fn declare(&mut self, identifier: &str, datatype: &str) -> Result<Place, Error> {
+        match self.structs.contains_key(datatype) || self.escaping.contains(identifier) {
+            true => Ok(Place::Memory (self.slot(datatype))),
+            // It's ok to use `unwrap_or` here because variables cannot be `nul`
+            false => Ok(Place::Local (self.local(identifier, self.ty(datatype)?.unwrap_or("i32")))),
+        }
+    }
+
+    /// Declares a local variable with an initial value.
+    fn emit_assignment(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<(), Error> {
+        let place = match value {
+            Expression::StructInit {
+                variables: v,
+            } => {
+                if pointee(datatype).is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                let place = self.declare(identifier, datatype)?;
+                if let Place::Memory (a) = &place {
+                    self.emit_fields(a, datatype, v)?;
+                }
+                place
+            },
+            _ => {
+                if matches!(value, Expression::Null) && !datatype.starts_with("opt ") {
+                    return Err(Error::ExpectedNullable (identifier.to_string()));
+                }
+                // Evaluate the value before declaring the variable, which it may not refer to
+                let from = self.emit_expr(value)?;
+                self.convert(&from, datatype)?;
+                let place = self.declare(identifier, datatype)?;
+                self.emit_store(&place, datatype)?;
+                place
+            },
+        };
+        self.bind(identifier, Variable {
+            place,
+            datatype: datatype.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Initializes a struct in memory from a struct initialization (`{ x 1 y 2 }`), zeroing any other fields.
+    fn emit_fields(&mut self, address: &str, datatype: &str, variables: &HashMap<String, Expression>) -> Result<(), Error> {
+        self.emit_zero(&Place::Memory (address.to_string()), datatype)?;
+        // Sort the fields so that they are always initialized in the same order
+        let mut fields = variables.iter().collect::<Vec<(&String, &Expression)>>();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (varname, variable) in fields {
+            let (offset, fieldtype) = self.field(datatype, varname, &format!("{}.{}", datatype, varname))?;
+            let from = self.emit_expr(variable)?;
+            self.convert(&from, &fieldtype)?;
+            let place = Place::Memory (format!("{}\ni32.const {}\ni32.add", address, offset));
+            self.emit_store(&place, &fieldtype)?;
+        }
+        Ok(())
+    }
+
+    /// Emits a single statement.
+    fn emit_statement(&mut self, statement: &Expression) -> Result<(), Error> {
+        match statement {
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } | Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.emit_assignment(d, i, v)?,
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                // A static variable is a global initialized once, when the module is loaded
+                let variable = self.emit_global(d, &format!("{}.{}", self.function, i), v)?.0;
+                self.bind(i, variable);
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                let place = self.declare(i, d)?;
+                self.emit_zero(&place, d)?;
+                self.bind(i, Variable {
+                    place,
+                    datatype: d.to_owned(),
+                });
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => match &**v {
+                Expression::StructInit {
+                    variables: fields,
+                } => {
+                    let mut fields = fields.iter().collect::<Vec<(&String, &Expression)>>();
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                    for (varname, variable) in fields {
+                        let from = self.emit_expr(variable)?;
+                        let field = self.lvalue(&format!("{}.{}", i, varname))?;
+                        self.convert(&from, &field.datatype)?;
+                        self.emit_store(&field.place, &field.datatype)?;
+                    }
+                },
+                _ => {
+                    let from = self.emit_expr(v)?;
+                    let variable = self.lvalue(i)?;
+                    self.convert(&from, &variable.datatype)?;
+                    self.emit_store(&variable.place, &variable.datatype)?;
+                },
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                let n = self.fresh();
+                self.line(&format!("block $while.end.{}", n));
+                self.depth += 1;
+                self.line(&format!("loop $while.cond.{}", n));
+                self.depth += 1;
+                let datatype = self.emit_expr(c)?;
+                self.truth(&datatype)?;
+                self.line("i32.eqz");
+                self.line(&format!("br_if $while.end.{}", n));
+                self.emit_block(b)?;
+                self.line(&format!("br $while.cond.{}", n));
+                self.depth -= 1;
+                self.line("end");
+                self.depth -= 1;
+                self.line("end");
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                let datatype = self.emit_expr(c)?;
+                self.truth(&datatype)?;
+                self.line("if");
+                self.depth += 1;
+                self.emit_block(b)?;
+                self.depth -= 1;
+                self.line("end");
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let datatype = self.emit_expr(c)?;
+                self.truth(&datatype)?;
+                self.line("if");
+                self.depth += 1;
+                self.emit_block(t)?;
+                self.depth -= 1;
+                self.line("else");
+                self.depth += 1;
+                self.emit_block(f)?;
+                self.depth -= 1;
+                self.line("end");
+            },
+            Expression::Return (v) => {
+                let from = self.emit_expr(v)?;
+                let return_type = self.return_type.to_owned();
+                match self.ty(&return_type)? {
+                    Some(_) => self.convert(&from, &return_type)?,
+                    None if from != "nul" => self.line("drop"),
+                    None => (),
+                }
+                self.emit_epilogue();
+                self.line("return");
+            },
+            Expression::Struct { .. }
+            | Expression::FnDeclaration { .. }
+            | Expression::Use (_) => return Err(Error::CouldNotEmit (format!("declaration inside {}", self.function))),
+            _ => {
+                if self.emit_expr(statement)? != "nul" {
+                    self.line("drop");
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Infers the Boron type of an expression without emitting it.
+    fn infer(&self, expr: &Expression) -> Result<String, Error> {
+        match expr {
+            Expression::Int (_) => Ok("int".to_string()),
+            Expression::Float (_) => Ok("flt".to_string()),
+            Expression::Bool (_) => Ok("bln".to_string()),
+            Expression::Char (_) => Ok("chr".to_string()),
+            Expression::Null => Ok("null".to_string()),
+            Expression::Identifier (i) => Ok(self.path_type(i)?),
+            Expression::UnaryOp {
+                op: TokenType::Not,
+                ..
+            } => Ok("bln".to_string()),
+            Expression::UnaryOp {
+                expr: e,
+                ..
+            } => Ok(arithmetic(&self.infer(e)?, "int")),
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => match is_comparison(*o) {
+                true => Ok("bln".to_string()),
+                false => Ok(arithmetic(&self.infer(l)?, &self.infer(r)?)),
+            },
+            Expression::TernaryIfElse {
+                body_true: t,
+                body_false: f,
+                ..
+            } => {
+                let (t, f) = (self.infer(t)?, self.infer(f)?);
+                match (t.as_str(), f.as_str()) {
+                    _ if t == f => Ok(t),
+                    ("null", _) => Ok(f),
+                    (_, "null") => Ok(t),
+                    _ if pointee(&t).is_some() && pointee(&t) == pointee(&f) => Ok(format!("opt {}", pointee(&t).unwrap_or_default())),
+                    _ => Ok(arithmetic(&t, &f)),
+                }
+            },
+            Expression::New {
+                datatype: d,
+                ..
+            } => Ok(format!("own {}", d)),
+            Expression::FnCall {
+                name: n,
+                ..
+            } => match n.as_str() {
+                "print" | "drop" => Ok("nul".to_string()),
+                _ => match self.functions.get(n) {
+                    Some(s) => Ok(s.return_type.to_owned()),
+                    None => Err(Error::UndeclaredFunction (n.to_string())),
+                },
+            },
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
+    }
+
+    /// Emits an expression, returning the Boron type of the value it pushes.
+    fn emit_expr(&mut self, expr: &Expression) -> Result<String, Error> {
+        match expr {
+            Expression::Int (i) => {
+                self.line(&format!("i32.const {}", i));
+                Ok("int".to_string())
+            },
+            Expression::Float (f) => {
+                self.line(&format!("f32.const {}", float(*f)));
+                Ok("flt".to_string())
+            },
+            Expression::Bool (b) => {
+                self.line(&format!("i32.const {}", *b as i32));
+                Ok("bln".to_string())
+            },
+            Expression::Char (c) => {
+                self.line(&format!("i32.const {}", *c as u8 as i8));
+                Ok("chr".to_string())
+            },
+            Expression::Null => {
+                self.line("i32.const 0");
+                Ok("null".to_string())
+            },
+            Expression::Identifier (i) => {
+                let variable = self.lvalue(i)?;
+                let code = self.load_code(&variable.place, &variable.datatype)?;
+                self.line(&code);
+                Ok(variable.datatype)
+            },
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                let from = self.emit_expr(e)?;
+                match o {
+                    TokenType::Not => {
+                        self.truth(&from)?;
+                        self.line("i32.eqz");
+                        Ok("bln".to_string())
+                    },
+                    _ if from == "flt" => {
+                        self.line("f32.neg");
+                        Ok("flt".to_string())
+                    },
+                    _ => {
+                        self.convert(&from, "int")?;
+                        self.line("i32.const -1");
+                        self.line("i32.mul");
+                        Ok("int".to_string())
+                    },
+                }
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => Ok(self.emit_binop(*o, l, r)?),
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let datatype = self.infer(expr)?;
+                let from = self.emit_expr(c)?;
+                self.truth(&from)?;
+                match self.ty(&datatype)? {
+                    Some(t) => self.line(&format!("if (result {})", t)),
+                    None => self.line("if"),
+                }
+                // Each arm is converted to the type of the whole expression
+                for (i, arm) in [t, f].iter().enumerate() {
+                    if i == 1 {
+                        self.line("else");
+                    }
+                    self.depth += 1;
+                    let from = self.emit_expr(arm)?;
+                    self.convert(&from, &datatype)?;
+                    self.depth -= 1;
+                }
+                self.line("end");
+                Ok(datatype)
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                if !self.structs.contains_key(d) {
+                    return Err(Error::UndeclaredVariable (d.to_owned()));
+                }
+                let pointer = self.local("new", "i32");
+                self.line(&format!("i32.const {}", self.size(d)));
+                self.line("call $boron.new");
+                self.line(&format!("local.set {}", pointer));
+                self.emit_fields(&format!("local.get {}", pointer), d, v)?;
+                self.line(&format!("local.get {}", pointer));
+                Ok(format!("own {}", d))
+            },
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => match n.as_str() {
+                "print" => {
+                    for arg in a {
+                        let datatype = self.emit_expr(arg)?;
+                        match datatype.as_str() {
+                            "int" | "flt" | "bln" | "chr" => self.line(&format!("call $boron.print_{}", datatype)),
+                            _ => return Err(Error::CouldNotEmit (format!("print of {}", datatype))),
+                        }
+                    }
+                    Ok("nul".to_string())
+                },
+                "drop" => {
+                    for arg in a {
+                        let id = match arg {
+                            Expression::Identifier (id) => id,
+                            _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+                        };
+                        let variable = self.lvalue(id)?;
+                        if pointee(&variable.datatype).is_none() {
+                            return Err(Error::ExpectedPointer (id.to_owned()));
+                        }
+                        let code = self.load_code(&variable.place, &variable.datatype)?;
+                        self.line(&code);
+                        self.line("call $boron.free");
+                        self.line("i32.const 0");
+                        self.emit_store(&variable.place, &variable.datatype)?;
+                    }
+                    Ok("nul".to_string())
+                },
+                _ => Ok(self.emit_call(n, a)?),
+            },
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
+    }
+
+    /// Emits a binary operation, promoting its operands as C does.
+    fn emit_binop(&mut self, op: TokenType, left: &Expression, right: &Expression) -> Result<String, Error> {
+        let (l, r) = (self.infer(left)?, self.infer(right)?);
+
+        // Pointers may only be compared for equality
+        let pointers = [&l, &r].iter().any(|t| pointee(t).is_some() || *t == "null");
+        let datatype = match (l.as_str(), r.as_str()) {
+            _ if pointers => {
+                if op != TokenType::Equal {
+                    return Err(Error::InvalidOperator (format!("{:?}", op)));
+                }
+                "int".to_string()
+            },
+            ("chr", "chr") | ("bln", "bln") if is_comparison(op) => "int".to_string(),
+            _ => arithmetic(&l, &r),
+        };
+
+        let from = self.emit_expr(left)?;
+        if !pointers {
+            self.convert(&from, &datatype)?;
+        }
+        let from = self.emit_expr(right)?;
+        if !pointers {
+            self.convert(&from, &datatype)?;
+        }
+
+        let (t, float) = match datatype.as_str() {
+            "flt" => ("f32", true),
+            _ => ("i32", false),
+        };
+        let instruction = match (op, float) {
+            (TokenType::Plus, _) => "add",
+            (TokenType::Minus, _) => "sub",
+            (TokenType::Multiply, _) => "mul",
+            (TokenType::Divide, false) => "div_s",
+            (TokenType::Divide, true) => "div",
+            (TokenType::Greater, false) => "gt_s",
+            (TokenType::Less, false) => "lt_s",
+            (TokenType::GreaterEqual, false) => "ge_s",
+            (TokenType::LessEqual, false) => "le_s",
+            (TokenType::Greater, true) => "gt",
+            (TokenType::Less, true) => "lt",
+            (TokenType::GreaterEqual, true) => "ge",
+            (TokenType::LessEqual, true) => "le",
+            (TokenType::Equal, _) => "eq",
+            _ => return Err(Error::InvalidOperator (format!("{:?}", op))),
+        };
+        self.line(&format!("{}.{}", t, instruction));
+        match is_comparison(op) {
+            true => Ok("bln".to_string()),
+            false => Ok(datatype),
+        }
+    }
+
+    /// Emits a function call, passing each argument according to the function's signature.
+    fn emit_call(&mut self, name: &str, args: &[Expression]) -> Result<String, Error> {
+        let signature = match self.functions.get(name) {
+            Some(s) => s.to_owned(),
+            None => return Err(Error::UndeclaredFunction (name.to_string())),
+        };
+        if signature.arguments.len() != args.len() {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+
+        for ((datatype, passing), arg) in signature.arguments.iter().zip(args.iter()) {
+            match (passing, arg) {
+                (Passing::Value, _) => {
+                    let from = self.emit_expr(arg)?;
+                    self.convert(&from, datatype)?;
+                },
+                (_, Expression::Identifier (id)) => {
+                    let variable = self.lvalue(id)?;
+                    match (&variable.place, pointee(&variable.datatype)) {
+                        // A heap pointer already refers to its struct, so it is passed as-is
+                        (place, Some(s)) if s == datatype => {
+                            let code = self.load_code(place, &variable.datatype)?;
+                            self.line(&code);
+                        },
+                        (Place::Memory (a), _) => self.line(a),
+                        (Place::Local (_), _) => return Err(Error::CouldNotEmit (format!("reference to {}", id))),
+                    }
+                },
+                (Passing::Reference, _) => return Err(Error::ExpectedReferenceArgument (name.to_string())),
+                // Any other struct is already in memory, so its address is passed
+                (Passing::ConstReference, _) => {
+                    self.emit_expr(arg)?;
+                },
+            }
+        }
+        self.line(&format!("call ${}", mangle(name)));
+
+        // A returned struct lies in the callee's freed frame, so copy it into this one
+        let return_type = signature.return_type;
+        if self.structs.contains_key(&return_type) {
+            let address = self.slot(&return_type);
+            self.emit_store(&Place::Memory (address.to_owned()), &return_type)?;
+            self.line(&address);
+        }
+        Ok(return_type)
+    }
+}
+
+
+/// Gets the struct a pointer type (`own Node` or `opt Node`) points to.
+fn pointee(datatype: &str) -> Option<&str> {
+    datatype.strip_prefix("own ").or_else(|| datatype.strip_prefix("opt "))
+}
+
+
+/// Gets the type of an arithmetic operation on two types, promoting as C does.
+fn arithmetic(left: &str, right: &str) -> String {
+    match left == "flt" || right == "flt" {
+        true => "flt".to_string(),
+        false => "int".to_string(),
+    }
+}
+
+
+/// Checks if an operator is a comparison.
+fn is_comparison(op: TokenType) -> bool {
+    matches!(
+        op,
+        TokenType::Greater
+        | TokenType::Less
+        | TokenType::Equal
+        | TokenType::GreaterEqual
+        | TokenType::LessEqual
+    )
+}
+
+
+/// Rounds a size up to a multiple of the given alignment.
+fn align(size: u32, alignment: u32) -> u32 {
+    size.div_ceil(alignment) * alignment
+}
+
+
+/// Formats a floating-point constant so that it is read back exactly.
+fn float(f: f32) -> String {
+    match f {
+        _ if f.is_nan() => "nan".to_string(),
+        _ if f.is_infinite() && f > 0.0 => "inf".to_string(),
+        _ if f.is_infinite() => "-inf".to_string(),
+        _ => format!("{:?}", f),
+    }
+}
+
+
+/// Converts a Boron identifier into a WebAssembly identifier, escaping characters that are not allowed.
+fn mangle(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            true => c.to_string(),
+            false => format!("_{:x}_", c as u32),
+        })
+        .collect()
+}
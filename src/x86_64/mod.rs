@@ -0,0 +1,1274 @@
+//! Emits x86-64 assembly (`.s`) in GNU assembler syntax for the Boron compiler.
+//!
+//! Code generation is deliberately naive: every variable lives in a stack
+//! slot, every expression leaves its value in `%rax` (or `%xmm0` for floats),
+//! and temporaries are pushed to the stack.  Functions follow the System V
+//! ABI, structs are passed by pointer, and `print` calls `printf` from libc.
+
+
+use std::collections::HashMap;
+
+use crate::parser::{
+    Expression,
+    Passing,
+    TokenType,
+};
+
+use crate::emitter::include_modules;
+
+use crate::consteval::Evaluator;
+
+use crate::desugar::{desugar, split_path};
+
+use crate::error::Error;
+
+
+/// Registers used to pass integer and pointer arguments, in order.
+const INTEGER_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// The number of `%xmm` registers used to pass floating-point arguments.
+const FLOAT_REGISTERS: usize = 8;
+
+
+/// Format strings and runtime support emitted into every program.
+const PRELUDE: &str = "    .section .rodata
+.Lformat.int:
+    .string \"%d\\n\"
+.Lformat.flt:
+    .string \"%f\\n\"
+.Lformat.chr:
+    .string \"%c\\n\"
+.Lformat.true:
+    .string \"true\\n\"
+.Lformat.false:
+    .string \"false\\n\"
+.Lformat.nul:
+    .string \"\\n\"
+.Lmessage.oom:
+    .string \"Out of memory\\n\"
+
+    .text
+boron.new:
+    pushq %rbp
+    movq %rsp, %rbp
+    call malloc@PLT
+    testq %rax, %rax
+    je .Lnew.oom
+    popq %rbp
+    ret
+.Lnew.oom:
+    movl $2, %edi
+    leaq .Lmessage.oom(%rip), %rsi
+    movl $14, %edx
+    call write@PLT
+    movl $1, %edi
+    call exit@PLT
+";
+
+
+/// Describes where a variable is stored.
+#[derive(Clone, Debug)]
+enum Place {
+    // Stack slot at the given offset from `%rbp`
+    Frame (i32),
+    // Labelled global data
+    Global (String),
+    // Stack slot holding the address of the variable, for references
+    Indirect (i32),
+}
+
+
+/// Holds the place and Boron type of a variable.
+#[derive(Clone, Debug)]
+struct Variable {
+    place: Place,
+    datatype: String,
+}
+
+
+/// Holds the variables of a block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, Variable>,
+}
+
+
+/// Holds the parameters and return type of a function.
+#[derive(Clone)]
+struct Signature {
+    arguments: Vec<(String, Passing)>,
+    return_type: String,
+}
+
+
+/// Emits x86-64 assembly for a Boron program.
+#[derive(Default)]
+pub struct Emitter {
+    // Fields of each struct, sorted by name
+    structs: HashMap<String, Vec<(String, String)>>,
+    functions: HashMap<String, Signature>,
+    globals: HashMap<String, Variable>,
+    // Values of top-level constants and declarations of `const` functions, for evaluating initializers
+    constants: HashMap<String, Expression>,
+    const_fns: HashMap<String, Expression>,
+    data: Vec<String>,
+    definitions: Vec<String>,
+    // State of the function being emitted
+    function: String,
+    return_type: String,
+    body: Vec<String>,
+    frame: i32,
+    // Number of temporaries currently pushed, which decides the stack's alignment at calls
+    pushed: usize,
+    // Slots holding the return value and, for struct results, the caller's destination
+    result: i32,
+    destination: i32,
+    scopes: Vec<Scope>,
+    // Counter used to give labels unique names
+    next: usize,
+}
+
+/// Provides an interface for the x86-64 emitter.
+impl Emitter {
+    /// Constructs a new x86-64 emitter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compiles a program, including the modules it uses, into an assembly file.
+    pub fn compile_exe(&mut self, expressions: Vec<Expression>) -> Result<String, Error> {
+        let expressions = desugar(include_modules(expressions)?)?;
+
+        // Register every struct and function first, so they may be used before they are declared
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct {
+                    identifier: i,
+                    variables: v,
+                    ..
+                } => {
+                    let mut fields = v.iter()
+                        .map(|(f, t)| (f.to_owned(), t.to_owned()))
+                        .collect::<Vec<(String, String)>>();
+                    fields.sort();
+                    self.structs.insert(i.to_owned(), fields);
+                },
+                Expression::FnDeclaration {
+                    identifier: i,
+                    arguments: a,
+                    return_type: r,
+                    constant: c,
+                    ..
+                } => {
+                    let signature = Signature {
+                        arguments: a.iter().map(|(_, t, p)| (t.to_owned(), *p)).collect(),
+                        return_type: r.to_owned(),
+                    };
+                    self.functions.insert(i.to_owned(), signature);
+                    if *c {
+                        self.const_fns.insert(i.to_owned(), expression.to_owned());
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        // Emit globals before functions, which may refer to them
+        for expression in expressions.iter() {
+            match expression {
+                Expression::Struct { .. }
+                | Expression::FnDeclaration { .. } => (),
+                Expression::Assignment {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } | Expression::Static {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let variable = self.emit_global(d, i, v)?.0;
+                    self.globals.insert(i.to_owned(), variable);
+                },
+                Expression::Const {
+                    datatype: d,
+                    identifier: i,
+                    value: v,
+                } => {
+                    let (variable, value) = self.emit_global(d, i, v)?;
+                    self.constants.insert(i.to_owned(), value);
+                    self.globals.insert(i.to_owned(), variable);
+                },
+                _ => return Err(Error::StatementOutsideFunction),
+            }
+        }
+
+        for expression in expressions.iter() {
+            if let Expression::FnDeclaration {
+                identifier: i,
+                arguments: a,
+                return_type: r,
+                body: b,
+                ..
+            } = expression {
+                self.emit_function(i, a, r, b)?;
+            }
+        }
+
+        let mut output = String::from(PRELUDE);
+        if !self.data.is_empty() {
+            output.push_str("\n    .data\n");
+            for data in self.data.iter() {
+                output.push_str(data);
+            }
+        }
+        output.push_str("\n    .text\n");
+        for definition in self.definitions.iter() {
+            output.push_str(definition);
+        }
+        output.push_str("\n    .section .note.GNU-stack,\"\",@progbits\n");
+        Ok(output)
+    }
+
+    /// Gets the size in bytes of a Boron type.
+    fn size(&self, datatype: &str) -> Result<i32, Error> {
+        match datatype {
+            "int" | "flt" => Ok(4),
+            "bln" | "chr" => Ok(1),
+            _ if pointee(datatype).is_some() => Ok(8),
+            _ => match self.structs.get(datatype) {
+                Some(fields) => {
+                    let mut size = 0;
+                    for (_, t) in fields {
+                        size = align(size, self.alignment(t)?) + self.size(t)?;
+                    }
+                    Ok(align(size, self.alignment(datatype)?))
+                },
+                None => Err(Error::UndeclaredVariable (datatype.to_string())),
+            },
+        }
+    }
+
+    /// Gets the alignment in bytes of a Boron type, which is that of its largest field for a struct.
+    fn alignment(&self, datatype: &str) -> Result<i32, Error> {
+        match self.structs.get(datatype) {
+            Some(fields) => {
+                let mut alignment = 1;
+                for (_, t) in fields {
+                    alignment = alignment.max(self.alignment(t)?);
+                }
+                Ok(alignment)
+            },
+            None => Ok(self.size(datatype)?),
+        }
+    }
+
+    /// Gets the offset and type of a struct field.
+    fn field(&self, datatype: &str, field: &str, path: &str) -> Result<(i32, String), Error> {
+        let fields = match self.structs.get(datatype) {
+            Some(f) => f,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        let mut offset = 0;
+        for (f, t) in fields {
+            offset = align(offset, self.alignment(t)?);
+            if f == field {
+                return Ok((offset, t.to_owned()));
+            }
+            offset += self.size(t)?;
+        }
+        Err(Error::UndeclaredVariable (path.to_string()))
+    }
+
+    /// Emits labelled data for a global variable with a constant initializer.
+    ///
+    /// Returns the variable and its initializer as a literal.
+    fn emit_global(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<(Variable, Expression), Error> {
+        let value = match value {
+            Expression::StructInit { .. } => {
+                if pointee(datatype).is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                value.to_owned()
+            },
+            Expression::Null if !datatype.starts_with("opt ") => return Err(Error::ExpectedNullable (identifier.to_string())),
+            _ => self.evaluate(value, identifier)?,
+        };
+
+        let label = format!("boron.global.{}", mangle(identifier));
+        let mut bytes = Vec::new();
+        self.constant(&value, datatype, identifier, &mut bytes)?;
+        let bytes = bytes.iter().map(|b| b.to_string()).collect::<Vec<String>>();
+        self.data.push(format!("    .balign 8\n{}:\n    .byte {}\n", label, bytes.join(", ")));
+
+        let variable = Variable {
+            place: Place::Global (label),
+            datatype: datatype.to_string(),
+        };
+        Ok((variable, value))
+    }
+
+    /// Evaluates a constant expression into a literal.
+    fn evaluate(&self, value: &Expression, identifier: &str) -> Result<Expression, Error> {
+        let constants = |n: &String| Ok(self.constants.get(n).cloned());
+        let functions = |n: &String| Ok(self.const_fns.get(n).cloned());
+        match Evaluator::new(&constants, &functions).evaluate(value)? {
+            Some(v) => Ok(v),
+            None => Err(Error::NonConstantInitializer (identifier.to_string())),
+        }
+    }
+
+    /// Encodes a literal or struct initialization of the given type as little-endian bytes.
+    fn constant(&self, value: &Expression, datatype: &str, identifier: &str, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let encoded = match (datatype, value) {
+            ("int", Expression::Int (i)) => i.to_le_bytes().to_vec(),
+            ("int", Expression::Float (f)) => (*f as i32).to_le_bytes().to_vec(),
+            ("int", Expression::Char (c)) => (*c as u8 as i8 as i32).to_le_bytes().to_vec(),
+            ("int", Expression::Bool (b)) => (*b as i32).to_le_bytes().to_vec(),
+            ("flt", Expression::Int (i)) => (*i as f32).to_le_bytes().to_vec(),
+            ("flt", Expression::Float (f)) => f.to_le_bytes().to_vec(),
+            ("bln", Expression::Bool (b)) => vec![*b as u8],
+            ("bln", Expression::Int (i)) => vec![(*i != 0) as u8],
+            ("chr", Expression::Char (c)) => vec![*c as u8],
+            ("chr", Expression::Int (i)) => vec![*i as u8],
+            (d, Expression::Null) if pointee(d).is_some() => vec![0; 8],
+            (d, Expression::StructInit {
+                variables: v,
+            }) if self.structs.contains_key(d) => {
+                for f in v.keys() {
+                    self.field(d, f, &format!("{}.{}", identifier, f))?;
+                }
+                let start = bytes.len();
+                for (f, t) in self.structs[d].iter() {
+                    let offset = self.field(d, f, identifier)?.0 as usize;
+                    bytes.resize(start + offset, 0);
+                    match v.get(f) {
+                        Some(e) => self.constant(&self.evaluate(e, identifier)?, t, identifier, bytes)?,
+                        None => bytes.resize(start + offset + self.size(t)? as usize, 0),
+                    }
+                }
+                bytes.resize(start + self.size(d)? as usize, 0);
+                return Ok(());
+            },
+            _ => return Err(Error::NonConstantInitializer (identifier.to_string())),
+        };
+        bytes.extend(encoded);
+        Ok(())
+    }
+
+    /// Emits a function definition.
+    fn emit_function(&mut self, identifier: &str, arguments: &[(String, String, Passing)], return_type: &str, body: &[Expression]) -> Result<(), Error> {
+        self.function = identifier.to_string();
+        self.return_type = return_type.to_string();
+        self.body = Vec::new();
+        self.frame = 0;
+        self.pushed = 0;
+        self.result = self.slot("int")?;
+        self.destination = self.slot("int")?;
+
+        // Store every parameter in a stack slot, taking the destination of a struct result first
+        let mut scope = Scope::default();
+        let (mut integers, mut floats, mut stack) = (0, 0, 0);
+        let mut parameters = Vec::new();
+        if self.structs.contains_key(return_type) {
+            parameters.push((self.destination, false));
+        }
+        for (name, datatype, passing) in arguments {
+            let offset = self.slot("int")?;
+            let place = match passing {
+                Passing::Value => Place::Frame (offset),
+                Passing::ConstReference
+                | Passing::Reference => Place::Indirect (offset),
+            };
+            parameters.push((offset, datatype == "flt" && *passing == Passing::Value));
+            scope.variables.insert(name.to_owned(), Variable {
+                place,
+                datatype: datatype.to_owned(),
+            });
+        }
+        for (offset, float) in parameters {
+            match float {
+                true if floats < FLOAT_REGISTERS => {
+                    self.line(&format!("movss %xmm{}, {}(%rbp)", floats, offset));
+                    floats += 1;
+                },
+                false if integers < INTEGER_REGISTERS.len() => {
+                    self.line(&format!("movq {}, {}(%rbp)", INTEGER_REGISTERS[integers], offset));
+                    integers += 1;
+                },
+                _ => {
+                    // The remaining arguments were pushed by the caller, above the return address
+                    self.line(&format!("movq {}(%rbp), %rax", 16 + 8 * stack));
+                    self.line(&format!("movq %rax, {}(%rbp)", offset));
+                    stack += 1;
+                },
+            }
+        }
+        self.scopes = vec![scope];
+
+        self.emit_block(body)?;
+        self.scopes.clear();
+
+        // Falling off the end of a function returns the zero value of its return type
+        match return_type {
+            "flt" => self.line("xorps %xmm0, %xmm0"),
+            _ if self.structs.contains_key(return_type) => self.line(&format!("movq {}(%rbp), %rax", self.destination)),
+            _ => self.line("xorl %eax, %eax"),
+        }
+
+        let name = mangle(identifier);
+        let mut definition = format!("\n    .globl {}\n    .type {}, @function\n{}:\n", name, name, name);
+        definition.push_str("    pushq %rbp\n    movq %rsp, %rbp\n");
+        definition.push_str(&format!("    subq ${}, %rsp\n", align(-self.frame, 16)));
+        for line in self.body.iter() {
+            definition.push_str(line);
+            definition.push('\n');
+        }
+        definition.push_str(&format!(".Lreturn.{}:\n", name));
+        definition.push_str("    movq %rbp, %rsp\n    popq %rbp\n    ret\n");
+        self.definitions.push(definition);
+        Ok(())
+    }
+
+    /// Emits an instruction.
+    fn line(&mut self, instruction: &str) {
+        self.body.push(format!("    {}", instruction));
+    }
+
+    /// Emits a label.
+    fn label(&mut self, label: &str) {
+        self.body.push(format!("{}:", label));
+    }
+
+    /// Returns a fresh number for naming a label.
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    /// Reserves a stack slot for a value, returning its offset from `%rbp`.  Scalars take eight bytes.
+    fn slot(&mut self, datatype: &str) -> Result<i32, Error> {
+        let size = match self.structs.contains_key(datatype) {
+            true => align(self.size(datatype)?, 8),
+            false => 8,
+        };
+        self.frame -= size;
+        Ok(self.frame)
+    }
+
+    /// Pushes the value in `%rax` or `%xmm0` onto the stack.
+    fn push(&mut self, datatype: &str) {
+        match datatype {
+            "flt" => {
+                self.line("subq $8, %rsp");
+                self.line("movss %xmm0, (%rsp)");
+            },
+            _ => self.line("pushq %rax"),
+        }
+        self.pushed += 1;
+    }
+
+    /// Pops a value pushed by `push` into `%rax` or `%xmm0`.
+    fn pop(&mut self, datatype: &str) {
+        match datatype {
+            "flt" => {
+                self.line("movss (%rsp), %xmm0");
+                self.line("addq $8, %rsp");
+            },
+            _ => self.line("popq %rax"),
+        }
+        self.pushed -= 1;
+    }
+
+    /// Binds a variable in the innermost scope.
+    fn bind(&mut self, name: &str, variable: Variable) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.variables.insert(name.to_string(), variable);
+        }
+    }
+
+    /// Looks up a variable, searching the scopes of the current function and then the globals.
+    fn lookup(&self, name: &str) -> Option<Variable> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(v) = scope.variables.get(name) {
+                return Some(v.to_owned());
+            }
+        }
+        self.globals.get(name).cloned()
+    }
+
+    /// Gets the Boron type of a variable or struct field (`point.x`).
+    fn path_type(&self, path: &str) -> Result<String, Error> {
+        let (base, fields) = split_path(path);
+        let mut datatype = match self.lookup(base) {
+            Some(v) => v.datatype,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        for field in fields {
+            let structure = pointee(&datatype).unwrap_or(&datatype).to_string();
+            datatype = self.field(&structure, field, path)?.1;
+        }
+        Ok(datatype)
+    }
+
+    /// Emits the instructions that put the address of a variable or struct field (`point.x`) in `%rax`.
+    ///
+    /// Only `%rax` is changed, so a value may be kept in `%rcx` or `%xmm0` meanwhile.
+    fn emit_address(&mut self, path: &str) -> Result<String, Error> {
+        let (base, fields) = split_path(path);
+        let variable = match self.lookup(base) {
+            Some(v) => v,
+            None => return Err(Error::UndeclaredVariable (path.to_string())),
+        };
+        match &variable.place {
+            Place::Frame (o) => self.line(&format!("leaq {}(%rbp), %rax", o)),
+            Place::Global (l) => self.line(&format!("leaq {}(%rip), %rax", l)),
+            Place::Indirect (o) => self.line(&format!("movq {}(%rbp), %rax", o)),
+        }
+
+        let mut datatype = variable.datatype;
+        for field in fields {
+            // A heap pointer is followed to the struct it points to
+            let structure = match pointee(&datatype) {
+                Some(s) => {
+                    self.line("movq (%rax), %rax");
+                    s.to_string()
+                },
+                None => datatype,
+            };
+            let (offset, fieldtype) = self.field(&structure, field, path)?;
+            if offset != 0 {
+                self.line(&format!("addq ${}, %rax", offset));
+            }
+            datatype = fieldtype;
+        }
+        Ok(datatype)
+    }
+
+    /// Emits a load of a value of the given type from the address in `%rax`.  Structs are left as their address.
+    fn emit_load(&mut self, datatype: &str) {
+        match datatype {
+            "int" => self.line("movl (%rax), %eax"),
+            "flt" => self.line("movss (%rax), %xmm0"),
+            "bln" => self.line("movzbl (%rax), %eax"),
+            "chr" => self.line("movsbl (%rax), %eax"),
+            _ if pointee(datatype).is_some() => self.line("movq (%rax), %rax"),
+            _ => (),
+        }
+    }
+
+    /// Emits a store of the value in `%rcx` or `%xmm0` to the address in `%rax`.  Structs are copied from the address in `%rcx`.
+    fn emit_store(&mut self, datatype: &str) -> Result<(), Error> {
+        match datatype {
+            "int" => self.line("movl %ecx, (%rax)"),
+            "flt" => self.line("movss %xmm0, (%rax)"),
+            "bln" | "chr" => self.line("movb %cl, (%rax)"),
+            _ if pointee(datatype).is_some() => self.line("movq %rcx, (%rax)"),
+            _ => {
+                self.line("movq %rcx, %rsi");
+                self.line("movq %rax, %rdi");
+                self.line(&format!("movq ${}, %rcx", self.size(datatype)?));
+                self.line("rep movsb");
+            },
+        }
+        Ok(())
+    }
+
+    /// Emits a store of the value just computed into a variable or struct field.
+    fn emit_assign(&mut self, path: &str, from: &str) -> Result<(), Error> {
+        let datatype = self.path_type(path)?;
+        self.convert(from, &datatype)?;
+        if datatype != "flt" {
+            self.line("movq %rax, %rcx");
+        }
+        self.emit_address(path)?;
+        self.emit_store(&datatype)?;
+        Ok(())
+    }
+
+    /// Emits the instructions that zero the memory of the given type at the address in `%rax`.
+    fn emit_zero(&mut self, datatype: &str) -> Result<(), Error> {
+        self.line("movq %rax, %rdi");
+        self.line("xorl %eax, %eax");
+        self.line(&format!("movq ${}, %rcx", self.size(datatype)?));
+        self.line("rep stosb");
+        Ok(())
+    }
+
+    /// Converts the value in `%rax` or `%xmm0` to another type, as C does on assignment.
+    fn convert(&mut self, from: &str, datatype: &str) -> Result<(), Error> {
+        if from == datatype || (pointee(from).is_some() && pointee(from) == pointee(datatype)) {
+            return Ok(());
+        }
+        let instructions: &[&str] = match (from, datatype) {
+            ("null", d) if pointee(d).is_some() => &[],
+            ("int" | "chr" | "bln", "flt") => &["cvtsi2ssl %eax, %xmm0"],
+            ("flt", "int") => &["cvttss2si %xmm0, %eax"],
+            ("flt", "chr") => &["cvttss2si %xmm0, %eax", "movsbl %al, %eax"],
+            ("int", "chr") => &["movsbl %al, %eax"],
+            ("chr" | "bln", "int") | ("bln", "chr") => &[],
+            ("int" | "chr", "bln") => &["testl %eax, %eax", "setne %al", "movzbl %al, %eax"],
+            // NaN is true, as it is not equal to zero
+            ("flt", "bln") => &[
+                "xorps %xmm1, %xmm1",
+                "ucomiss %xmm1, %xmm0",
+                "setne %al",
+                "setp %cl",
+                "orb %cl, %al",
+                "movzbl %al, %eax",
+            ],
+            _ => return Err(Error::CouldNotEmit (format!("{} as {}", from, datatype))),
+        };
+        for instruction in instructions {
+            self.line(instruction);
+        }
+        Ok(())
+    }
+
+    /// Emits a jump to a label if the value of a condition is false.
+    fn emit_jump_unless(&mut self, condition: &Expression, label: &str) -> Result<(), Error> {
+        let datatype = self.emit_expr(condition)?;
+        match datatype.as_str() {
+            "flt" => {
+                self.convert("flt", "bln")?;
+                self.line("testl %eax, %eax");
+            },
+            "int" | "bln" | "chr" => self.line("testl %eax, %eax"),
+            _ if pointee(&datatype).is_some() => self.line("testq %rax, %rax"),
+            _ => return Err(Error::CouldNotEmit ("condition".to_string())),
+        }
+        self.line(&format!("je {}", label));
+        Ok(())
+    }
+
+    /// Emits a block in a new scope.
+    fn emit_block(&mut self, body: &[Expression]) -> Result<(), Error> {
+        self.scopes.push(Scope::default());
+        for statement in body {
+            self.emit_statement(statement)?;
+        }
+
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Declares a local variable with an initial value.
+    fn emit_assignment(&mut self, datatype: &str, identifier: &str, value: &Expression) -> Result<(), Error> {
+        let offset = match value {
+            Expression::StructInit {
+                variables: v,
+            } => {
+                if pointee(datatype).is_some() {
+                    return Err(Error::ExpectedNew (identifier.to_string()));
+                }
+                let offset = self.slot(datatype)?;
+                self.line(&format!("leaq {}(%rbp), %rax", offset));
+                self.emit_fields(datatype, v)?;
+                offset
+            },
+            _ => {
+                if matches!(value, Expression::Null) && !datatype.starts_with("opt ") {
+                    return Err(Error::ExpectedNullable (identifier.to_string()));
+                }
+                // Evaluate the value before declaring the variable, which it may not refer to
+                let from = self.emit_expr(value)?;
+                self.convert(&from, datatype)?;
+                if datatype != "flt" {
+                    self.line("movq %rax, %rcx");
+                }
+                let offset = self.slot(datatype)?;
+                self.line(&format!("leaq {}(%rbp), %rax", offset));
+                self.emit_store(datatype)?;
+                offset
+            },
+        };
+        self.bind(identifier, Variable {
+            place: Place::Frame (offset),
+            datatype: datatype.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Initializes the struct at the address in `%rax` from a struct initialization (`{ x 1 y 2 }`), zeroing any other fields.
+    ///
+    /// The address is left in `%rax`.
+    fn emit_fields(&mut self, datatype: &str, variables: &HashMap<String, Expression>) -> Result<(), Error> {
+        self.push("int");
+        self.emit_zero(datatype)?;
+
+        // Sort the fields so that they are always initialized in the same order
+        let mut fields = variables.iter().collect::<Vec<(&String, &Expression)>>();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (varname, variable) in fields {
+            let (offset, fieldtype) = self.field(datatype, varname, &format!("{}.{}", datatype, varname))?;
+            let from = self.emit_expr(variable)?;
+            self.convert(&from, &fieldtype)?;
+            if fieldtype != "flt" {
+                self.line("movq %rax, %rcx");
+            }
+            self.line("movq (%rsp), %rax");
+            if offset != 0 {
+                self.line(&format!("addq ${}, %rax", offset));
+            }
+            self.emit_store(&fieldtype)?;
+        }
+        self.pop("int");
+        Ok(())
+    }
+
+    /// Emits a single statement.
+    fn emit_statement(&mut self, statement: &Expression) -> Result<(), Error> {
+        match statement {
+            Expression::Assignment {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } | Expression::Const {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => self.emit_assignment(d, i, v)?,
+            Expression::Static {
+                datatype: d,
+                identifier: i,
+                value: v,
+            } => {
+                // A static variable is a global initialized once, when the program is loaded
+                let variable = self.emit_global(d, &format!("{}.{}", self.function, i), v)?.0;
+                self.bind(i, variable);
+            },
+            Expression::Declaration {
+                datatype: d,
+                identifier: i,
+            } => {
+                let offset = self.slot(d)?;
+                self.line(&format!("leaq {}(%rbp), %rax", offset));
+                self.emit_zero(d)?;
+                self.bind(i, Variable {
+                    place: Place::Frame (offset),
+                    datatype: d.to_owned(),
+                });
+            },
+            Expression::Reassignment {
+                identifier: i,
+                value: v,
+            } => match &**v {
+                Expression::StructInit {
+                    variables: fields,
+                } => {
+                    let mut fields = fields.iter().collect::<Vec<(&String, &Expression)>>();
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                    for (varname, variable) in fields {
+                        let from = self.emit_expr(variable)?;
+                        self.emit_assign(&format!("{}.{}", i, varname), &from)?;
+                    }
+                },
+                _ => {
+                    let from = self.emit_expr(v)?;
+                    self.emit_assign(i, &from)?;
+                },
+            },
+            Expression::While {
+                condition: c,
+                body: b,
+            } => {
+                let n = self.fresh();
+                self.label(&format!(".Lwhile.cond.{}", n));
+                self.emit_jump_unless(c, &format!(".Lwhile.end.{}", n))?;
+                self.emit_block(b)?;
+                self.line(&format!("jmp .Lwhile.cond.{}", n));
+                self.label(&format!(".Lwhile.end.{}", n));
+            },
+            Expression::If {
+                condition: c,
+                body: b,
+            } => {
+                let n = self.fresh();
+                self.emit_jump_unless(c, &format!(".Lif.end.{}", n))?;
+                self.emit_block(b)?;
+                self.label(&format!(".Lif.end.{}", n));
+            },
+            Expression::IfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let n = self.fresh();
+                self.emit_jump_unless(c, &format!(".Lif.else.{}", n))?;
+                self.emit_block(t)?;
+                self.line(&format!("jmp .Lif.end.{}", n));
+                self.label(&format!(".Lif.else.{}", n));
+                self.emit_block(f)?;
+                self.label(&format!(".Lif.end.{}", n));
+            },
+            Expression::Return (v) => {
+                let from = self.emit_expr(v)?;
+                let return_type = self.return_type.to_owned();
+                match return_type.as_str() {
+                    "nul" => (),
+                    "flt" => {
+                        self.convert(&from, "flt")?;
+                        self.line(&format!("movss %xmm0, {}(%rbp)", self.result));
+                    },
+                    _ if self.structs.contains_key(&return_type) => {
+                        self.line("movq %rax, %rcx");
+                        self.line(&format!("movq {}(%rbp), %rax", self.destination));
+                        self.emit_store(&return_type)?;
+                        self.line(&format!("movq {}(%rbp), %rax", self.destination));
+                        self.line(&format!("movq %rax, {}(%rbp)", self.result));
+                    },
+                    _ => {
+                        self.convert(&from, &return_type)?;
+                        self.line(&format!("movq %rax, {}(%rbp)", self.result));
+                    },
+                }
+                match return_type.as_str() {
+                    "nul" => (),
+                    "flt" => self.line(&format!("movss {}(%rbp), %xmm0", self.result)),
+                    _ => self.line(&format!("movq {}(%rbp), %rax", self.result)),
+                }
+                self.line(&format!("jmp .Lreturn.{}", mangle(&self.function)));
+            },
+            Expression::Struct { .. }
+            | Expression::FnDeclaration { .. }
+            | Expression::Use (_) => return Err(Error::CouldNotEmit (format!("declaration inside {}", self.function))),
+            _ => {
+                self.emit_expr(statement)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Infers the Boron type of an expression without emitting it.
+    fn infer(&self, expr: &Expression) -> Result<String, Error> {
+        match expr {
+            Expression::Int (_) => Ok("int".to_string()),
+            Expression::Float (_) => Ok("flt".to_string()),
+            Expression::Bool (_) => Ok("bln".to_string()),
+            Expression::Char (_) => Ok("chr".to_string()),
+            Expression::Null => Ok("null".to_string()),
+            Expression::Identifier (i) => Ok(self.path_type(i)?),
+            Expression::UnaryOp {
+                op: TokenType::Not,
+                ..
+            } => Ok("bln".to_string()),
+            Expression::UnaryOp {
+                expr: e,
+                ..
+            } => Ok(arithmetic(&self.infer(e)?, "int")),
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => match is_comparison(*o) {
+                true => Ok("bln".to_string()),
+                false => Ok(arithmetic(&self.infer(l)?, &self.infer(r)?)),
+            },
+            Expression::TernaryIfElse {
+                body_true: t,
+                body_false: f,
+                ..
+            } => {
+                let (t, f) = (self.infer(t)?, self.infer(f)?);
+                match (t.as_str(), f.as_str()) {
+                    _ if t == f => Ok(t),
+                    ("null", _) => Ok(f),
+                    (_, "null") => Ok(t),
+                    _ if pointee(&t).is_some() && pointee(&t) == pointee(&f) => Ok(format!("opt {}", pointee(&t).unwrap_or_default())),
+                    _ => Ok(arithmetic(&t, &f)),
+                }
+            },
+            Expression::New {
+                datatype: d,
+                ..
+            } => Ok(format!("own {}", d)),
+            Expression::FnCall {
+                name: n,
+                ..
+            } => match n.as_str() {
+                "print" | "drop" => Ok("nul".to_string()),
+                _ => match self.functions.get(n) {
+                    Some(s) => Ok(s.return_type.to_owned()),
+                    None => Err(Error::UndeclaredFunction (n.to_string())),
+                },
+            },
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
+    }
+
+    /// Emits an expression, leaving its value in `%rax` or `%xmm0`, and returns its Boron type.
+    fn emit_expr(&mut self, expr: &Expression) -> Result<String, Error> {
+        match expr {
+            Expression::Int (i) => {
+                self.line(&format!("movl ${}, %eax", i));
+                Ok("int".to_string())
+            },
+            Expression::Float (f) => {
+                self.line(&format!("movl ${}, %eax", f.to_bits() as i32));
+                self.line("movd %eax, %xmm0");
+                Ok("flt".to_string())
+            },
+            Expression::Bool (b) => {
+                self.line(&format!("movl ${}, %eax", *b as i32));
+                Ok("bln".to_string())
+            },
+            Expression::Char (c) => {
+                self.line(&format!("movl ${}, %eax", *c as u8 as i8));
+                Ok("chr".to_string())
+            },
+            Expression::Null => {
+                self.line("xorl %eax, %eax");
+                Ok("null".to_string())
+            },
+            Expression::Identifier (i) => {
+                let datatype = self.emit_address(i)?;
+                self.emit_load(&datatype);
+                Ok(datatype)
+            },
+            Expression::UnaryOp {
+                op: o,
+                expr: e,
+                ..
+            } => {
+                let from = self.emit_expr(e)?;
+                match o {
+                    TokenType::Not => {
+                        match pointee(&from) {
+                            Some(_) => {
+                                self.line("testq %rax, %rax");
+                                self.line("setne %al");
+                                self.line("movzbl %al, %eax");
+                            },
+                            None => self.convert(&from, "bln")?,
+                        }
+                        self.line("xorl $1, %eax");
+                        Ok("bln".to_string())
+                    },
+                    _ if from == "flt" => {
+                        self.line("movl $-2147483648, %eax");
+                        self.line("movd %eax, %xmm1");
+                        self.line("xorps %xmm1, %xmm0");
+                        Ok("flt".to_string())
+                    },
+                    _ => {
+                        self.convert(&from, "int")?;
+                        self.line("negl %eax");
+                        Ok("int".to_string())
+                    },
+                }
+            },
+            Expression::BinOp {
+                left: l,
+                op: o,
+                right: r,
+                ..
+            } => Ok(self.emit_binop(*o, l, r)?),
+            Expression::TernaryIfElse {
+                condition: c,
+                body_true: t,
+                body_false: f,
+            } => {
+                let datatype = self.infer(expr)?;
+                let n = self.fresh();
+                self.emit_jump_unless(c, &format!(".Lternary.else.{}", n))?;
+                let from = self.emit_expr(t)?;
+                self.convert(&from, &datatype)?;
+                self.line(&format!("jmp .Lternary.end.{}", n));
+                self.label(&format!(".Lternary.else.{}", n));
+                let from = self.emit_expr(f)?;
+                self.convert(&from, &datatype)?;
+                self.label(&format!(".Lternary.end.{}", n));
+                Ok(datatype)
+            },
+            Expression::New {
+                datatype: d,
+                variables: v,
+            } => {
+                let size = self.size(d)?;
+                self.line(&format!("movl ${}, %edi", size));
+                self.emit_runtime_call("boron.new");
+                self.emit_fields(d, v)?;
+                Ok(format!("own {}", d))
+            },
+            Expression::StructInit { .. } => Err(Error::FoundBareStruct),
+            Expression::FnCall {
+                name: n,
+                args: a,
+            } => match n.as_str() {
+                "print" => {
+                    for arg in a {
+                        self.emit_print(arg)?;
+                    }
+                    Ok("nul".to_string())
+                },
+                "drop" => {
+                    for arg in a {
+                        let id = match arg {
+                            Expression::Identifier (id) => id,
+                            _ => return Err(Error::ExpectedPointer ("drop".to_string())),
+                        };
+                        let datatype = self.emit_address(id)?;
+                        if pointee(&datatype).is_none() {
+                            return Err(Error::ExpectedPointer (id.to_owned()));
+                        }
+                        self.push("int");
+                        self.line("movq (%rax), %rdi");
+                        self.emit_runtime_call("free@PLT");
+                        self.pop("int");
+                        self.line("movq $0, (%rax)");
+                    }
+                    Ok("nul".to_string())
+                },
+                _ => Ok(self.emit_call(n, a)?),
+            },
+            _ => Err(Error::CouldNotEmit (format!("{:?}", expr))),
+        }
+    }
+
+    /// Emits a binary operation, promoting its operands as C does.
+    fn emit_binop(&mut self, op: TokenType, left: &Expression, right: &Expression) -> Result<String, Error> {
+        let (l, r) = (self.infer(left)?, self.infer(right)?);
+
+        // Pointers may only be compared for equality
+        let pointers = [&l, &r].iter().any(|t| pointee(t).is_some() || *t == "null");
+        let datatype = match (l.as_str(), r.as_str()) {
+            _ if pointers => {
+                if op != TokenType::Equal {
+                    return Err(Error::InvalidOperator (format!("{:?}", op)));
+                }
+                "ptr".to_string()
+            },
+            ("chr", "chr") | ("bln", "bln") if is_comparison(op) => "int".to_string(),
+            _ => arithmetic(&l, &r),
+        };
+
+        // Evaluate the left operand first, then move the right one into `%rcx` or `%xmm1`
+        let from = self.emit_expr(left)?;
+        if !pointers {
+            self.convert(&from, &datatype)?;
+        }
+        self.push(&datatype);
+        let from = self.emit_expr(right)?;
+        if !pointers {
+            self.convert(&from, &datatype)?;
+        }
+        match datatype.as_str() {
+            "flt" => self.line("movaps %xmm0, %xmm1"),
+            _ => self.line("movq %rax, %rcx"),
+        }
+        self.pop(&datatype);
+
+        let instructions: &[&str] = match (datatype.as_str(), op) {
+            ("ptr", _) => &["cmpq %rcx, %rax", "sete %al"],
+            ("flt", TokenType::Plus) => &["addss %xmm1, %xmm0"],
+            ("flt", TokenType::Minus) => &["subss %xmm1, %xmm0"],
+            ("flt", TokenType::Multiply) => &["mulss %xmm1, %xmm0"],
+            ("flt", TokenType::Divide) => &["divss %xmm1, %xmm0"],
+            // Unordered comparisons, involving NaN, are false
+            ("flt", TokenType::Greater) => &["ucomiss %xmm1, %xmm0", "seta %al"],
+            ("flt", TokenType::Less) => &["ucomiss %xmm0, %xmm1", "seta %al"],
+            ("flt", TokenType::GreaterEqual) => &["ucomiss %xmm1, %xmm0", "setae %al"],
+            ("flt", TokenType::LessEqual) => &["ucomiss %xmm0, %xmm1", "setae %al"],
+            ("flt", TokenType::Equal) => &["ucomiss %xmm1, %xmm0", "sete %al", "setnp %cl", "andb %cl, %al"],
+            (_, TokenType::Plus) => &["addl %ecx, %eax"],
+            (_, TokenType::Minus) => &["subl %ecx, %eax"],
+            (_, TokenType::Multiply) => &["imull %ecx, %eax"],
+            (_, TokenType::Divide) => &["cltd", "idivl %ecx"],
+            (_, TokenType::Greater) => &["cmpl %ecx, %eax", "setg %al"],
+            (_, TokenType::Less) => &["cmpl %ecx, %eax", "setl %al"],
+            (_, TokenType::GreaterEqual) => &["cmpl %ecx, %eax", "setge %al"],
+            (_, TokenType::LessEqual) => &["cmpl %ecx, %eax", "setle %al"],
+            (_, TokenType::Equal) => &["cmpl %ecx, %eax", "sete %al"],
+            _ => return Err(Error::InvalidOperator (format!("{:?}", op))),
+        };
+        for instruction in instructions {
+            self.line(instruction);
+        }
+        match is_comparison(op) {
+            true => {
+                self.line("movzbl %al, %eax");
+                Ok("bln".to_string())
+            },
+            false => Ok(datatype),
+        }
+    }
+
+    /// Emits a call to a runtime or libc function whose arguments are already in registers,
+    /// aligning the stack to 16 bytes as the ABI requires.
+    fn emit_runtime_call(&mut self, name: &str) {
+        let padded = self.pushed % 2 == 1;
+        if padded {
+            self.line("subq $8, %rsp");
+        }
+        self.line(&format!("call {}", name));
+        if padded {
+            self.line("addq $8, %rsp");
+        }
+    }
+
+    /// Emits a call to `printf` for a single value.
+    fn emit_print(&mut self, arg: &Expression) -> Result<(), Error> {
+        let datatype = self.emit_expr(arg)?;
+        match datatype.as_str() {
+            "int" => {
+                self.line("movl %eax, %esi");
+                self.line("leaq .Lformat.int(%rip), %rdi");
+            },
+            "chr" => {
+                self.line("movsbl %al, %esi");
+                self.line("leaq .Lformat.chr(%rip), %rdi");
+            },
+            "bln" => {
+                self.line("leaq .Lformat.true(%rip), %rdi");
+                self.line("leaq .Lformat.false(%rip), %rcx");
+                self.line("testl %eax, %eax");
+                self.line("cmove %rcx, %rdi");
+            },
+            // C promotes floats to doubles when passing them to `printf`, and `%al` counts the vector registers used
+            "flt" => {
+                self.line("cvtss2sd %xmm0, %xmm0");
+                self.line("leaq .Lformat.flt(%rip), %rdi");
+                self.line("movl $1, %eax");
+                self.emit_runtime_call("printf@PLT");
+                return Ok(());
+            },
+            "nul" => self.line("leaq .Lformat.nul(%rip), %rdi"),
+            _ => return Err(Error::CouldNotEmit (format!("print of {}", datatype))),
+        }
+        self.line("xorl %eax, %eax");
+        self.emit_runtime_call("printf@PLT");
+        Ok(())
+    }
+
+    /// Emits a function call, passing each argument according to the function's signature.
+    fn emit_call(&mut self, name: &str, args: &[Expression]) -> Result<String, Error> {
+        let signature = match self.functions.get(name) {
+            Some(s) => s.to_owned(),
+            None => return Err(Error::UndeclaredFunction (name.to_string())),
+        };
+        if signature.arguments.len() != args.len() {
+            return Err(Error::WrongArgumentCount (name.to_string()));
+        }
+
+        // A struct result is written to a slot in this frame, whose address is passed first
+        let mut floats = Vec::new();
+        if self.structs.contains_key(&signature.return_type) {
+            let offset = self.slot(&signature.return_type)?;
+            self.line(&format!("leaq {}(%rbp), %rax", offset));
+            self.push("int");
+            floats.push(false);
+        }
+
+        // Evaluate every argument onto the stack, in order
+        for ((datatype, passing), arg) in signature.arguments.iter().zip(args.iter()) {
+            match (passing, arg) {
+                (Passing::Value, _) => {
+                    let from = self.emit_expr(arg)?;
+                    self.convert(&from, datatype)?;
+                },
+                (_, Expression::Identifier (id)) => {
+                    let from = self.emit_address(id)?;
+                    // A heap pointer already refers to its struct, so it is passed as-is
+                    if pointee(&from) == Some(datatype.as_str()) {
+                        self.line("movq (%rax), %rax");
+                    }
+                },
+                (Passing::Reference, _) => return Err(Error::ExpectedReferenceArgument (name.to_string())),
+                // Any other struct is already in memory, so its address is passed
+                (Passing::ConstReference, _) => {
+                    self.emit_expr(arg)?;
+                },
+            }
+            let float = datatype == "flt" && *passing == Passing::Value;
+            self.push(if float { "flt" } else { "int" });
+            floats.push(float);
+        }
+
+        // Assign the arguments to registers, and the rest to the stack
+        let count = floats.len();
+        let (mut integers, mut vectors) = (0, 0);
+        let mut registers = Vec::new();
+        let mut stack = Vec::new();
+        for (index, float) in floats.iter().enumerate() {
+            match float {
+                true if vectors < FLOAT_REGISTERS => {
+                    registers.push((index, format!("%xmm{}", vectors)));
+                    vectors += 1;
+                },
+                false if integers < INTEGER_REGISTERS.len() => {
+                    registers.push((index, INTEGER_REGISTERS[integers].to_string()));
+                    integers += 1;
+                },
+                _ => stack.push(index),
+            }
+        }
+
+        // Stack arguments are pushed last to first, after padding that keeps the stack aligned at the call
+        let padding = (self.pushed + stack.len()) % 2;
+        if padding == 1 {
+            self.line("subq $8, %rsp");
+        }
+        for (pushed, index) in stack.iter().rev().enumerate() {
+            let offset = 8 * (count - 1 - index + padding + pushed);
+            self.line(&format!("pushq {}(%rsp)", offset));
+        }
+        for (index, register) in registers {
+            let offset = 8 * (count - 1 - index + padding + stack.len());
+            match register.starts_with("%xmm") {
+                true => self.line(&format!("movss {}(%rsp), {}", offset, register)),
+                false => self.line(&format!("movq {}(%rsp), {}", offset, register)),
+            }
+        }
+        self.line(&format!("call {}", mangle(name)));
+        self.line(&format!("addq ${}, %rsp", 8 * (count + padding + stack.len())));
+        self.pushed -= count;
+        Ok(signature.return_type)
+    }
+}
+
+
+/// Gets the struct a pointer type (`own Node` or `opt Node`) points to.
+fn pointee(datatype: &str) -> Option<&str> {
+    datatype.strip_prefix("own ").or_else(|| datatype.strip_prefix("opt "))
+}
+
+
+/// Gets the type of an arithmetic operation on two types, promoting as C does.
+fn arithmetic(left: &str, right: &str) -> String {
+    match left == "flt" || right == "flt" {
+        true => "flt".to_string(),
+        false => "int".to_string(),
+    }
+}
+
+
+/// Checks if an operator is a comparison.
+fn is_comparison(op: TokenType) -> bool {
+    matches!(
+        op,
+        TokenType::Greater
+        | TokenType::Less
+        | TokenType::Equal
+        | TokenType::GreaterEqual
+        | TokenType::LessEqual
+    )
+}
+
+
+/// Rounds a size up to a multiple of the given alignment.
+fn align(size: i32, alignment: i32) -> i32 {
+    (size + alignment - 1) / alignment * alignment
+}
+
+
+/// Converts a Boron identifier into an assembler symbol, escaping characters that are not allowed.
+fn mangle(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            true => c.to_string(),
+            false => format!("_{:x}_", c as u32),
+        })
+        .collect()
+}
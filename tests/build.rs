@@ -0,0 +1,180 @@
+//! Drives directory builds, checking that the cache skips files that have not changed.
+
+
+mod common;
+
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use common::{boron, scratch};
+
+use boron_lang::cache::{
+    hash,
+    Cache,
+};
+
+
+/// Builds the current directory of a project, with any extra flags.
+fn build(directory: &Path, flags: &[&str]) {
+    build_in(directory, ".", flags);
+}
+
+
+/// Builds a project directory from the directory the compiler runs in, with any extra flags.
+fn build_in(directory: &Path, project: &str, flags: &[&str]) {
+    let output = boron(directory, &[&["--build"], flags, &[project]].concat());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success() && !stdout.contains("Error"), "{}", stdout);
+}
+
+
+/// Replaces each header in a project with a marker, so that a later build shows which it rewrote.
+fn mark(directory: &Path, names: &[&str]) {
+    for name in names {
+        fs::write(directory.join(format!("{}.h", name)), "stale").unwrap();
+    }
+}
+
+
+/// Lists the headers in a project that were rewritten since they were marked.
+fn rebuilt(directory: &Path, names: &[&str]) -> Vec<String> {
+    names.iter()
+        .filter(|n| fs::read_to_string(directory.join(format!("{}.h", n))).unwrap() != "stale")
+        .map(|n| n.to_string())
+        .collect()
+}
+
+
+#[test]
+fn hashes_are_stable() {
+    assert_eq!(hash(""), "cbf29ce484222325");
+    assert_eq!(hash("main -> int {}"), hash("main -> int {}"));
+    assert_ne!(hash("main -> int {}"), hash("main -> int { }"));
+}
+
+
+#[test]
+fn rebuilds_changed_files_and_their_dependents() {
+    let directory = scratch("incremental");
+    let names = ["shapes", "area", "other"];
+    fs::write(directory.join("shapes.brn"), "struct Square { int side }\n").unwrap();
+    fs::write(directory.join("area.brn"), "use shapes\narea :: int side -> int {\n    return side * side\n}\n").unwrap();
+    fs::write(directory.join("other.brn"), "double :: int x -> int {\n    return x * 2\n}\n").unwrap();
+
+    build(&directory, &[]);
+    assert!(fs::read_to_string(directory.join("area.h")).unwrap().contains("#include \"shapes.h\""));
+    assert!(directory.join(".boron-cache").is_dir());
+
+    // Nothing has changed
+    mark(&directory, &names);
+    build(&directory, &[]);
+    assert!(rebuilt(&directory, &names).is_empty());
+
+    // A changed module is rebuilt along with the modules that use it
+    fs::write(directory.join("shapes.brn"), "struct Square { int side int depth }\n").unwrap();
+    build(&directory, &[]);
+    assert_eq!(rebuilt(&directory, &names), ["shapes", "area"]);
+
+    // A missing output is rebuilt
+    mark(&directory, &names);
+    fs::remove_file(directory.join("other.h")).unwrap();
+    build(&directory, &[]);
+    assert!(directory.join("other.h").exists());
+    assert!(rebuilt(&directory, &["shapes", "area"]).is_empty());
+
+    // Forcing a build rebuilds everything
+    mark(&directory, &names);
+    build(&directory, &["--force"]);
+    assert_eq!(rebuilt(&directory, &names), names);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn tracks_modules_when_building_from_outside_the_project() {
+    let directory = scratch("outside");
+    let project = directory.join("project");
+    fs::create_dir_all(&project).unwrap();
+    let names = ["shapes", "area"];
+    fs::write(project.join("shapes.brn"), "struct Square { int side }\n").unwrap();
+    fs::write(project.join("area.brn"), "use shapes\narea :: int side -> int {\n    return side * side\n}\n").unwrap();
+
+    build_in(&directory, "project", &[]);
+    assert!(project.join(".boron-cache").is_dir());
+
+    // Modules are found next to the files that use them, not in the directory the compiler runs in
+    mark(&project, &names);
+    build_in(&directory, "project", &[]);
+    assert!(rebuilt(&project, &names).is_empty());
+
+    fs::write(project.join("shapes.brn"), "struct Square { int side int depth }\n").unwrap();
+    build_in(&directory, "project", &[]);
+    assert_eq!(rebuilt(&project, &names), names);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn ignores_caches_for_other_targets() {
+    let directory = scratch("target");
+    let source = directory.join("a.brn");
+    fs::write(&source, "main -> int {\n    return 0\n}\n").unwrap();
+    let (root, filename) = (directory.display().to_string(), source.display().to_string());
+
+    let mut cache = Cache::empty(&root, "c");
+    assert!(!cache.is_fresh(&filename));
+    cache.record(&filename, &fs::read_to_string(&source).unwrap(), &[]).unwrap();
+    assert!(cache.is_fresh(&filename));
+    cache.save().unwrap();
+
+    assert!(Cache::open(&root, "c").is_fresh(&filename));
+    assert!(!Cache::open(&root, "llvm").is_fresh(&filename));
+
+    fs::write(&source, "main -> int {\n    return 1\n}\n").unwrap();
+    assert!(!Cache::open(&root, "c").is_fresh(&filename));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn builds_the_same_output_with_any_number_of_jobs() {
+    let directory = scratch("parallel");
+    for index in 0..12 {
+        let code = format!("use m{}\nf{} :: int x -> int {{\n    return x * {}\n}}\n", (index + 1) % 12, index, index);
+        fs::write(directory.join(format!("m{}.brn", index)), code).unwrap();
+    }
+    let headers = || (0..12)
+        .map(|i| fs::read(directory.join(format!("m{}.h", i))).unwrap())
+        .collect::<Vec<Vec<u8>>>();
+
+    build(&directory, &["-j", "1"]);
+    let sequential = headers();
+    build(&directory, &["--force", "-j", "8"]);
+    assert_eq!(headers(), sequential);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn reports_the_first_error_in_filename_order() {
+    let directory = scratch("errors");
+    fs::write(directory.join("a.brn"), "f :: int x -> int {\n    return x\n}\n").unwrap();
+    fs::write(directory.join("b.brn"), "g -> int {\n    return $\n}\n").unwrap();
+    fs::write(directory.join("c.brn"), "h -> int {\n    return @\n}\n").unwrap();
+
+    for jobs in ["1", "3"] {
+        let output = boron(&directory, &["--build", "--force", "-j", jobs, "."]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.matches("Error").count(), 1, "{}", stdout);
+        assert!(stdout.contains("Unexpected character '$' at line 2"), "{}", stdout);
+        // Files that compiled are still written
+        assert!(directory.join("a.h").exists());
+    }
+
+    let output = boron(&directory, &["--build", "-j", "0", "."]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Expected a positive number of jobs, got 0"));
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,166 @@
+//! Compiles programs to bytecode, checking that they survive serialization and run correctly on the VM.
+
+
+mod common;
+
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use common::{boron, scratch};
+
+use boron_lang::{
+    bytecode::{
+        compile,
+        Instruction,
+        Program,
+        Vm,
+    },
+    error::Error,
+    interpreter::Value,
+    parser::{
+        Expression,
+        Parser,
+    },
+    tokenizer::Tokenizer,
+};
+
+
+const PROGRAM: &str = "
+struct Point { int x int y }
+fib :: int n -> int {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+main -> int {
+    let Point p: { x 3 y 4 }
+    let int f: fib(10)
+    print(f)
+    let own Point h: new Point { x 5 y 6 }
+    print(h.y)
+    drop(h)
+    return p.x + p.y
+}
+";
+
+
+/// Parses a Boron program.
+fn parse(code: &str) -> Vec<Expression> {
+    let mut tokenizer = Tokenizer::new(code);
+    Parser::new().parse_all(&mut tokenizer).unwrap()
+}
+
+
+/// Runs a serialized program with `boron run`, returning what it printed and its exit code.
+fn run(path: &Path) -> (String, Option<i32>, String) {
+    // It's ok to use `unwrap` here because the programs are all written to scratch directories
+    let output = boron(path.parent().unwrap(), &["run", path.to_str().unwrap()]);
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+
+#[test]
+fn round_trips_through_bytes() {
+    let program = compile(parse(PROGRAM)).unwrap();
+    let bytes = program.to_bytes();
+    assert_eq!(Program::from_bytes(&bytes), Ok(program));
+    assert_eq!(Program::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+}
+
+
+#[test]
+fn runs_a_compiled_program() {
+    let directory = scratch("run");
+    let source = directory.join("sample.brn");
+    fs::write(&source, PROGRAM).unwrap();
+    let compiled = boron(&directory, &["compile", "--target", "bytecode", "sample.brn"]);
+    assert!(compiled.status.success());
+
+    let (stdout, code, _) = run(&directory.join("sample.brnc"));
+    assert_eq!(stdout, "55\n6\n");
+    assert_eq!(code, Some(7));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn calls_native_functions() {
+    let program = compile(parse("main -> int {\n    return square(7) + 1\n}\n")).unwrap();
+    assert!(program.functions.iter().any(|f| f.code.iter().any(|i| matches!(i, Instruction::CallNative (_, 1)))));
+
+    let mut vm = Vm::new(program).unwrap();
+    vm.register_native("square", |args| match args {
+        [Value::Int (i)] => Value::Int (i * i),
+        _ => Value::Void,
+    });
+    assert_eq!(vm.run(), Ok(50));
+}
+
+
+#[test]
+fn rejects_corrupted_programs() {
+    let directory = scratch("corrupted");
+    let bytes = compile(parse(PROGRAM)).unwrap().to_bytes();
+    let mut out_of_range = bytes.clone();
+    let length = out_of_range.len();
+    out_of_range[length - 4..].copy_from_slice(&9999u32.to_le_bytes());
+
+    let cases: [(&str, Vec<u8>, &str); 4] = [
+        ("truncated", bytes[..bytes.len() / 2].to_vec(), "unexpected end of program"),
+        ("init", out_of_range, "function 9999 out of range"),
+        ("magic", b"BRNX".to_vec(), "not a Boron program"),
+        ("trailing", [bytes.as_slice(), &[0]].concat(), "trailing bytes"),
+    ];
+    for (name, bytes, message) in cases {
+        assert_eq!(Program::from_bytes(&bytes), Err(Error::InvalidBytecode (message.to_string())), "{}", name);
+        let path = directory.join(format!("{}.brnc", name));
+        fs::write(&path, bytes).unwrap();
+        let (stdout, _, stderr) = run(&path);
+        assert!(stdout.contains(&format!("Invalid bytecode: {}", message)), "{}: {}", name, stdout);
+        assert!(!stderr.contains("panicked"), "{}: {}", name, stderr);
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn rejects_calls_to_missing_functions() {
+    let mut program = compile(parse(PROGRAM)).unwrap();
+    let main = program.find_function("main").unwrap();
+    program.functions[main].code.insert(0, Instruction::Call (4242, 0));
+
+    let directory = scratch("call");
+    let path = directory.join("call.brnc");
+    fs::write(&path, program.to_bytes()).unwrap();
+    let (stdout, _, stderr) = run(&path);
+    assert!(stdout.contains("Invalid bytecode: function 4242 out of range"), "{}", stdout);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn fails_on_runtime_errors() {
+    let directory = scratch("runtime");
+    let code = "struct Point { int x }\nmain -> int {\n    let opt Point p: null\n    print(1)\n    return p.x\n}\n";
+    fs::write(directory.join("sample.brn"), code).unwrap();
+    boron(&directory, &["compile", "--target", "bytecode", "sample.brn"]);
+
+    // Both ways of running a program stop at the error with a failing status
+    for args in [&["interp", "sample.brn"], &["run", "sample.brnc"]] {
+        let output = boron(&directory, args);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("1\n") && stdout.contains("Dereferenced null pointer"), "{:?}: {}", args, stdout);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
+
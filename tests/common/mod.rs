@@ -0,0 +1,45 @@
+//! Fixtures shared by the tests that run the `boron` binary.
+
+// Each test file uses only some of these
+#![allow(dead_code)]
+
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+
+/// Creates an empty scratch directory for a test, named after the test file, the test and the process.
+pub fn scratch(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("boron-{}-{}-{}", env!("CARGO_CRATE_NAME"), name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+
+/// Runs `boron` with the given arguments in a directory.
+pub fn boron(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_boron"))
+        .args(args)
+        .current_dir(directory)
+        .output()
+        .unwrap()
+}
+
+
+/// Gets what a command printed to standard output.
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+
+/// Gets the C compiler (`$CC`, or `cc`), if this machine has one.
+pub fn compiler() -> Option<String> {
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    let found = Command::new(&compiler).arg("--version").output().is_ok_and(|o| o.status.success());
+    found.then_some(compiler)
+}
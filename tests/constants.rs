@@ -0,0 +1,85 @@
+//! Checks the C emitted for constants, statics and top-level variables, whose initializers are evaluated at compile time.
+
+
+use std::{
+    env,
+    fs,
+    process::Command,
+};
+
+use boron_lang::{
+    emitter::Emitter,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+/// Compiles a Boron program to C.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+/// Compiles a Boron program with `boron`, returning what the compiler printed.
+fn report(name: &str, code: &str) -> String {
+    let path = env::temp_dir().join(format!("boron-constants-{}-{}.brn", name, std::process::id()));
+    fs::write(&path, code).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_boron"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("c"));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+
+#[test]
+fn emits_constants_and_statics_with_folded_initializers() {
+    let c = compile("
+const int LIMIT: 4 * 25
+const flt HALF: 1.0 / 2
+let int counter: LIMIT - 1
+const square :: int x -> int {
+    return x * x
+}
+const int AREA: square(LIMIT)
+tick -> int {
+    static int calls: AREA / 1000
+    calls: calls + 1
+    return calls
+}
+main -> int {
+    const int local: square(3) + 1
+    return local + tick()
+}
+");
+    assert!(c.contains("\
+// Globals begin here
+static const int LIMIT = 100;
+static const float HALF = 0.5;
+int counter = 99;
+static const int AREA = 10000;
+"), "{}", c);
+    assert!(c.contains("int tick() {\nstatic int calls = 10;\n"), "{}", c);
+    assert!(c.contains("static const int local = 10;"), "{}", c);
+    // `const` functions can still be called at runtime
+    assert!(c.contains("int square(int x) {\nreturn (x * x);\n}"), "{}", c);
+}
+
+#[test]
+fn rejects_initializers_that_are_not_constant() {
+    let cases = [
+        ("variable", "let int n: 2\nconst int A: n * 2\n", "A"),
+        ("function", "tick -> int {\n    return 1\n}\nconst int A: tick()\n", "A"),
+        ("global", "tick -> int {\n    return 1\n}\nlet int x: tick()\n", "x"),
+        ("static", "tick -> int {\n    let int n: 1\n    static int s: n\n    return s\n}\n", "s"),
+    ];
+    for (name, declarations, identifier) in cases {
+        let stdout = report(name, &format!("{}main -> int {{\n    return 0\n}}\n", declarations));
+        let message = format!("The initializer of {} cannot be evaluated at compile time", identifier);
+        assert!(stdout.contains(&message), "{}: {}", name, stdout);
+    }
+}
@@ -0,0 +1,50 @@
+//! Checks that errors found while evaluating constants are reported where they occur in the source.
+
+
+use std::{
+    env,
+    fs,
+    process::Command,
+};
+
+
+/// Compiles a Boron program with `boron`, returning what the compiler printed.
+fn report(name: &str, code: &str) -> String {
+    let path = env::temp_dir().join(format!("boron-consteval-{}-{}.brn", name, std::process::id()));
+    fs::write(&path, code).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_boron"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("c"));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+
+#[test]
+fn locates_integer_overflow() {
+    let cases = [
+        ("add", "const int A: 2147483647 + 1\n", "at line 1, column 25"),
+        ("subtract", "local -> int {\n    const int a: 0 - 2147483647 - 2\n    return a\n}\n", "at line 2, column 33"),
+        ("multiply", "const int A: 65536 * 65536\n", "at line 1, column 20"),
+        ("function", "const big :: int x -> int {\n    return x * x\n}\nconst int A: big(100000)\n", "at line 2, column 14"),
+    ];
+    for (name, code, location) in cases {
+        let stdout = report(name, &format!("{}main -> int {{\n    return 0\n}}\n", code));
+        let message = format!("Integer overflow in constant expression {}", location);
+        assert!(stdout.contains(&message), "{}: {}", name, stdout);
+    }
+}
+
+#[test]
+fn locates_division_by_zero() {
+    let stdout = report("divide", "const int A: 10 / (3 - 3)\n");
+    assert!(stdout.contains("Division by zero at line 1, column 17"), "{}", stdout);
+}
+
+#[test]
+fn reports_dividing_the_smallest_integer_by_minus_one_as_overflow() {
+    let stdout = report("minimum", "const int MIN: 0 - 2147483647 - 1\n\nconst int A: MIN / (0 - 1)\n");
+    assert!(stdout.contains("Integer overflow in constant expression at line 3, column 18"), "{}", stdout);
+}
@@ -0,0 +1,152 @@
+//! Checks that deferred statements run in reverse order when each block ends, including on early returns.
+//!
+//! Running the emitted C needs a C compiler (`cc`, or `$CC`).  On machines that
+//! lack one, the tests say that they were skipped and check only the interpreter.
+
+
+mod common;
+
+
+use std::{
+    env,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::Command,
+};
+
+use common::{boron, scratch};
+
+use boron_lang::{
+    emitter::Emitter,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+const PROGRAM: &str = "
+pick :: int n -> int {
+    defer print(n)
+    let int a: 1
+    defer print(a)
+    if n > 0 {
+        let int b: 2
+        defer print(b)
+        while n > 2 {
+            let int c: 3
+            defer print(c)
+            return n + 100
+        }
+        return n + 10
+    }
+    return n
+}
+count -> int {
+    let int i: 0
+    while i < 3 {
+        let int j: i * 10
+        defer print(j)
+        defer print(i)
+        i: i + 1
+    }
+    return i
+}
+main -> int {
+    let int r: pick(3)
+    print(r)
+    r: pick(1)
+    print(r)
+    r: pick(0)
+    print(r)
+    r: count()
+    return r
+}
+";
+
+
+/// Compiles a Boron program to C.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+/// Runs a program with `boron interp`, returning what it printed and its exit code.
+fn interpret(directory: &Path) -> (String, Option<i32>) {
+    let output = boron(directory, &["interp", "sample.brn"]);
+    (String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code())
+}
+
+
+#[test]
+fn unwinds_every_block_on_early_returns() {
+    let c = compile(PROGRAM);
+    // The return value is computed before any deferred statement runs
+    assert!(c.contains("\
+int c = 3;
+int boron_return = (n + 100);
+printf(\"%d\\n\", c);
+printf(\"%d\\n\", b);
+printf(\"%d\\n\", a);
+printf(\"%d\\n\", n);
+return boron_return;
+}"), "{}", c);
+    assert!(c.contains("\
+}
+int boron_return = (n + 10);
+printf(\"%d\\n\", b);
+printf(\"%d\\n\", a);
+printf(\"%d\\n\", n);
+return boron_return;
+}"), "{}", c);
+    assert!(c.contains("\
+}
+int boron_return = n;
+printf(\"%d\\n\", a);
+printf(\"%d\\n\", n);
+return boron_return;
+}"), "{}", c);
+}
+
+#[test]
+fn runs_deferred_statements_at_the_end_of_each_loop_iteration() {
+    let c = compile(PROGRAM);
+    assert!(c.contains("i = (i + 1);\nprintf(\"%d\\n\", i);\nprintf(\"%d\\n\", j);\n}"), "{}", c);
+}
+
+#[test]
+fn agrees_with_the_interpreter() {
+    let expected = "3\n2\n1\n3\n103\n2\n1\n1\n11\n1\n0\n0\n1\n0\n2\n10\n3\n20\n";
+
+    let directory = scratch("run");
+    fs::write(directory.join("sample.brn"), PROGRAM).unwrap();
+    assert_eq!(interpret(&directory), (expected.to_string(), Some(3)));
+
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    if !Command::new(&compiler).arg("--version").output().is_ok_and(|o| o.status.success()) {
+        let _ = writeln!(io::stderr(), "skipping the C half of agrees_with_the_interpreter: `{}` is needed to compile programs", compiler);
+        let _ = fs::remove_dir_all(&directory);
+        return;
+    }
+    fs::write(directory.join("sample.c"), compile(PROGRAM)).unwrap();
+    let compiled = Command::new(compiler)
+        .args(["sample.c", "-o", "sample"])
+        .current_dir(&directory)
+        .status()
+        .unwrap();
+    assert!(compiled.success());
+    let output = Command::new(directory.join("sample")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    assert_eq!(output.status.code(), Some(3));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn rejects_defer_outside_functions() {
+    let directory = scratch("outside");
+    fs::write(directory.join("sample.brn"), "defer print(1)\nmain -> int {\n    return 0\n}\n").unwrap();
+    let (stdout, _) = interpret(&directory);
+    assert!(stdout.contains("Found `defer` outside of a function"), "{}", stdout);
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,173 @@
+//! Checks the rewriting of method calls and deferred statements shared by the backends, and that each backend runs the result the same way.
+//!
+//! Running the emitted C needs a C compiler (`cc`, or `$CC`), and is skipped on machines that lack one.
+
+
+mod common;
+
+
+use std::{
+    fs,
+    process::Command,
+};
+
+use common::{boron, compiler, scratch, stdout};
+
+use boron_lang::{
+    desugar::{desugar, split_path},
+    error::Error,
+    ir,
+    parser::{
+        Expression,
+        Parser,
+    },
+    tokenizer::Tokenizer,
+};
+
+
+const PROGRAM: &str = "
+struct Counter { int count }
+total :: Counter c int extra -> int {
+    return c.count + extra
+}
+bump :: ref int n -> int {
+    defer n: n + 10
+    if n > 0 {
+        return n
+    }
+    n: 5
+    return n * 2
+}
+report :: int n -> nul {
+    defer print(0 - 1)
+    while n > 0 {
+        defer n: n - 1
+        if n = 2 {
+            return print(n)
+        }
+    }
+}
+main -> int {
+    let Counter c: { count 3 }
+    print(c.total(4))
+    let int a: 1
+    let int r: bump(a)
+    let int b: 0
+    let int s: bump(b)
+    print(r)
+    print(a)
+    print(s)
+    print(b)
+    report(5)
+    return 0
+}
+";
+
+
+/// What `PROGRAM` prints: each return value is computed before the deferred statements run.
+const OUTPUT: &str = "7\n1\n11\n10\n15\n2\n-1\n";
+
+
+/// Parses a Boron program.
+fn parse(code: &str) -> Vec<Expression> {
+    let mut tokenizer = Tokenizer::new(code);
+    Parser::new().parse_all(&mut tokenizer).unwrap()
+}
+
+
+/// Gets the body of the function with the given name.
+fn body(expressions: &[Expression], name: &str) -> Vec<Expression> {
+    expressions.iter()
+        .find_map(|e| match e {
+            Expression::FnDeclaration { identifier, body, .. } if identifier == name => Some(body.to_owned()),
+            _ => None,
+        })
+        .unwrap()
+}
+
+
+#[test]
+fn passes_the_receiver_of_a_method_call_first() {
+    let expressions = desugar(parse(PROGRAM)).unwrap();
+    let main = body(&expressions, "main");
+    assert_eq!(main[1], Expression::FnCall {
+        name: "print".to_string(),
+        args: vec![Expression::FnCall {
+            name: "total".to_string(),
+            args: vec![Expression::Identifier ("c".to_string()), Expression::Int (4)],
+        }],
+    });
+
+    assert_eq!(desugar(parse("main -> int {\n    return a.b.c()\n}\n")), Err(Error::TooManyLeadingKeywords ("a.b.c".to_string())));
+}
+
+#[test]
+fn copies_deferred_statements_to_each_exit() {
+    let code = "
+f :: ref int n -> int {
+    defer n: 1
+    if n > 0 {
+        defer n: 2
+        return n
+    }
+    return 0
+}
+";
+    let set = |value: i32| Expression::Reassignment {
+        identifier: "n".to_string(),
+        value: Box::new(Expression::Int (value)),
+    };
+    let hold = |value: Expression| Expression::Assignment {
+        datatype: "int".to_string(),
+        identifier: "boron_return".to_string(),
+        value: Box::new(value),
+    };
+    let returned = Expression::Return (Box::new(Expression::Identifier ("boron_return".to_string())));
+
+    let f = body(&desugar(parse(code)).unwrap(), "f");
+    assert!(!f.iter().any(|s| matches!(s, Expression::Defer (_))));
+    // The early return runs both blocks' statements, innermost first, and the block it ends runs none again
+    match &f[0] {
+        Expression::If { body, .. } => assert_eq!(body, &vec![
+            hold(Expression::Identifier ("n".to_string())),
+            set(2),
+            set(1),
+            returned.to_owned(),
+        ]),
+        other => panic!("expected an if statement, got {:?}", other),
+    }
+    assert_eq!(f[1..], [hold(Expression::Int (0)), set(1), returned]);
+}
+
+#[test]
+fn rejects_defer_outside_functions() {
+    assert_eq!(desugar(parse("defer print(1)\n")), Err(Error::DeferOutsideFunction));
+}
+
+#[test]
+fn splits_field_paths() {
+    let (base, fields) = split_path("node.next.value");
+    assert_eq!(base, "node");
+    assert_eq!(fields.collect::<Vec<&str>>(), ["next", "value"]);
+    let (base, mut fields) = split_path("n");
+    assert_eq!(base, "n");
+    assert_eq!(fields.next(), None);
+}
+
+#[test]
+fn runs_the_same_on_each_backend() {
+    assert!(ir::lower(parse(PROGRAM)).is_ok());
+
+    let directory = scratch("run");
+    fs::write(directory.join("sample.brn"), PROGRAM).unwrap();
+    assert_eq!(stdout(&boron(&directory, &["interp", "sample.brn"])), OUTPUT);
+    boron(&directory, &["compile", "--target", "bytecode", "sample.brn"]);
+    assert_eq!(stdout(&boron(&directory, &["run", "sample.brnc"])), OUTPUT);
+    if let Some(compiler) = compiler() {
+        boron(&directory, &["sample.brn"]);
+        let compiled = Command::new(compiler).args(["sample.c", "-o", "sample"]).current_dir(&directory).status().unwrap();
+        assert!(compiled.success());
+        assert_eq!(stdout(&Command::new(directory.join("sample")).output().unwrap()), OUTPUT);
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,220 @@
+//! Checks the JSON and tree views of tokens and syntax trees.
+
+
+mod common;
+
+
+use std::fs;
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use common::{boron, scratch, stdout};
+
+use boron_lang::{
+    dump,
+    emitter::Emitter,
+    ir,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+const PROGRAM: &str = "\
+## A point
+struct Point { int y int x }
+main -> int {
+    return -1 + 2
+}
+";
+
+
+#[test]
+fn dumps_tokens_with_spans() {
+    let tokens: Value = serde_json::from_str(&dump::tokens(PROGRAM).unwrap()).unwrap();
+    assert_eq!(tokens[0], json!({
+        "type": "Struct",
+        "value": "struct",
+        "span": {"start": 11, "end": 17, "line": 2, "column": 1},
+        "doc": "A point",
+    }));
+    assert_eq!(tokens[1], json!({
+        "type": "Identifier",
+        "value": "Point",
+        "span": {"start": 18, "end": 23, "line": 2, "column": 8},
+    }));
+    assert_eq!(tokens.as_array().unwrap().len(), 18);
+}
+
+
+#[test]
+fn dumps_syntax_trees_with_spans() {
+    let output = dump::ast(PROGRAM).unwrap();
+    assert_eq!(output, dump::ast(PROGRAM).unwrap(), "the output is not deterministic");
+
+    let items: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(items[0]["span"], json!({"start": 11, "end": 39, "line": 2, "column": 1}));
+    assert_eq!(items[0]["expression"]["Struct"]["variables"], json!({"x": "int", "y": "int"}));
+    assert_eq!(items[1]["span"], json!({"start": 40, "end": 73, "line": 3, "column": 1}));
+
+    let function = &items[1]["expression"]["FnDeclaration"];
+    assert_eq!(function["identifier"], "main");
+    assert_eq!(function["arguments"], json!([]));
+    assert_eq!(function["body"][0]["Return"]["BinOp"]["op"], "Plus");
+    assert_eq!(function["body"][0]["Return"]["BinOp"]["right"], json!({"Int": 2}));
+    assert_eq!(function["body"][0]["Return"]["BinOp"]["span"]["column"], 15);
+}
+
+
+#[test]
+fn prints_readable_trees() {
+    assert_eq!(dump::tree(PROGRAM).unwrap(), "\
+Struct Point @ 2:1
+  int x
+  int y
+FnDeclaration main -> int @ 3:1
+  Return
+    BinOp Plus @ 4:15
+      UnaryOp Minus @ 4:12
+        Int 1
+      Int 2
+");
+}
+
+
+/// Pins the exact JSON of a token stream, so that changes to the format are deliberate.
+#[test]
+fn keeps_the_token_format_stable() {
+    assert_eq!(dump::tokens("## Count\nlet int n: 2\n").unwrap(), r#"[
+  {
+    "type": "Let",
+    "value": "let",
+    "span": {
+      "start": 9,
+      "end": 12,
+      "line": 2,
+      "column": 1
+    },
+    "doc": "Count"
+  },
+  {
+    "type": "Type",
+    "value": "int",
+    "span": {
+      "start": 13,
+      "end": 16,
+      "line": 2,
+      "column": 5
+    }
+  },
+  {
+    "type": "Identifier",
+    "value": "n",
+    "span": {
+      "start": 17,
+      "end": 18,
+      "line": 2,
+      "column": 9
+    }
+  },
+  {
+    "type": "Assignment",
+    "value": ":",
+    "span": {
+      "start": 18,
+      "end": 19,
+      "line": 2,
+      "column": 10
+    }
+  },
+  {
+    "type": "Int",
+    "value": "2",
+    "span": {
+      "start": 20,
+      "end": 21,
+      "line": 2,
+      "column": 12
+    }
+  }
+]
+"#);
+}
+
+
+/// Pins the exact JSON of a syntax tree, including the variant tags of expressions.
+#[test]
+fn keeps_the_syntax_tree_format_stable() {
+    assert_eq!(dump::ast("## Doubles\ndouble :: int x -> int {\n    return x * 2\n}\n").unwrap(), r#"[
+  {
+    "span": {
+      "start": 11,
+      "end": 54,
+      "line": 2,
+      "column": 1
+    },
+    "expression": {
+      "FnDeclaration": {
+        "identifier": "double",
+        "arguments": [
+          {
+            "name": "x",
+            "datatype": "int",
+            "passing": "Value"
+          }
+        ],
+        "return_type": "int",
+        "body": [
+          {
+            "Return": {
+              "BinOp": {
+                "left": {
+                  "Identifier": "x"
+                },
+                "op": "Multiply",
+                "right": {
+                  "Int": 2
+                },
+                "span": {
+                  "start": 49,
+                  "end": 50,
+                  "line": 3,
+                  "column": 14
+                }
+              }
+            }
+          }
+        ],
+        "doc": "Doubles",
+        "constant": false
+      }
+    }
+  }
+]
+"#);
+}
+
+
+#[test]
+fn emits_each_form_from_the_command_line() {
+    let directory = scratch("emit");
+    fs::write(directory.join("sample.brn"), PROGRAM).unwrap();
+    let emit = |kind: &str| stdout(&boron(&directory, &["--emit", kind, "sample.brn"]));
+    let parse = || Parser::new().parse_all(&mut Tokenizer::new(PROGRAM)).unwrap();
+
+    assert_eq!(emit("tokens"), dump::tokens(PROGRAM).unwrap());
+    assert_eq!(emit("ast"), dump::ast(PROGRAM).unwrap());
+    assert_eq!(emit("tree"), dump::tree(PROGRAM).unwrap());
+    assert_eq!(emit("ir"), ir::optimize(ir::lower(parse()).unwrap()).to_string());
+    // The header records when the C was generated, so compare everything after it
+    let body = |c: &str| c.split_once("local time\n").unwrap().1.to_string();
+    let c = emit("c");
+    assert_eq!(body(&c), body(&Emitter::new().compile_exe(parse()).unwrap()));
+    assert!(c.contains("int main()"), "{}", c);
+    // The C is printed instead of written
+    assert!(!directory.join("sample.c").exists());
+    assert!(emit("asm").contains("Got unknown intermediate form asm"));
+    let _ = fs::remove_dir_all(&directory);
+}
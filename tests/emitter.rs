@@ -0,0 +1,91 @@
+//! Checks the C emitted for sample programs.
+
+
+use boron_lang::{
+    emitter::Emitter,
+    error::Error,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+/// Compiles a Boron program to C.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+#[test]
+fn passes_arguments_by_their_parameter_modes() {
+    let c = compile("
+struct Point { int x int y }
+bump :: ref int n {
+    n: n + 1
+}
+twice :: ref int n {
+    bump(n)
+    bump(n)
+}
+norm :: Point p -> int {
+    return p.x * p.x + p.y * p.y
+}
+half :: int x -> int {
+    return x / 2
+}
+main -> int {
+    let int a: 1
+    twice(a)
+    let int b: half(a)
+    let Point p: { x 3 y 4 }
+    bump(p.x)
+    return norm(p) + b
+}
+");
+    // `ref int` is a pointer, dereferenced where it is used and passed on as is
+    assert!(c.contains("void bump(int *n) {\n(*n) = ((*n) + 1);\n}"), "{}", c);
+    assert!(c.contains("void twice(int *n) {\nbump(n);\nbump(n);\n}"), "{}", c);
+    assert!(c.contains("twice(&a);"), "{}", c);
+    assert!(c.contains("bump(&p.x);"), "{}", c);
+    // Scalars are passed by value
+    assert!(c.contains("int half(int x) {"), "{}", c);
+    assert!(c.contains("int b = half(a);"), "{}", c);
+    // Structs are passed by const reference
+    assert!(c.contains("int norm(const struct Point *p);"), "{}", c);
+    assert!(c.contains("return ((p->x * p->x) + (p->y * p->y));"), "{}", c);
+    assert!(c.contains("return (norm(&p) + b);"), "{}", c);
+}
+
+#[test]
+fn calls_methods_as_functions() {
+    let c = compile("
+struct Point { int x int y }
+norm :: Point p -> int {
+    return p.x * p.x + p.y * p.y
+}
+scale :: ref Point p int k -> int {
+    p.x: p.x * k
+    bump(p.y)
+    return p.norm()
+}
+bump :: ref int n {
+    n: n + 1
+}
+main -> int {
+    let Point p: { x 3 y 4 }
+    p.scale(2)
+    return p.norm()
+}
+");
+    assert!(c.contains("int scale(struct Point *p, int k) {\np->x = (p->x * k);\nbump(&p->y);\nreturn norm(p);\n}"), "{}", c);
+    assert!(c.contains("scale(&p, 2);"), "{}", c);
+    assert!(c.contains("return norm(&p);"), "{}", c);
+}
+
+#[test]
+fn returns_errors_instead_of_exiting() {
+    let mut tokenizer = Tokenizer::new("main -> int {\n    let Shape s: { x 1 }\n    return 0\n}\n");
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    assert_eq!(Emitter::new().compile_exe(expressions), Err(Error::UndeclaredVariable ("Shape".to_string())));
+}
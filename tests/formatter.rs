@@ -0,0 +1,155 @@
+//! Checks the canonical layout produced by the formatter.
+
+
+mod common;
+
+
+use std::fs;
+
+use common::{boron, scratch, stdout};
+
+use boron_lang::{
+    error::Error,
+    formatter::format,
+};
+
+
+/// Formats a program, checking that formatting the output again leaves it unchanged.
+fn check(code: &str) -> String {
+    let formatted = format(code).unwrap();
+    assert_eq!(format(&formatted).unwrap(), formatted, "formatting is not idempotent");
+    formatted
+}
+
+
+#[test]
+fn lays_out_statements() {
+    let formatted = check("
+struct Point { int x
+int y }
+add::int a int b->int{return a+b}
+main :: -> int {
+  let Point p: { y 2, x 1 }
+  if p.x = 1 { print(add(p.x p.y)) } else { print( 0 ) }
+  while p.x < 3 { p.x: p.x+1 }
+  return 0
+}
+");
+    assert_eq!(formatted, "\
+struct Point {
+    int x
+    int y
+}
+
+add :: int a int b -> int {
+    return a + b
+}
+
+main -> int {
+    let Point p: { y 2 x 1 }
+    if p.x = 1 {
+        print(add(p.x p.y))
+    } else {
+        print(0)
+    }
+    while p.x < 3 {
+        p.x: p.x + 1
+    }
+    return 0
+}
+");
+}
+
+#[test]
+fn keeps_comments_and_blank_lines() {
+    let formatted = check("
+# Header
+
+## Says hello
+hello :: {   # no arguments
+    print('h')
+
+
+    #[ a block
+       comment ]#
+    print('i') # done
+}
+# Footer
+");
+    assert_eq!(formatted, "\
+# Header
+
+## Says hello
+hello :: { # no arguments
+    print('h')
+
+    #[ a block
+       comment ]#
+    print('i') # done
+}
+# Footer
+");
+}
+
+#[test]
+fn keeps_needed_parentheses_only() {
+    let formatted = check("
+main -> int {
+    let int a: (1 + 2) * 3
+    let int b: ((1 * 2)) + 3
+    let int c: 1 - (2 - 3)
+    let int d: (-a) * 3
+    let bln e: (a > 2) ? true | false
+    let flt f: 1.50
+    return (a)
+}
+");
+    assert!(formatted.contains("let int a: (1 + 2) * 3\n"));
+    assert!(formatted.contains("let int b: 1 * 2 + 3\n"));
+    assert!(formatted.contains("let int c: 1 - (2 - 3)\n"));
+    assert!(formatted.contains("let int d: (-a) * 3\n"));
+    assert!(formatted.contains("let bln e: (a > 2) ? true | false\n"));
+    assert!(formatted.contains("let flt f: 1.5\n"));
+    assert!(formatted.contains("return a\n"));
+}
+
+#[test]
+fn keeps_each_field_with_its_own_value() {
+    let formatted = check("
+struct Point { int y int x }
+main -> int {
+    let Point p: {y 1 x (2)}
+    let own Point h: new Point {x 3 y 4}
+    return p.x
+}
+");
+    assert!(formatted.contains("let Point p: { y 1 x 2 }\n"), "{}", formatted);
+    assert!(formatted.contains("let own Point h: new Point { x 3 y 4 }\n"), "{}", formatted);
+
+    // A repeated field has no single value to keep, so the program is rejected rather than rewritten
+    let repeated = "struct Point { int x }\nmain -> int {\n    let Point p: { x 1 x 2 }\n    return p.x\n}\n";
+    assert_eq!(format(repeated), Err(Error::DuplicateField ("x".to_string())));
+    assert_eq!(format("struct Point { int x int x }\n"), Err(Error::DuplicateField ("x".to_string())));
+}
+
+#[test]
+fn checks_every_file_and_fails_on_any_problem() {
+    let directory = scratch("check");
+    fs::write(directory.join("a.brn"), "main -> int {\n    return 0\n}\n").unwrap();
+    fs::write(directory.join("b.brn"), "main -> int {\n    return $\n}\n").unwrap();
+    fs::write(directory.join("c.brn"), "main->int{\nreturn (1)\n}\n").unwrap();
+
+    let output = boron(&directory, &["fmt", "--check", "."]);
+    let printed = stdout(&output);
+    // The unparseable file does not stop the files after it from being checked
+    assert!(printed.contains("could not format ./b.brn"), "{}", printed);
+    assert!(printed.contains("./c.brn is not formatted"), "{}", printed);
+    assert!(!printed.contains("a.brn"), "{}", printed);
+    assert_eq!(output.status.code(), Some(1));
+
+    fs::remove_file(directory.join("c.brn")).unwrap();
+    assert_eq!(boron(&directory, &["fmt", "--check", "."]).status.code(), Some(1));
+    fs::remove_file(directory.join("b.brn")).unwrap();
+    assert_eq!(boron(&directory, &["fmt", "--check", "."]).status.code(), Some(0));
+    let _ = fs::remove_dir_all(&directory);
+}
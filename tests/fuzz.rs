@@ -0,0 +1,174 @@
+//! Feeds generated programs through the front end, checking that errors are reported rather than panicking.
+//!
+//! The inputs are mutations of sample programs and random sequences of tokens,
+//! drawn from a fixed seed so that every run checks the same programs.  The
+//! targets in `fuzz/` run the same checks under `cargo fuzz` for much longer.
+
+
+use std::panic;
+
+use boron_lang::{
+    pipeline,
+    tokenizer::Tokenizer,
+    parser::MAX_DEPTH,
+};
+
+
+/// Number of programs generated by each test.
+const CASES: usize = 3000;
+
+
+const SAMPLES: &[&str] = &[
+    "
+struct Point { int x int y }
+shift :: ref Point p int dx {
+    p.x: p.x + dx
+}
+add :: int a int b -> int {
+    return a + b
+}
+main -> int {
+    let Point p: { x 1 y 2 }
+    p.shift(10)
+    defer print(p.x)
+    return add(p.x p.y)
+}
+",
+    "
+struct Node { int value opt Node next }
+const int LIMIT: 3 * 4
+const square :: int x -> int {
+    return x * x
+}
+main -> int {
+    let own Node n: new Node { value 1 next null }
+    static int count: 0
+    while count < LIMIT {
+        count: count + 1
+    }
+    if count = 12 { print(count) } else { drop(n) }
+    let chr c: '\\n'
+    let flt f: 1.5
+    return count > 3 ? square(2) | -1
+}
+test \"adds\" {
+    assert_eq(square(2) 4)
+    assert(!false)
+}
+",
+];
+
+
+const FRAGMENTS: &[&str] = &[
+    "main", "x", "p.x", "p.shift", "Point", "int", "flt", "bln", "chr", "nul", "let", "const", "static",
+    "struct", "while", "if", "else", "return", "defer", "use", "ref", "own", "opt", "new", "null", "test",
+    "assert", "assert_eq", "true", "false", "0", "1", "2147483647", "2147483648", "1.5", "'a'", "'\\n'",
+    "'", "\"name\"", "\"", "::", "->", ":", "=", "+", "-", "*", "/", "<", ">", "<=", ">=", "!", "?", "|",
+    "(", ")", "{", "}", ",", ".", "#", "##", "#[", "]#", "\n", " ", "$", "é", "\\",
+];
+
+
+/// Generates pseudo-random numbers with a xorshift generator.
+struct Rng (u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+
+/// Generates a program by mutating a sample, or by stringing fragments together.
+fn generate(rng: &mut Rng) -> String {
+    if rng.below(2) == 0 {
+        let mut chars = SAMPLES[rng.below(SAMPLES.len())].chars().collect::<Vec<char>>();
+        for _ in 0..1 + rng.below(4) {
+            let at = rng.below(chars.len() + 1);
+            match rng.below(4) {
+                // Delete a run of characters
+                0 => {
+                    let end = (at + 1 + rng.below(8)).min(chars.len());
+                    chars.drain(at.min(end)..end);
+                },
+                // Insert a fragment
+                1 => {
+                    let fragment = FRAGMENTS[rng.below(FRAGMENTS.len())];
+                    chars.splice(at..at, fragment.chars());
+                },
+                // Insert a printable character
+                2 => chars.insert(at, (b' ' + rng.below(95) as u8) as char),
+                // Cut the program short
+                _ => chars.truncate(at),
+            }
+        }
+        chars.into_iter().collect()
+    } else {
+        (0..rng.below(24))
+            .map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())])
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+
+/// Runs a check on many generated programs, reporting the first that panics.
+fn check(seed: u64, f: impl Fn(&str)) {
+    let mut rng = Rng (seed);
+    for _ in 0..CASES {
+        let source = generate(&mut rng);
+        if panic::catch_unwind(panic::AssertUnwindSafe(|| f(&source))).is_err() {
+            panic!("the front end panicked on {:?}", source);
+        }
+    }
+}
+
+
+#[test]
+fn tokenizes_without_panicking() {
+    check(0x9e37_79b9_7f4a_7c15, |source| {
+        let _ = Tokenizer::new(source).count();
+    });
+}
+
+
+#[test]
+fn parses_without_panicking() {
+    check(0xdead_beef_cafe_f00d, |source| {
+        let _ = pipeline::parse(source);
+    });
+}
+
+
+#[test]
+fn compiles_without_panicking() {
+    check(0x0123_4567_89ab_cdef, |source| {
+        let _ = pipeline::check(source);
+    });
+}
+
+
+#[test]
+fn reports_deep_nesting() {
+    // Just within the limit, the whole front end still runs on a test thread's stack
+    let nested = |depth: usize| format!("main -> int {{\n    return {}1{}\n}}\n", "(".repeat(depth), ")".repeat(depth));
+    assert!(pipeline::check(&nested(MAX_DEPTH - 8)).is_ok());
+
+    for source in [
+        nested(100000),
+        format!("main -> int {{\n    return {}1\n}}\n", "-".repeat(100000)),
+        format!("main -> int {{\n    return 1{}\n}}\n", " + 1".repeat(100000)),
+        format!("main -> int {{\n{}\n}}\n", "if true {\n".repeat(100000)),
+    ] {
+        match pipeline::parse(&source) {
+            Err(diagnostic) => assert_eq!(diagnostic.kind, "NestingTooDeep"),
+            Ok(_) => panic!("expected deep nesting to be reported"),
+        }
+    }
+}
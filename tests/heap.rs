@@ -0,0 +1,179 @@
+//! Checks heap allocation with `new`, freeing with `drop`, and the checks on heap pointers.
+//!
+//! Running the emitted C needs a C compiler (`cc`, or `$CC`).  On machines that
+//! lack one, the tests that run C say that they were skipped.
+
+
+mod common;
+
+
+use std::{
+    env,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use common::{boron, scratch};
+
+use boron_lang::{
+    emitter::Emitter,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+const LIST: &str = "
+struct Node { int value opt Node next }
+main -> int {
+    let opt Node list: null
+    let int i: 1
+    while i < 4 {
+        list: new Node { value i next list }
+        i: i + 1
+    }
+    let int total: 0
+    let opt Node node: list
+    while !(node = null) {
+        total: total + node.value
+        node: node.next
+    }
+    print(total)
+    let own Node last: new Node { value 9 next null }
+    let bln end: last.next = null
+    print(end)
+    drop(last list)
+    let bln gone: list = null
+    print(gone)
+    return total
+}
+";
+
+
+/// Compiles a Boron program to C.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+/// Writes a Boron program into an empty scratch directory, returning the directory.
+fn sample(name: &str, code: &str) -> PathBuf {
+    let directory = scratch(name);
+    fs::write(directory.join("sample.brn"), code).unwrap();
+    directory
+}
+
+
+/// Compiles C with the C compiler and runs it, or returns `None`, after saying so, if there is no C compiler.
+fn run_c(name: &str, directory: &Path, code: &str) -> Option<Output> {
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    if !Command::new(&compiler).arg("--version").output().is_ok_and(|o| o.status.success()) {
+        let _ = writeln!(io::stderr(), "skipping {}: `{}` is needed to compile programs", name, compiler);
+        return None;
+    }
+    fs::write(directory.join("sample.c"), code).unwrap();
+    let compiled = Command::new(compiler)
+        .args(["sample.c", "-o", "sample"])
+        .current_dir(directory)
+        .status()
+        .unwrap();
+    assert!(compiled.success(), "could not compile {}", name);
+    Some(Command::new(directory.join("sample")).output().unwrap())
+}
+
+
+#[test]
+fn allocates_and_frees_structs() {
+    let c = compile(LIST);
+    assert!(c.contains("list = boron_new(sizeof(struct Node), &(struct Node) { .next = list, .value = i });"), "{}", c);
+    assert!(c.contains("struct Node * last = boron_new(sizeof(struct Node), &(struct Node) { .next = NULL, .value = 9 });"), "{}", c);
+    // `drop` clears each pointer it frees
+    assert!(c.contains("free(last);\nlast = NULL;\nfree(list);\nlist = NULL;"), "{}", c);
+
+    let directory = sample("list", LIST);
+    let interpreted = boron(&directory, &["interp", "sample.brn"]);
+    assert_eq!(String::from_utf8_lossy(&interpreted.stdout), "6\ntrue\ntrue\n");
+    assert_eq!(interpreted.status.code(), Some(6));
+    if let Some(output) = run_c("list", &directory, &c) {
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "6\ntrue\ntrue\n");
+        assert_eq!(output.status.code(), Some(6));
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn reports_running_out_of_memory() {
+    let code = "struct Point { int x int y }\nmain -> int {\n    let own Point p: new Point { x 1 y 2 }\n    return p.x\n}\n";
+    // Make every allocation fail
+    let c = compile(code).replacen("#include <string.h>\n", "#include <string.h>\n#define malloc(size) NULL\n", 1);
+    let directory = sample("oom", code);
+    if let Some(output) = run_c("oom", &directory, &c) {
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "Out of memory\n");
+        assert_eq!(output.status.code(), Some(1));
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn rejects_misused_pointers() {
+    let cases = [
+        ("null", "let own Point p: null", "Cannot assign `null` to p, which is not declared with `opt`"),
+        ("literal", "let own Point p: { x 1 y 2 }", "Heap pointer p must be allocated with `new`"),
+        ("drop", "let int n: 1\n    drop(n)", "Expected heap pointer declared with `own` or `opt`, got n"),
+        ("moved", "let own Point a: new Point { x 1 y 2 }\n    let own Point b: a\n    drop(a b)", "Heap pointer a was moved to an `own` pointer and can no longer be used"),
+        ("reassigned", "let own Point a: new Point { x 1 y 2 }\n    let own Point b: new Point { x 3 y 4 }\n    drop(b)\n    b: a\n    print(a.x)", "Heap pointer a was moved to an `own` pointer and can no longer be used"),
+    ];
+    for (name, statements, message) in cases {
+        let code = format!("struct Point {{ int x int y }}\nmain -> int {{\n    {}\n    return 0\n}}\n", statements);
+        let directory = sample(name, &code);
+        for args in [&["sample.brn"][..], &["interp", "sample.brn"]] {
+            let stdout = String::from_utf8_lossy(&boron(&directory, args).stdout).into_owned();
+            assert!(stdout.contains(message), "{} ({:?}): {}", name, args, stdout);
+        }
+        assert!(!directory.join("sample.c").exists(), "{}", name);
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
+
+#[test]
+fn moves_pointers_into_own_variables() {
+    let code = "
+struct Point { int x int y }
+main -> int {
+    let own Point a: new Point { x 1 y 2 }
+    let opt Point view: a
+    print(view.x)
+    let own Point b: a
+    print(b.x)
+    a: new Point { x 3 y 4 }
+    print(a.x)
+    drop(a b)
+    return 0
+}
+";
+    // Only `own` variables take the pointer, and assigning to a moved pointer gives it a value again
+    let c = compile(code);
+    assert!(c.contains("struct Point * b = a;"), "{}", c);
+
+    let directory = sample("move", code);
+    let interpreted = boron(&directory, &["interp", "sample.brn"]);
+    assert_eq!(String::from_utf8_lossy(&interpreted.stdout), "1\n1\n3\n");
+    if let Some(output) = run_c("move", &directory, &c) {
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n1\n3\n");
+        assert_eq!(output.status.code(), Some(0));
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn interprets_null_dereferences_as_errors() {
+    let directory = sample("null", "struct Point { int x }\nmain -> int {\n    let opt Point p: new Point { x 1 }\n    drop(p)\n    return p.x\n}\n");
+    let output = boron(&directory, &["interp", "sample.brn"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Dereferenced null pointer in p.x"), "{}", stdout);
+    assert_eq!(output.status.code(), Some(1));
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,204 @@
+//! Runs sample programs with `boron interp` and as compiled C, checking that both behave the same.
+//!
+//! Running the C needs a C compiler (`cc`, or `$CC`).  On machines that lack
+//! one, the tests say that they were skipped and check only the interpreter.
+
+
+mod common;
+
+
+use std::{
+    env,
+    fs,
+    io::{self, Write},
+    process::{Command, Output},
+};
+
+use common::{boron, scratch};
+
+
+/// Returns what a program printed and its exit code.
+fn result(output: Output) -> (String, Option<i32>) {
+    (String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code())
+}
+
+
+/// Runs a Boron program with the interpreter and as compiled C, checking that both print the same and exit with the same code.
+///
+/// Returns what the interpreter printed and its exit code.
+fn compare(name: &str, code: &str) -> (String, Option<i32>) {
+    let directory = scratch(name);
+    fs::write(directory.join("sample.brn"), code).unwrap();
+
+    let interpreted = result(boron(&directory, &["interp", "sample.brn"]));
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    if !Command::new(&compiler).arg("--version").output().is_ok_and(|o| o.status.success()) {
+        let _ = writeln!(io::stderr(), "skipping the C half of {}: `{}` is needed to compile programs", name, compiler);
+        let _ = fs::remove_dir_all(&directory);
+        return interpreted;
+    }
+
+    let emitted = boron(&directory, &["sample.brn"]);
+    assert!(directory.join("sample.c").exists(), "{}", String::from_utf8_lossy(&emitted.stdout));
+    let compiled = Command::new(compiler)
+        .args(["sample.c", "-o", "sample"])
+        .current_dir(&directory)
+        .status()
+        .unwrap();
+    assert!(compiled.success(), "could not compile {}", name);
+    let native = result(Command::new(directory.join("sample")).output().unwrap());
+
+    let _ = fs::remove_dir_all(&directory);
+    assert_eq!(interpreted, native, "{} behaves differently when interpreted and compiled", name);
+    interpreted
+}
+
+
+#[test]
+fn agrees_on_recursion() {
+    let (output, code) = compare("recursion", "
+fib :: int n -> int {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+gcd :: int a int b -> int {
+    if a = b {
+        return a
+    }
+    if a > b {
+        return gcd(a - b b)
+    }
+    return gcd(a b - a)
+}
+main -> int {
+    let int f: fib(15)
+    print(f)
+    let int g: gcd(84 36)
+    print(g)
+    return g
+}
+");
+    assert_eq!(output, "610\n12\n");
+    assert_eq!(code, Some(12));
+}
+
+#[test]
+fn agrees_on_structs() {
+    let (output, code) = compare("structs", "
+struct Point { int x int y }
+length :: Point from Point to -> int {
+    return to.x - from.x + to.y - from.y
+}
+shift :: ref Point p int by {
+    p.x: p.x + by
+    p.y: p.y + by
+}
+main -> int {
+    let Point a: { x 1 y 2 }
+    let Point b: a
+    b.x: 10
+    shift(a 3)
+    let int n: length(a b)
+    print(n)
+    let int x: a.x
+    print(x)
+    return b.y
+}
+");
+    assert_eq!(output, "3\n4\n");
+    assert_eq!(code, Some(2));
+}
+
+#[test]
+fn agrees_on_defer() {
+    let (output, code) = compare("defer", "
+shout :: int n -> int {
+    defer print(n)
+    if n > 0 {
+        let int m: n * 10
+        defer print(m)
+        return n + 1
+    }
+    return 0
+}
+main -> int {
+    let int s: shout(2)
+    print(s)
+    let int i: 0
+    while i < 3 {
+        defer print(i)
+        i: i + 1
+    }
+    if i = 3 {
+        let int inner: 7
+        defer print(inner)
+        let int outer: 8
+        print(outer)
+    }
+    return i
+}
+");
+    assert_eq!(output, "20\n2\n3\n1\n2\n3\n8\n7\n");
+    assert_eq!(code, Some(3));
+}
+
+#[test]
+fn agrees_on_the_heap() {
+    let (output, code) = compare("heap", "
+struct Node { int value opt Node next }
+main -> int {
+    let opt Node list: null
+    let int i: 0
+    while i < 4 {
+        list: new Node { value i next list }
+        i: i + 1
+    }
+    let int total: 0
+    let opt Node node: list
+    while !(node = null) {
+        total: total + node.value
+        node: node.next
+    }
+    print(total)
+    let own Node h: new Node { value 5 next null }
+    h.value: h.value + 1
+    let int v: h.value
+    print(v)
+    drop(h)
+    drop(list)
+    return total
+}
+");
+    assert_eq!(output, "6\n6\n");
+    assert_eq!(code, Some(6));
+}
+
+#[test]
+fn agrees_on_printing_expressions() {
+    let (output, code) = compare("print", "
+struct Point { int x int y }
+norm :: Point p -> int {
+    return p.x * p.x + p.y * p.y
+}
+shout :: int n -> nul {
+    print(n * 2)
+}
+main -> int {
+    let int a: 4
+    let Point p: { x 3 y 4 }
+    print(a + 1)
+    print(p.norm())
+    print(norm(p) - a)
+    print(a > 3)
+    print(!(a = 4))
+    print((a > 9) ? 1 | -a)
+    print('b')
+    print(shout(a))
+    return a
+}
+");
+    assert_eq!(output, "5\n25\n21\ntrue\nfalse\n-4\nb\n8\n\n");
+    assert_eq!(code, Some(4));
+}
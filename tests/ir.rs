@@ -0,0 +1,85 @@
+//! Checks the IR that sample programs are lowered and optimized into.
+
+
+use boron_lang::{
+    ir,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+/// Lowers and optimizes a Boron program, returning its printed IR.
+fn optimize(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    ir::optimize(ir::lower(expressions).unwrap()).to_string()
+}
+
+
+#[test]
+fn folds_constants_through_locals() {
+    let module = optimize("
+main -> int {
+    let int a: 3 * 4
+    let int b: a + 1
+    let flt h: b / 2.0
+    print(h)
+    return b
+}
+");
+    assert!(module.contains("print 6.5"));
+    assert!(module.contains("return 13"));
+    assert!(!module.contains("let $a"));
+}
+
+#[test]
+fn removes_dead_branches_and_unused_functions() {
+    let module = optimize("
+unused :: int n -> int {
+    return n
+}
+used -> int {
+    return 1
+}
+main -> int {
+    if 2 > 3 {
+        print(unused(4))
+    }
+    return used()
+}
+");
+    assert!(!module.contains("fn unused"));
+    assert!(module.contains("fn used"));
+    assert!(!module.contains("branch"));
+}
+
+#[test]
+fn keeps_runtime_errors() {
+    let module = optimize("
+main -> int {
+    let int zero: 0
+    let int big: 2147483647
+    print(big + 1)
+    return 1 / zero
+}
+");
+    assert!(module.contains("add 2147483647 1"));
+    assert!(module.contains("div 1 0"));
+}
+
+#[test]
+fn keeps_variables_passed_by_reference() {
+    let module = optimize("
+bump :: ref int n {
+    n: n + 1
+}
+main -> int {
+    let int a: 1
+    bump(a)
+    return a
+}
+");
+    assert!(module.contains("store $a 1"));
+    assert!(module.contains("call bump(&$a)"));
+    assert!(!module.contains("return 1"));
+}
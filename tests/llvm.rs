@@ -0,0 +1,238 @@
+//! Checks the LLVM IR emitted for sample programs with a plain-text verifier.
+//!
+//! The verifier does not need LLVM installed.  It checks that every basic block
+//! ends with a terminator, that every register is defined exactly once, and that
+//! every register, label, and global referred to is defined.
+
+
+use std::collections::HashSet;
+
+use boron_lang::{
+    llvm::Emitter,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+/// Compiles a Boron program to LLVM IR.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+/// Finds every name following a sigil (`%` or `@`) in a line, outside of string constants.
+fn names(line: &str, sigil: char) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == 'c' && chars.peek() == Some(&'"') {
+            // Skip a string constant (`c"..."`)
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+            }
+        } else if c == sigil {
+            let mut name = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    name.push(c);
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+            }
+            names.push(name);
+        }
+    }
+    names
+}
+
+
+/// Checks that a module is well formed, returning a description of the first problem found.
+fn verify(module: &str) -> Result<(), String> {
+    let mut types = HashSet::new();
+    let mut globals = HashSet::new();
+    for line in module.lines() {
+        if line.contains(" = type ") {
+            types.extend(names(line, '%').into_iter().take(1));
+        } else if line.starts_with('@') || line.starts_with("declare ") || line.starts_with("define ") {
+            globals.extend(names(line, '@').into_iter().take(1));
+        }
+    }
+
+    let mut lines = module.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        // Global references may appear anywhere
+        for name in names(line, '@') {
+            if !globals.contains(&name) {
+                return Err(format!("line {}: undefined global @{}", number + 1, name));
+            }
+        }
+        if !line.starts_with("define ") {
+            continue;
+        }
+
+        // Collect the body of the function, with the registers and labels it defines
+        let mut defined = HashSet::new();
+        let mut labels = HashSet::new();
+        for name in names(line, '%').into_iter().filter(|n| !types.contains(n)) {
+            defined.insert(name);
+        }
+        let mut body = Vec::new();
+        let mut closed = false;
+        for (number, line) in lines.by_ref() {
+            if line == "}" {
+                closed = true;
+                break;
+            }
+            if let Some(label) = line.strip_suffix(':') {
+                if !defined.insert(label.to_string()) {
+                    return Err(format!("line {}: label {} defined twice", number + 1, label));
+                }
+                labels.insert(label.to_string());
+            } else if let Some((register, _)) = line.trim().split_once(" = ") {
+                let register = names(register, '%').concat();
+                if !defined.insert(register.to_owned()) {
+                    return Err(format!("line {}: register %{} defined twice", number + 1, register));
+                }
+            }
+            body.push((number, line));
+        }
+        if !closed {
+            return Err(format!("line {}: unterminated function", number + 1));
+        }
+
+        let mut terminated = true;
+        for (number, line) in body {
+            if line.ends_with(':') {
+                if !terminated {
+                    return Err(format!("line {}: block falls through without a terminator", number + 1));
+                }
+                terminated = false;
+                continue;
+            }
+            if terminated {
+                return Err(format!("line {}: instruction after a terminator", number + 1));
+            }
+            let instruction = line.trim().split_once(" = ").map_or(line.trim(), |(_, i)| i);
+            terminated = instruction.starts_with("ret ")
+                || instruction.starts_with("br ")
+                || instruction == "unreachable";
+
+            for name in names(line, '%').into_iter().filter(|n| !types.contains(n)) {
+                if !defined.contains(&name) {
+                    return Err(format!("line {}: undefined register %{}", number + 1, name));
+                }
+            }
+            if instruction.starts_with("br ") {
+                for target in instruction.split("label %").skip(1) {
+                    let target = target.split([',', ' ']).next().unwrap_or_default();
+                    if !labels.contains(target) {
+                        return Err(format!("line {}: branch to unknown label {}", number + 1, target));
+                    }
+                }
+            }
+        }
+        if !terminated {
+            return Err("function ends without a terminator".to_string());
+        }
+    }
+    Ok(())
+}
+
+
+#[test]
+fn verifies_functions_and_locals() {
+    let module = compile("
+add :: int a int b -> int {
+    let int sum: a + b
+    return sum
+}
+main -> int {
+    let flt x: 2
+    print(x)
+    return add(1 2)
+}
+");
+    assert_eq!(verify(&module), Ok(()));
+    assert!(module.contains("define i32 @add(i32 %a.arg, i32 %b.arg)"));
+    assert!(module.contains("alloca float"));
+}
+
+#[test]
+fn verifies_structs_and_references() {
+    let module = compile("
+struct Point { int x int y }
+shift :: ref Point p int dx {
+    p.x: p.x + dx
+}
+norm :: Point p -> int {
+    return p.x + p.y
+}
+main -> int {
+    let Point p: { x 1 y 2 }
+    p.shift(10)
+    let own Point q: new Point { x 3 y 4 }
+    print(q.norm())
+    drop(q)
+    return norm(p)
+}
+");
+    assert_eq!(verify(&module), Ok(()));
+    assert!(module.contains("%Point = type { i32, i32 }"));
+    assert!(module.contains("define void @shift(%Point* %p.arg, i32 %dx.arg)"));
+}
+
+#[test]
+fn verifies_control_flow() {
+    let module = compile("
+collatz :: int n -> int {
+    let int steps: 0
+    while n > 1 {
+        defer steps: steps + 1
+        if n / 2 * 2 = n {
+            n: n / 2
+        } else {
+            n: 3 * n + 1
+        }
+    }
+    return steps
+}
+sign :: int n -> int {
+    if n < 0 {
+        return 0 - 1
+    }
+    return n > 0 ? 1 | 0
+}
+main -> int {
+    let bln done: collatz(27) > 100
+    print(done)
+    return sign(0 - 5)
+}
+");
+    assert_eq!(verify(&module), Ok(()));
+    assert!(module.contains("phi i32"));
+}
+
+#[test]
+fn rejects_malformed_modules() {
+    let module = "define i32 @main() {\nentry:\n  %x = add i32 1, %y\n  ret i32 %x\n}\n";
+    assert!(verify(module).is_err());
+    let module = "define i32 @main() {\nentry:\n  br label %exit\n}\n";
+    assert!(verify(module).is_err());
+    let module = "define void @main() {\nentry:\n  ret void\nnext:\n  call void @missing()\n}\n";
+    assert!(verify(module).is_err());
+}
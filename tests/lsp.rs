@@ -0,0 +1,147 @@
+//! Drives the language server and the library compile pipeline.
+
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use boron_lang::{
+    lsp::Server,
+    pipeline,
+};
+
+
+const PROGRAM: &str = "
+## A point in the plane
+struct Point { int x int y }
+shift :: ref Point p int dx {
+    p.x: p.x + dx
+}
+add :: int a int b -> int {
+    return a + b
+}
+main -> int {
+    let Point p: { x 1 y 2 }
+    p.shift(10)
+    return add(p.x p.y)
+}
+";
+
+
+/// Opens a document in a new server, returning the server and the diagnostics it published.
+fn open(text: &str) -> (Server, Value) {
+    let mut server = Server::new();
+    server.handle(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}));
+    let replies = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": "file:///main.brn", "languageId": "boron", "version": 1, "text": text}},
+    }));
+    assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+    let diagnostics = replies[0]["params"]["diagnostics"].to_owned();
+    (server, diagnostics)
+}
+
+
+/// Sends a request about a position in the document, returning its result.
+fn request(server: &mut Server, method: &str, line: usize, character: usize) -> Value {
+    let replies = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": method,
+        "params": {
+            "textDocument": {"uri": "file:///main.brn"},
+            "position": {"line": line, "character": character},
+        },
+    }));
+    replies[0]["result"].to_owned()
+}
+
+
+/// Lists the labels of a list of completion items.
+fn labels(items: &Value) -> Vec<&str> {
+    items.as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["label"].as_str().unwrap())
+        .collect()
+}
+
+
+#[test]
+fn reports_errors_without_exiting() {
+    assert_eq!(pipeline::check(PROGRAM), Ok(()));
+
+    let diagnostic = pipeline::check("main -> int {\n    return missing\n}\n").unwrap_err();
+    assert_eq!(diagnostic.message, "Found undeclared variable missing");
+    assert_eq!((diagnostic.span.line, diagnostic.span.column), (2, 12));
+
+    let diagnostic = pipeline::parse("main -> int {\n    let int x: $\n}\n").unwrap_err();
+    assert_eq!((diagnostic.span.line, diagnostic.span.column), (2, 16));
+}
+
+
+#[test]
+fn publishes_diagnostics() {
+    let (_, diagnostics) = open(PROGRAM);
+    assert_eq!(diagnostics, json!([]));
+
+    let (_, diagnostics) = open("main -> int {\n    return add(1 2)\n}\n");
+    assert_eq!(diagnostics[0]["message"], "Found undeclared function add");
+    assert_eq!(diagnostics[0]["range"]["start"], json!({"line": 1, "character": 11}));
+}
+
+
+#[test]
+fn shows_types_on_hover() {
+    let (mut server, _) = open(PROGRAM);
+
+    let hover = request(&mut server, "textDocument/hover", 12, 12);
+    assert_eq!(hover["contents"]["value"], "```boron\nadd :: int a int b -> int\n```");
+
+    let hover = request(&mut server, "textDocument/hover", 12, 21);
+    assert_eq!(hover["contents"]["value"], "```boron\nint Point.y\n```");
+
+    let hover = request(&mut server, "textDocument/hover", 10, 9);
+    assert_eq!(hover["contents"]["value"], "```boron\nstruct Point {\n    int x\n    int y\n}\n```\n\nA point in the plane");
+}
+
+
+#[test]
+fn goes_to_definitions() {
+    let (mut server, _) = open(PROGRAM);
+
+    // The variable `p` in `p.shift(10)`
+    let location = request(&mut server, "textDocument/definition", 11, 4);
+    assert_eq!(location["range"]["start"], json!({"line": 10, "character": 14}));
+
+    // The method `shift`
+    let location = request(&mut server, "textDocument/definition", 11, 7);
+    assert_eq!(location["range"]["start"], json!({"line": 3, "character": 0}));
+
+    // The argument `dx`
+    let location = request(&mut server, "textDocument/definition", 4, 16);
+    assert_eq!(location["range"]["start"], json!({"line": 3, "character": 25}));
+}
+
+
+#[test]
+fn completes_fields_and_functions() {
+    let (mut server, _) = open(PROGRAM);
+    let text = PROGRAM.replace("    p.shift(10)", "    p.");
+    server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {
+            "textDocument": {"uri": "file:///main.brn", "version": 2},
+            "contentChanges": [{"text": text}],
+        },
+    }));
+
+    let items = request(&mut server, "textDocument/completion", 11, 6);
+    assert_eq!(labels(&items), ["x", "y", "shift"]);
+
+    let items = request(&mut server, "textDocument/completion", 12, 4);
+    assert_eq!(labels(&items), ["shift", "add", "main", "p"]);
+}
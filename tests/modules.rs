@@ -0,0 +1,140 @@
+//! Compiles programs that use modules, checking that used modules are found and compiled automatically.
+//!
+//! Module paths are relative to the file that uses them, so each test writes a
+//! project into a scratch directory.  Tests that run the compiled
+//! program need a C compiler (`cc`, or `$CC`) and skip that step without one.
+
+
+mod common;
+
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use common::{boron, stdout};
+
+use boron_lang::{
+    modules::resolve,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+/// Creates a scratch directory for a test, holding the given source files.
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let directory = env::temp_dir().join(format!("boron-modules-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    for (filename, code) in files {
+        let path = directory.join(filename);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, code).unwrap();
+    }
+    directory
+}
+
+
+/// Compiles a program in a project, returning what the compiler printed.
+fn compile(directory: &Path, filename: &str) -> String {
+    stdout(&boron(directory, &[filename]))
+}
+
+
+#[test]
+fn returns_the_parsed_program() {
+    let source = "## Entry point\nmain -> int {\n    return 0\n}\n";
+    let (expressions, modules) = resolve("./main.brn", source).unwrap();
+    assert!(modules.is_empty());
+    assert_eq!(expressions, Parser::new().parse_all(&mut Tokenizer::new(source)).unwrap());
+}
+
+
+#[test]
+fn compiles_used_modules_transitively() {
+    let directory = project("transitive", &[
+        ("main.brn", "use geo.square\nuse geo.shapes\nmain -> int {\n    let int a: area(3)\n    print(a)\n    return 0\n}\n"),
+        ("geo/square.brn", "use shapes\narea :: int side -> int {\n    return twice(side) * side / 2\n}\n"),
+        ("geo/shapes.brn", "twice :: int x -> int {\n    return x * 2\n}\n"),
+    ]);
+
+    let stdout = compile(&directory, "main.brn");
+    assert!(!stdout.contains("Error"), "{}", stdout);
+    assert!(fs::read_to_string(directory.join("geo/square.h")).unwrap().contains("#include \"shapes.h\""));
+    assert!(directory.join("geo/shapes.h").exists());
+    assert!(directory.join("main.c").exists());
+
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    if !Command::new(&compiler).arg("--version").output().is_ok_and(|o| o.status.success()) {
+        let _ = fs::remove_dir_all(&directory);
+        return;
+    }
+    let compiled = Command::new(compiler)
+        .args(["main.c", "-o", "main"])
+        .current_dir(&directory)
+        .status()
+        .unwrap();
+    assert!(compiled.success());
+    let output = Command::new(directory.join("main")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9\n");
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn reports_missing_modules_where_they_are_used() {
+    let directory = project("missing", &[
+        ("main.brn", "use geo.square\nmain -> int {\n    return 0\n}\n"),
+        ("geo/square.brn", "## Squares\n\nuse geo.nothing\n"),
+    ]);
+
+    let stdout = compile(&directory, "main.brn");
+    assert!(stdout.contains("Could not find module geo/nothing, used in geo/square.brn at line 3, column 1"), "{}", stdout);
+    assert!(!directory.join("main.c").exists());
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn reports_cycles_with_the_whole_chain() {
+    let directory = project("cycle", &[
+        ("main.brn", "use a\nmain -> int {\n    return 0\n}\n"),
+        ("a.brn", "use b\n"),
+        ("b.brn", "use c\n"),
+        ("c.brn", "use a\n"),
+    ]);
+
+    let stdout = compile(&directory, "./main.brn");
+    assert!(stdout.contains("Found modules that use each other: a.brn -> b.brn -> c.brn -> a.brn"), "{}", stdout);
+    assert!(!directory.join("a.h").exists());
+
+    fs::write(directory.join("c.brn"), "use main\n").unwrap();
+    let stdout = compile(&directory, "./main.brn");
+    assert!(stdout.contains("main.brn -> a.brn -> b.brn -> c.brn -> main.brn"), "{}", stdout);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn finds_modules_next_to_the_file_that_uses_them() {
+    let directory = project("outside", &[
+        ("app/main.brn", "use geo.square\nmain -> int {\n    let int a: area(3)\n    print(a)\n    return a\n}\n"),
+        ("app/geo/square.brn", "use shapes\narea :: int side -> int {\n    return twice(side) * side / 2\n}\n"),
+        ("app/geo/shapes.brn", "twice :: int x -> int {\n    return x * 2\n}\n"),
+    ]);
+
+    // Compiling from outside the project finds the same modules
+    let printed = compile(&directory, "app/main.brn");
+    assert!(!printed.contains("Error"), "{}", printed);
+    assert!(directory.join("app/geo/shapes.h").exists());
+    assert!(directory.join("app/main.c").exists());
+
+    // So do the interpreter and the backends that compile modules along with the program
+    assert_eq!(stdout(&boron(&directory, &["interp", "app/main.brn"])), "9\n");
+    boron(&directory, &["compile", "--target", "bytecode", "app/main.brn"]);
+    assert_eq!(stdout(&boron(&directory, &["run", "app/main.brnc"])), "9\n");
+    let _ = fs::remove_dir_all(&directory);
+}
+
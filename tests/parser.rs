@@ -0,0 +1,72 @@
+//! Parses sample programs, checking the syntax trees that the parser produces.
+
+
+use std::collections::HashMap;
+
+use boron_lang::{
+    error::Error,
+    parser::{
+        Expression,
+        Parser,
+    },
+    tokenizer::Tokenizer,
+};
+
+
+/// Parses a Boron program.
+fn parse(code: &str) -> Vec<Expression> {
+    let mut tokenizer = Tokenizer::new(code);
+    Parser::new().parse_all(&mut tokenizer).unwrap()
+}
+
+
+#[test]
+fn attaches_doc_comments_to_declarations() {
+    let program = parse("
+## A point
+## in the plane
+struct Point {
+    ## Across
+    int x
+    int y
+}
+#[ Not a doc comment ]#
+## The origin
+origin -> Point {
+    ## Not attached to statements
+    let Point p: { x 0 y 0 }
+    return p
+}
+undocumented -> int {
+    return 0
+}
+");
+
+    match &program[0] {
+        Expression::Struct { identifier, doc, docs, .. } => {
+            assert_eq!(identifier, "Point");
+            assert_eq!(doc.as_deref(), Some("A point\nin the plane"));
+            assert_eq!(docs, &HashMap::from([("x".to_string(), "Across".to_string())]));
+        },
+        e => panic!("expected a struct, got {:?}", e),
+    }
+    match &program[1] {
+        Expression::FnDeclaration { identifier, doc, body, .. } => {
+            assert_eq!(identifier, "origin");
+            assert_eq!(doc.as_deref(), Some("The origin"));
+            assert!(matches!(body[0], Expression::Assignment { .. }), "{:?}", body[0]);
+        },
+        e => panic!("expected a function, got {:?}", e),
+    }
+    assert!(matches!(&program[2], Expression::FnDeclaration { doc: None, .. }));
+}
+
+#[test]
+fn returns_syntax_errors() {
+    let parser = Parser::new();
+    let result = parser.parse_all(&mut Tokenizer::new("main -> int {\n    return )\n}\n"));
+    assert!(matches!(result, Err(Error::UnexpectedToken (_, 2, 12))), "{:?}", result);
+
+    // The parser can be used again after an error
+    assert_eq!(parser.parse_all(&mut Tokenizer::new("let int x: 1\n")).map(|e| e.len()), Ok(1));
+}
@@ -0,0 +1,67 @@
+//! Drives `boron repl` through its standard input, checking what it prints back.
+
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+
+/// Feeds lines to a REPL session, returning everything it printed.
+fn session(input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_boron"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+
+/// Splits the output of a session into what was printed after each prompt.
+fn replies(output: &str) -> Vec<&str> {
+    output.split(">>> ")
+        .skip(1)
+        .map(|r| r.trim_end_matches('\n'))
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+
+#[test]
+fn evaluates_expressions_with_their_types() {
+    let output = session("let int x: 2\nx * 21\nlet flt h: 0.5\nh\n3 > 2\n");
+    assert!(output.starts_with("Boron "), "{}", output);
+    assert_eq!(replies(&output), vec!["42: int", "0.500000: flt", "true: bln"]);
+}
+
+#[test]
+fn prints_types_trees_and_c() {
+    let output = session("let int x: 2\n:type x * 3\n:type 1.5\n:ast 1 + x\n:c x + 1\n");
+    let replies = replies(&output);
+    assert_eq!(replies[..2], ["int", "flt"]);
+    assert!(replies[2].starts_with("BinOp {\n    left: Int(\n        1,\n    ),\n    op: Plus,\n    right: Identifier(\n        \"x\",\n    ),"), "{}", replies[2]);
+    assert_eq!(replies[3], "(x + 1);");
+}
+
+#[test]
+fn reads_declarations_over_several_lines() {
+    let output = session("square :: int n -> int {\n    let int m: n * n\n    return m\n}\nsquare(7)\n");
+    // Each line of an unfinished declaration gets a continuation prompt
+    assert!(output.contains(">>> ... ... ... >>> 49: int\n"), "{}", output);
+}
+
+#[test]
+fn keeps_going_after_errors() {
+    let output = session("let int x: 2\nlet int y: $\nundefined + 1\nbroken :: int n -> int {\n    return n +\n}\nx + 40\n");
+    let replies = replies(&output);
+    assert_eq!(replies[0], "Error: Unexpected character '$' at line 1, column 12");
+    assert_eq!(replies[1], "Error: Found undeclared variable undefined");
+    assert_eq!(replies[2], "... ... Error: Unexpected token } at line 3, column 1");
+    // Earlier declarations survive every error
+    assert_eq!(replies.last(), Some(&"42: int"));
+}
@@ -0,0 +1,148 @@
+//! Compiles and runs test harnesses, and drives `boron test` over a project.
+//!
+//! Tests that run a harness need a C compiler (`cc`, or `$CC`) and pass
+//! without running anything on machines that lack one.
+
+
+mod common;
+
+
+use std::{
+    fs,
+    process::Command,
+};
+
+use common::{boron, compiler, scratch};
+
+use boron_lang::{
+    emitter::Emitter,
+    parser::{
+        Expression,
+        Parser,
+    },
+    tokenizer::Tokenizer,
+};
+
+
+const PROGRAM: &str = "
+add :: int a int b -> int {
+    return a + b
+}
+
+main -> int {
+    return add(1 2)
+}
+
+test \"adds numbers\" {
+    assert_eq(add(1 2) 3)
+    assert(add(1 1) > 1)
+}
+
+test \"fails on purpose\" {
+    let int x: add(2 2)
+    assert(x = 5)
+}
+";
+
+
+/// Parses a Boron program.
+fn parse(code: &str) -> Vec<Expression> {
+    let mut tokenizer = Tokenizer::new(code);
+    Parser::new().parse_all(&mut tokenizer).unwrap()
+}
+
+
+#[test]
+fn parses_tests_and_assertions() {
+    let expressions = parse(PROGRAM);
+    let names = expressions.iter()
+        .filter_map(|e| match e {
+            Expression::Test { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<&str>>();
+    assert_eq!(names, ["adds numbers", "fails on purpose"]);
+
+    match &parse("test \"escapes \\\"quotes\\\"\" { assert(true) }")[0] {
+        Expression::Test { name, body } => {
+            assert_eq!(name, "escapes \"quotes\"");
+            assert!(matches!(&body[0], Expression::Assert { span, .. } if (span.line, span.column) == (1, 29)));
+        },
+        e => panic!("expected a test, got {:?}", e),
+    }
+}
+
+
+#[test]
+fn leaves_tests_out_of_executables() {
+    let code = Emitter::new().compile_exe(parse(PROGRAM)).unwrap();
+    assert!(code.contains("int main()"));
+    assert!(!code.contains("boron_test_"));
+    assert!(!code.contains("boron_tests"));
+}
+
+
+#[test]
+fn runs_each_test_in_isolation() {
+    let code = Emitter::new().compile_tests("math.brn", parse(PROGRAM)).unwrap();
+    assert!(!code.contains("int main()"));
+    assert!(code.contains("boron_assert((add(1, 2)) == (3), \"math.brn\", 11, "));
+
+    let Some(compiler) = compiler() else { return };
+    let directory = scratch("harness");
+    let (source, binary) = (directory.join("harness.c"), directory.join("harness"));
+    fs::write(&source, code).unwrap();
+    let compiled = Command::new(compiler).arg(&source).arg("-o").arg(&binary).status().unwrap();
+    assert!(compiled.success(), "could not compile {}", source.display());
+
+    let output = Command::new(&binary).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stdout, "test adds numbers ... ok\ntest fails on purpose ... FAILED\n");
+    assert_eq!(stderr, "math.brn:17: assertion failed\n");
+    assert_eq!(output.status.code(), Some(1));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn summarizes_a_project() {
+    if compiler().is_none() {
+        return;
+    }
+    let directory = scratch("project");
+    fs::write(directory.join("math.brn"), PROGRAM).unwrap();
+    fs::write(directory.join("main.brn"), "main -> int {\n    return 0\n}\n").unwrap();
+
+    let output = boron(&directory, &["test", "."]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("running 2 tests in "), "{}", stdout);
+    assert!(!stdout.contains("main.brn"), "{}", stdout);
+    assert!(stdout.ends_with("test result: FAILED. 1 passed; 1 failed\n"), "{}", stdout);
+    assert_eq!(output.status.code(), Some(1));
+
+    fs::write(directory.join("math.brn"), PROGRAM.replace("x = 5", "x = 4")).unwrap();
+    let output = boron(&directory, &["test", "."]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with("test result: ok. 2 passed; 0 failed\n"), "{}", stdout);
+    assert_eq!(output.status.code(), Some(0));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+
+#[test]
+fn counts_files_that_cannot_be_parsed_as_failures() {
+    let directory = scratch("parse");
+    fs::write(directory.join("a.brn"), "test \"broken\" {\n    assert($)\n}\n").unwrap();
+    fs::write(directory.join("b.brn"), PROGRAM.replace("x = 5", "x = 4")).unwrap();
+
+    let output = boron(&directory, &["test", "."]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("could not parse ./a.brn"), "{}", stdout);
+    assert!(stdout.contains("Unexpected character '$' at line 2"), "{}", stdout);
+    // The files after it are still tested
+    let result = if compiler().is_some() { "2 passed; 1 failed" } else { "0 passed; 3 failed" };
+    assert!(stdout.ends_with(&format!("test result: FAILED. {}\n", result)), "{}", stdout);
+    assert_eq!(output.status.code(), Some(1));
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,159 @@
+//! Tokenizes sample programs, checking the tokens, spans and errors that the tokenizer produces.
+
+
+use std::{
+    env,
+    fs,
+    process::Command,
+};
+
+use boron_lang::{
+    error::Error,
+    tokenizer::{
+        Token,
+        TokenType,
+        Tokenizer,
+    },
+};
+
+
+const PROGRAM: &str = "## A point\r\nstruct Point { int x, int y }\n#[ block #[ nested ]# ]#\nmain -> int {\n\tlet chr c: '\\n' # line\n    return 0\n}";
+
+
+/// Lists the type and text of each token in a source string.
+fn tokens(source: &str) -> Vec<(TokenType, &str)> {
+    Tokenizer::new(source).map(|t| t.map(|t| (t.get_type(), t.as_str()))).collect::<Result<_, _>>().unwrap()
+}
+
+
+/// Tokenizes a file with `boron --emit tokens`, returning what the compiler printed.
+fn emit(name: &str, source: &str) -> String {
+    let path = env::temp_dir().join(format!("boron-tokenizer-{}-{}.brn", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_boron"))
+        .args(["--emit", "tokens"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+
+#[test]
+fn splits_operators_from_operands() {
+    use TokenType::*;
+    assert_eq!(tokens("a+b"), vec![(Identifier, "a"), (Plus, "+"), (Identifier, "b")]);
+    assert_eq!(tokens("a-b->c"), vec![(Identifier, "a"), (Minus, "-"), (Identifier, "b"), (FnReturnType, "->"), (Identifier, "c")]);
+    assert_eq!(tokens("x>=1<=y"), vec![(Identifier, "x"), (GreaterEqual, ">="), (Int, "1"), (LessEqual, "<="), (Identifier, "y")]);
+    assert_eq!(tokens("f::g:2.5*p.x"), vec![
+        (Identifier, "f"), (FnDeclaration, "::"), (Identifier, "g"), (Assignment, ":"),
+        (Float, "2.5"), (Multiply, "*"), (Identifier, "p.x"),
+    ]);
+}
+
+#[test]
+fn reads_unicode_identifiers() {
+    use TokenType::*;
+    assert_eq!(tokens("let flt größe: π * 变量_2"), vec![
+        (Let, "let"), (Type, "flt"), (Identifier, "größe"), (Assignment, ":"),
+        (Identifier, "π"), (Multiply, "*"), (Identifier, "变量_2"),
+    ]);
+    let spans = Tokenizer::new("größe π").map(|t| t.unwrap().get_span()).collect::<Vec<_>>();
+    assert_eq!((spans[1].start, spans[1].column), ("größe ".len(), 7));
+}
+
+#[test]
+fn locates_unexpected_characters() {
+    let stdout = emit("character", "main -> int {\n    let int x: 3 $ 4\n}\n");
+    assert!(stdout.contains("Unexpected character '$' at line 2, column 18"), "{}", stdout);
+    let stdout = emit("dot", "let int x: 1.2.3\n");
+    assert!(stdout.contains("Unexpected character '.' at line 1, column 15"), "{}", stdout);
+}
+
+#[test]
+fn locates_invalid_numbers() {
+    let stdout = emit("suffix", "main -> int {\n  let int x: 12ab\n}\n");
+    assert!(stdout.contains("Invalid numeric literal 12ab at line 2, column 14"), "{}", stdout);
+    let stdout = emit("overflow", "let int x: 99999999999\n");
+    assert!(stdout.contains("Invalid numeric literal 99999999999 at line 1, column 12"), "{}", stdout);
+}
+
+#[test]
+fn skips_nested_block_comments() {
+    use TokenType::*;
+    let source = "let #[ outer #[ inner ]# still #[#]# outer ]# x\n#[ a\n]# # line ]#\ny";
+    assert_eq!(tokens(source), vec![(Let, "let"), (Identifier, "x"), (Identifier, "y")]);
+
+    let comments = Tokenizer::lossless(source)
+        .map(Result::unwrap)
+        .filter(|t| t.check(Comment))
+        .map(|t| t.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(comments, vec!["#[ outer #[ inner ]# still #[#]# outer ]#", "#[ a\n]#", "# line ]#"]);
+}
+
+#[test]
+fn locates_unterminated_block_comments() {
+    let stdout = emit("comment", "let int x: 1\n  #[ a #[ b ]#\n");
+    assert!(stdout.contains("Unterminated block comment starting at line 2, column 3"), "{}", stdout);
+}
+
+#[test]
+fn attaches_doc_comments_to_the_next_token() {
+    let mut tokenizer = Tokenizer::new("## First\n  ##   Second  \n# not a doc\nstruct ## Field\nPoint");
+    let first = tokenizer.next().unwrap().unwrap();
+    assert_eq!(first.get_doc(), Some("First\n  Second".to_string()));
+    let second = tokenizer.next().unwrap().unwrap();
+    assert_eq!((second.as_str(), second.get_doc()), ("Point", Some("Field".to_string())));
+}
+
+#[test]
+fn reproduces_the_source_losslessly() {
+    let tokens = Tokenizer::lossless(PROGRAM).collect::<Result<Vec<Token>, Error>>().unwrap();
+    assert_eq!(tokens.iter().map(|t| t.as_str()).collect::<String>(), PROGRAM);
+    assert!(tokens.iter().any(|t| t.is_trivia()));
+
+    // Each token's span covers exactly its text
+    for token in &tokens {
+        let span = token.get_span();
+        assert_eq!(&PROGRAM[span.start..span.end], token.as_str());
+    }
+}
+
+#[test]
+fn looks_ahead_past_the_end() {
+    let mut tokenizer = Tokenizer::new("let int x: 1");
+    assert_eq!(tokenizer.look_ahead(4).unwrap().map(|t| t.get_value()), Some("1".to_string()));
+    assert!(tokenizer.look_ahead(5).is_ok_and(|t| t.is_none()));
+    assert!(tokenizer.look_ahead(100).is_ok_and(|t| t.is_none()));
+
+    // Looking past the end does not lose the tokens already scanned
+    assert_eq!(tokenizer.peek().unwrap().map(|t| t.get_value()), Some("let".to_string()));
+    assert_eq!(tokenizer.by_ref().count(), 5);
+    assert!(tokenizer.peek().is_ok_and(|t| t.is_none()));
+    assert!(tokenizer.look_ahead(0).is_ok_and(|t| t.is_none()));
+    assert!(tokenizer.next().is_none());
+}
+
+#[test]
+fn scans_lazily() {
+    // The error after the first token is not reached until the tokenizer looks that far
+    let mut tokenizer = Tokenizer::new("let $");
+    assert_eq!(tokenizer.peek().unwrap().map(|t| t.get_type()), Some(TokenType::Let));
+    assert_eq!(tokenizer.next().unwrap().map(|t| t.get_span().end), Ok(3));
+}
+
+#[test]
+fn returns_the_error_that_stops_the_stream() {
+    let error = Error::UnexpectedCharacter ('$', 1, 5);
+    let mut tokenizer = Tokenizer::new("let $ x");
+    assert!(tokenizer.next().is_some_and(|t| t.is_ok()));
+
+    // Looking ahead keeps returning the error, but iteration ends after yielding it once
+    assert_eq!(tokenizer.peek().err(), Some(error.to_owned()));
+    assert_eq!(tokenizer.look_ahead(3).err(), Some(error.to_owned()));
+    assert_eq!(tokenizer.next().transpose().err(), Some(error));
+    assert!(tokenizer.next().is_none());
+    assert_eq!(Tokenizer::new("a $ b $").count(), 2);
+}
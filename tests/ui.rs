@@ -0,0 +1,124 @@
+//! Checks UI test annotations, and drives `boron test --ui` over a directory of files.
+
+
+use std::{
+    env,
+    fs,
+    process::Command,
+};
+
+use boron_lang::ui::{
+    bless,
+    diff,
+    expectations,
+    Outcome,
+};
+
+
+/// Constructs an outcome in which a program compiled and printed the given lines.
+fn printed(lines: &[&str]) -> Outcome {
+    Outcome {
+        error: None,
+        stdout: lines.iter().map(|l| l.to_string()).collect(),
+    }
+}
+
+
+#[test]
+fn reads_annotations() {
+    let expected = expectations("
+# expect-stdout: 1
+#   expect-stdout: is not an annotation
+main -> int {
+    # expect-stdout:  2
+    # expect-stdout:
+    return 0
+}
+");
+    assert_eq!(expected, printed(&["1", " 2", ""]));
+
+    let expected = expectations("# expect-error: UndeclaredVariable\nmain -> int {\n    return x\n}\n");
+    assert_eq!(expected.error.as_deref(), Some("UndeclaredVariable"));
+}
+
+
+#[test]
+fn describes_differences() {
+    assert!(diff(&printed(&["1", "2"]), &printed(&["1", "2"])).is_empty());
+    assert_eq!(
+        diff(&printed(&["1", "2", "4"]), &printed(&["1", "3", "4", "5"])),
+        ["stdout differs from the expected output:", " 1", "-2", "+3", " 4", "+5"],
+    );
+
+    let error = |e: &str| Outcome {
+        error: Some(e.to_string()),
+        stdout: Vec::new(),
+    };
+    assert!(diff(&error("UndeclaredVariable"), &error("UndeclaredVariable")).is_empty());
+    assert_eq!(
+        diff(&error("UndeclaredVariable"), &error("UndeclaredFunction")),
+        ["expected error UndeclaredVariable, found error UndeclaredFunction"],
+    );
+    assert_eq!(
+        diff(&printed(&[]), &error("UndeclaredFunction")),
+        ["expected no error, found error UndeclaredFunction"],
+    );
+}
+
+
+#[test]
+fn rewrites_annotations_in_place() {
+    let source = "# Prints two numbers\n# expect-stdout: 1\n# expect-stdout: 3\nmain -> int {\n    return 0\n}\n";
+    assert_eq!(
+        bless(source, &printed(&["1", "2"])),
+        "# Prints two numbers\n# expect-stdout: 1\n# expect-stdout: 2\nmain -> int {\n    return 0\n}\n",
+    );
+
+    let source = "main -> int {\n    return x\n}\n";
+    let outcome = Outcome {
+        error: Some("UndeclaredVariable".to_string()),
+        stdout: Vec::new(),
+    };
+    let blessed = bless(source, &outcome);
+    assert_eq!(blessed, "# expect-error: UndeclaredVariable\nmain -> int {\n    return x\n}\n");
+    assert_eq!(expectations(&blessed), outcome);
+}
+
+
+#[test]
+fn checks_and_blesses_a_directory() {
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    if !Command::new(compiler).arg("--version").output().is_ok_and(|o| o.status.success()) {
+        return;
+    }
+    let directory = env::temp_dir().join(format!("boron-ui-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("error.brn"), "# expect-error: UndeclaredVariable\nmain -> int {\n    return x\n}\n").unwrap();
+    fs::write(directory.join("print.brn"), "# expect-stdout: 7\nmain -> int {\n    let int x: 6\n    print(x)\n    return 0\n}\n").unwrap();
+
+    let ui = |bless: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_boron"));
+        command.arg("test").arg("--ui");
+        if bless {
+            command.arg("--bless");
+        }
+        let output = command.arg(&directory).output().unwrap();
+        (String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code())
+    };
+
+    let (stdout, code) = ui(false);
+    assert!(stdout.contains("error.brn ... ok"), "{}", stdout);
+    assert!(stdout.contains("print.brn ... FAILED\n    stdout differs from the expected output:\n    -7\n    +6\n"), "{}", stdout);
+    assert_eq!(code, Some(1));
+
+    let (stdout, code) = ui(true);
+    assert!(stdout.contains("print.brn ... blessed"), "{}", stdout);
+    assert_eq!(code, Some(0));
+    assert!(fs::read_to_string(directory.join("print.brn")).unwrap().starts_with("# expect-stdout: 6\n"));
+
+    let (stdout, code) = ui(false);
+    assert!(stdout.ends_with("test result: ok. 2 passed; 0 failed\n"), "{}", stdout);
+    assert_eq!(code, Some(0));
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,191 @@
+//! Runs the WebAssembly emitted for sample programs in a local runtime.
+//!
+//! Each module is assembled from its text format, instantiated with host
+//! functions that record what the program prints, and its `main` is called.
+
+
+use boron_lang::{
+    wasm::Emitter,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+use wasmi::{
+    Caller,
+    Config,
+    Engine,
+    Linker,
+    Module,
+    StackLimits,
+    Store,
+};
+
+
+/// Compiles a Boron program to the WebAssembly text format.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+/// Compiles and runs a Boron program, returning the lines it prints and the result of `main`.
+fn run(code: &str) -> (Vec<String>, Result<i32, String>) {
+    let text = compile(code);
+    let bytes = match wat::parse_str(&text) {
+        Ok(b) => b,
+        Err(e) => panic!("invalid module: {}\n{}", e, text),
+    };
+
+    // Allow deep recursion, so that programs exhaust their own stack before the runtime's
+    let mut config = Config::default();
+    config.set_stack_limits(StackLimits::new(1024, 1 << 24, 1 << 20).unwrap_or_default());
+    let engine = Engine::new(&config);
+    let module = match Module::new(&engine, &bytes[..]) {
+        Ok(m) => m,
+        Err(e) => panic!("invalid module: {}\n{}", e, text),
+    };
+    let mut store = Store::new(&engine, Vec::new());
+    let mut linker = <Linker<Vec<String>>>::new(&engine);
+    let imports = linker.func_wrap("env", "print_int", |mut c: Caller<Vec<String>>, i: i32| c.data_mut().push(i.to_string()))
+        .and_then(|l| l.func_wrap("env", "print_flt", |mut c: Caller<Vec<String>>, f: f32| c.data_mut().push(format!("{:.6}", f))))
+        .and_then(|l| l.func_wrap("env", "print_bln", |mut c: Caller<Vec<String>>, b: i32| c.data_mut().push((b != 0).to_string())))
+        .and_then(|l| l.func_wrap("env", "print_chr", |mut c: Caller<Vec<String>>, c2: i32| c.data_mut().push((c2 as u8 as char).to_string())));
+    assert!(imports.is_ok());
+
+    let instance = match linker.instantiate(&mut store, &module).and_then(|i| i.start(&mut store)) {
+        Ok(i) => i,
+        Err(e) => panic!("could not instantiate module: {}", e),
+    };
+    let main = match instance.get_typed_func::<(), i32>(&store, "main") {
+        Ok(f) => f,
+        Err(e) => panic!("main is not exported: {}", e),
+    };
+    let result = main.call(&mut store, ()).map_err(|e| e.to_string());
+    (store.into_data(), result)
+}
+
+
+#[test]
+fn runs_functions_and_locals() {
+    let (output, result) = run("
+add :: int a int b -> int {
+    let int sum: a + b
+    return sum
+}
+main -> int {
+    let flt x: 2
+    print(x / 8)
+    let chr c: 'A' + 2
+    print(c)
+    print(add(1 2) > 2)
+    return add(40 2)
+}
+");
+    assert_eq!(output, ["0.250000", "C", "true"]);
+    assert_eq!(result, Ok(42));
+}
+
+#[test]
+fn runs_structs_and_references() {
+    let (output, result) = run("
+struct Point { int x int y }
+struct Node { int value opt Node next }
+let Point origin: { x 5 y 6 }
+bump :: ref int n {
+    n: n + 1
+}
+shift :: ref Point p int dx {
+    p.x: p.x + dx
+}
+swap :: Point p -> Point {
+    let Point q: { x p.y y p.x }
+    return q
+}
+norm :: Point p -> int {
+    return p.x + p.y
+}
+main -> int {
+    let Point p: { x 1 y 2 }
+    p.shift(10)
+    bump(p.y)
+    let int n: 0
+    bump(n)
+    print(n)
+    print(norm(swap(origin)))
+    let opt Node list: null
+    let int i: 0
+    while i < 3 {
+        list: new Node { value i next list }
+        i: i + 1
+    }
+    print(list.next.value)
+    drop(list)
+    return norm(p)
+}
+");
+    assert_eq!(output, ["1", "11", "1"]);
+    assert_eq!(result, Ok(14));
+}
+
+#[test]
+fn runs_control_flow() {
+    let (output, result) = run("
+collatz :: int n -> int {
+    let int steps: 0
+    while n > 1 {
+        defer steps: steps + 1
+        if n / 2 * 2 = n {
+            n: n / 2
+        } else {
+            n: 3 * n + 1
+        }
+    }
+    return steps
+}
+counter -> int {
+    static int count: 0
+    count: count + 1
+    return count
+}
+sign :: int n -> int {
+    if n < 0 {
+        return 0 - 1
+    }
+    return n > 0 ? 1 | 0
+}
+main -> int {
+    print(collatz(27))
+    counter()
+    print(counter())
+    return sign(0 - 5)
+}
+");
+    assert_eq!(output, ["17", "2"]);
+    assert_eq!(result, Ok(-1));
+}
+
+#[test]
+fn traps_on_runtime_errors() {
+    let (_, result) = run("
+main -> int {
+    let int zero: 0
+    return 1 / zero
+}
+");
+    assert!(result.is_err());
+
+    // Struct locals live on the stack in linear memory, which must not overflow into the globals
+    let (_, result) = run("
+struct Big { int a int b int c int d }
+let int sentinel: 7
+deep :: int n -> int {
+    let Big big: { a n }
+    return deep(n + 1) + big.a
+}
+main -> int {
+    return deep(0)
+}
+");
+    assert!(result.is_err_and(|e| e.contains("unreachable")));
+}
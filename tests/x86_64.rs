@@ -0,0 +1,190 @@
+//! Assembles, links, and runs the x86-64 assembly emitted for sample programs.
+//!
+//! These tests need the system assembler `as` and a C compiler (`cc`, or `$CC`)
+//! to link with the C library.  On machines that lack them, the tests that run
+//! programs say that they were skipped and check only the emitted assembly.
+
+
+use std::{
+    env,
+    fs,
+    io::{self, Write},
+    process::Command,
+};
+
+use boron_lang::{
+    x86_64::Emitter,
+    parser::Parser,
+    tokenizer::Tokenizer,
+};
+
+
+/// Compiles a Boron program to x86-64 assembly.
+fn compile(code: &str) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let expressions = Parser::new().parse_all(&mut tokenizer).unwrap();
+    Emitter::new().compile_exe(expressions).unwrap()
+}
+
+
+/// Checks if a tool can be run on this machine.
+fn found(tool: &str) -> bool {
+    Command::new(tool).arg("--version").output().is_ok_and(|o| o.status.success())
+}
+
+
+/// Reports that a test was skipped, writing to standard error directly so that the test harness does not hide it.
+fn skip(name: &str, reason: &str) {
+    let _ = writeln!(io::stderr(), "skipping {}: {}", name, reason);
+}
+
+
+/// Compiles, assembles, links and runs a Boron program, returning its output and exit code.
+///
+/// Returns `None`, after saying so, if the program cannot be linked on this machine.
+fn run(name: &str, code: &str) -> Option<(String, i32)> {
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        skip(name, "programs only run on x86-64 Linux");
+        return None;
+    }
+    if !found("as") || !found(&compiler) {
+        skip(name, &format!("`as` and `{}` are needed to assemble and link programs", compiler));
+        return None;
+    }
+    let base = env::temp_dir().join(format!("boron-x86_64-{}-{}", name, std::process::id()));
+    let (source, object) = (base.with_extension("s"), base.with_extension("o"));
+    fs::write(&source, compile(code)).unwrap();
+
+    let assembled = Command::new("as").arg(&source).arg("-o").arg(&object).status().unwrap();
+    assert!(assembled.success(), "could not assemble {}", source.display());
+    // The C compiler knows where this system keeps the C runtime objects
+    let linked = Command::new(compiler)
+        .arg("-no-pie")
+        .arg(&object)
+        .arg("-o")
+        .arg(&base)
+        .status()
+        .unwrap();
+    assert!(linked.success(), "could not link {}", object.display());
+
+    let output = Command::new(&base).output().unwrap();
+    for file in [&source, &object, &base] {
+        let _ = fs::remove_file(file);
+    }
+    Some((String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code()?))
+}
+
+
+#[test]
+fn emits_functions_and_locals() {
+    let module = compile("
+add :: int a int b -> int {
+    return a + b
+}
+main -> int {
+    return add(1 2)
+}
+");
+    assert!(module.contains(".globl add"));
+    assert!(module.contains("movq %rdi, "));
+    assert!(module.contains("call add"));
+}
+
+#[test]
+fn runs_arithmetic_and_calls() {
+    let result = run("arithmetic", "
+many :: int a int b int c int d int e int f int g int h -> int {
+    return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8
+}
+half :: flt x -> flt {
+    return x / 2
+}
+main -> int {
+    print(many(1 2 3 4 5 6 7 8))
+    print(half(5) + 0.25)
+    let chr c: 'A' + 2
+    print(c)
+    print(0 - 7 / 2)
+    print(3 > 2)
+    return 42
+}
+");
+    if let Some((output, code)) = result {
+        assert_eq!(output, "204\n2.750000\nC\n-3\ntrue\n");
+        assert_eq!(code, 42);
+    }
+}
+
+#[test]
+fn runs_structs_and_pointers() {
+    let result = run("structs", "
+struct Pair { chr tag flt weight int count }
+struct Node { int value opt Node next }
+let Pair unit: { tag 'u' weight 1 count 1 }
+make :: chr t flt w int n -> Pair {
+    let Pair p: { tag t weight w count n }
+    return p
+}
+weigh :: Pair p -> flt {
+    return p.weight * p.count
+}
+bump :: ref int n {
+    n: n + 1
+}
+main -> int {
+    let Pair q: make('z' 1.5 4)
+    bump(q.count)
+    print(q.tag)
+    print(weigh(q) + weigh(unit))
+    let opt Node list: null
+    let int i: 0
+    while i < 3 {
+        list: new Node { value i next list }
+        i: i + 1
+    }
+    print(list.next.value)
+    drop(list)
+    return q.count
+}
+");
+    if let Some((output, code)) = result {
+        assert_eq!(output, "z\n8.500000\n1\n");
+        assert_eq!(code, 5);
+    }
+}
+
+#[test]
+fn runs_control_flow() {
+    let result = run("control", "
+count :: int n -> int {
+    let int steps: 0
+    while n > 0 {
+        defer steps: steps + 1
+        n: n - 1
+    }
+    return steps
+}
+counter -> int {
+    static int calls: 0
+    calls: calls + 1
+    return calls
+}
+sign :: int n -> int {
+    if n < 0 {
+        return 0 - 1
+    }
+    return n > 0 ? 1 | 0
+}
+main -> int {
+    print(count(7))
+    counter()
+    print(counter())
+    return sign(0 - 5) + 10
+}
+");
+    if let Some((output, code)) = result {
+        assert_eq!(output, "7\n2\n");
+        assert_eq!(code, 9);
+    }
+}